@@ -0,0 +1,99 @@
+//! Checks properties of a service over its event log, read as NDJSON from stdin, one
+//! snapshot per line:
+//!
+//! ```text
+//! {"time": 1700000000000, "snapshot": {"status": "up", "queue": 3}}
+//! {"time": 1700000001000, "snapshot": {"status": "down", "queue": 40}}
+//! ```
+//!
+//! where `time` is in milliseconds since the Unix epoch. Violations are printed as they're
+//! found, and the remaining properties are decided once the input ends. Exits with 1 if any
+//! property is violated.
+//!
+//!     cargo run --example ndjson_monitor < events.ndjson
+
+use std::io::{self, BufRead};
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use bombadil::specification::ltl::Value;
+use bombadil::specification::monitor::{
+    Monitor, always, eventually, now, once,
+};
+use bombadil::specification::stop::StopDefault;
+use serde::Deserialize;
+use serde_json as json;
+
+#[derive(Deserialize)]
+struct Line {
+    time: u64,
+    snapshot: json::Value,
+}
+
+fn monitor() -> Monitor {
+    let down = || now("status is down", |s| s["status"] == "down");
+    let up = || now("status is up", |s| s["status"] == "up");
+    let queue_bounded = always(now("queue below 1000", |s| {
+        s["queue"].as_u64().is_some_and(|queue| queue < 1000)
+    }));
+    let recovers = always(
+        down().implies(eventually(up()).within(Duration::from_secs(30))),
+    );
+    let down_after_up = always(down().implies(once(up())));
+    Monitor::new([
+        ("queue_bounded", queue_bounded),
+        ("recovers", recovers),
+        ("down_after_up", down_after_up),
+    ])
+}
+
+fn main() -> Result<ExitCode> {
+    let mut monitor = monitor();
+    let mut violated = false;
+    let mut reported = Vec::new();
+    let mut time = SystemTime::UNIX_EPOCH;
+
+    for (number, line) in io::stdin().lock().lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let line: Line = json::from_str(&line).with_context(|| {
+            format!("invalid snapshot on line {}", number + 1)
+        })?;
+        time = SystemTime::UNIX_EPOCH + Duration::from_millis(line.time);
+        for (name, value) in monitor.step(&line.snapshot, time)? {
+            if let Value::False(violation) = value
+                && !reported.contains(&name)
+            {
+                println!("{} violated:\n\n{}\n", name, violation);
+                reported.push(name);
+                violated = true;
+            }
+        }
+    }
+
+    for (name, default) in monitor.finish(time) {
+        match default {
+            Some(StopDefault::False(violation))
+                if !reported.contains(&name) =>
+            {
+                println!(
+                    "{} violated at end of input:\n\n{}\n",
+                    name, violation
+                );
+                violated = true;
+            }
+            Some(StopDefault::False(_)) => {}
+            Some(StopDefault::True) => println!("{} held", name),
+            None => println!("{} wasn't checked, the input is empty", name),
+        }
+    }
+
+    Ok(if violated {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
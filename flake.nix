@@ -59,7 +59,7 @@
 
         checks = {
 
-          inherit (bombadil) clippy fmt types;
+          inherit (bombadil) clippy fmt types noDefaultFeatures;
         }
         // pkgs.lib.optionalAttrs pkgs.stdenv.isLinux {
           inherit (bombadil) tests;
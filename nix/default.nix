@@ -113,6 +113,16 @@ in
     }
   );
 
+  # The library without any of its optional features, which would otherwise go unbuilt.
+  noDefaultFeatures = craneLib.cargoBuild (
+    commonArgs
+    // {
+      inherit cargoArtifacts;
+      pname = "bombadil-no-default-features";
+      cargoExtraArgs = "--locked --lib --no-default-features";
+    }
+  );
+
   fmt = craneLib.cargoFmt {
     inherit (commonArgs) src;
   };
@@ -7,35 +7,77 @@ use chromiumoxide::cdp::browser_protocol::target::{self, TargetId};
 use chromiumoxide::cdp::browser_protocol::{dom, emulation};
 use chromiumoxide::cdp::js_protocol::debugger::{self, CallFrameId};
 use chromiumoxide::cdp::js_protocol::runtime::{self};
-use chromiumoxide::page::ScreenshotParams;
 use chromiumoxide::{BrowserConfig, Page};
+use futures::future::BoxFuture;
 use futures::{StreamExt, stream};
 use log;
 use serde_json as json;
 use std::path::PathBuf;
 use std::pin::Pin;
 use std::sync::Arc;
-use std::time::{Duration, UNIX_EPOCH};
+use std::time::{Duration, Instant, UNIX_EPOCH};
 use tempfile::TempDir;
 use tokio::sync::broadcast::error::RecvError;
 use tokio::sync::broadcast::{Receiver, Sender, channel};
-use tokio::sync::oneshot;
+use tokio::sync::{Mutex, oneshot};
 use tokio::time::sleep;
 use tokio::{select, spawn};
 use tokio_stream::wrappers::BroadcastStream;
 use url::Url;
 
 use crate::browser::actions::BrowserAction;
+use crate::browser::checkpoints::Checkpoint;
+use crate::browser::chrome::{
+    ChromeChannel, ChromeVersion, HeadlessVariant, check_compatibility,
+};
+use crate::browser::coverage::{CoverageSource, PreciseCoverage};
+use crate::browser::dialogs::{BeforeUnloadPolicy, Dialogs};
+use crate::browser::extensions::PreparedExtension;
+use crate::browser::faults::FaultInjection;
+use crate::browser::gpu::{GpuInfo, GpuPreset};
+use crate::browser::instrumentation::{CachingDiagnostics, ScriptSizes};
+use crate::browser::interception::Interception;
+use crate::browser::media::{FakeMedia, Permission};
+use crate::browser::navigation::{InitialNavigationError, NavigationFailure};
+use crate::browser::network_log::NetworkLog;
+use crate::browser::screenshots::ScreenshotPool;
+use crate::browser::service_workers::{ServiceWorkerPolicy, ServiceWorkers};
 use crate::browser::state::{
-    BrowserState, CallFrame, ConsoleEntry, Exception, Screenshot,
-    ScreenshotFormat,
+    BrowserState, CallFrame, CaptureTimings, ConsoleEntry, Exception,
+    LogLimits, Screenshot, ScreenshotFormat, StepLogs,
 };
+use crate::browser::viewports::{ViewportProfile, emulate_viewport};
+use crate::browser::websockets::WebSockets;
+use crate::run_id::RunId;
 
+pub mod accessibility;
 pub mod actions;
+pub mod checkpoints;
+pub mod chrome;
+pub mod click_deny_list;
+pub mod coverage;
+pub mod dialogs;
 pub mod evaluation;
+pub mod extensions;
+pub mod faults;
+pub mod gpu;
 pub mod instrumentation;
+pub mod interception;
 pub mod keys;
+pub mod listeners;
+pub mod media;
+pub mod mock;
+pub mod mutations;
+pub mod navigation;
+pub mod network_log;
+pub mod payment_guard;
+pub mod provisioning;
+pub mod screencast;
+pub mod screenshots;
+pub mod service_workers;
 pub mod state;
+pub mod viewports;
+pub mod websockets;
 
 #[derive(Debug, Clone)]
 #[allow(clippy::large_enum_variant)]
@@ -47,9 +89,17 @@ pub enum BrowserEvent {
 #[derive(Debug, Default)]
 struct InnerStateShared {
     generation: Generation,
-    console_entries: Vec<ConsoleEntry>,
-    exceptions: Vec<Exception>,
+    logs: StepLogs,
     screenshot: Option<Screenshot>,
+    /// DOM mutations seen since the last state capture.
+    dom_mutations: u64,
+    /// How long to wait after a DOM mutation before capturing, so that a burst of mutations
+    /// ends up in a single state. Captures immediately when zero.
+    settle_delay: Duration,
+    /// Whether a capture is already scheduled after the settle delay.
+    settling: bool,
+    timings: CaptureTimings,
+    pause_requested: Option<Instant>,
 }
 
 #[derive(Debug)]
@@ -58,11 +108,42 @@ struct InnerState {
     shared: InnerStateShared,
 }
 
+/// An action to apply when resuming, along with the checkpoint it restores, if any, which
+/// the action only refers to by id.
+#[derive(Clone)]
+struct AcceptedAction {
+    action: BrowserAction,
+    checkpoint: Option<Arc<Checkpoint>>,
+}
+
+impl AcceptedAction {
+    async fn apply(
+        &self,
+        page: &Page,
+        interception: &Interception,
+        dialogs: &Dialogs,
+    ) -> Result<()> {
+        match &self.checkpoint {
+            Some(checkpoint) => checkpoint.restore(page).await,
+            None => self.action.apply(page, interception, dialogs).await,
+        }
+    }
+}
+
+// Only the action is logged, keeping the cookies and storage of checkpoints out of the
+// logs.
+impl std::fmt::Debug for AcceptedAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.action.fmt(f)
+    }
+}
+
 #[derive(Debug)]
 enum InnerStateKind {
     Pausing,
     Paused,
-    Resuming(BrowserAction, Timeout),
+    /// Resuming in order to apply an action, or to merely observe the page when there's none.
+    Resuming(Option<AcceptedAction>, Timeout),
     Navigating,
     Loading,
     Running,
@@ -85,8 +166,10 @@ enum InnerEvent {
     TargetDestroyed(TargetId),
     NodeTreeModified(NodeModification),
     ConsoleEntry(ConsoleEntry),
-    ActionAccepted(BrowserAction, Timeout),
+    ActionAccepted(AcceptedAction, Timeout),
     ActionApplied(Generation),
+    ObservationRequested(Timeout),
+    SettleDelayChanged(Duration),
     ExceptionThrown(Exception),
 }
 
@@ -97,6 +180,7 @@ enum StateRequestReason {
     Loaded,
     BackForwardCacheRestore,
     Watchdog,
+    Settled,
 }
 
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
@@ -140,20 +224,39 @@ pub enum NodeModification {
 
 struct BrowserContext {
     sender: Sender<BrowserEvent>,
-    actions_sender: Sender<(BrowserAction, Timeout)>,
+    actions_sender: Sender<(AcceptedAction, Timeout)>,
     inner_events_sender: Sender<InnerEvent>,
     shutdown_receiver: oneshot::Receiver<()>,
     page: Arc<Page>,
     frame_id: FrameId,
     #[allow(unused, reason = "this is going into the scripts soon")]
     origin: Url,
+    service_workers: ServiceWorkers,
+    network_log: NetworkLog,
+    web_sockets: WebSockets,
+    interception: Interception,
+    dialogs: Dialogs,
+    log_limits: LogLimits,
+    screenshots: ScreenshotPool,
+    /// Coverage from the V8 profiler, when it's the coverage source.
+    precise_coverage: Option<Mutex<PreciseCoverage>>,
 }
 
 #[derive(Clone)]
 pub struct LaunchOptions {
-    pub headless: bool,
+    /// The headless mode to run in, or none for a visible window.
+    pub headless: Option<HeadlessVariant>,
+    /// Chrome binary to launch, instead of the one of `chrome_channel`.
+    pub chrome_binary: Option<PathBuf>,
+    /// Release channel of the installed Chrome to launch, instead of the detected one.
+    pub chrome_channel: Option<ChromeChannel>,
     pub user_data_directory: PathBuf,
     pub no_sandbox: bool,
+    /// Unpacked extensions to load, with their scripts instrumented for coverage.
+    pub extensions: Vec<PathBuf>,
+    pub gpu: GpuPreset,
+    /// Fake camera and microphone devices, if any.
+    pub fake_media: Option<FakeMedia>,
 }
 
 #[derive(Clone)]
@@ -161,12 +264,215 @@ pub struct Emulation {
     pub width: u16,
     pub height: u16,
     pub device_scale_factor: f64,
+    pub mobile: bool,
+    /// Emulate a touch screen.
+    pub touch: bool,
+    /// The user agent reported to the page, instead of the browser's own.
+    pub user_agent: Option<String>,
+    /// Emulate `prefers-reduced-motion: reduce` and disable CSS animations and transitions,
+    /// making states and screenshots less dependent on timing.
+    pub reduce_motion: bool,
+}
+
+impl Emulation {
+    /// The viewport profile emulated at the start of a run.
+    pub fn profile(&self) -> ViewportProfile {
+        ViewportProfile {
+            name: "default".to_string(),
+            width: self.width,
+            height: self.height,
+            device_scale_factor: self.device_scale_factor,
+            mobile: self.mobile,
+            touch: self.touch,
+            user_agent: self.user_agent.clone(),
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct BrowserOptions {
     pub emulation: Emulation,
     pub create_target: bool,
+    pub run_id: RunId,
+    /// URL patterns (with `*` wildcards) of resources to forward without instrumentation,
+    /// preserving their original caching headers.
+    pub cache_pass_through: Vec<String>,
+    pub service_worker_policy: ServiceWorkerPolicy,
+    pub faults: FaultInjection,
+    pub before_unload: BeforeUnloadPolicy,
+    /// Seed for the random answers to dialogs.
+    pub seed: u64,
+    /// Permissions granted to the origin, e.g. geolocation.
+    pub permissions: Vec<Permission>,
+    /// Selectors of the elements blacked out in screenshots.
+    pub screenshot_masks: Vec<String>,
+    /// Directory to record a screencast of the whole run to, if any.
+    pub screencast: Option<PathBuf>,
+}
+
+/// How to navigate to the origin at the start of a test, e.g. while a backend container is
+/// still starting.
+#[derive(Clone, Debug)]
+pub struct InitialNavigation {
+    /// How long to wait for each navigation attempt to load.
+    pub timeout: Duration,
+    /// Attempts after the first failed one, with a backoff doubling from one second.
+    pub retries: u32,
+    /// URL to poll until it responds successfully, before navigating.
+    pub health_check: Option<Url>,
+    /// How long to poll the health check URL before giving up.
+    pub health_check_timeout: Duration,
+}
+
+impl Default for InitialNavigation {
+    fn default() -> Self {
+        InitialNavigation {
+            timeout: Duration::from_secs(30),
+            retries: 0,
+            health_check: None,
+            health_check_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+const INITIAL_NAVIGATION_BACKOFF: Duration = Duration::from_secs(1);
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const HEALTH_CHECK_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+async fn wait_until_healthy(url: &Url, timeout: Duration) -> Result<()> {
+    let client = reqwest::Client::builder()
+        .timeout(HEALTH_CHECK_REQUEST_TIMEOUT)
+        .build()?;
+    let started = Instant::now();
+    log::info!("waiting for {} to be healthy", url);
+    loop {
+        let failure = match client.get(url.clone()).send().await {
+            Ok(response) if response.status().is_success() => {
+                log::info!("{} is healthy after {:?}", url, started.elapsed());
+                return Ok(());
+            }
+            Ok(response) => format!("status {}", response.status()),
+            Err(error) => error.to_string(),
+        };
+        if started.elapsed() >= timeout {
+            bail!(
+                "health check {} still failing after {:?}: {}",
+                url,
+                timeout,
+                failure
+            );
+        }
+        log::debug!("health check {} failed: {}", url, failure);
+        sleep(HEALTH_CHECK_INTERVAL).await;
+    }
+}
+
+async fn go_to_origin(
+    page: &Page,
+    origin: &str,
+    navigation: &InitialNavigation,
+) -> std::result::Result<(), InitialNavigationError> {
+    let mut backoff = INITIAL_NAVIGATION_BACKOFF;
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        log::info!("going to origin");
+        let (failure, message) =
+            match tokio::time::timeout(navigation.timeout, page.goto(origin))
+                .await
+            {
+                Ok(Ok(_)) => match document_content_type(page).await {
+                    Ok(content_type) => {
+                        match NavigationFailure::from_content_type(
+                            &content_type,
+                        ) {
+                            Some(failure) => (
+                                failure,
+                                format!("loaded a {} document", content_type),
+                            ),
+                            None => return Ok(()),
+                        }
+                    }
+                    // Leave it to the state capture to fail, if it's not a document at all.
+                    Err(error) => {
+                        log::debug!(
+                            "failed getting the content type: {}",
+                            error
+                        );
+                        return Ok(());
+                    }
+                },
+                Ok(Err(error)) => {
+                    let message = error.to_string();
+                    (NavigationFailure::from_error(&message), message)
+                }
+                Err(_) => (
+                    NavigationFailure::Timeout(navigation.timeout),
+                    format!("timed out after {:?}", navigation.timeout),
+                ),
+            };
+        if attempt > navigation.retries {
+            return Err(InitialNavigationError {
+                origin: origin.to_string(),
+                attempts: attempt,
+                failure,
+                message,
+            });
+        }
+        log::warn!(
+            "navigating to {} failed ({}), retrying in {:?}",
+            origin,
+            message,
+            backoff
+        );
+        sleep(backoff).await;
+        backoff *= 2;
+    }
+}
+
+async fn document_content_type(page: &Page) -> Result<String> {
+    Ok(page
+        .evaluate_expression("document.contentType")
+        .await?
+        .into_value()?)
+}
+
+/// The browser-facing interface driven by the runner, implemented by `Browser` and, in
+/// tests without Chrome, by `MockBrowser`.
+pub trait BrowserBackend: Send {
+    fn initiate<'a>(
+        &'a mut self,
+        navigation: &'a InitialNavigation,
+    ) -> BoxFuture<'a, Result<()>>;
+
+    fn terminate(self: Box<Self>) -> BoxFuture<'static, Result<()>>;
+
+    fn next_event(&mut self) -> BoxFuture<'_, Option<BrowserEvent>>;
+
+    fn apply(&mut self, action: BrowserAction, timeout: Timeout) -> Result<()>;
+
+    /// Restores a checkpoint kept by the runner, applying the action referring to it.
+    fn restore_checkpoint(
+        &mut self,
+        action: BrowserAction,
+        checkpoint: Checkpoint,
+        timeout: Timeout,
+    ) -> Result<()>;
+
+    fn observe(&mut self, timeout: Timeout) -> Result<()>;
+
+    fn set_viewport<'a>(
+        &'a mut self,
+        profile: &'a ViewportProfile,
+    ) -> BoxFuture<'a, Result<()>>;
+
+    fn set_settle_delay(&mut self, delay: Duration) -> Result<()>;
+
+    fn caching_diagnostics(&self) -> CachingDiagnostics;
+
+    fn script_sizes(&self) -> ScriptSizes;
+
+    fn gpu_info(&self) -> Option<GpuInfo>;
 }
 
 #[derive(Clone)]
@@ -177,21 +483,27 @@ pub enum DebuggerOptions {
 
 pub struct Browser {
     receiver: Receiver<BrowserEvent>,
+    events_sender: Sender<BrowserEvent>,
     inner_events_sender: Sender<InnerEvent>,
-    actions_sender: Sender<(BrowserAction, Timeout)>,
+    actions_sender: Sender<(AcceptedAction, Timeout)>,
     shutdown_sender: oneshot::Sender<()>,
     done_receiver: oneshot::Receiver<()>,
     browser: chromiumoxide::Browser,
     page: Arc<Page>,
     origin: Url,
     go_to_origin_on_init: bool,
+    caching_diagnostics: CachingDiagnostics,
+    script_sizes: ScriptSizes,
+    gpu_info: Option<GpuInfo>,
 }
 
 impl Browser {
     pub async fn new(
-        origin: Url,
+        mut origin: Url,
         browser_options: BrowserOptions,
         debugger_options: DebuggerOptions,
+        log_limits: LogLimits,
+        coverage_source: CoverageSource,
     ) -> Result<Self> {
         let (mut browser, mut handler) = match debugger_options {
             DebuggerOptions::External {
@@ -201,8 +513,14 @@ impl Browser {
                     .await?
             }
             DebuggerOptions::Managed { ref launch_options } => {
+                let extensions = extensions::prepare_extensions(
+                    &launch_options.extensions,
+                    &launch_options.user_data_directory,
+                )?;
+                origin = extensions::rewrite_origin(&origin, &extensions);
                 let browser_config = launch_options_to_config(
                     launch_options,
+                    &extensions,
                     &browser_options.emulation,
                 )?;
                 chromiumoxide::Browser::launch(browser_config).await?
@@ -217,7 +535,7 @@ impl Browser {
 
         let (sender, receiver) = channel::<BrowserEvent>(1);
 
-        let (actions_sender, _) = channel::<(BrowserAction, Timeout)>(1);
+        let (actions_sender, _) = channel::<(AcceptedAction, Timeout)>(1);
 
         let page = if browser_options.create_target {
             Arc::new(browser.new_page("about:blank").await.context(
@@ -227,28 +545,58 @@ impl Browser {
             Arc::new(find_page(&mut browser).await?)
         };
 
+        media::grant_permissions(
+            &browser,
+            &origin,
+            &browser_options.permissions,
+        )
+        .await?;
+
         page.enable_dom().await?;
         page.enable_css().await?;
         page.enable_runtime().await?;
         page.enable_debugger().await?;
 
-        page.execute(
-            emulation::SetDeviceMetricsOverrideParams::builder()
-                .width(browser_options.emulation.width)
-                .height(browser_options.emulation.height)
-                .device_scale_factor(
-                    browser_options.emulation.device_scale_factor,
-                )
-                .mobile(false)
-                .scale(1)
-                .build()
-                .map_err(|err| {
-                    anyhow!(err)
-                        .context("build SetDeviceMetricsOverrideParams failed")
-                })?,
-        )
+        let gpu_info = match GpuInfo::detect(&page).await {
+            Ok(info) => {
+                if !info.webgl {
+                    log::warn!(
+                        "WebGL isn't available in this browser, canvas-heavy pages may render blank (try --gpu swiftshader)"
+                    );
+                } else if info.software {
+                    log::info!(
+                        "WebGL is rendered in software ({})",
+                        info.renderer.as_deref().unwrap_or("unknown renderer")
+                    );
+                }
+                Some(info)
+            }
+            Err(error) => {
+                log::warn!("could not detect WebGL support: {:#}", error);
+                None
+            }
+        };
+
+        emulate_viewport(&page, &browser_options.emulation.profile()).await?;
+
+        // Screenshots omit the default (white) background, set once rather than around each
+        // screenshot.
+        page.execute(emulation::SetDefaultBackgroundColorOverrideParams {
+            color: Some(dom::Rgba {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: Some(0.),
+            }),
+        })
         .await?;
 
+        if browser_options.emulation.reduce_motion {
+            reduce_motion(&page).await?;
+        }
+
+        mutations::observe_mutations(&page).await?;
+
         let (inner_events_sender, inner_events_receiver) =
             channel::<InnerEvent>(1024);
 
@@ -260,6 +608,34 @@ impl Browser {
             .await?
             .ok_or(anyhow!("no main frame available"))?;
 
+        let service_workers = service_workers::manage_service_workers(
+            page.clone(),
+            &origin,
+            browser_options.service_worker_policy,
+        )
+        .await?;
+        let network_log = network_log::track_network(&page).await?;
+        let web_sockets = websockets::track_web_sockets(&page).await?;
+        let dialogs = dialogs::handle_dialogs(
+            page.clone(),
+            browser_options.before_unload,
+            browser_options.seed,
+        )
+        .await?;
+        let interception =
+            Interception::start(page.clone(), &browser_options.faults).await?;
+        if let Some(directory) = &browser_options.screencast {
+            screencast::record_screencast(page.clone(), directory).await?;
+        }
+
+        let precise_coverage = match coverage_source {
+            CoverageSource::Instrumentation => None,
+            CoverageSource::Precise => {
+                Some(Mutex::new(PreciseCoverage::start(&page).await?))
+            }
+        };
+        let events_sender = sender.clone();
+
         let context = BrowserContext {
             sender,
             actions_sender: actions_sender.clone(),
@@ -268,9 +644,39 @@ impl Browser {
             page: page.clone(),
             frame_id,
             origin: origin.clone(),
+            service_workers,
+            network_log,
+            web_sockets,
+            interception: interception.clone(),
+            dialogs,
+            log_limits,
+            screenshots: ScreenshotPool::new(
+                browser_options.screenshot_masks.clone(),
+            ),
+            precise_coverage,
         };
 
-        instrumentation::instrument_js_coverage(page.clone()).await?;
+        let script_sizes = ScriptSizes::default();
+        let caching_diagnostics = match coverage_source {
+            #[cfg(feature = "instrumentation")]
+            CoverageSource::Instrumentation => {
+                instrumentation::instrument_js_coverage(
+                    page.clone(),
+                    &browser_options.run_id,
+                    browser_options.cache_pass_through.clone(),
+                    script_sizes.clone(),
+                    &interception,
+                )
+                .await?
+            }
+            #[cfg(not(feature = "instrumentation"))]
+            CoverageSource::Instrumentation => {
+                bail!(
+                    "instrumentation coverage needs the `instrumentation` feature, use precise coverage instead"
+                )
+            }
+            CoverageSource::Precise => CachingDiagnostics::default(),
+        };
 
         let browser_events = browser
             .event_listener::<target::EventTargetDestroyed>()
@@ -287,6 +693,7 @@ impl Browser {
         Ok(Browser {
             browser,
             receiver,
+            events_sender,
             inner_events_sender,
             actions_sender,
             shutdown_sender,
@@ -294,16 +701,53 @@ impl Browser {
             page,
             origin,
             go_to_origin_on_init: browser_options.create_target,
+            caching_diagnostics,
+            script_sizes,
+            gpu_info,
         })
     }
 
-    pub async fn initiate(&mut self) -> Result<()> {
+    /// The origin tested, with the ID of a loaded extension rewritten to that of its copy.
+    pub fn origin(&self) -> &Url {
+        &self.origin
+    }
+
+    /// Resources whose caching semantics have been altered by instrumentation so far.
+    pub fn caching_diagnostics(&self) -> CachingDiagnostics {
+        self.caching_diagnostics.clone()
+    }
+
+    /// Sizes of the scripts intercepted so far, which are only collected when coverage is
+    /// instrumented.
+    pub fn script_sizes(&self) -> ScriptSizes {
+        self.script_sizes.clone()
+    }
+
+    /// Whether WebGL is available, detected when the browser started, unless that failed.
+    pub fn gpu_info(&self) -> Option<GpuInfo> {
+        self.gpu_info.clone()
+    }
+
+    pub async fn initiate(
+        &mut self,
+        navigation: &InitialNavigation,
+    ) -> Result<()> {
         if self.go_to_origin_on_init {
+            if let Some(url) = &navigation.health_check {
+                wait_until_healthy(url, navigation.health_check_timeout)
+                    .await?;
+            }
             let page = self.page.clone();
             let origin = self.origin.to_string();
+            let navigation = navigation.clone();
+            let events_sender = self.events_sender.clone();
             spawn(async move {
-                log::info!("going to origin");
-                let _ = page.goto(origin).await;
+                if let Err(error) =
+                    go_to_origin(&page, &origin, &navigation).await
+                {
+                    let _ = events_sender
+                        .send(BrowserEvent::Error(Arc::new(error.into())));
+                }
             });
         } else {
             let _ = self.inner_events_sender.send(InnerEvent::StateRequested(
@@ -353,9 +797,113 @@ impl Browser {
         action: BrowserAction,
         timeout: Timeout,
     ) -> Result<()> {
-        self.actions_sender.send((action, timeout))?;
+        self.actions_sender.send((
+            AcceptedAction {
+                action,
+                checkpoint: None,
+            },
+            timeout,
+        ))?;
+        Ok(())
+    }
+
+    pub fn restore_checkpoint(
+        &mut self,
+        action: BrowserAction,
+        checkpoint: Checkpoint,
+        timeout: Timeout,
+    ) -> Result<()> {
+        self.actions_sender.send((
+            AcceptedAction {
+                action,
+                checkpoint: Some(Arc::new(checkpoint)),
+            },
+            timeout,
+        ))?;
         Ok(())
     }
+
+    /// Resume the page without applying any action, capturing the next state when the page
+    /// changes or when the timeout is reached.
+    pub fn observe(&mut self, timeout: Timeout) -> Result<()> {
+        self.inner_events_sender
+            .send(InnerEvent::ObservationRequested(timeout))?;
+        Ok(())
+    }
+
+    /// Emulate the device metrics of another viewport profile, from the next state on.
+    pub async fn set_viewport(&self, profile: &ViewportProfile) -> Result<()> {
+        emulate_viewport(&self.page, profile)
+            .await
+            .with_context(|| {
+                format!("failed switching to viewport `{}`", profile.name)
+            })
+    }
+
+    /// Delay state captures triggered by DOM mutations, coalescing the mutations within the
+    /// delay into a single state. A zero delay captures on the first mutation.
+    pub fn set_settle_delay(&mut self, delay: Duration) -> Result<()> {
+        self.inner_events_sender
+            .send(InnerEvent::SettleDelayChanged(delay))?;
+        Ok(())
+    }
+}
+
+impl BrowserBackend for Browser {
+    fn initiate<'a>(
+        &'a mut self,
+        navigation: &'a InitialNavigation,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(Browser::initiate(self, navigation))
+    }
+
+    fn terminate(self: Box<Self>) -> BoxFuture<'static, Result<()>> {
+        Box::pin(Browser::terminate(*self))
+    }
+
+    fn next_event(&mut self) -> BoxFuture<'_, Option<BrowserEvent>> {
+        Box::pin(Browser::next_event(self))
+    }
+
+    fn apply(&mut self, action: BrowserAction, timeout: Timeout) -> Result<()> {
+        Browser::apply(self, action, timeout)
+    }
+
+    fn restore_checkpoint(
+        &mut self,
+        action: BrowserAction,
+        checkpoint: Checkpoint,
+        timeout: Timeout,
+    ) -> Result<()> {
+        Browser::restore_checkpoint(self, action, checkpoint, timeout)
+    }
+
+    fn observe(&mut self, timeout: Timeout) -> Result<()> {
+        Browser::observe(self, timeout)
+    }
+
+    fn set_viewport<'a>(
+        &'a mut self,
+        profile: &'a ViewportProfile,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(Browser::set_viewport(self, profile))
+    }
+
+    fn set_settle_delay(&mut self, delay: Duration) -> Result<()> {
+        Browser::set_settle_delay(self, delay)
+    }
+
+    fn caching_diagnostics(&self) -> CachingDiagnostics {
+        Browser::caching_diagnostics(self)
+    }
+
+    fn script_sizes(&self) -> ScriptSizes {
+        Browser::script_sizes(self)
+    }
+
+    fn gpu_info(&self) -> Option<GpuInfo> {
+        Browser::gpu_info(self)
+    }
 }
 
 async fn inner_events(
@@ -643,11 +1191,33 @@ async fn process_event(
     use InnerStateKind::*;
     Ok(match (state_current, event) {
         (
-            state @ InnerState { kind: Running, .. },
+            mut state @ InnerState { kind: Running, .. },
             InnerEvent::NodeTreeModified(modification),
         ) => {
             handle_node_modification(context, &modification).await?;
-            capture_browser_state(state, context).await?
+            state.shared.dom_mutations += 1;
+            if state.shared.settle_delay.is_zero() {
+                capture_browser_state(state, context).await?
+            } else {
+                if !state.shared.settling {
+                    let sender = context.inner_events_sender.clone();
+                    let delay = state.shared.settle_delay;
+                    let generation = state.shared.generation;
+                    spawn(async move {
+                        sleep(delay).await;
+                        let _ = sender.send(InnerEvent::StateRequested(
+                            StateRequestReason::Settled,
+                            generation,
+                        ));
+                    });
+                    state.shared.settling = true;
+                }
+                state
+            }
+        }
+        (mut state, InnerEvent::SettleDelayChanged(delay)) => {
+            state.shared.settle_delay = delay;
+            state
         }
         (state, InnerEvent::StateRequested(reason, generation)) => {
             if state.shared.generation != generation {
@@ -669,8 +1239,9 @@ async fn process_event(
                 capture_browser_state(state, context).await?
             }
         }
-        (state, InnerEvent::NodeTreeModified(modification)) => {
+        (mut state, InnerEvent::NodeTreeModified(modification)) => {
             handle_node_modification(context, &modification).await?;
+            state.shared.dom_mutations += 1;
             state
         }
         (
@@ -715,23 +1286,45 @@ async fn process_event(
             }
 
             let InnerStateShared {
-                console_entries,
-                exceptions,
+                mut logs,
                 generation,
                 screenshot,
+                dom_mutations,
+                settle_delay,
+                timings,
+                pause_requested,
+                ..
             } = state.shared;
 
             let screenshot = screenshot
                 .ok_or(anyhow!("no screenshot available for state capture"))?;
-
-            let browser_state = BrowserState::current(
+            (logs.network_requests, logs.network_requests_dropped) =
+                context.network_log.take();
+            (logs.web_socket_frames, logs.web_socket_frames_dropped) =
+                context.web_sockets.take();
+            logs.dialogs = context.dialogs.take();
+            logs.redirects = context.network_log.take_redirects();
+
+            let mut browser_state = BrowserState::current(
                 context.page.clone(),
                 &call_frame_id,
-                console_entries,
-                exceptions,
+                logs,
+                dom_mutations,
+                context.service_workers.statuses(),
                 screenshot,
+                CaptureTimings {
+                    pause: pause_requested
+                        .map(|instant| instant.elapsed())
+                        .unwrap_or_default(),
+                    ..timings
+                },
             )
             .await?;
+            if let Some(precise_coverage) = &context.precise_coverage {
+                (browser_state.coverage, browser_state.transition_hash) =
+                    precise_coverage.lock().await.take(&context.page).await?;
+            }
+            log::debug!("captured state: {:?}", browser_state.timings);
 
             context
                 .sender
@@ -753,9 +1346,13 @@ async fn process_event(
                 kind: Paused,
                 shared: InnerStateShared {
                     generation,
-                    console_entries: vec![],
-                    exceptions: vec![],
+                    logs: StepLogs::default(),
                     screenshot: None,
+                    dom_mutations: 0,
+                    settle_delay,
+                    settling: false,
+                    timings: CaptureTimings::default(),
+                    pause_requested: None,
                 },
             }
         }
@@ -771,7 +1368,23 @@ async fn process_event(
                 .execute(debugger::ResumeParams::builder().build())
                 .await?;
             InnerState {
-                kind: Resuming(browser_action, timeout),
+                kind: Resuming(Some(browser_action), timeout),
+                shared,
+            }
+        }
+        (
+            InnerState {
+                kind: Paused,
+                shared,
+            },
+            InnerEvent::ObservationRequested(timeout),
+        ) => {
+            context
+                .page
+                .execute(debugger::ResumeParams::builder().build())
+                .await?;
+            InnerState {
+                kind: Resuming(None, timeout),
                 shared,
             }
         }
@@ -789,6 +1402,19 @@ async fn process_event(
             );
             state
         }
+        (
+            state @ InnerState {
+                kind: Loading | Navigating,
+                ..
+            },
+            InnerEvent::ObservationRequested(_),
+        ) => {
+            log::debug!(
+                "ignoring observation received during {:?}",
+                state.kind
+            );
+            state
+        }
         (
             InnerState {
                 kind: Pausing,
@@ -810,7 +1436,31 @@ async fn process_event(
             InnerEvent::Resumed,
         ) => {
             log::warn!("running + resumed");
-            shared.console_entries.clear();
+            shared.logs.clear_console_entries();
+            InnerState {
+                kind: Running,
+                shared,
+            }
+        }
+        (
+            InnerState {
+                kind: Resuming(None, timeout),
+                mut shared,
+            },
+            InnerEvent::Resumed,
+        ) => {
+            // Nothing to apply, so we let the page run until something happens
+            // or the timeout is reached.
+            let sender = context.inner_events_sender.clone();
+            spawn(async move {
+                sleep(timeout).await;
+                let _ = sender.send(InnerEvent::StateRequested(
+                    StateRequestReason::Timeout,
+                    shared.generation,
+                ));
+            });
+
+            shared.logs.clear_console_entries();
             InnerState {
                 kind: Running,
                 shared,
@@ -818,12 +1468,14 @@ async fn process_event(
         }
         (
             InnerState {
-                kind: Resuming(browser_action, timeout),
+                kind: Resuming(Some(browser_action), timeout),
                 mut shared,
             },
             InnerEvent::Resumed,
         ) => {
             let page = context.page.clone();
+            let interception = context.interception.clone();
+            let dialogs = context.dialogs.clone();
             let sender = context.inner_events_sender.clone();
             // We can't block on running the action, in case it synchronously
             // throws an uncaught exception blocking the evaluation indefinitely.
@@ -831,7 +1483,8 @@ async fn process_event(
             // resume (extracting the uncaught exception information).
             let action_handle = spawn(async move {
                 log::debug!("applying: {:?}", browser_action);
-                match browser_action.apply(&page).await {
+                match browser_action.apply(&page, &interception, &dialogs).await
+                {
                     Ok(_) => {
                         log::debug!("applied: {:?}", browser_action);
                     }
@@ -869,7 +1522,7 @@ async fn process_event(
                 }
             });
 
-            shared.console_entries.clear();
+            shared.logs.clear_console_entries();
             InnerState {
                 kind: Acting,
                 shared,
@@ -929,18 +1582,24 @@ async fn process_event(
             InnerEvent::ConsoleEntry(_),
         ) => {
             // NOTE: clearing between page navigations, but we could retain logs
-            shared.console_entries.clear();
+            shared.logs.clear_console_entries();
             InnerState {
                 kind: Navigating,
                 shared,
             }
         }
         (mut state, InnerEvent::ConsoleEntry(entry)) => {
-            state.shared.console_entries.push(entry);
+            state
+                .shared
+                .logs
+                .push_console_entry(entry, &context.log_limits);
             state
         }
         (mut state, InnerEvent::ExceptionThrown(exception)) => {
-            state.shared.exceptions.push(exception);
+            state
+                .shared
+                .logs
+                .push_exception(exception, &context.log_limits);
             if matches!(state.kind, Running) {
                 capture_browser_state(state, context).await?
             } else {
@@ -999,21 +1658,15 @@ async fn capture_browser_state(
     log::debug!("pausing, going into next generation...");
 
     log::debug!("taking screenshot before pause");
-    let format = ScreenshotFormat::Webp;
-    let screenshot = Screenshot {
-        data: context
-            .page
-            .screenshot(
-                ScreenshotParams::builder()
-                    .omit_background(true)
-                    .format(format)
-                    .build(),
-            )
-            .await
-            .context("take screenshot before pause")?,
-        format,
-    };
+    let started = Instant::now();
+    let screenshot = context
+        .screenshots
+        .capture(&context.page, ScreenshotFormat::Webp)
+        .await
+        .context("take screenshot before pause")?;
     state.shared.screenshot = Some(screenshot);
+    state.shared.timings.screenshot = started.elapsed();
+    state.shared.pause_requested = Some(Instant::now());
 
     context
         .page
@@ -1072,8 +1725,84 @@ fn remote_object_to_json(object: &runtime::RemoteObject) -> json::Value {
     }
 }
 
+/// Disables animations and transitions in every document, using a constructed stylesheet as
+/// there might not be any element to attach a `<style>` to when the script runs.
+const REDUCE_MOTION_SCRIPT: &str = r#"(() => {
+    const sheet = new CSSStyleSheet();
+    sheet.replaceSync(`
+        *, *::before, *::after {
+            animation-duration: 0s !important;
+            animation-delay: 0s !important;
+            transition-duration: 0s !important;
+            transition-delay: 0s !important;
+            scroll-behavior: auto !important;
+        }
+    `);
+    document.adoptedStyleSheets = [...document.adoptedStyleSheets, sheet];
+})()"#;
+
+async fn reduce_motion(page: &Page) -> Result<()> {
+    page.execute(
+        emulation::SetEmulatedMediaParams::builder()
+            .feature(emulation::MediaFeature::new(
+                "prefers-reduced-motion",
+                "reduce",
+            ))
+            .build(),
+    )
+    .await
+    .context("failed emulating reduced motion")?;
+    page.execute(page::AddScriptToEvaluateOnNewDocumentParams::new(
+        REDUCE_MOTION_SCRIPT,
+    ))
+    .await
+    .context("failed injecting animation disabling stylesheet")?;
+    Ok(())
+}
+
+/// Picks the Chrome binary to launch and checks that it supports the headless mode.
+fn chrome_binary(launch_options: &LaunchOptions) -> Result<PathBuf> {
+    let binary = match (
+        &launch_options.chrome_binary,
+        launch_options.chrome_channel,
+    ) {
+        (Some(binary), _) => {
+            if !binary.is_file() {
+                bail!("Chrome binary {} doesn't exist", binary.display());
+            }
+            binary.clone()
+        }
+        (None, Some(channel)) => channel.find_binary()?,
+        (None, None) => match provisioning::find_chrome()? {
+            Some(binary) => binary,
+            None => bail!(
+                "no Chrome or Chromium found, install one, set {} or --chrome-binary to its path, or pass --download-chrome",
+                provisioning::CHROME_BINARY_VARIABLE
+            ),
+        },
+    };
+    match ChromeVersion::of_binary(&binary) {
+        Ok(version) => {
+            log::info!(
+                "using {} ({} {})",
+                binary.display(),
+                version.product,
+                version.major
+            );
+            check_compatibility(&binary, &version, launch_options.headless)?;
+        }
+        Err(error) => log::warn!(
+            "could not detect the version of {}: {:#}",
+            binary.display(),
+            error
+        ),
+    }
+    Ok(binary)
+}
+
 fn launch_options_to_config(
     launch_options: &LaunchOptions,
+    extensions: &[PreparedExtension],
     emulation: &Emulation,
 ) -> Result<BrowserConfig> {
     let crash_dumps_dir = TempDir::new()?;
@@ -1088,12 +1817,30 @@ fn launch_options_to_config(
                 builder
             }
         };
-    apply_sandbox(BrowserConfig::builder())
-        .headless_mode(if launch_options.headless {
-            HeadlessMode::New
-        } else {
-            HeadlessMode::False
-        })
+    let apply_extensions =
+        |builder: BrowserConfigBuilder| -> BrowserConfigBuilder {
+            if extensions.is_empty() {
+                return builder;
+            }
+            let extensions = extensions
+                .iter()
+                .map(|extension| extension.path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+            // Chrome only honors the last `--load-extension` flag, so all extensions are
+            // passed as a single comma-separated one.
+            builder
+                .extension(&extensions)
+                .arg(format!("--disable-extensions-except={}", extensions))
+        };
+    let builder = BrowserConfig::builder()
+        .chrome_executable(chrome_binary(launch_options)?);
+    apply_extensions(apply_sandbox(builder))
+        .headless_mode(
+            launch_options
+                .headless
+                .map_or(HeadlessMode::False, HeadlessMode::from),
+        )
         .window_size(emulation.width as u32, emulation.height as u32)
         .user_data_dir(launch_options.user_data_directory.clone())
         .args([
@@ -1112,6 +1859,14 @@ fn launch_options_to_config(
             "--no-pings",
             "--disable-crash-reporter",
         ])
+        .args(launch_options.gpu.launch_args().iter().copied())
+        .args(
+            launch_options
+                .fake_media
+                .as_ref()
+                .map(FakeMedia::launch_args)
+                .unwrap_or_default(),
+        )
         .build()
         .map_err(|s| anyhow!(s))
 }
@@ -0,0 +1,144 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{Context, Result};
+use chromiumoxide::Page;
+use chromiumoxide::cdp::browser_protocol::accessibility::{
+    AxNode, AxValue, GetFullAxTreeParams,
+};
+use serde::Serialize;
+use serde_json as json;
+
+/// A node of the accessibility tree, as exposed to assistive technologies, with ignored
+/// nodes (e.g. layout containers) left out and their children lifted up.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AccessibilityNode {
+    pub role: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<json::Value>,
+    /// States and relations like `focused`, `disabled`, `checked` or `expanded`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: BTreeMap<String, json::Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<AccessibilityNode>,
+}
+
+/// Takes the accessibility tree of the page's main frame, or none if it's empty.
+pub async fn accessibility_tree(
+    page: &Page,
+) -> Result<Option<AccessibilityNode>> {
+    let nodes = page
+        .execute(GetFullAxTreeParams::default())
+        .await
+        .context("failed getting the accessibility tree")?
+        .result
+        .nodes;
+    Ok(build_tree(&nodes))
+}
+
+fn build_tree(nodes: &[AxNode]) -> Option<AccessibilityNode> {
+    let by_id: HashMap<&str, &AxNode> = nodes
+        .iter()
+        .map(|node| (node.node_id.inner().as_str(), node))
+        .collect();
+    let root = nodes.iter().find(|node| node.parent_id.is_none())?;
+    build_nodes(root, &by_id).into_iter().next()
+}
+
+/// The node itself, or its children if it's ignored.
+fn build_nodes(
+    node: &AxNode,
+    by_id: &HashMap<&str, &AxNode>,
+) -> Vec<AccessibilityNode> {
+    let children: Vec<AccessibilityNode> = node
+        .child_ids
+        .iter()
+        .flatten()
+        .filter_map(|id| by_id.get(id.inner().as_str()))
+        .flat_map(|child| build_nodes(child, by_id))
+        .collect();
+    if node.ignored {
+        return children;
+    }
+    vec![AccessibilityNode {
+        role: node
+            .role
+            .as_ref()
+            .and_then(value_string)
+            .unwrap_or_else(|| "unknown".to_string()),
+        name: node
+            .name
+            .as_ref()
+            .and_then(value_string)
+            .filter(|name| !name.is_empty()),
+        value: node.value.as_ref().and_then(|value| value.value.clone()),
+        properties: node
+            .properties
+            .iter()
+            .flatten()
+            .filter_map(|property| {
+                let name = json::to_value(&property.name).ok()?;
+                Some((
+                    name.as_str()?.to_string(),
+                    property.value.value.clone()?,
+                ))
+            })
+            .collect(),
+        children,
+    }]
+}
+
+fn value_string(value: &AxValue) -> Option<String> {
+    value.value.as_ref()?.as_str().map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chromiumoxide::cdp::browser_protocol::accessibility::{
+        AxNodeId, AxValueType,
+    };
+
+    fn node(
+        id: &str,
+        parent: Option<&str>,
+        children: &[&str],
+        role: &str,
+        name: &str,
+    ) -> AxNode {
+        let string = |value: &str| {
+            let mut ax_value = AxValue::new(AxValueType::String);
+            ax_value.value = Some(json::json!(value));
+            ax_value
+        };
+        let mut node = AxNode::new(AxNodeId::new(id), role == "none");
+        node.parent_id = parent.map(AxNodeId::new);
+        node.child_ids =
+            Some(children.iter().map(|id| AxNodeId::new(*id)).collect());
+        node.role = Some(string(role));
+        node.name = Some(string(name));
+        node
+    }
+
+    #[test]
+    fn test_build_tree_lifts_ignored_nodes() {
+        let nodes = vec![
+            node("1", None, &["2", "4"], "RootWebArea", "Shop"),
+            node("2", Some("1"), &["3"], "none", ""),
+            node("3", Some("2"), &[], "button", "Log out"),
+            node("4", Some("1"), &[], "heading", "Cart"),
+        ];
+        let tree = build_tree(&nodes).unwrap();
+        assert_eq!(tree.role, "RootWebArea");
+        let children: Vec<(&str, Option<&str>)> = tree
+            .children
+            .iter()
+            .map(|child| (child.role.as_str(), child.name.as_deref()))
+            .collect();
+        assert_eq!(
+            children,
+            vec![("button", Some("Log out")), ("heading", Some("Cart"))]
+        );
+    }
+}
@@ -1,15 +1,81 @@
 use std::time::Duration;
 
-use anyhow::{Result, anyhow, bail};
+use anyhow::{Context, Result, anyhow, bail};
 use chromiumoxide::Page;
-use chromiumoxide::cdp::browser_protocol::{input, page};
-use serde::Serialize;
-use tokio::time::sleep;
+use chromiumoxide::cdp::browser_protocol::{
+    dom, emulation, input, network, page,
+};
+use chromiumoxide::cdp::js_protocol::runtime;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json as json;
+use tokio::time::{sleep, timeout};
 
+use crate::browser::dialogs::{DialogPolicy, Dialogs};
+use crate::browser::faults::FaultRule;
+use crate::browser::interception::Interception;
 use crate::browser::keys::key_name;
+use crate::browser::viewports::{ViewportProfile, emulate_viewport};
 use crate::geometry::Point;
 
-#[derive(Clone, Debug, Serialize)]
+/// Number of mouse moves between pressing and releasing the button in a drag.
+const DRAG_STEPS: u32 = 10;
+const DRAG_STEP_DELAY: Duration = Duration::from_millis(20);
+
+/// How long advancing the clock may take in real time, however far it's advanced.
+const ADVANCE_CLOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Selects an option and notifies the page like a user picking it would.
+const SELECT_OPTION_FUNCTION: &str = r#"function(value) {
+    if (!(this instanceof HTMLSelectElement)) {
+        throw new Error("not a select element");
+    }
+    if (!Array.from(this.options).some((option) => option.value === value)) {
+        throw new Error(`no option with value ${JSON.stringify(value)}`);
+    }
+    this.value = value;
+    this.dispatchEvent(new Event("input", { bubbles: true }));
+    this.dispatchEvent(new Event("change", { bubbles: true }));
+}"#;
+
+/// Called on the node at the point of a click, with the name and content of the element
+/// the click was generated for. Returns `null` when the node is the element or inside it,
+/// or the current center of the element if it moved, or `"missing"` when it can't be
+/// found in the document anymore.
+const CLICK_TARGET_FUNCTION: &str = r#"function(name, content) {
+    const matches = (element) =>
+        element.nodeName === name &&
+        (content === null ||
+            (element.textContent ?? "").trim().replace(/\s+/g, " ") === content);
+    for (
+        let element = this.nodeType === Node.ELEMENT_NODE ? this : this.parentElement;
+        element;
+        element = element.parentElement ?? element.getRootNode().host ?? null
+    ) {
+        if (matches(element)) return null;
+    }
+    // Points are relative to the top frame, where the element is looked up.
+    if (window !== window.top) return "missing";
+    for (const element of document.querySelectorAll(name)) {
+        if (!matches(element)) continue;
+        const rect = element.getBoundingClientRect();
+        if (rect.width > 0 && rect.height > 0) {
+            return { x: rect.left + rect.width / 2, y: rect.top + rect.height / 2 };
+        }
+    }
+    return "missing";
+}"#;
+
+const SET_CHECKED_FUNCTION: &str = r#"function(checked) {
+    if (!(this instanceof HTMLInputElement) || !["checkbox", "radio"].includes(this.type)) {
+        throw new Error("not a checkbox or radio button");
+    }
+    if (this.checked !== checked) {
+        this.click();
+    }
+}"#;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum BrowserAction {
     Back,
     Forward,
@@ -18,6 +84,44 @@ pub enum BrowserAction {
         content: Option<String>,
         point: Point,
     },
+    DoubleClick {
+        name: String,
+        content: Option<String>,
+        point: Point,
+    },
+    /// Clicks with the right mouse button, e.g. to open a context menu.
+    RightClick {
+        name: String,
+        content: Option<String>,
+        point: Point,
+    },
+    /// Moves the mouse over a point without pressing any button, e.g. to open a menu
+    /// shown on hover.
+    Hover {
+        point: Point,
+    },
+    /// Presses the left mouse button at `from`, moves to `to` in a few steps, and releases
+    /// it there.
+    DragAndDrop {
+        from: Point,
+        to: Point,
+    },
+    /// Focuses the first element matching a CSS selector.
+    Focus {
+        selector: String,
+    },
+    /// Selects the option with `value` of a `select` element, as if picked from its
+    /// dropdown.
+    SelectOption {
+        selector: String,
+        value: String,
+    },
+    /// Checks or unchecks a checkbox or radio button by clicking it, unless it's already
+    /// in that state.
+    SetChecked {
+        selector: String,
+        checked: bool,
+    },
     TypeText {
         text: String,
         delay_millis: u64,
@@ -34,10 +138,64 @@ pub enum BrowserAction {
         distance: f64,
     },
     Reload,
+    /// Emulates losing the network connection, until `GoOnline`.
+    GoOffline,
+    GoOnline,
+    /// Emulates a connection with the given latency and throughput (in bytes per second,
+    /// or unlimited if negative), or none if offline. `GoOnline` lifts the emulation.
+    SetNetworkConditions {
+        offline: bool,
+        latency_millis: f64,
+        throughput: f64,
+    },
+    /// Replaces the rules of the faults injected into the page's requests, e.g. to make
+    /// the API fail from now on, or none to stop injecting faults.
+    InjectFaults {
+        rules: Vec<FaultRule>,
+    },
+    /// Enables or disables the instrumentation of the scripts loaded by subsequent
+    /// navigations, e.g. to compare the app's behavior with and without it. Scripts
+    /// already loaded are left as they are.
+    SetInstrumentation {
+        enabled: bool,
+    },
+    /// Replaces how the `alert`, `confirm` and `prompt` dialogs opened from now on are
+    /// answered.
+    SetDialogPolicy {
+        policy: DialogPolicy,
+    },
+    /// Switches to another device profile, e.g. to check a responsive layout mid-run.
+    SetViewport {
+        profile: ViewportProfile,
+    },
+    /// Fast-forwards the page's clock, running the timers due in the meantime, e.g. to
+    /// get past a debounce or a session expiry without waiting. The page's time then
+    /// stands still until the clock is advanced again.
+    AdvanceClock {
+        millis: u64,
+    },
+    /// Sends a raw Chrome DevTools Protocol command to the page, e.g. `Network.setCookie`
+    /// in a specification's setup.
+    CdpCommand {
+        method: String,
+        params: json::Value,
+    },
+    /// Restores the cookies, storage and URL of a state saved earlier in the run, to
+    /// explore other actions from there. The checkpoint itself is kept by the runner, and
+    /// passed along to the browser separately.
+    RestoreCheckpoint {
+        id: u64,
+        url: String,
+    },
 }
 
 impl BrowserAction {
-    pub async fn apply(&self, page: &Page) -> Result<()> {
+    pub async fn apply(
+        &self,
+        page: &Page,
+        interception: &Interception,
+        dialogs: &Dialogs,
+    ) -> Result<()> {
         match self {
             BrowserAction::Back => {
                 let history =
@@ -76,6 +234,72 @@ impl BrowserAction {
             BrowserAction::Reload => {
                 page.reload().await?;
             }
+            BrowserAction::GoOffline
+            | BrowserAction::GoOnline
+            | BrowserAction::SetNetworkConditions { .. } => {
+                let (offline, latency_millis, throughput) = match self {
+                    BrowserAction::SetNetworkConditions {
+                        offline,
+                        latency_millis,
+                        throughput,
+                    } => (*offline, *latency_millis, *throughput),
+                    _ => (matches!(self, BrowserAction::GoOffline), 0.0, -1.0),
+                };
+                // Its replacement, splitting request emulation from the `navigator.onLine`
+                // override, needs a recent Chrome.
+                #[allow(deprecated)]
+                page.execute(network::EmulateNetworkConditionsParams::new(
+                    offline,
+                    latency_millis,
+                    throughput,
+                    throughput,
+                ))
+                .await?;
+            }
+            BrowserAction::InjectFaults { rules } => {
+                interception.set_fault_rules(rules.clone()).await?;
+            }
+            BrowserAction::SetInstrumentation { enabled } => {
+                interception.set_instrumentation(*enabled).await?;
+                // Otherwise the scripts instrumented so far would be loaded from the cache.
+                page.execute(network::ClearBrowserCacheParams {}).await?;
+            }
+            BrowserAction::SetDialogPolicy { policy } => {
+                dialogs.set_policy(policy.clone());
+            }
+            BrowserAction::SetViewport { profile } => {
+                emulate_viewport(page, profile).await?;
+            }
+            BrowserAction::AdvanceClock { millis } => {
+                let mut expired = page
+                    .event_listener::<emulation::EventVirtualTimeBudgetExpired>(
+                    )
+                    .await?;
+                page.execute(
+                    emulation::SetVirtualTimePolicyParams::builder()
+                        .policy(emulation::VirtualTimePolicy::Advance)
+                        .budget(*millis as f64)
+                        .build()
+                        .map_err(|err| anyhow!(err))?,
+                )
+                .await?;
+                // Virtual time is paused again once the budget is spent.
+                timeout(ADVANCE_CLOCK_TIMEOUT, expired.next())
+                    .await
+                    .with_context(|| {
+                        format!("advancing the clock by {}ms timed out", millis)
+                    })?;
+            }
+            BrowserAction::CdpCommand { method, params } => {
+                page.execute(RawCommand {
+                    method: method.clone(),
+                    params: params.clone(),
+                })
+                .await?;
+            }
+            BrowserAction::RestoreCheckpoint { id, .. } => {
+                bail!("checkpoint {} wasn't passed along to restore", id);
+            }
             BrowserAction::ScrollUp { origin, distance } => {
                 page.execute(
                     input::SynthesizeScrollGestureParams::builder()
@@ -100,8 +324,119 @@ impl BrowserAction {
                 )
                 .await?;
             }
-            BrowserAction::Click { point, .. } => {
-                page.click((*point).into()).await?;
+            BrowserAction::Click {
+                name,
+                content,
+                point,
+            } => {
+                let point = current_click_point(page, name, content, *point)
+                    .await
+                    .unwrap_or_else(|error| {
+                        log::debug!(
+                            "could not check the element at {:?}: {:#}",
+                            point,
+                            error
+                        );
+                        *point
+                    });
+                page.click(point.into()).await?;
+            }
+            BrowserAction::Hover { point } => {
+                page.execute(
+                    input::DispatchMouseEventParams::builder()
+                        .r#type(input::DispatchMouseEventType::MouseMoved)
+                        .x(point.x)
+                        .y(point.y)
+                        .build()
+                        .map_err(|err| anyhow!(err))?,
+                )
+                .await?;
+            }
+            BrowserAction::DoubleClick { point, .. } => {
+                // Browsers recognize a double click by the click count of the second
+                // press.
+                for click_count in [1, 2] {
+                    for r#type in [
+                        input::DispatchMouseEventType::MousePressed,
+                        input::DispatchMouseEventType::MouseReleased,
+                    ] {
+                        page.execute(mouse_event(
+                            r#type,
+                            *point,
+                            input::MouseButton::Left,
+                            click_count,
+                        )?)
+                        .await?;
+                    }
+                }
+            }
+            BrowserAction::RightClick { point, .. } => {
+                for r#type in [
+                    input::DispatchMouseEventType::MousePressed,
+                    input::DispatchMouseEventType::MouseReleased,
+                ] {
+                    page.execute(mouse_event(
+                        r#type,
+                        *point,
+                        input::MouseButton::Right,
+                        1,
+                    )?)
+                    .await?;
+                }
+            }
+            BrowserAction::DragAndDrop { from, to } => {
+                page.execute(mouse_event(
+                    input::DispatchMouseEventType::MousePressed,
+                    *from,
+                    input::MouseButton::Left,
+                    1,
+                )?)
+                .await?;
+                // Pages start dragging once the mouse has moved a little, so there are
+                // moves on the way rather than a jump to the target.
+                for step in 1..=DRAG_STEPS {
+                    let t = step as f64 / DRAG_STEPS as f64;
+                    let point = Point {
+                        x: from.x + (to.x - from.x) * t,
+                        y: from.y + (to.y - from.y) * t,
+                    };
+                    sleep(DRAG_STEP_DELAY).await;
+                    page.execute(mouse_event(
+                        input::DispatchMouseEventType::MouseMoved,
+                        point,
+                        input::MouseButton::Left,
+                        1,
+                    )?)
+                    .await?;
+                }
+                page.execute(mouse_event(
+                    input::DispatchMouseEventType::MouseReleased,
+                    *to,
+                    input::MouseButton::Left,
+                    1,
+                )?)
+                .await?;
+            }
+            BrowserAction::Focus { selector } => {
+                page.find_element(selector.as_str()).await?.focus().await?;
+            }
+            BrowserAction::SelectOption { selector, value } => {
+                call_on_element(
+                    page,
+                    selector,
+                    SELECT_OPTION_FUNCTION,
+                    json::json!(value),
+                )
+                .await?;
+            }
+            BrowserAction::SetChecked { selector, checked } => {
+                call_on_element(
+                    page,
+                    selector,
+                    SET_CHECKED_FUNCTION,
+                    json::json!(checked),
+                )
+                .await?;
             }
             BrowserAction::TypeText { text, delay_millis } => {
                 let delay = Duration::from_millis(*delay_millis);
@@ -140,3 +475,165 @@ impl BrowserAction {
         Ok(())
     }
 }
+
+/// Where to click the element with `name` and `content` expected at `point`: the same
+/// point if the element is still there, or its current center if the page was laid out
+/// differently since the point was found, so that the click doesn't land on another
+/// element.
+async fn current_click_point(
+    page: &Page,
+    name: &str,
+    content: &Option<String>,
+    point: Point,
+) -> Result<Point> {
+    let node = page
+        .execute(dom::GetNodeForLocationParams::new(
+            point.x as i64,
+            point.y as i64,
+        ))
+        .await?
+        .result;
+    let object_id = page
+        .execute(
+            dom::ResolveNodeParams::builder()
+                .backend_node_id(node.backend_node_id)
+                .build(),
+        )
+        .await?
+        .result
+        .object
+        .object_id
+        .context("node at the click point has no object")?;
+    let result = page
+        .execute(
+            runtime::CallFunctionOnParams::builder()
+                .function_declaration(CLICK_TARGET_FUNCTION)
+                .object_id(object_id)
+                .argument(
+                    runtime::CallArgument::builder()
+                        .value(json::json!(name))
+                        .build(),
+                )
+                .argument(
+                    runtime::CallArgument::builder()
+                        .value(json::json!(content))
+                        .build(),
+                )
+                .return_by_value(true)
+                .build()
+                .map_err(|err| anyhow!(err))?,
+        )
+        .await?
+        .result;
+    if let Some(exception) = result.exception_details {
+        bail!("{}", exception.text);
+    }
+    match result.result.value {
+        None | Some(json::Value::Null) => Ok(point),
+        Some(json::Value::String(_)) => {
+            log::debug!(
+                "{} element {:?} is gone from {:?}, clicking there anyway",
+                name,
+                content,
+                point
+            );
+            Ok(point)
+        }
+        Some(value) => {
+            let moved: Point = json::from_value(value)?;
+            log::info!(
+                "{} element {:?} moved from {:?} to {:?}, clicking it there",
+                name,
+                content,
+                point,
+                moved
+            );
+            Ok(moved)
+        }
+    }
+}
+
+/// Calls `function` on the first element matching `selector`, failing if it throws.
+async fn call_on_element(
+    page: &Page,
+    selector: &str,
+    function: &str,
+    argument: json::Value,
+) -> Result<()> {
+    let element = page.find_element(selector).await?;
+    let result = page
+        .execute(
+            runtime::CallFunctionOnParams::builder()
+                .function_declaration(function)
+                .object_id(element.remote_object_id.clone())
+                .argument(
+                    runtime::CallArgument::builder().value(argument).build(),
+                )
+                .await_promise(true)
+                .build()
+                .map_err(|err| anyhow!(err))?,
+        )
+        .await?
+        .result;
+    if let Some(exception) = result.exception_details {
+        bail!(
+            "failed on element {}: {}",
+            selector,
+            exception
+                .exception
+                .and_then(|exception| exception.description)
+                .unwrap_or(exception.text)
+        );
+    }
+    Ok(())
+}
+
+/// A CDP command given by name, with its parameters as JSON.
+#[derive(Debug)]
+struct RawCommand {
+    method: String,
+    params: json::Value,
+}
+
+impl Serialize for RawCommand {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        self.params.serialize(serializer)
+    }
+}
+
+impl chromiumoxide::Method for RawCommand {
+    fn identifier(&self) -> chromiumoxide::types::MethodId {
+        self.method.clone().into()
+    }
+}
+
+impl chromiumoxide::Command for RawCommand {
+    type Response = json::Value;
+}
+
+/// A mouse event at a point, with `button` pressed.
+fn mouse_event(
+    r#type: input::DispatchMouseEventType,
+    point: Point,
+    button: input::MouseButton,
+    click_count: i64,
+) -> Result<input::DispatchMouseEventParams> {
+    let buttons = match button {
+        input::MouseButton::Left => 1,
+        input::MouseButton::Right => 2,
+        input::MouseButton::Middle => 4,
+        _ => 0,
+    };
+    input::DispatchMouseEventParams::builder()
+        .r#type(r#type)
+        .x(point.x)
+        .y(point.y)
+        .button(button)
+        .buttons(buttons)
+        .click_count(click_count)
+        .build()
+        .map_err(|err| anyhow!(err))
+}
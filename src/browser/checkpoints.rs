@@ -0,0 +1,345 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use anyhow::{Context, Result, anyhow};
+use chromiumoxide::Page;
+use chromiumoxide::cdp::browser_protocol::{network, page};
+use chromiumoxide::cdp::js_protocol::debugger::CallFrameId;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json as json;
+
+use crate::browser::evaluation::evaluate_expression_in_debugger;
+
+/// Dumps the storage of the page's origin, which throws for opaque origins like
+/// `about:blank`.
+const STORAGE_EXPRESSION: &str = r#"(() => {
+    const dump = (storage) => {
+        const entries = {};
+        for (let i = 0; i < storage.length; i++) {
+            const key = storage.key(i);
+            entries[key] = storage.getItem(key);
+        }
+        return entries;
+    };
+    try {
+        return { local: dump(window.localStorage), session: dump(window.sessionStorage) };
+    } catch (error) {
+        return { local: {}, session: {} };
+    }
+})()"#;
+
+/// The file in a run's directory the checkpoints saved are written to, when enabled.
+pub const CHECKPOINTS_FILE_NAME: &str = "checkpoints.jsonl";
+
+/// Restores the storage of a checkpoint in the next document of its origin, before the
+/// page's own scripts run.
+const RESTORE_STORAGE_SCRIPT: &str = r#"((checkpoint) => {
+    if (window !== window.top || location.origin !== new URL(checkpoint.url).origin) {
+        return;
+    }
+    const restore = (storage, entries) => {
+        storage.clear();
+        for (const [key, value] of Object.entries(entries)) {
+            storage.setItem(key, value);
+        }
+    };
+    try {
+        restore(window.localStorage, checkpoint.localStorage);
+        restore(window.sessionStorage, checkpoint.sessionStorage);
+    } catch (error) {}
+})"#;
+
+/// What the session of a page depends on at some state: its URL, cookies and storage.
+/// Restoring it takes the exploration back to that state, or close to it, without
+/// repeating the actions leading there, e.g. logging in.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Checkpoint {
+    pub url: String,
+    pub cookies: Vec<network::Cookie>,
+    pub local_storage: BTreeMap<String, String>,
+    pub session_storage: BTreeMap<String, String>,
+}
+
+/// A checkpoint along with the id that actions restoring it refer to it by.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedCheckpoint {
+    pub id: u64,
+    #[serde(flatten)]
+    pub checkpoint: Checkpoint,
+}
+
+/// The checkpoints of a run by id. Actions restoring a checkpoint only carry its id and
+/// URL, which keeps its cookies and storage out of traces and reports.
+#[derive(Debug, Default)]
+pub struct CheckpointStore {
+    saved: HashMap<u64, Checkpoint>,
+    next_id: u64,
+}
+
+impl CheckpointStore {
+    /// A store starting with checkpoints saved earlier, e.g. by a run being replayed.
+    pub fn new(saved: Vec<SavedCheckpoint>) -> Self {
+        CheckpointStore {
+            next_id: saved.iter().map(|saved| saved.id + 1).max().unwrap_or(0),
+            saved: saved
+                .into_iter()
+                .map(|saved| (saved.id, saved.checkpoint))
+                .collect(),
+        }
+    }
+
+    /// Keeps a checkpoint, returning its id.
+    pub fn insert(&mut self, checkpoint: Checkpoint) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.saved.insert(id, checkpoint);
+        id
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Checkpoint> {
+        self.saved.get(&id)
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        self.saved.remove(&id);
+    }
+}
+
+#[derive(Deserialize)]
+struct Storage {
+    local: BTreeMap<String, String>,
+    session: BTreeMap<String, String>,
+}
+
+/// Saves a checkpoint of a page paused at a state.
+pub async fn capture_checkpoint(
+    page: &Page,
+    call_frame_id: &CallFrameId,
+) -> Result<Checkpoint> {
+    let url: String = evaluate_expression_in_debugger(
+        page,
+        call_frame_id,
+        "window.location.href",
+    )
+    .await?;
+    let storage: Storage = evaluate_expression_in_debugger(
+        page,
+        call_frame_id,
+        STORAGE_EXPRESSION,
+    )
+    .await
+    .context("failed reading storage")?;
+    let cookies = page
+        .execute(network::GetCookiesParams::default())
+        .await
+        .context("failed getting cookies")?
+        .result
+        .cookies;
+    Ok(Checkpoint {
+        url,
+        cookies,
+        local_storage: storage.local,
+        session_storage: storage.session,
+    })
+}
+
+impl Checkpoint {
+    /// A checkpoint of only a URL, without cookies or storage.
+    pub fn at(url: &str) -> Self {
+        Checkpoint {
+            url: url.to_string(),
+            cookies: vec![],
+            local_storage: BTreeMap::new(),
+            session_storage: BTreeMap::new(),
+        }
+    }
+
+    /// Replaces the cookies and storage with those of the checkpoint, and navigates to its
+    /// URL.
+    pub async fn restore(&self, page: &Page) -> Result<()> {
+        page.execute(network::ClearBrowserCookiesParams {}).await?;
+        let cookies: Vec<network::CookieParam> =
+            self.cookies.iter().map(cookie_param).collect();
+        if !cookies.is_empty() {
+            page.execute(network::SetCookiesParams::new(cookies))
+                .await
+                .context("failed restoring cookies")?;
+        }
+
+        let script = page
+            .execute(page::AddScriptToEvaluateOnNewDocumentParams::new(
+                format!(
+                    "{}({});",
+                    RESTORE_STORAGE_SCRIPT,
+                    json::json!({
+                        "url": self.url,
+                        "localStorage": self.local_storage,
+                        "sessionStorage": self.session_storage,
+                    })
+                ),
+            ))
+            .await?
+            .result
+            .identifier;
+        let navigation = page
+            .execute(
+                page::NavigateParams::builder()
+                    .url(&self.url)
+                    .build()
+                    .map_err(|err| anyhow!(err))?,
+            )
+            .await;
+        page.execute(page::RemoveScriptToEvaluateOnNewDocumentParams::new(
+            script,
+        ))
+        .await?;
+        navigation.with_context(|| {
+            format!("failed navigating to checkpoint at {}", self.url)
+        })?;
+        Ok(())
+    }
+}
+
+fn cookie_param(cookie: &network::Cookie) -> network::CookieParam {
+    network::CookieParam {
+        name: cookie.name.clone(),
+        value: cookie.value.clone(),
+        url: None,
+        domain: Some(cookie.domain.clone()),
+        path: Some(cookie.path.clone()),
+        secure: Some(cookie.secure),
+        http_only: Some(cookie.http_only),
+        same_site: cookie.same_site.clone(),
+        expires: (!cookie.session)
+            .then(|| network::TimeSinceEpoch::new(cookie.expires)),
+        priority: Some(cookie.priority.clone()),
+        same_party: None,
+        source_scheme: Some(cookie.source_scheme.clone()),
+        source_port: Some(cookie.source_port),
+        partition_key: cookie.partition_key.clone(),
+    }
+}
+
+/// When to save checkpoints, and branch from them.
+#[derive(Clone, Debug)]
+pub struct Checkpointing {
+    /// Number of states between two decisions to either save a checkpoint or branch from
+    /// a saved one.
+    pub interval: u64,
+    /// Number of checkpoints kept, dropping the oldest ones.
+    pub capacity: usize,
+}
+
+/// The ids and URLs of the checkpoints to branch off from, kept in a `CheckpointStore`.
+#[derive(Debug)]
+pub struct Checkpoints {
+    checkpointing: Checkpointing,
+    saved: VecDeque<(u64, String)>,
+}
+
+/// What to do at a checkpoint interval.
+#[derive(Debug, PartialEq)]
+pub enum CheckpointDecision {
+    Save,
+    Restore { id: u64, url: String },
+}
+
+impl Checkpoints {
+    pub fn new(checkpointing: Checkpointing) -> Self {
+        Checkpoints {
+            checkpointing,
+            saved: VecDeque::new(),
+        }
+    }
+
+    /// Decides at every interval whether to save the current state or, as often, branch
+    /// off from a random checkpoint saved earlier.
+    pub fn decide(
+        &self,
+        step_index: u64,
+        rng: &mut impl Rng,
+    ) -> Option<CheckpointDecision> {
+        if step_index == 0
+            || !step_index.is_multiple_of(self.checkpointing.interval.max(1))
+        {
+            return None;
+        }
+        if self.saved.is_empty() || rng.random_bool(0.5) {
+            return Some(CheckpointDecision::Save);
+        }
+        let index = rng.random_range(0..self.saved.len());
+        let (id, url) = self.saved[index].clone();
+        Some(CheckpointDecision::Restore { id, url })
+    }
+
+    /// Adds a checkpoint to branch off from, returning the id of the oldest one if it's
+    /// dropped to make room.
+    pub fn save(&mut self, id: u64, url: String) -> Option<u64> {
+        let dropped = if self.saved.len() >= self.checkpointing.capacity.max(1)
+        {
+            self.saved.pop_front().map(|(id, _)| id)
+        } else {
+            None
+        };
+        self.saved.push_back((id, url));
+        dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn test_checkpoint_decisions() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut checkpoints = Checkpoints::new(Checkpointing {
+            interval: 10,
+            capacity: 2,
+        });
+        assert_eq!(checkpoints.decide(0, &mut rng), None);
+        assert_eq!(checkpoints.decide(5, &mut rng), None);
+        assert_eq!(
+            checkpoints.decide(10, &mut rng),
+            Some(CheckpointDecision::Save)
+        );
+
+        assert_eq!(checkpoints.save(0, "http://localhost/a".to_string()), None);
+        assert_eq!(checkpoints.save(1, "http://localhost/b".to_string()), None);
+        assert_eq!(
+            checkpoints.save(2, "http://localhost/c".to_string()),
+            Some(0)
+        );
+        let restored: Vec<String> = (1..100)
+            .filter_map(|index| {
+                match checkpoints.decide(index * 10, &mut rng) {
+                    Some(CheckpointDecision::Restore { url, .. }) => Some(url),
+                    _ => None,
+                }
+            })
+            .collect();
+        assert!(!restored.is_empty());
+        assert!(restored.iter().all(|url| url != "http://localhost/a"));
+    }
+
+    #[test]
+    fn test_checkpoint_store() {
+        let mut store = CheckpointStore::new(vec![SavedCheckpoint {
+            id: 3,
+            checkpoint: Checkpoint::at("http://localhost/a"),
+        }]);
+        assert_eq!(
+            store.get(3).map(|checkpoint| checkpoint.url.as_str()),
+            Some("http://localhost/a")
+        );
+        // Ids of checkpoints saved earlier aren't reused.
+        let id = store.insert(Checkpoint::at("http://localhost/b"));
+        assert_eq!(id, 4);
+        store.remove(3);
+        assert_eq!(store.get(3), None);
+        assert!(store.get(4).is_some());
+    }
+}
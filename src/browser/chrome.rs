@@ -0,0 +1,271 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+use anyhow::{Context, Result, bail};
+use chromiumoxide::browser::HeadlessMode;
+
+/// The first major version of Chrome without the old headless mode, which moved to the
+/// separate `chrome-headless-shell` binary.
+const OLD_HEADLESS_REMOVED_IN: u32 = 132;
+/// The first major version of Chrome supporting `--headless=new`.
+const NEW_HEADLESS_ADDED_IN: u32 = 112;
+
+/// A Chrome release channel, to pick the installed binary of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChromeChannel {
+    Stable,
+    Beta,
+    Dev,
+    Canary,
+}
+
+impl FromStr for ChromeChannel {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "stable" => Ok(ChromeChannel::Stable),
+            "beta" => Ok(ChromeChannel::Beta),
+            "dev" => Ok(ChromeChannel::Dev),
+            "canary" => Ok(ChromeChannel::Canary),
+            other => Err(format!(
+                "unknown channel {:?}, expected stable, beta, dev or canary",
+                other
+            )),
+        }
+    }
+}
+
+impl ChromeChannel {
+    /// Executable names on the `PATH`, in order of preference.
+    fn executable_names(&self) -> &'static [&'static str] {
+        match self {
+            ChromeChannel::Stable => {
+                &["google-chrome-stable", "google-chrome", "chrome"]
+            }
+            ChromeChannel::Beta => &["google-chrome-beta"],
+            ChromeChannel::Dev => &["google-chrome-dev"],
+            ChromeChannel::Canary => {
+                &["google-chrome-canary", "google-chrome-unstable"]
+            }
+        }
+    }
+
+    fn application_paths(&self) -> &'static [&'static str] {
+        match self {
+            ChromeChannel::Stable => &[
+                "/Applications/Google Chrome.app/Contents/MacOS/Google Chrome",
+                "/opt/google/chrome/chrome",
+                r"C:\Program Files\Google\Chrome\Application\chrome.exe",
+            ],
+            ChromeChannel::Beta => &[
+                "/Applications/Google Chrome Beta.app/Contents/MacOS/Google Chrome Beta",
+                "/opt/google/chrome-beta/chrome",
+                r"C:\Program Files\Google\Chrome Beta\Application\chrome.exe",
+            ],
+            ChromeChannel::Dev => &[
+                "/Applications/Google Chrome Dev.app/Contents/MacOS/Google Chrome Dev",
+                "/opt/google/chrome-unstable/chrome",
+                r"C:\Program Files\Google\Chrome Dev\Application\chrome.exe",
+            ],
+            ChromeChannel::Canary => &[
+                "/Applications/Google Chrome Canary.app/Contents/MacOS/Google Chrome Canary",
+                "/opt/google/chrome-canary/chrome",
+            ],
+        }
+    }
+
+    /// Finds the installed binary of this channel.
+    pub fn find_binary(&self) -> Result<PathBuf> {
+        let on_path = std::env::var_os("PATH")
+            .map(|paths| std::env::split_paths(&paths).collect::<Vec<_>>())
+            .unwrap_or_default();
+        self.executable_names()
+            .iter()
+            .flat_map(|name| on_path.iter().map(move |path| path.join(name)))
+            .chain(self.application_paths().iter().map(PathBuf::from))
+            .find(|path| path.is_file())
+            .with_context(|| {
+                format!(
+                    "no Chrome {:?} installation found (looked for {} on the PATH and in the default install locations), use --chrome-binary to pick one",
+                    self,
+                    self.executable_names().join(", ")
+                )
+            })
+    }
+}
+
+/// The headless mode variant to launch Chrome in. They differ in rendering, supported
+/// features and timing, which regularly changes test outcomes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HeadlessVariant {
+    /// `--headless=new`: the regular browser without a window.
+    #[default]
+    New,
+    /// `--headless=old`: the legacy headless implementation, only available in
+    /// `chrome-headless-shell` since Chrome 132.
+    Old,
+}
+
+impl FromStr for HeadlessVariant {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "new" => Ok(HeadlessVariant::New),
+            "old" => Ok(HeadlessVariant::Old),
+            other => Err(format!(
+                "unknown headless mode {:?}, expected new or old",
+                other
+            )),
+        }
+    }
+}
+
+impl From<HeadlessVariant> for HeadlessMode {
+    fn from(variant: HeadlessVariant) -> Self {
+        match variant {
+            HeadlessVariant::New => HeadlessMode::New,
+            HeadlessVariant::Old => HeadlessMode::True,
+        }
+    }
+}
+
+/// The version reported by a Chrome binary, e.g. "Google Chrome 131.0.6778.85".
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChromeVersion {
+    pub product: String,
+    pub major: u32,
+}
+
+impl ChromeVersion {
+    pub fn of_binary(binary: &Path) -> Result<Self> {
+        let output = Command::new(binary)
+            .arg("--version")
+            .output()
+            .with_context(|| format!("failed running {}", binary.display()))?;
+        if !output.status.success() {
+            bail!(
+                "{} --version failed: {}",
+                binary.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let version = String::from_utf8_lossy(&output.stdout);
+        Self::parse(version.trim()).with_context(|| {
+            format!("unexpected version of {}: {:?}", binary.display(), version)
+        })
+    }
+
+    fn parse(version: &str) -> Option<Self> {
+        let (product, number) = version.rsplit_once(' ')?;
+        let major = number.split('.').next()?.parse().ok()?;
+        Some(ChromeVersion {
+            product: product.to_string(),
+            major,
+        })
+    }
+
+    fn is_headless_shell(&self) -> bool {
+        self.product.to_ascii_lowercase().contains("headless")
+    }
+}
+
+/// Checks that a binary supports the requested headless mode, rather than failing to
+/// launch or silently running in another mode.
+pub fn check_compatibility(
+    binary: &Path,
+    version: &ChromeVersion,
+    headless: Option<HeadlessVariant>,
+) -> Result<()> {
+    match headless {
+        Some(HeadlessVariant::Old)
+            if version.major >= OLD_HEADLESS_REMOVED_IN
+                && !version.is_headless_shell() =>
+        {
+            bail!(
+                "{} ({} {}) no longer supports the old headless mode, use --headless-mode=new or a chrome-headless-shell binary",
+                binary.display(),
+                version.product,
+                version.major
+            )
+        }
+        Some(HeadlessVariant::New) if version.major < NEW_HEADLESS_ADDED_IN => {
+            bail!(
+                "{} ({} {}) doesn't support the new headless mode, use --headless-mode=old or Chrome {} or later",
+                binary.display(),
+                version.product,
+                version.major,
+                NEW_HEADLESS_ADDED_IN
+            )
+        }
+        Some(HeadlessVariant::New) if version.is_headless_shell() => {
+            bail!(
+                "{} ({}) only supports the old headless mode, use --headless-mode=old",
+                binary.display(),
+                version.product
+            )
+        }
+        None if version.is_headless_shell() => {
+            bail!(
+                "{} ({}) can't run with a visible window, use --headless",
+                binary.display(),
+                version.product
+            )
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headless_compatibility() {
+        let binary = Path::new("chrome");
+        let chrome = |major| {
+            ChromeVersion::parse(&format!("Google Chrome {major}.0.1.2"))
+                .unwrap()
+        };
+        let shell = ChromeVersion::parse("Chrome-Headless-Shell 133.0.6943.53")
+            .unwrap();
+        assert_eq!(chrome(131).major, 131);
+
+        assert!(
+            check_compatibility(
+                binary,
+                &chrome(131),
+                Some(HeadlessVariant::Old)
+            )
+            .is_ok()
+        );
+        assert!(
+            check_compatibility(
+                binary,
+                &chrome(132),
+                Some(HeadlessVariant::Old)
+            )
+            .is_err()
+        );
+        assert!(
+            check_compatibility(
+                binary,
+                &chrome(111),
+                Some(HeadlessVariant::New)
+            )
+            .is_err()
+        );
+        assert!(check_compatibility(binary, &chrome(132), None).is_ok());
+        assert!(
+            check_compatibility(binary, &shell, Some(HeadlessVariant::Old))
+                .is_ok()
+        );
+        assert!(
+            check_compatibility(binary, &shell, Some(HeadlessVariant::New))
+                .is_err()
+        );
+        assert!(check_compatibility(binary, &shell, None).is_err());
+    }
+}
@@ -0,0 +1,234 @@
+use anyhow::Result;
+use serde_json as json;
+
+use crate::browser::actions::BrowserAction;
+use crate::browser::state::BrowserState;
+use crate::geometry::Point;
+
+/// Texts of click targets that end the session or have effects outside of the tested
+/// application, which would otherwise be clicked over and over during a run.
+const DEFAULT_DENIED: &[&str] = &[
+    "log out",
+    "logout",
+    "log off",
+    "sign out",
+    "signout",
+    "delete account",
+    "delete my account",
+    "close account",
+    "buy now",
+    "place order",
+];
+
+/// Default denied texts only matching a whole label, as they're common in the labels of
+/// harmless targets too, e.g. "Purchase history".
+const DEFAULT_DENIED_LABELS: &[&str] = &["purchase"];
+
+/// Labels longer than this are the text of a whole container rather than the name of a
+/// control, and aren't matched.
+const LABEL_LENGTH_MAX: usize = 100;
+
+/// Evaluated in the page with the click points of the candidate actions, returning the
+/// labels of the clicked elements: their `aria-label` (or `aria-labelledby`), `title`,
+/// value and text.
+pub(crate) const CLICK_LABELS_FUNCTION: &str = r#"(points, lengthMax) => {
+    // Elements named by their text. Other roles, e.g. regions or lists, contain other
+    // elements, whose texts aren't their name.
+    const CONTROLS = [
+        "a", "button", "input", "select", "textarea", "summary", "label",
+        ...["button", "link", "menuitem", "menuitemcheckbox", "menuitemradio", "tab",
+            "option", "checkbox", "radio", "switch"].map((role) => `[role=${role}]`),
+    ].join(",");
+
+    function normalize(text) {
+        return (text ?? "").trim().replace(/\s+/g, " ");
+    }
+
+    function labels(element) {
+        const labels = [];
+        for (const attribute of ["aria-label", "title"]) {
+            labels.push(element.getAttribute(attribute));
+        }
+        const labelledBy = element.getAttribute("aria-labelledby");
+        if (labelledBy) {
+            labels.push(
+                labelledBy
+                    .split(/\s+/)
+                    .map((id) => document.getElementById(id)?.textContent)
+                    .join(" "),
+            );
+        }
+        if (element instanceof HTMLInputElement) {
+            labels.push(element.value);
+        }
+        labels.push(element.textContent);
+        return labels
+            .map(normalize)
+            .filter((label) => label && label.length <= lengthMax);
+    }
+
+    return points.map(({ x, y }) => {
+        const element = document.elementFromPoint(x, y);
+        if (!element) return [];
+        // The point often lands on an icon or text inside the clicked control.
+        return labels(element.closest(CONTROLS) ?? element);
+    });
+}"#;
+
+/// The lowercase words of a text, ignoring punctuation.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Texts (matched case-insensitively as whole words in a label) of elements not to click.
+/// Allowed texts take precedence, to still explore some of the denied targets.
+#[derive(Clone, Debug)]
+pub struct ClickDenyList {
+    pub denied: Vec<String>,
+    pub allowed: Vec<String>,
+}
+
+impl Default for ClickDenyList {
+    fn default() -> Self {
+        Self::new(vec![], vec![])
+    }
+}
+
+impl ClickDenyList {
+    /// The default denied texts, extended with `denied`.
+    pub fn new(denied: Vec<String>, allowed: Vec<String>) -> Self {
+        ClickDenyList {
+            denied: DEFAULT_DENIED
+                .iter()
+                .map(|text| text.to_string())
+                .chain(denied)
+                .collect(),
+            allowed,
+        }
+    }
+
+    /// The denied entry matching a label, unless an allowed one does too.
+    fn matching(&self, label: &str) -> Option<&str> {
+        if label.chars().count() > LABEL_LENGTH_MAX {
+            return None;
+        }
+        let label = words(label);
+        let matches = |text: &str| {
+            let text = words(text);
+            !text.is_empty()
+                && label.windows(text.len()).any(|window| window == text)
+        };
+        if self.allowed.iter().any(|text| matches(text)) {
+            return None;
+        }
+        self.denied
+            .iter()
+            .map(String::as_str)
+            .find(|text| matches(text))
+            .or_else(|| {
+                DEFAULT_DENIED_LABELS
+                    .iter()
+                    .copied()
+                    .find(|text| words(text) == label)
+            })
+    }
+
+    pub async fn evaluate(
+        &self,
+        state: &BrowserState,
+        actions: &[&BrowserAction],
+    ) -> Result<DeniedClicks> {
+        let points: Vec<Point> = actions
+            .iter()
+            .filter_map(|action| match action {
+                BrowserAction::Click { point, .. }
+                | BrowserAction::DoubleClick { point, .. } => Some(*point),
+                _ => None,
+            })
+            .collect();
+        if self.denied.is_empty() || points.is_empty() {
+            return Ok(DeniedClicks::default());
+        }
+        let labels: Vec<Vec<String>> = state
+            .evaluate_function_call(
+                CLICK_LABELS_FUNCTION,
+                vec![json::to_value(&points)?, json::json!(LABEL_LENGTH_MAX)],
+            )
+            .await?;
+        Ok(DeniedClicks {
+            clicks: points
+                .into_iter()
+                .zip(labels)
+                .filter_map(|(point, labels)| {
+                    labels.into_iter().find_map(|label| {
+                        let text = self.matching(&label)?.to_string();
+                        Some(DeniedClick { point, label, text })
+                    })
+                })
+                .collect(),
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DeniedClick {
+    pub point: Point,
+    /// The label of the clicked element.
+    pub label: String,
+    /// The denied text it matched.
+    pub text: String,
+}
+
+/// The candidate clicks of a state refused by the deny-list.
+#[derive(Debug, Default)]
+pub struct DeniedClicks {
+    pub clicks: Vec<DeniedClick>,
+}
+
+impl DeniedClicks {
+    pub fn denial(&self, action: &BrowserAction) -> Option<&DeniedClick> {
+        match action {
+            BrowserAction::Click { point, .. }
+            | BrowserAction::DoubleClick { point, .. } => {
+                self.clicks.iter().find(|denied| {
+                    denied.point.x == point.x && denied.point.y == point.y
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matching() {
+        let mut deny_list = ClickDenyList::default();
+        assert_eq!(deny_list.matching("Sign Out"), Some("sign out"));
+        assert_eq!(
+            deny_list.matching("Delete account forever"),
+            Some("delete account")
+        );
+        assert_eq!(deny_list.matching("Add to cart"), None);
+        // Whole words only.
+        assert_eq!(deny_list.matching("Blogout archive"), None);
+        assert_eq!(deny_list.matching("Log out."), Some("log out"));
+
+        assert_eq!(deny_list.matching("Purchase"), Some("purchase"));
+        assert_eq!(deny_list.matching("Purchase history"), None);
+
+        assert_eq!(
+            deny_list.matching(&format!("Sign out {}", "x".repeat(100))),
+            None
+        );
+
+        deny_list.allowed.push("sign out".to_string());
+        assert_eq!(deny_list.matching("Sign out"), None);
+        assert_eq!(deny_list.matching("Log out"), Some("log out"));
+    }
+}
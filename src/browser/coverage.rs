@@ -0,0 +1,184 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::str::FromStr;
+
+use anyhow::Result;
+use chromiumoxide::Page;
+use chromiumoxide::cdp::js_protocol::profiler;
+
+use crate::browser::state::{Coverage, EdgeBucket, EdgeIndex};
+use crate::instrumentation::EDGE_MAP_SIZE;
+
+/// Where the coverage signal guiding the exploration comes from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CoverageSource {
+    /// Branch edges of instrumented scripts, rewritten as they're loaded. Needs the
+    /// `instrumentation` feature.
+    #[cfg_attr(feature = "instrumentation", default)]
+    Instrumentation,
+    /// Block coverage from the V8 profiler. Less precise, as it counts executed blocks rather
+    /// than edges between them, but it leaves the application's scripts (and their
+    /// integrity checks and content security policies) untouched.
+    #[cfg_attr(not(feature = "instrumentation"), default)]
+    Precise,
+}
+
+impl FromStr for CoverageSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "instrumentation" => Ok(CoverageSource::Instrumentation),
+            "precise" => Ok(CoverageSource::Precise),
+            other => Err(format!(
+                "unknown coverage source {:?}, expected instrumentation or precise",
+                other
+            )),
+        }
+    }
+}
+
+/// Coverage taken from the V8 profiler (CDP `Profiler.takePreciseCoverage`), mapped onto an
+/// edge map like the one maintained by instrumented scripts, with executed blocks in place
+/// of edges.
+pub struct PreciseCoverage {
+    previous: Vec<EdgeBucket>,
+}
+
+impl PreciseCoverage {
+    pub async fn start(page: &Page) -> Result<Self> {
+        page.execute(profiler::EnableParams::default()).await?;
+        page.execute(
+            profiler::StartPreciseCoverageParams::builder()
+                .call_count(true)
+                .detailed(true)
+                .build(),
+        )
+        .await?;
+        Ok(PreciseCoverage {
+            previous: vec![0; EDGE_MAP_SIZE],
+        })
+    }
+
+    /// Takes the coverage since the previous call (as taking it resets the counters), and
+    /// the transition hash of the blocks executed in between.
+    pub async fn take(
+        &mut self,
+        page: &Page,
+    ) -> Result<(Coverage, Option<u64>)> {
+        let scripts = page
+            .execute(profiler::TakePreciseCoverageParams::default())
+            .await?
+            .result
+            .result;
+        let mut current = vec![0; EDGE_MAP_SIZE];
+        for script in &scripts {
+            // Scripts without a URL are evaluated by Bombadil itself, e.g. extractors.
+            if script.url.is_empty() {
+                continue;
+            }
+            for range in script
+                .functions
+                .iter()
+                .flat_map(|function| &function.ranges)
+                .filter(|range| range.count > 0)
+            {
+                let index = block_index(
+                    &script.url,
+                    range.start_offset,
+                    range.end_offset,
+                );
+                current[index] = current[index].max(bucket(range.count));
+            }
+        }
+
+        let edges_new = current
+            .iter()
+            .zip(&self.previous)
+            .enumerate()
+            .filter(|(_, (current, previous))| current != previous)
+            .map(|(index, (current, _))| (index as EdgeIndex, *current))
+            .collect();
+        let transition_hash = simhash(&current);
+        self.previous = current;
+        Ok((Coverage { edges_new }, transition_hash))
+    }
+}
+
+fn block_index(url: &str, start_offset: i64, end_offset: i64) -> usize {
+    let mut hasher = DefaultHasher::new();
+    (url, start_offset, end_offset).hash(&mut hasher);
+    (hasher.finish() % EDGE_MAP_SIZE as u64) as usize
+}
+
+/// Buckets hit counts into [1,8], the same way as the instrumented edge map.
+fn bucket(hits: i64) -> EdgeBucket {
+    if hits <= 3 {
+        return hits.max(0) as EdgeBucket;
+    }
+    let msb = 64 - hits.leading_zeros();
+    (msb + 1).min(8) as EdgeBucket
+}
+
+/// A simhash of the bucketed edge map, like the one computed in the page for instrumented
+/// scripts, or none when nothing was covered.
+fn simhash(buckets: &[EdgeBucket]) -> Option<u64> {
+    let mut accumulators = [0i64; 64];
+    for (index, &bucket) in buckets.iter().enumerate() {
+        if bucket == 0 {
+            continue;
+        }
+        let weight = (bucket as f64).log2().floor().clamp(1.0, 3.0) as i64;
+        let hash = mix64(index as u64);
+        for (bit, accumulator) in accumulators.iter_mut().enumerate() {
+            if (hash >> bit) & 1 == 1 {
+                *accumulator += weight;
+            } else {
+                *accumulator -= weight;
+            }
+        }
+    }
+    if accumulators.iter().all(|&accumulator| accumulator == 0) {
+        return None;
+    }
+    Some(
+        accumulators
+            .iter()
+            .enumerate()
+            .filter(|(_, accumulator)| **accumulator > 0)
+            .fold(0, |hash, (bit, _)| hash | (1 << bit)),
+    )
+}
+
+/// SplitMix64 finalizer.
+fn mix64(x: u64) -> u64 {
+    let mut h = x.wrapping_add(0x9e3779b97f4a7c15);
+    h = (h ^ (h >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94d049bb133111eb);
+    h ^ (h >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket() {
+        let buckets: Vec<EdgeBucket> = [0, 1, 3, 4, 7, 8, 100, 1 << 40]
+            .into_iter()
+            .map(bucket)
+            .collect();
+        assert_eq!(buckets, vec![0, 1, 3, 4, 4, 5, 8, 8]);
+    }
+
+    #[test]
+    fn test_simhash() {
+        let mut buckets = vec![0; EDGE_MAP_SIZE];
+        assert_eq!(simhash(&buckets), None);
+        buckets[1] = 1;
+        let single = simhash(&buckets).unwrap();
+        buckets[2] = 8;
+        let both = simhash(&buckets).unwrap();
+        assert_ne!(single, both);
+        assert_eq!(simhash(&buckets), Some(both));
+    }
+}
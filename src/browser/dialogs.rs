@@ -0,0 +1,234 @@
+use anyhow::Result;
+use chromiumoxide::Page;
+use chromiumoxide::cdp::browser_protocol::page::{
+    self, DialogType, HandleJavaScriptDialogParams,
+};
+use futures::StreamExt;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::spawn;
+
+/// How `beforeunload` confirmation dialogs ("Leave site?") are answered. Nobody answers
+/// them otherwise, so navigations away from the page wait until they time out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BeforeUnloadPolicy {
+    /// Leave the page.
+    #[default]
+    Accept,
+    /// Stay on the page, cancelling the navigation.
+    Dismiss,
+}
+
+impl FromStr for BeforeUnloadPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "accept" => Ok(BeforeUnloadPolicy::Accept),
+            "dismiss" => Ok(BeforeUnloadPolicy::Dismiss),
+            other => Err(format!(
+                "invalid beforeunload policy `{other}`, expected one of: \
+                accept, dismiss"
+            )),
+        }
+    }
+}
+
+/// How `alert`, `confirm` and `prompt` dialogs are answered. Nobody answers them otherwise,
+/// so they stall the page until the action times out.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DialogPolicy {
+    /// Accept them, answering prompts with their default text.
+    #[default]
+    Accept,
+    Dismiss,
+    /// Accept or dismiss each of them at random.
+    Random,
+    /// Accept them, answering prompts with this text.
+    Text(String),
+}
+
+/// A dialog opened by the page, as exposed to extractors.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Dialog {
+    /// `alert`, `confirm`, `prompt`, or `beforeunload`.
+    pub r#type: String,
+    pub message: String,
+    /// The URL of the frame that opened the dialog.
+    pub url: String,
+    /// Whether the dialog was accepted, rather than dismissed.
+    pub accepted: bool,
+    /// The text a prompt was answered with, if accepted.
+    pub prompt_text: Option<String>,
+}
+
+#[derive(Debug)]
+struct DialogsInner {
+    dialogs: Vec<Dialog>,
+    before_unload: BeforeUnloadPolicy,
+    policy: DialogPolicy,
+    rng: ChaCha8Rng,
+}
+
+impl DialogsInner {
+    /// Whether to accept a dialog, and the text to answer it with if it's a prompt.
+    fn answer(
+        &mut self,
+        r#type: &DialogType,
+        default_prompt: Option<&str>,
+    ) -> (bool, Option<String>) {
+        let accept = match (r#type, &self.policy) {
+            (DialogType::Beforeunload, _) => {
+                self.before_unload == BeforeUnloadPolicy::Accept
+            }
+            (_, DialogPolicy::Dismiss) => false,
+            (_, DialogPolicy::Random) => self.rng.random_bool(0.5),
+            (_, DialogPolicy::Accept | DialogPolicy::Text(_)) => true,
+        };
+        let prompt_text = match (r#type, &self.policy) {
+            (DialogType::Prompt, _) if !accept => None,
+            (DialogType::Prompt, DialogPolicy::Text(text)) => {
+                Some(text.clone())
+            }
+            (DialogType::Prompt, _) => {
+                Some(default_prompt.unwrap_or_default().to_string())
+            }
+            _ => None,
+        };
+        (accept, prompt_text)
+    }
+}
+
+/// The dialogs opened by the page, answered as they open.
+#[derive(Clone, Debug)]
+pub struct Dialogs {
+    inner: Arc<Mutex<DialogsInner>>,
+}
+
+impl Dialogs {
+    fn new(before_unload: BeforeUnloadPolicy, seed: u64) -> Self {
+        Dialogs {
+            inner: Arc::new(Mutex::new(DialogsInner {
+                dialogs: vec![],
+                before_unload,
+                policy: DialogPolicy::default(),
+                rng: ChaCha8Rng::seed_from_u64(seed),
+            })),
+        }
+    }
+
+    /// Takes the dialogs opened since the last call.
+    pub fn take(&self) -> Vec<Dialog> {
+        let Ok(mut inner) = self.inner.lock() else {
+            return vec![];
+        };
+        std::mem::take(&mut inner.dialogs)
+    }
+
+    /// Replaces the policy of the `alert`, `confirm` and `prompt` dialogs opened from now
+    /// on, e.g. from a spec's `dialogs()` action.
+    pub fn set_policy(&self, policy: DialogPolicy) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.policy = policy;
+        }
+    }
+}
+
+/// Start answering the dialogs of the page, `beforeunload` ones according to
+/// `before_unload`, and the others accepting them until a policy is set. Random answers
+/// are picked reproducibly for a given `seed`.
+pub async fn handle_dialogs(
+    page: Arc<Page>,
+    before_unload: BeforeUnloadPolicy,
+    seed: u64,
+) -> Result<Dialogs> {
+    let mut events = page
+        .event_listener::<page::EventJavascriptDialogOpening>()
+        .await?;
+    let dialogs = Dialogs::new(before_unload, seed);
+    let inner = dialogs.inner.clone();
+    let _handle = spawn(async move {
+        while let Some(event) = events.next().await {
+            let (accept, prompt_text) = match inner.lock() {
+                Ok(mut inner) => {
+                    inner.answer(&event.r#type, event.default_prompt.as_deref())
+                }
+                Err(_) => break,
+            };
+            log::info!(
+                "{} {} dialog of {}",
+                if accept { "accepting" } else { "dismissing" },
+                event.r#type.as_ref(),
+                event.url
+            );
+            if let Err(error) = page
+                .execute(HandleJavaScriptDialogParams {
+                    accept,
+                    prompt_text: prompt_text.clone(),
+                })
+                .await
+            {
+                log::warn!(
+                    "failed handling {} dialog: {}",
+                    event.r#type.as_ref(),
+                    error
+                );
+            }
+            let Ok(mut inner) = inner.lock() else {
+                break;
+            };
+            inner.dialogs.push(Dialog {
+                r#type: event.r#type.as_ref().to_string(),
+                message: event.message.clone(),
+                url: event.url.clone(),
+                accepted: accept,
+                prompt_text,
+            });
+        }
+    });
+    Ok(dialogs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_before_unload_policy_from_str() {
+        assert_eq!(
+            "dismiss".parse::<BeforeUnloadPolicy>(),
+            Ok(BeforeUnloadPolicy::Dismiss)
+        );
+        assert!("ignore".parse::<BeforeUnloadPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_answers_by_policy() {
+        let dialogs = Dialogs::new(BeforeUnloadPolicy::Dismiss, 0);
+        let mut inner = dialogs.inner.lock().unwrap();
+        assert_eq!(
+            inner.answer(&DialogType::Prompt, Some("name")),
+            (true, Some("name".to_string()))
+        );
+        assert_eq!(
+            inner.answer(&DialogType::Beforeunload, None),
+            (false, None)
+        );
+
+        inner.policy = DialogPolicy::Text("Ada".to_string());
+        assert_eq!(
+            inner.answer(&DialogType::Prompt, Some("name")),
+            (true, Some("Ada".to_string()))
+        );
+        assert_eq!(inner.answer(&DialogType::Confirm, None), (true, None));
+
+        inner.policy = DialogPolicy::Dismiss;
+        assert_eq!(inner.answer(&DialogType::Prompt, None), (false, None));
+        assert_eq!(inner.answer(&DialogType::Alert, None), (false, None));
+    }
+}
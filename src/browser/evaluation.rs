@@ -1,14 +1,23 @@
-use anyhow::{Result, anyhow, bail};
+use anyhow::{Context, Result, anyhow, bail};
 use chromiumoxide::{
     Page,
-    cdp::js_protocol::{
-        debugger,
-        runtime::{self, RemoteObjectType},
+    cdp::{
+        browser_protocol::{network::LoaderId, page},
+        js_protocol::{
+            debugger,
+            runtime::{
+                self, ExceptionDetails, ExecutionContextId, RemoteObject,
+                RemoteObjectType,
+            },
+        },
     },
 };
 use serde::de::DeserializeOwned;
 use serde_json as json;
 
+/// Name of the isolated world that extractors can be evaluated in.
+const ISOLATED_WORLD_NAME: &str = "bombadil";
+
 pub async fn evaluate_expression_in_debugger<Output: DeserializeOwned>(
     page: &Page,
     call_frame_id: &debugger::CallFrameId,
@@ -27,20 +36,114 @@ pub async fn evaluate_expression_in_debugger<Output: DeserializeOwned>(
         .await
         .map_err(|err| anyhow!(err))?
         .result;
-    if let Some(exception) = returns.exception_details {
+    output_value(returns.result, returns.exception_details)
+}
+
+/// Evaluates an expression in the page's main world, outside of the debugger.
+pub async fn evaluate_expression<Output: DeserializeOwned>(
+    page: &Page,
+    expression: impl Into<String>,
+) -> Result<Output> {
+    let returns: runtime::EvaluateReturns = page
+        .execute(
+            runtime::EvaluateParams::builder()
+                .expression(expression)
+                .return_by_value(true)
+                .build()
+                .map_err(|err| anyhow!(err))?,
+        )
+        .await
+        .map_err(|err| anyhow!(err))?
+        .result;
+    output_value(returns.result, returns.exception_details)
+}
+
+/// A world with its own JavaScript globals, sharing the DOM of the main frame's current
+/// document, so that the page's scripts can't interfere with what's evaluated in it.
+#[derive(Clone, Debug)]
+pub struct IsolatedWorld {
+    /// The document the world was created for, as it's destroyed when navigating away.
+    loader_id: LoaderId,
+    context_id: ExecutionContextId,
+}
+
+/// Returns the isolated world of the main frame's current document, reusing `previous` if
+/// it's still that of the current document.
+pub async fn isolated_world(
+    page: &Page,
+    previous: Option<&IsolatedWorld>,
+) -> Result<IsolatedWorld> {
+    let frame = page
+        .execute(page::GetFrameTreeParams::default())
+        .await
+        .context("failed getting the frame tree")?
+        .result
+        .frame_tree
+        .frame;
+    if let Some(previous) = previous
+        && previous.loader_id == frame.loader_id
+    {
+        return Ok(previous.clone());
+    }
+    let context_id = page
+        .execute(
+            page::CreateIsolatedWorldParams::builder()
+                .frame_id(frame.id)
+                .world_name(ISOLATED_WORLD_NAME)
+                .grant_univeral_access(false)
+                .build()
+                .map_err(|err| anyhow!(err))?,
+        )
+        .await
+        .context("failed creating isolated world")?
+        .result
+        .execution_context_id;
+    Ok(IsolatedWorld {
+        loader_id: frame.loader_id,
+        context_id,
+    })
+}
+
+pub async fn evaluate_function_call_in_world<Output: DeserializeOwned>(
+    page: &Page,
+    world: &IsolatedWorld,
+    function_expression: impl Into<String>,
+    arguments: Vec<json::Value>,
+) -> Result<Output> {
+    let returns: runtime::EvaluateReturns = page
+        .execute(
+            runtime::EvaluateParams::builder()
+                .expression(function_call_expression(
+                    function_expression,
+                    arguments,
+                )?)
+                .context_id(world.context_id)
+                .return_by_value(true)
+                .build()
+                .map_err(|err| anyhow!(err))?,
+        )
+        .await
+        .map_err(|err| anyhow!(err))?
+        .result;
+    output_value(returns.result, returns.exception_details)
+}
+
+fn output_value<Output: DeserializeOwned>(
+    result: RemoteObject,
+    exception_details: Option<ExceptionDetails>,
+) -> Result<Output> {
+    if let Some(exception) = exception_details {
         bail!("evaluate_function failed: {:?}", exception)
     } else {
-        match returns.result.value.clone() {
+        match result.value.clone() {
             Some(value) => json::from_value(value).map_err(|err| anyhow!(err)),
             None => {
-                if let Some(runtime::RemoteObjectSubtype::Null) =
-                    returns.result.subtype
+                if let Some(runtime::RemoteObjectSubtype::Null) = result.subtype
                 {
                     json::from_value(json::Value::Null)
                         .map_err(|err| anyhow!(err))
-                } else if let Some(ref value) =
-                    returns.result.unserializable_value
-                    && returns.result.r#type == RemoteObjectType::Bigint
+                } else if let Some(ref value) = result.unserializable_value
+                    && result.r#type == RemoteObjectType::Bigint
                 {
                     let s = value
                         .inner()
@@ -53,31 +156,34 @@ pub async fn evaluate_expression_in_debugger<Output: DeserializeOwned>(
                         )
                     })
                 } else {
-                    bail!(
-                        "no return value from function call: {:?}",
-                        returns.result
-                    );
+                    bail!("no return value from function call: {:?}", result);
                 }
             }
         }
     }
 }
 
-pub async fn evaluate_function_call_in_debugger<Output: DeserializeOwned>(
-    page: &Page,
-    call_frame_id: &debugger::CallFrameId,
+fn function_call_expression(
     function_expression: impl Into<String>,
     arguments: Vec<json::Value>,
-) -> Result<Output> {
+) -> Result<String> {
     let mut arguments_json = Vec::with_capacity(arguments.len());
     for arg in arguments {
         arguments_json.push(json::to_string(&arg)?);
     }
-    let expression = format!(
+    Ok(format!(
         "({})({})",
         function_expression.into(),
         arguments_json.join(", ")
-    );
+    ))
+}
 
+pub async fn evaluate_function_call_in_debugger<Output: DeserializeOwned>(
+    page: &Page,
+    call_frame_id: &debugger::CallFrameId,
+    function_expression: impl Into<String>,
+    arguments: Vec<json::Value>,
+) -> Result<Output> {
+    let expression = function_call_expression(function_expression, arguments)?;
     evaluate_expression_in_debugger(page, call_frame_id, expression).await
 }
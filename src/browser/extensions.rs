@@ -0,0 +1,299 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+#[cfg(feature = "instrumentation")]
+use oxc::span::SourceType;
+use serde_json as json;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+#[cfg(feature = "instrumentation")]
+use crate::instrumentation;
+#[cfg(feature = "instrumentation")]
+use crate::instrumentation::source_id::SourceId;
+
+/// Directory (inside the browser's user data directory) where instrumented copies of
+/// extensions are stored.
+const EXTENSIONS_DIRECTORY: &str = "bombadil_extensions";
+
+/// An extension copied into the user data directory, to be loaded from there.
+#[derive(Clone, Debug)]
+pub struct PreparedExtension {
+    pub path: PathBuf,
+    /// The ID Chrome assigns to the extension when loading it from its original directory.
+    pub original_id: String,
+    /// The ID of the copy, the same as the original one if the manifest has a `key`.
+    pub id: String,
+}
+
+/// Copy unpacked extensions into the user data directory, instrumenting their scripts for
+/// coverage along the way, and return the copies to load.
+///
+/// Chrome derives the ID of an unpacked extension from its path, unless the manifest
+/// contains a `key`, so the copies only keep the original ID when such a key is present.
+/// Otherwise, origins are rewritten to the ID of the copy with `rewrite_origin`.
+pub fn prepare_extensions(
+    extensions: &[PathBuf],
+    user_data_directory: &Path,
+) -> Result<Vec<PreparedExtension>> {
+    extensions
+        .iter()
+        .enumerate()
+        .map(|(index, source)| {
+            let destination = user_data_directory
+                .join(EXTENSIONS_DIRECTORY)
+                .join(index.to_string());
+            prepare_extension(source, &destination).with_context(|| {
+                format!("failed preparing extension {}", source.display())
+            })
+        })
+        .collect()
+}
+
+fn prepare_extension(
+    source: &Path,
+    destination: &Path,
+) -> Result<PreparedExtension> {
+    let manifest: json::Value = json::from_str(
+        &std::fs::read_to_string(source.join("manifest.json"))
+            .context("failed reading manifest.json")?,
+    )
+    .context("failed parsing manifest.json")?;
+
+    let skipped = uninstrumented_scripts(&manifest);
+    copy_instrumented(source, source, destination, &skipped)?;
+    log::info!(
+        "prepared instrumented copy of extension {} in {}",
+        source.display(),
+        destination.display()
+    );
+
+    let (original_id, id) = match manifest
+        .get("key")
+        .and_then(json::Value::as_str)
+    {
+        Some(key) => {
+            let id = extension_id(
+                &BASE64_STANDARD
+                    .decode(key)
+                    .context("failed decoding the manifest's `key`")?,
+            );
+            (id.clone(), id)
+        }
+        None => {
+            let original_id = extension_id_for_path(source)?;
+            let id = extension_id_for_path(destination)?;
+            log::info!(
+                "extension {} has no `key` in its manifest, so its ID changes from {} to {} once copied, and origins using it are rewritten",
+                source.display(),
+                original_id,
+                id
+            );
+            (original_id, id)
+        }
+    };
+    Ok(PreparedExtension {
+        path: destination.to_path_buf(),
+        original_id,
+        id,
+    })
+}
+
+/// The ID Chrome derives from a public key, or a path: the first 128 bits of its SHA-256
+/// digest, in hexadecimal written with the letters `a` to `p`.
+fn extension_id(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)[..16]
+        .iter()
+        .flat_map(|byte| [byte >> 4, byte & 0xf])
+        .map(|digit| char::from(b'a' + digit))
+        .collect()
+}
+
+/// The ID of an unpacked extension without a `key`, derived from its absolute path with
+/// symbolic links resolved, as Chrome does on Linux and macOS.
+fn extension_id_for_path(path: &Path) -> Result<String> {
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("failed resolving {}", path.display()))?;
+    Ok(extension_id(path.as_os_str().as_encoded_bytes()))
+}
+
+/// The origin with the ID of an extension replaced by that of its copy, if it's the page of
+/// an extension whose ID changed once copied.
+pub fn rewrite_origin(origin: &Url, extensions: &[PreparedExtension]) -> Url {
+    if origin.scheme() != "chrome-extension" {
+        return origin.clone();
+    }
+    let extension = extensions.iter().find(|extension| {
+        origin.host_str() == Some(extension.original_id.as_str())
+    });
+    let Some(extension) = extension.filter(|e| e.id != e.original_id) else {
+        return origin.clone();
+    };
+    let mut rewritten = origin.clone();
+    match rewritten.set_host(Some(&extension.id)) {
+        Ok(()) => {
+            log::info!("testing {} as {}", origin, rewritten);
+            rewritten
+        }
+        Err(error) => {
+            log::warn!("failed rewriting origin {}: {}", origin, error);
+            origin.clone()
+        }
+    }
+}
+
+/// Scripts that don't run in extension pages, and therefore can't report coverage through
+/// `window` (background service workers and content scripts).
+fn uninstrumented_scripts(manifest: &json::Value) -> HashSet<PathBuf> {
+    let background = manifest
+        .pointer("/background/service_worker")
+        .and_then(json::Value::as_str)
+        .into_iter();
+    let content_scripts = manifest
+        .get("content_scripts")
+        .and_then(json::Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.get("js").and_then(json::Value::as_array))
+        .flatten()
+        .filter_map(json::Value::as_str);
+    background
+        .chain(content_scripts)
+        .map(|path| PathBuf::from(path.trim_start_matches('/')))
+        .collect()
+}
+
+fn copy_instrumented(
+    root: &Path,
+    source: &Path,
+    destination: &Path,
+    skipped: &HashSet<PathBuf>,
+) -> Result<()> {
+    std::fs::create_dir_all(destination)?;
+    for entry in std::fs::read_dir(source)? {
+        let entry = entry?;
+        let source_path = entry.path();
+        let destination_path = destination.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_instrumented(root, &source_path, &destination_path, skipped)?;
+            continue;
+        }
+
+        let relative = source_path.strip_prefix(root)?;
+        let is_script = matches!(
+            source_path.extension().and_then(|e| e.to_str()),
+            Some("js" | "mjs")
+        );
+        if !is_script || skipped.contains(relative) {
+            std::fs::copy(&source_path, &destination_path)?;
+            continue;
+        }
+
+        let source_text = std::fs::read_to_string(&source_path)?;
+        match instrument_script(&source_text) {
+            Ok(instrumented) => {
+                std::fs::write(&destination_path, instrumented)?
+            }
+            Err(error) => {
+                log::warn!(
+                    "failed to instrument extension script {}: {}",
+                    source_path.display(),
+                    error
+                );
+                std::fs::copy(&source_path, &destination_path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "instrumentation")]
+fn instrument_script(source_text: &str) -> Result<String> {
+    Ok(instrumentation::js::instrument_source_code(
+        SourceId::hash(source_text),
+        source_text,
+        SourceType::unambiguous(),
+    )?)
+}
+
+/// Without the `instrumentation` feature, scripts are copied as they are, and extensions
+/// report no coverage.
+#[cfg(not(feature = "instrumentation"))]
+fn instrument_script(source_text: &str) -> Result<String> {
+    Ok(source_text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uninstrumented_scripts_from_manifest() {
+        let manifest = json::json!({
+            "manifest_version": 3,
+            "background": { "service_worker": "background.js" },
+            "content_scripts": [
+                { "matches": ["<all_urls>"], "js": ["/content/a.js", "b.js"] }
+            ],
+            "action": { "default_popup": "popup.html" },
+        });
+        let expected: HashSet<PathBuf> =
+            ["background.js", "content/a.js", "b.js"]
+                .into_iter()
+                .map(PathBuf::from)
+                .collect();
+        assert_eq!(uninstrumented_scripts(&manifest), expected);
+    }
+
+    #[test]
+    fn uninstrumented_scripts_empty_manifest() {
+        assert!(uninstrumented_scripts(&json::json!({})).is_empty());
+    }
+
+    #[test]
+    fn extension_ids() {
+        assert_eq!(extension_id(b"test"), "jpignaibiiemhngfjkcpokkamffknabf");
+    }
+
+    #[test]
+    fn manifest_without_key() {
+        let source = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            source.path().join("manifest.json"),
+            r#"{ "manifest_version": 3, "name": "test" }"#,
+        )
+        .unwrap();
+        std::fs::write(source.path().join("popup.html"), "<p>hi</p>").unwrap();
+        let user_data_directory = tempfile::TempDir::new().unwrap();
+
+        let extensions = prepare_extensions(
+            &[source.path().to_path_buf()],
+            user_data_directory.path(),
+        )
+        .unwrap();
+        let [extension] = extensions.as_slice() else {
+            panic!("expected one extension");
+        };
+        assert!(extension.path.join("popup.html").exists());
+        assert_eq!(
+            extension.original_id,
+            extension_id_for_path(source.path()).unwrap()
+        );
+        assert_ne!(extension.id, extension.original_id);
+
+        let origin = Url::parse(&format!(
+            "chrome-extension://{}/popup.html",
+            extension.original_id
+        ))
+        .unwrap();
+        assert_eq!(
+            rewrite_origin(&origin, &extensions).as_str(),
+            format!("chrome-extension://{}/popup.html", extension.id)
+        );
+        let other = Url::parse("http://localhost:3000/").unwrap();
+        assert_eq!(rewrite_origin(&other, &extensions), other);
+    }
+}
@@ -0,0 +1,136 @@
+use std::time::Duration;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::browser::instrumentation::matches_wildcard;
+
+/// How long a delayed request waits by default before being sent on.
+pub const DEFAULT_FAULT_DELAY: Duration = Duration::from_secs(3);
+
+/// What happens to a request picked for a fault.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FaultKind {
+    /// Sent on after a delay.
+    Delay,
+    /// Failed as if the connection had failed.
+    Drop,
+    /// Answered with a `500 Internal Server Error`, without reaching the server.
+    ServerError,
+}
+
+/// Requests whose URL matches `pattern` (with `*` wildcards) get one of `kinds` of faults,
+/// picked at random, with probability `rate`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FaultRule {
+    pub pattern: String,
+    pub rate: f64,
+    pub kinds: Vec<FaultKind>,
+    pub delay_millis: u64,
+}
+
+/// The fault rules of a run, and the seed deciding which requests they hit.
+#[derive(Clone, Debug, Default)]
+pub struct FaultInjection {
+    pub rules: Vec<FaultRule>,
+    pub seed: u64,
+}
+
+/// A fault to inject into a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    Delay(Duration),
+    Drop,
+    ServerError,
+}
+
+/// Decides which requests get faults, reproducibly for a given seed and sequence of
+/// requests.
+#[derive(Debug)]
+pub struct FaultDecider {
+    rules: Vec<FaultRule>,
+    rng: ChaCha8Rng,
+}
+
+impl FaultDecider {
+    pub fn new(injection: &FaultInjection) -> Self {
+        FaultDecider {
+            rules: injection.rules.clone(),
+            rng: ChaCha8Rng::seed_from_u64(injection.seed),
+        }
+    }
+
+    pub fn rules(&self) -> &[FaultRule] {
+        &self.rules
+    }
+
+    pub fn set_rules(&mut self, rules: Vec<FaultRule>) {
+        self.rules = rules;
+    }
+
+    /// The fault for a request to `url`, from the first rule matching it, if any.
+    pub fn decide(&mut self, url: &str) -> Option<Fault> {
+        let rule = self
+            .rules
+            .iter()
+            .find(|rule| matches_wildcard(&rule.pattern, url))?;
+        if rule.kinds.is_empty()
+            || !self.rng.random_bool(rule.rate.clamp(0.0, 1.0))
+        {
+            return None;
+        }
+        let kind = rule.kinds[self.rng.random_range(0..rule.kinds.len())];
+        Some(match kind {
+            FaultKind::Delay => {
+                Fault::Delay(Duration::from_millis(rule.delay_millis))
+            }
+            FaultKind::Drop => Fault::Drop,
+            FaultKind::ServerError => Fault::ServerError,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str, rate: f64) -> FaultRule {
+        FaultRule {
+            pattern: pattern.to_string(),
+            rate,
+            kinds: vec![FaultKind::Drop, FaultKind::ServerError],
+            delay_millis: 0,
+        }
+    }
+
+    #[test]
+    fn test_decide_by_first_matching_rule() {
+        let mut decider = FaultDecider::new(&FaultInjection {
+            rules: vec![rule("*/api/health", 0.0), rule("*/api/*", 1.0)],
+            seed: 0,
+        });
+        assert_eq!(decider.decide("http://localhost/api/health"), None);
+        assert_eq!(decider.decide("http://localhost/app.js"), None);
+        assert!(matches!(
+            decider.decide("http://localhost/api/items"),
+            Some(Fault::Drop | Fault::ServerError)
+        ));
+    }
+
+    #[test]
+    fn test_decisions_reproducible() {
+        let decisions = || {
+            let mut decider = FaultDecider::new(&FaultInjection {
+                rules: vec![rule("*", 0.5)],
+                seed: 7,
+            });
+            (0..20)
+                .map(|_| decider.decide("http://localhost/"))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(decisions(), decisions());
+    }
+}
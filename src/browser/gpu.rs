@@ -0,0 +1,115 @@
+use std::str::FromStr;
+
+use anyhow::Result;
+use chromiumoxide::Page;
+use serde::{Deserialize, Serialize};
+
+use crate::browser::evaluation::evaluate_expression;
+
+/// Renderers of WebGL without a GPU, which work but are much slower.
+const SOFTWARE_RENDERERS: &[&str] = &["swiftshader", "llvmpipe", "software"];
+
+/// Creates a WebGL and a WebGL 2 context on detached canvases, returning what they're
+/// backed by.
+const GPU_INFO_EXPRESSION: &str = r#"(() => {
+    const gl = document.createElement("canvas").getContext("webgl");
+    const webgl2 = document.createElement("canvas").getContext("webgl2") !== null;
+    if (!gl) {
+        return { webgl: false, webgl2, vendor: null, renderer: null };
+    }
+    const debug = gl.getExtension("WEBGL_debug_renderer_info");
+    return {
+        webgl: true,
+        webgl2,
+        vendor: gl.getParameter(debug ? debug.UNMASKED_VENDOR_WEBGL : gl.VENDOR),
+        renderer: gl.getParameter(debug ? debug.UNMASKED_RENDERER_WEBGL : gl.RENDERER),
+    };
+})()"#;
+
+/// Launch flags for environments without a GPU, e.g. containers, where Chrome otherwise
+/// often has no WebGL and canvas-heavy apps render blank pages.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GpuPreset {
+    /// Chrome's own choice, using the GPU if there's one.
+    #[default]
+    Default,
+    /// Render WebGL in software with SwiftShader, which works without a GPU.
+    Swiftshader,
+    /// Disable GPU acceleration, e.g. when a broken driver crashes the GPU process.
+    Disabled,
+}
+
+impl FromStr for GpuPreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(GpuPreset::Default),
+            "swiftshader" => Ok(GpuPreset::Swiftshader),
+            "disabled" => Ok(GpuPreset::Disabled),
+            other => Err(format!(
+                "unknown GPU preset {:?}, expected default, swiftshader or disabled",
+                other
+            )),
+        }
+    }
+}
+
+impl GpuPreset {
+    pub fn launch_args(&self) -> &'static [&'static str] {
+        match self {
+            GpuPreset::Default => &[],
+            GpuPreset::Swiftshader => &[
+                "--use-gl=angle",
+                "--use-angle=swiftshader",
+                "--enable-unsafe-swiftshader",
+            ],
+            GpuPreset::Disabled => &["--disable-gpu"],
+        }
+    }
+}
+
+/// Whether WebGL is available in the browser, and what renders it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GpuInfo {
+    pub webgl: bool,
+    pub webgl2: bool,
+    pub vendor: Option<String>,
+    pub renderer: Option<String>,
+    /// Whether WebGL is rendered in software rather than by a GPU.
+    #[serde(default)]
+    pub software: bool,
+}
+
+impl GpuInfo {
+    pub async fn detect(page: &Page) -> Result<Self> {
+        let mut info: GpuInfo =
+            evaluate_expression(page, GPU_INFO_EXPRESSION).await?;
+        info.software = info.renderer.as_deref().is_some_and(is_software);
+        Ok(info)
+    }
+}
+
+fn is_software(renderer: &str) -> bool {
+    let renderer = renderer.to_lowercase();
+    SOFTWARE_RENDERERS
+        .iter()
+        .any(|software| renderer.contains(software))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_software_renderers() {
+        assert!(is_software(
+            "ANGLE (Google, Vulkan 1.3.0 (SwiftShader Device (Subzero)), SwiftShader driver)"
+        ));
+        assert!(is_software("llvmpipe (LLVM 15.0.7, 256 bits)"));
+        assert!(!is_software(
+            "ANGLE (Apple, ANGLE Metal Renderer: Apple M2, Unspecified Version)"
+        ));
+    }
+}
@@ -1,3 +1,7 @@
+// Without the `instrumentation` feature only the diagnostics remain, not the interception
+// they're collected by.
+#![cfg_attr(not(feature = "instrumentation"), allow(dead_code, unused_imports))]
+
 use anyhow::{Context, Result, anyhow, bail};
 use base64::Engine;
 use base64::prelude::BASE64_STANDARD;
@@ -6,14 +10,21 @@ use chromiumoxide::cdp::browser_protocol::fetch;
 use chromiumoxide::cdp::browser_protocol::network;
 use futures::StreamExt;
 use log;
+#[cfg(feature = "instrumentation")]
 use oxc::span::SourceType;
+use serde::Serialize;
 use serde_json as json;
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use tokio::spawn;
+use url::Url;
 
+use crate::browser::interception::{Interception, is_request_stage};
+#[cfg(feature = "instrumentation")]
 use crate::instrumentation;
 use crate::instrumentation::source_id::SourceId;
+use crate::run_id::RunId;
 
 /// Response headers that must be stripped after script instrumentation.
 ///
@@ -43,34 +54,285 @@ const STRIPPED_RESPONSE_HEADERS: &[&str] = &[
     "digest",
 ];
 
-pub async fn instrument_js_coverage(page: Arc<Page>) -> Result<()> {
-    page.execute(
-        fetch::EnableParams::builder()
-            .pattern(
-                fetch::RequestPattern::builder()
-                    .request_stage(fetch::RequestStage::Response)
-                    .resource_type(network::ResourceType::Script)
-                    .build(),
-            )
-            .pattern(
-                fetch::RequestPattern::builder()
-                    .request_stage(fetch::RequestStage::Response)
-                    .resource_type(network::ResourceType::Document)
-                    .build(),
-            )
-            .build(),
-    )
-    .await
-    .context("failed enabling request interception")?;
+/// Response headers that affect HTTP caching and that instrumentation may alter.
+///
+/// Each entry is lower-cased for case-insensitive matching.
+const CACHING_RESPONSE_HEADERS: &[&str] = &["etag", "digest"];
+
+/// A resource whose caching semantics were altered by instrumentation.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct CachingAlteration {
+    pub url: String,
+    pub resource_type: String,
+    /// The upstream `etag`, if any, which was replaced by `instrumented_etag`.
+    pub original_etag: Option<String>,
+    pub instrumented_etag: String,
+    /// Caching-relevant headers that were dropped from the response.
+    pub stripped_headers: Vec<String>,
+}
+
+/// Collects the resources whose caching semantics were altered by instrumentation over the
+/// course of a run, keyed by URL.
+#[derive(Clone, Debug, Default)]
+pub struct CachingDiagnostics {
+    alterations: Arc<Mutex<BTreeMap<String, CachingAlteration>>>,
+}
+
+impl CachingDiagnostics {
+    fn record(&self, alteration: CachingAlteration) {
+        if let Ok(mut alterations) = self.alterations.lock() {
+            alterations.insert(alteration.url.clone(), alteration);
+        }
+    }
+
+    pub fn report(&self) -> Vec<CachingAlteration> {
+        self.alterations
+            .lock()
+            .map(|alterations| alterations.values().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Collects the sizes of the scripts intercepted over the course of a run, as served
+/// (decompressed, before instrumentation), keyed by origin and URL. A script loaded several
+/// times is counted once, with its latest size.
+#[derive(Clone, Debug, Default)]
+pub struct ScriptSizes {
+    sizes: Arc<Mutex<BTreeMap<String, BTreeMap<String, u64>>>>,
+}
+
+/// The sizes of the scripts of an origin.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OriginScriptSizes {
+    pub total_bytes: u64,
+    /// Size in bytes of each script, by URL.
+    pub scripts: BTreeMap<String, u64>,
+}
+
+/// The sizes of all scripts intercepted so far.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptSizesReport {
+    pub total_bytes: u64,
+    pub by_origin: BTreeMap<String, OriginScriptSizes>,
+}
+
+/// The total size of the scripts of an origin.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OriginScriptTotal {
+    pub total_bytes: u64,
+}
+
+/// The total sizes of the scripts intercepted so far, exposed to extractors on every
+/// state. The size of each script is only in the report written at the end of the run.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptSizeTotals {
+    pub total_bytes: u64,
+    pub by_origin: BTreeMap<String, OriginScriptTotal>,
+}
+
+impl ScriptSizes {
+    fn record(&self, url: &str, bytes: u64) {
+        let origin = match Url::parse(url) {
+            Ok(url) => url.origin().ascii_serialization(),
+            Err(_) => return,
+        };
+        if let Ok(mut sizes) = self.sizes.lock() {
+            sizes
+                .entry(origin)
+                .or_default()
+                .insert(url.to_string(), bytes);
+        }
+    }
+
+    pub fn totals(&self) -> ScriptSizeTotals {
+        let Ok(sizes) = self.sizes.lock() else {
+            return ScriptSizeTotals::default();
+        };
+        let by_origin: BTreeMap<String, OriginScriptTotal> = sizes
+            .iter()
+            .map(|(origin, scripts)| {
+                (
+                    origin.clone(),
+                    OriginScriptTotal {
+                        total_bytes: scripts.values().sum(),
+                    },
+                )
+            })
+            .collect();
+        ScriptSizeTotals {
+            total_bytes: by_origin
+                .values()
+                .map(|total| total.total_bytes)
+                .sum(),
+            by_origin,
+        }
+    }
+
+    pub fn report(&self) -> ScriptSizesReport {
+        let Ok(sizes) = self.sizes.lock() else {
+            return ScriptSizesReport::default();
+        };
+        let by_origin: BTreeMap<String, OriginScriptSizes> = sizes
+            .iter()
+            .map(|(origin, scripts)| {
+                (
+                    origin.clone(),
+                    OriginScriptSizes {
+                        total_bytes: scripts.values().sum(),
+                        scripts: scripts.clone(),
+                    },
+                )
+            })
+            .collect();
+        ScriptSizesReport {
+            total_bytes: by_origin
+                .values()
+                .map(|sizes| sizes.total_bytes)
+                .sum(),
+            by_origin,
+        }
+    }
+}
+
+/// Whether a request URL matches any of the pass-through patterns, where `*` matches any
+/// sequence of characters.
+fn is_pass_through(url: &str, patterns: &[String]) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| matches_wildcard(pattern, url))
+}
+
+pub(crate) fn matches_wildcard(pattern: &str, text: &str) -> bool {
+    let mut segments = pattern.split('*');
+    let first = segments.next().unwrap_or("");
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let segments: Vec<&str> = segments.collect();
+    let Some((last, middle)) = segments.split_last() else {
+        // No wildcard at all, so the whole text must match.
+        return rest.is_empty();
+    };
+    for segment in middle {
+        match rest.find(segment) {
+            Some(index) => rest = &rest[index + segment.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Compare the upstream response headers with the ones we send back, returning what changed
+/// in terms of caching semantics, if anything.
+fn caching_alteration(
+    url: &str,
+    resource_type: &network::ResourceType,
+    original: &Option<Vec<fetch::HeaderEntry>>,
+    instrumented: &[fetch::HeaderEntry],
+) -> Option<CachingAlteration> {
+    let find = |headers: &[fetch::HeaderEntry], name: &str| {
+        headers
+            .iter()
+            .find(|h| h.name.eq_ignore_ascii_case(name))
+            .map(|h| h.value.clone())
+    };
+    let original = original.as_deref().unwrap_or_default();
+    let original_etag = find(original, "etag");
+    let instrumented_etag = find(instrumented, "etag").unwrap_or_default();
+    let stripped_headers: Vec<String> = CACHING_RESPONSE_HEADERS
+        .iter()
+        .filter(|name| {
+            find(original, name).is_some() && find(instrumented, name).is_none()
+        })
+        .map(|name| name.to_string())
+        .collect();
+
+    if original_etag.as_ref() == Some(&instrumented_etag)
+        && stripped_headers.is_empty()
+    {
+        return None;
+    }
+    Some(CachingAlteration {
+        url: url.to_string(),
+        resource_type: format!("{:?}", resource_type),
+        original_etag,
+        instrumented_etag,
+        stripped_headers,
+    })
+}
 
+fn is_redirect(status: i64) -> bool {
+    matches!(status, 301 | 302 | 303 | 307 | 308)
+}
+
+/// Directory where instrumented scripts are dumped for debugging, namespaced by run so that
+/// concurrent runs don't overwrite each other's files.
+fn debug_scripts_directory(run_id: &RunId) -> PathBuf {
+    std::env::temp_dir().join(format!("bombadil_scripts_{}", run_id))
+}
+
+/// Intercept script and document responses to instrument them for coverage. Requests whose
+/// URL matches one of the `pass_through` patterns are forwarded untouched, preserving their
+/// original caching headers (at the cost of not collecting coverage for them, nor their
+/// sizes in `script_sizes`).
+#[cfg(feature = "instrumentation")]
+pub async fn instrument_js_coverage(
+    page: Arc<Page>,
+    run_id: &RunId,
+    pass_through: Vec<String>,
+    script_sizes: ScriptSizes,
+    interception: &Interception,
+) -> Result<CachingDiagnostics> {
     let mut events = page.event_listener::<fetch::EventRequestPaused>().await?;
+    interception
+        .set_response_patterns(vec![
+            fetch::RequestPattern::builder()
+                .request_stage(fetch::RequestStage::Response)
+                .resource_type(network::ResourceType::Script)
+                .build(),
+            fetch::RequestPattern::builder()
+                .request_stage(fetch::RequestStage::Response)
+                .resource_type(network::ResourceType::Document)
+                .build(),
+        ])
+        .await?;
+
+    let debug_directory = debug_scripts_directory(run_id);
+    let diagnostics = CachingDiagnostics::default();
+    let diagnostics_shared = diagnostics.clone();
 
     let _handle = spawn(async move {
         let intercept =
             async |event: &fetch::EventRequestPaused| -> Result<()> {
-                // Any non-200 upstream response is forwarded as-is.
-                if let Some(status) = event.response_status_code
-                    && status != 200
+                // Any non-200 upstream response, or one configured to pass through, is
+                // forwarded as-is. Redirects are followed by the browser, their chains
+                // being recorded from network events by `track_network`.
+                let passes_through =
+                    is_pass_through(&event.request.url, &pass_through);
+                if passes_through {
+                    log::debug!(
+                        "passing through request without instrumentation: {}",
+                        event.request.url
+                    );
+                }
+                if let Some(status) = event
+                    .response_status_code
+                    .filter(|status| is_redirect(*status))
+                {
+                    log::debug!(
+                        "following {} redirect of {}",
+                        status,
+                        event.request.url
+                    );
+                }
+                if passes_through
+                    || event
+                        .response_status_code
+                        .is_some_and(|status| status != 200)
                 {
                     return page
                         .execute(
@@ -116,6 +378,10 @@ pub async fn instrument_js_coverage(page: Arc<Page>) -> Result<()> {
 
                 let source_id = source_id(headers, &body);
 
+                if event.resource_type == network::ResourceType::Script {
+                    script_sizes.record(&event.request.url, body.len() as u64);
+                }
+
                 let is_html_document = event.resource_type
                     == network::ResourceType::Document
                     && event
@@ -143,25 +409,30 @@ pub async fn instrument_js_coverage(page: Arc<Page>) -> Result<()> {
                             SourceType::unambiguous(),
                         )?;
 
-                    // Write to /tmp/ for debugging
+                    // Write to the run's debug directory for debugging
                     if let Some(filename) =
                         event.request.url.split('/').next_back()
                     {
                         let safe_filename =
                             filename.replace(['?', '#', '&', '='], "_");
-                        let path = format!("/tmp/{}", safe_filename);
-                        if let Err(e) =
-                            tokio::fs::write(&path, &instrumented).await
-                        {
+                        let path = debug_directory.join(safe_filename);
+                        let written =
+                            tokio::fs::create_dir_all(&debug_directory)
+                                .await
+                                .and(
+                                    tokio::fs::write(&path, &instrumented)
+                                        .await,
+                                );
+                        if let Err(e) = written {
                             log::debug!(
                                 "failed to write debug file to {}: {}",
-                                path,
+                                path.display(),
                                 e
                             );
                         } else {
                             log::debug!(
                                 "wrote instrumented script to {}",
-                                path
+                                path.display()
                             );
                         }
                     }
@@ -186,16 +457,26 @@ pub async fn instrument_js_coverage(page: Arc<Page>) -> Result<()> {
                 // Capture resource type before the iterator borrows `event`.
                 let resource_type = event.resource_type.clone();
 
+                let response_headers = build_response_headers(
+                    &event.response_headers,
+                    &resource_type,
+                    source_id,
+                );
+                if let Some(alteration) = caching_alteration(
+                    &event.request.url,
+                    &resource_type,
+                    &event.response_headers,
+                    &response_headers,
+                ) {
+                    diagnostics_shared.record(alteration);
+                }
+
                 page.execute(
                     fetch::FulfillRequestParams::builder()
                         .request_id(event.request_id.clone())
                         .body(BASE64_STANDARD.encode(body_instrumented))
                         .response_code(200)
-                        .response_headers(build_response_headers(
-                            &event.response_headers,
-                            &resource_type,
-                            source_id,
-                        ))
+                        .response_headers(response_headers)
                         .build()
                         .map_err(|error| {
                             anyhow!(
@@ -213,6 +494,10 @@ pub async fn instrument_js_coverage(page: Arc<Page>) -> Result<()> {
                 Ok(())
             };
         while let Some(event) = events.next().await {
+            // Requests paused before being sent are left to fault injection.
+            if is_request_stage(&event) {
+                continue;
+            }
             if let Err(error) = intercept(&event).await {
                 let error_debug = format!("{error:?}");
                 if error_debug.contains("Invalid InterceptionId") {
@@ -244,7 +529,7 @@ pub async fn instrument_js_coverage(page: Arc<Page>) -> Result<()> {
         }
     });
 
-    Ok(())
+    Ok(diagnostics)
 }
 
 /// Calculate source ID from etag or body.
@@ -407,6 +692,110 @@ fn build_response_headers(
 mod tests {
     use super::*;
 
+    fn header(name: &str, value: &str) -> fetch::HeaderEntry {
+        fetch::HeaderEntry {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn matches_wildcard_exact() {
+        assert!(matches_wildcard("http://a/b.js", "http://a/b.js"));
+        assert!(!matches_wildcard("http://a/b.js", "http://a/b.jsx"));
+    }
+
+    #[test]
+    fn matches_wildcard_prefix_and_suffix() {
+        assert!(matches_wildcard("*/vendor/*", "http://a/vendor/react.js"));
+        assert!(matches_wildcard("*.woff2", "http://a/font.woff2"));
+        assert!(!matches_wildcard("*.woff2", "http://a/font.woff2?v=1"));
+        assert!(matches_wildcard("http://a/*.js*", "http://a/x.js?v=1"));
+        assert!(!matches_wildcard("http://b/*", "http://a/x.js"));
+    }
+
+    #[test]
+    fn is_pass_through_any_pattern() {
+        let patterns = vec!["*/static/*".to_string(), "*.css".to_string()];
+        assert!(is_pass_through("http://a/static/app.js", &patterns));
+        assert!(is_pass_through("http://a/site.css", &patterns));
+        assert!(!is_pass_through("http://a/app.js", &patterns));
+        assert!(!is_pass_through("http://a/app.js", &[]));
+    }
+
+    #[test]
+    fn caching_alteration_replaced_etag_and_stripped_digest() {
+        let original = Some(vec![
+            header("ETag", "\"abc\""),
+            header("Digest", "sha-256=x"),
+        ]);
+        let instrumented = vec![header("etag", "123")];
+        let alteration = caching_alteration(
+            "http://a/app.js",
+            &network::ResourceType::Script,
+            &original,
+            &instrumented,
+        )
+        .unwrap();
+        assert_eq!(alteration.original_etag, Some("\"abc\"".to_string()));
+        assert_eq!(alteration.instrumented_etag, "123");
+        assert_eq!(alteration.stripped_headers, vec!["digest".to_string()]);
+    }
+
+    #[test]
+    fn caching_alteration_none_when_unchanged() {
+        let original = Some(vec![header("etag", "123")]);
+        let instrumented = vec![header("etag", "123")];
+        assert_eq!(
+            caching_alteration(
+                "http://a/app.js",
+                &network::ResourceType::Script,
+                &original,
+                &instrumented,
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn caching_diagnostics_deduplicates_by_url() {
+        let diagnostics = CachingDiagnostics::default();
+        let alteration = CachingAlteration {
+            url: "http://a/app.js".to_string(),
+            resource_type: "Script".to_string(),
+            original_etag: None,
+            instrumented_etag: "1".to_string(),
+            stripped_headers: vec![],
+        };
+        diagnostics.record(alteration.clone());
+        diagnostics.record(CachingAlteration {
+            instrumented_etag: "2".to_string(),
+            ..alteration
+        });
+        let report = diagnostics.report();
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].instrumented_etag, "2");
+    }
+
+    #[test]
+    fn script_sizes_totals_by_origin() {
+        let sizes = ScriptSizes::default();
+        sizes.record("http://a/app.js", 100);
+        sizes.record("http://a/app.js?v=2", 50);
+        sizes.record("http://a/app.js", 120);
+        sizes.record("https://cdn.b:8443/lib.js", 30);
+        let report = sizes.report();
+        assert_eq!(report.total_bytes, 200);
+        assert_eq!(report.by_origin["http://a"].total_bytes, 170);
+        assert_eq!(report.by_origin["http://a"].scripts.len(), 2);
+        assert_eq!(report.by_origin["https://cdn.b:8443"].total_bytes, 30);
+
+        let totals = sizes.totals();
+        assert_eq!(totals.total_bytes, 200);
+        assert_eq!(totals.by_origin["http://a"].total_bytes, 170);
+        assert_eq!(totals.by_origin["https://cdn.b:8443"].total_bytes, 30);
+    }
+
     #[test]
     fn sanitize_csp_removes_sha256() {
         assert_eq!(
@@ -0,0 +1,207 @@
+use anyhow::{Context, Result, anyhow};
+use chromiumoxide::Page;
+use chromiumoxide::cdp::browser_protocol::{fetch, network};
+use futures::StreamExt;
+use std::sync::{Arc, Mutex};
+use tokio::spawn;
+use tokio::time::sleep;
+
+use crate::browser::faults::{Fault, FaultDecider, FaultInjection, FaultRule};
+
+/// The request interception of a page, shared by instrumentation (pausing script and
+/// document responses) and fault injection (pausing matching requests before they're
+/// sent), whose patterns have to be enabled together.
+#[derive(Clone)]
+pub struct Interception {
+    page: Arc<Page>,
+    inner: Arc<Mutex<InterceptionInner>>,
+}
+
+struct InterceptionInner {
+    response_patterns: Vec<fetch::RequestPattern>,
+    /// Whether the response patterns are enabled, i.e. scripts get instrumented.
+    instrumentation_enabled: bool,
+    faults: FaultDecider,
+}
+
+/// Whether a paused request hasn't been sent yet, as opposed to having its response
+/// paused.
+pub fn is_request_stage(event: &fetch::EventRequestPaused) -> bool {
+    event.response_status_code.is_none()
+        && event.response_error_reason.is_none()
+}
+
+impl Interception {
+    /// Starts injecting faults into the requests of the page, which are paused before being
+    /// sent.
+    pub async fn start(
+        page: Arc<Page>,
+        faults: &FaultInjection,
+    ) -> Result<Self> {
+        let interception = Interception {
+            page: page.clone(),
+            inner: Arc::new(Mutex::new(InterceptionInner {
+                response_patterns: vec![],
+                instrumentation_enabled: true,
+                faults: FaultDecider::new(faults),
+            })),
+        };
+
+        let mut events =
+            page.event_listener::<fetch::EventRequestPaused>().await?;
+        let inner = interception.inner.clone();
+        let _handle = spawn(async move {
+            while let Some(event) = events.next().await {
+                if !is_request_stage(&event) {
+                    continue;
+                }
+                let fault = match inner.lock() {
+                    Ok(mut inner) => inner.faults.decide(&event.request.url),
+                    Err(_) => break,
+                };
+                let page = page.clone();
+                // Delays mustn't hold up the other requests.
+                spawn(async move {
+                    if let Err(error) = apply_fault(&page, &event, fault).await
+                    {
+                        log::warn!(
+                            "failed handling intercepted request {}: {}",
+                            event.request.url,
+                            error
+                        );
+                    }
+                });
+            }
+        });
+
+        interception.enable().await?;
+        Ok(interception)
+    }
+
+    /// Sets the patterns of the responses paused for instrumentation.
+    pub async fn set_response_patterns(
+        &self,
+        patterns: Vec<fetch::RequestPattern>,
+    ) -> Result<()> {
+        self.inner
+            .lock()
+            .map_err(|_| anyhow!("interception lock poisoned"))?
+            .response_patterns = patterns;
+        self.enable().await
+    }
+
+    /// Enables or disables the instrumentation of the responses loaded from now on.
+    pub async fn set_instrumentation(&self, enabled: bool) -> Result<()> {
+        self.inner
+            .lock()
+            .map_err(|_| anyhow!("interception lock poisoned"))?
+            .instrumentation_enabled = enabled;
+        self.enable().await
+    }
+
+    /// Replaces the fault rules, e.g. from a spec's `faults()` action.
+    pub async fn set_fault_rules(&self, rules: Vec<FaultRule>) -> Result<()> {
+        self.inner
+            .lock()
+            .map_err(|_| anyhow!("interception lock poisoned"))?
+            .faults
+            .set_rules(rules);
+        self.enable().await
+    }
+
+    async fn enable(&self) -> Result<()> {
+        let patterns: Vec<fetch::RequestPattern> = {
+            let inner = self
+                .inner
+                .lock()
+                .map_err(|_| anyhow!("interception lock poisoned"))?;
+            inner
+                .faults
+                .rules()
+                .iter()
+                .map(|rule| {
+                    fetch::RequestPattern::builder()
+                        .url_pattern(rule.pattern.clone())
+                        .request_stage(fetch::RequestStage::Request)
+                        .build()
+                })
+                .chain(
+                    inner
+                        .response_patterns
+                        .iter()
+                        .filter(|_| inner.instrumentation_enabled)
+                        .cloned(),
+                )
+                .collect()
+        };
+        // Without any patterns, all requests would be paused.
+        if patterns.is_empty() {
+            self.page
+                .execute(fetch::DisableParams {})
+                .await
+                .context("failed disabling request interception")?;
+            return Ok(());
+        }
+        self.page
+            .execute(fetch::EnableParams {
+                patterns: Some(patterns),
+                handle_auth_requests: None,
+            })
+            .await
+            .context("failed enabling request interception")?;
+        Ok(())
+    }
+}
+
+async fn apply_fault(
+    page: &Page,
+    event: &fetch::EventRequestPaused,
+    fault: Option<Fault>,
+) -> Result<()> {
+    if let Some(fault) = fault {
+        log::info!("injecting {:?} into request {}", fault, event.request.url);
+    }
+    match fault {
+        None => {}
+        Some(Fault::Delay(delay)) => sleep(delay).await,
+        Some(Fault::Drop) => {
+            page.execute(fetch::FailRequestParams::new(
+                event.request_id.clone(),
+                network::ErrorReason::ConnectionFailed,
+            ))
+            .await?;
+            return Ok(());
+        }
+        Some(Fault::ServerError) => {
+            page.execute(
+                fetch::FulfillRequestParams::builder()
+                    .request_id(event.request_id.clone())
+                    .response_code(500)
+                    .response_header(fetch::HeaderEntry::new(
+                        "content-type",
+                        "text/plain",
+                    ))
+                    .body(String::new())
+                    .build()
+                    .map_err(|error| {
+                        anyhow!(
+                            "failed building FulfillRequestParams: {}",
+                            error
+                        )
+                    })?,
+            )
+            .await?;
+            return Ok(());
+        }
+    }
+    page.execute(
+        fetch::ContinueRequestParams::builder()
+            .request_id(event.request_id.clone())
+            .build()
+            .map_err(|error| {
+                anyhow!("failed building ContinueRequestParams: {}", error)
+            })?,
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result, anyhow};
+use chromiumoxide::Page;
+use chromiumoxide::cdp::browser_protocol::{dom, dom_debugger};
+use chromiumoxide::cdp::js_protocol::debugger::{self, CallFrameId};
+use chromiumoxide::cdp::js_protocol::runtime;
+use futures::{StreamExt, TryStreamExt, stream};
+use serde::{Deserialize, Serialize};
+
+use crate::geometry::Point;
+
+/// The event types whose listeners are looked up, for generators of the actions
+/// triggering them.
+pub const LISTENER_TYPES: &[&str] = &["click", "dblclick", "contextmenu"];
+
+/// Elements with listeners described per state, the others being left out.
+const LISTENER_TARGETS_MAX: usize = 200;
+
+/// Concurrent CDP calls describing the elements with listeners.
+const DESCRIBE_CONCURRENCY: usize = 16;
+
+/// Object group of the document evaluated to look up its listeners, released afterwards.
+const OBJECT_GROUP: &str = "bombadil-listeners";
+
+/// An element with listeners for some of the `LISTENER_TYPES`, and the center of its
+/// first box.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ListenerTarget {
+    pub name: String,
+    pub types: Vec<String>,
+    pub point: Point,
+}
+
+/// Finds the elements of the page (including shadow roots and iframes) with listeners
+/// for `LISTENER_TYPES`, in a page paused on `call_frame_id`. Listeners added to
+/// `document`, `html` or `body`, e.g. by frameworks delegating events, can't be traced to
+/// the elements they handle, so they're left out.
+pub async fn listener_targets(
+    page: &Page,
+    call_frame_id: &CallFrameId,
+) -> Result<Vec<ListenerTarget>> {
+    // The document is evaluated rather than requested with `DOM.getDocument`, which would
+    // replace the tree the DOM agent tracks for mutation events.
+    let document = page
+        .execute(
+            debugger::EvaluateOnCallFrameParams::builder()
+                .call_frame_id(call_frame_id.clone())
+                .expression("document")
+                .object_group(OBJECT_GROUP)
+                .build()
+                .map_err(|err| anyhow!(err))?,
+        )
+        .await?
+        .result
+        .result
+        .object_id
+        .ok_or_else(|| anyhow!("document has no object id"))?;
+    let listeners = page
+        .execute(
+            dom_debugger::GetEventListenersParams::builder()
+                .object_id(document)
+                .depth(-1)
+                .pierce(true)
+                .build()
+                .map_err(|err| anyhow!(err))?,
+        )
+        .await
+        .context("failed getting event listeners");
+    page.execute(runtime::ReleaseObjectGroupParams::new(OBJECT_GROUP))
+        .await?;
+    let listeners = listeners?.result.listeners;
+
+    // Nodes keep the order their first listener is listed in.
+    let mut nodes: Vec<dom::BackendNodeId> = vec![];
+    let mut types_by_node: HashMap<dom::BackendNodeId, Vec<String>> =
+        HashMap::new();
+    for listener in listeners {
+        let Some(node) = listener.backend_node_id else {
+            continue;
+        };
+        if !LISTENER_TYPES.contains(&listener.r#type.as_str()) {
+            continue;
+        }
+        let types = types_by_node.entry(node).or_insert_with(|| {
+            nodes.push(node);
+            vec![]
+        });
+        if !types.contains(&listener.r#type) {
+            types.push(listener.r#type);
+        }
+    }
+
+    let targets: Vec<Option<ListenerTarget>> = stream::iter(
+        nodes.into_iter().take(LISTENER_TARGETS_MAX).map(|node| {
+            let types = types_by_node.remove(&node).unwrap_or_default();
+            listener_target(page, node, types)
+        }),
+    )
+    .buffered(DESCRIBE_CONCURRENCY)
+    .try_collect()
+    .await?;
+    Ok(targets.into_iter().flatten().collect())
+}
+
+/// Describes an element with listeners, or none if it's the document, `html` or `body`,
+/// or isn't rendered.
+async fn listener_target(
+    page: &Page,
+    node: dom::BackendNodeId,
+    types: Vec<String>,
+) -> Result<Option<ListenerTarget>> {
+    let name = page
+        .execute(
+            dom::DescribeNodeParams::builder()
+                .backend_node_id(node)
+                .build(),
+        )
+        .await?
+        .result
+        .node
+        .node_name;
+    if matches!(name.as_str(), "#document" | "HTML" | "BODY") {
+        return Ok(None);
+    }
+    // Nodes that aren't rendered have no quads, which fails.
+    let Ok(quads) = page
+        .execute(
+            dom::GetContentQuadsParams::builder()
+                .backend_node_id(node)
+                .build(),
+        )
+        .await
+    else {
+        return Ok(None);
+    };
+    Ok(quads
+        .result
+        .quads
+        .first()
+        .and_then(quad_center)
+        .map(|point| ListenerTarget { name, types, point }))
+}
+
+fn quad_center(quad: &dom::Quad) -> Option<Point> {
+    let points = quad.inner();
+    if points.len() != 8 {
+        return None;
+    }
+    Some(Point {
+        x: (points[0] + points[2] + points[4] + points[6]) / 4.0,
+        y: (points[1] + points[3] + points[5] + points[7]) / 4.0,
+    })
+}
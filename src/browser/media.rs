@@ -0,0 +1,119 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use chromiumoxide::cdp::browser_protocol::browser::{
+    GrantPermissionsParams, PermissionType,
+};
+use serde_json as json;
+use url::Url;
+
+/// Fake camera and microphone devices, so that pages calling `getUserMedia` get a stream
+/// without a real device or a permission prompt blocking them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FakeMedia {
+    /// A `.y4m` or `.mjpeg` file played by the fake camera, instead of Chrome's test
+    /// pattern.
+    pub video: Option<PathBuf>,
+    /// A `.wav` file played by the fake microphone, instead of Chrome's beep.
+    pub audio: Option<PathBuf>,
+}
+
+impl FakeMedia {
+    pub fn launch_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "--use-fake-device-for-media-stream".to_string(),
+            "--use-fake-ui-for-media-stream".to_string(),
+        ];
+        if let Some(video) = &self.video {
+            args.push(format!(
+                "--use-file-for-fake-video-capture={}",
+                video.display()
+            ));
+        }
+        if let Some(audio) = &self.audio {
+            args.push(format!(
+                "--use-file-for-fake-audio-capture={}",
+                audio.display()
+            ));
+        }
+        args
+    }
+}
+
+/// A browser permission granted to the origin, by its name in the DevTools protocol (e.g.
+/// `geolocation` or `clipboardReadWrite`), or `camera` and `microphone`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Permission(pub PermissionType);
+
+impl FromStr for Permission {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "camera" => Ok(Permission(PermissionType::VideoCapture)),
+            "microphone" => Ok(Permission(PermissionType::AudioCapture)),
+            other => json::from_value(json::Value::String(other.to_string()))
+                .map(Permission)
+                .map_err(|_| {
+                    format!(
+                        "unknown permission {:?}, expected camera, microphone or a DevTools protocol permission type such as geolocation",
+                        other
+                    )
+                }),
+        }
+    }
+}
+
+/// Grants `permissions` to the origin, so that the page isn't stuck behind their prompts.
+pub async fn grant_permissions(
+    browser: &chromiumoxide::Browser,
+    origin: &Url,
+    permissions: &[Permission],
+) -> Result<()> {
+    if permissions.is_empty() {
+        return Ok(());
+    }
+    browser
+        .execute(GrantPermissionsParams {
+            permissions: permissions
+                .iter()
+                .map(|permission| permission.0.clone())
+                .collect(),
+            origin: Some(origin.origin().ascii_serialization()),
+            browser_context_id: None,
+        })
+        .await
+        .context("failed granting permissions")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fake_media() {
+        let media = FakeMedia {
+            video: Some(PathBuf::from("/tmp/face.y4m")),
+            audio: None,
+        };
+        assert_eq!(
+            media.launch_args(),
+            vec![
+                "--use-fake-device-for-media-stream",
+                "--use-fake-ui-for-media-stream",
+                "--use-file-for-fake-video-capture=/tmp/face.y4m",
+            ]
+        );
+        assert_eq!(
+            "camera".parse::<Permission>(),
+            Ok(Permission(PermissionType::VideoCapture))
+        );
+        assert_eq!(
+            "clipboardReadWrite".parse::<Permission>(),
+            Ok(Permission(PermissionType::ClipboardReadWrite))
+        );
+        assert!("teleport".parse::<Permission>().is_err());
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Result, bail};
+use futures::future::{self, BoxFuture};
+use serde::de::DeserializeOwned;
+use serde_json as json;
+use url::Url;
+
+use crate::browser::actions::BrowserAction;
+use crate::browser::checkpoints::Checkpoint;
+use crate::browser::click_deny_list::CLICK_LABELS_FUNCTION;
+use crate::browser::gpu::GpuInfo;
+use crate::browser::instrumentation::{CachingDiagnostics, ScriptSizes};
+use crate::browser::network_log::NetworkRequest;
+use crate::browser::payment_guard::PAYMENT_GUARD_FUNCTION;
+use crate::browser::state::{
+    BrowserState, CaptureTimings, ConsoleEntry, EdgeBucket, EdgeIndex,
+    Exception,
+};
+use crate::browser::viewports::ViewportProfile;
+use crate::browser::{BrowserBackend, BrowserEvent, InitialNavigation};
+
+/// The results of the scripts evaluated in a fixture state, looked up by a part of their
+/// source.
+#[derive(Clone, Debug, Default)]
+pub struct Answers {
+    answers: Vec<(String, json::Value)>,
+}
+
+impl Answers {
+    pub(crate) fn answer<Output: DeserializeOwned>(
+        &self,
+        function: &str,
+    ) -> Result<Output> {
+        let Some((_, value)) = self
+            .answers
+            .iter()
+            .rev()
+            .find(|(needle, _)| function.contains(needle.as_str()))
+        else {
+            bail!(
+                "no answer to script in fixture state: {}",
+                function.chars().take(80).collect::<String>()
+            );
+        };
+        Ok(json::from_value(value.clone())?)
+    }
+}
+
+/// A state scripted for a `MockBrowser`.
+#[derive(Clone, Debug)]
+pub struct MockState {
+    pub url: Url,
+    pub title: String,
+    pub content_type: String,
+    pub console_entries: Vec<ConsoleEntry>,
+    pub exceptions: Vec<Exception>,
+    pub network_requests: Vec<NetworkRequest>,
+    /// The edges newly hit in this state, with their buckets.
+    pub coverage: Vec<(EdgeIndex, EdgeBucket)>,
+    pub timings: CaptureTimings,
+    answers: Answers,
+}
+
+impl MockState {
+    /// An HTML page at `url`, where the payment guard and the click deny-list find nothing
+    /// to refuse.
+    pub fn new(url: Url) -> Self {
+        MockState {
+            url,
+            title: String::new(),
+            content_type: "text/html".to_string(),
+            console_entries: vec![],
+            exceptions: vec![],
+            network_requests: vec![],
+            coverage: vec![],
+            timings: CaptureTimings::default(),
+            answers: Answers {
+                answers: vec![
+                    (
+                        PAYMENT_GUARD_FUNCTION.to_string(),
+                        json::json!({
+                            "activeIsPayment": false,
+                            "activeInPaymentForm": false,
+                            "clicksSubmitting": [],
+                        }),
+                    ),
+                    (CLICK_LABELS_FUNCTION.to_string(), json::json!([])),
+                ],
+            },
+        }
+    }
+
+    /// Answers the scripts containing `needle`, e.g. the source of an extractor, with
+    /// `value`. Later answers take precedence.
+    pub fn answer(
+        mut self,
+        needle: impl Into<String>,
+        value: json::Value,
+    ) -> Self {
+        self.answers.answers.push((needle.into(), value));
+        self
+    }
+
+    fn into_state(self) -> BrowserState {
+        let mut state = BrowserState::fixture(
+            self.url,
+            self.title,
+            self.content_type,
+            Arc::new(self.answers),
+        );
+        state.console_entries = self.console_entries;
+        state.exceptions = self.exceptions;
+        state.network_requests = self.network_requests;
+        state.coverage.edges_new = self.coverage;
+        state.timings = self.timings;
+        state
+    }
+}
+
+/// The actions applied to a `MockBrowser`, readable after handing it over to a runner.
+#[derive(Clone, Debug, Default)]
+pub struct AppliedActions(Arc<Mutex<Vec<BrowserAction>>>);
+
+impl AppliedActions {
+    pub fn get(&self) -> Vec<BrowserAction> {
+        self.0
+            .lock()
+            .expect("applied actions lock poisoned")
+            .clone()
+    }
+}
+
+/// A browser replaying scripted states instead of running Chrome, for testing the runner
+/// and specifications quickly. The next state is emitted on start and after every action
+/// or observation, and once they've all been emitted the page never changes again.
+pub struct MockBrowser {
+    states: VecDeque<MockState>,
+    /// Whether a state was requested since the last one was emitted.
+    requested: bool,
+    applied: AppliedActions,
+}
+
+impl MockBrowser {
+    pub fn new(states: Vec<MockState>) -> Self {
+        MockBrowser {
+            states: states.into(),
+            requested: false,
+            applied: AppliedActions::default(),
+        }
+    }
+
+    pub fn applied(&self) -> AppliedActions {
+        self.applied.clone()
+    }
+}
+
+impl BrowserBackend for MockBrowser {
+    fn initiate<'a>(
+        &'a mut self,
+        _navigation: &'a InitialNavigation,
+    ) -> BoxFuture<'a, Result<()>> {
+        self.requested = true;
+        Box::pin(future::ready(Ok(())))
+    }
+
+    fn terminate(self: Box<Self>) -> BoxFuture<'static, Result<()>> {
+        Box::pin(future::ready(Ok(())))
+    }
+
+    fn next_event(&mut self) -> BoxFuture<'_, Option<BrowserEvent>> {
+        let state = if self.requested {
+            self.states.pop_front()
+        } else {
+            None
+        };
+        match state {
+            Some(state) => {
+                self.requested = false;
+                Box::pin(future::ready(Some(BrowserEvent::StateChanged(
+                    state.into_state(),
+                ))))
+            }
+            None => Box::pin(future::pending()),
+        }
+    }
+
+    fn apply(
+        &mut self,
+        action: BrowserAction,
+        _timeout: Duration,
+    ) -> Result<()> {
+        self.applied
+            .0
+            .lock()
+            .expect("applied actions lock poisoned")
+            .push(action);
+        self.requested = true;
+        Ok(())
+    }
+
+    fn restore_checkpoint(
+        &mut self,
+        action: BrowserAction,
+        _checkpoint: Checkpoint,
+        timeout: Duration,
+    ) -> Result<()> {
+        self.apply(action, timeout)
+    }
+
+    fn observe(&mut self, _timeout: Duration) -> Result<()> {
+        self.requested = true;
+        Ok(())
+    }
+
+    fn set_viewport<'a>(
+        &'a mut self,
+        _profile: &'a ViewportProfile,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(future::ready(Ok(())))
+    }
+
+    fn set_settle_delay(&mut self, _delay: Duration) -> Result<()> {
+        Ok(())
+    }
+
+    fn caching_diagnostics(&self) -> CachingDiagnostics {
+        CachingDiagnostics::default()
+    }
+
+    fn script_sizes(&self) -> ScriptSizes {
+        ScriptSizes::default()
+    }
+
+    fn gpu_info(&self) -> Option<GpuInfo> {
+        None
+    }
+}
@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use chromiumoxide::Page;
+use chromiumoxide::cdp::browser_protocol::page;
+use chromiumoxide::cdp::js_protocol::debugger::CallFrameId;
+use serde::{Deserialize, Serialize};
+
+use crate::browser::evaluation::evaluate_expression_in_debugger;
+
+/// Most frequently mutated elements kept in a summary.
+const TARGETS_MAX: usize = 5;
+
+/// Tallies the mutated elements of every document with a `MutationObserver`, until the
+/// next state takes (and resets) them. Targets are described as `tag#id.class`, which is
+/// enough to tell which part of the page changed without recording the nodes themselves.
+/// The mutations themselves are counted from the DOM events, for `dom_mutations`.
+const MUTATION_OBSERVER_SCRIPT: &str = r##"(() => {
+    if (window.__bombadil_mutations__) return;
+
+    let targets = new Map();
+
+    function describe(node) {
+        const element = node instanceof Element ? node : node.parentElement;
+        if (!element) return "#document";
+        let description = element.localName;
+        if (element.id) description += "#" + element.id;
+        for (const name of Array.from(element.classList).slice(0, 2)) {
+            description += "." + name;
+        }
+        return description;
+    }
+
+    function record(mutations) {
+        for (const mutation of mutations) {
+            const target = describe(mutation.target);
+            targets.set(target, (targets.get(target) || 0) + 1);
+        }
+    }
+
+    const observer = new MutationObserver(record);
+    observer.observe(document, {
+        childList: true,
+        attributes: true,
+        characterData: true,
+        subtree: true,
+    });
+
+    window.__bombadil_mutations__ = {
+        take(targetsMax) {
+            record(observer.takeRecords());
+            const taken = targets;
+            targets = new Map();
+            return Array.from(taken)
+                .sort((a, b) => b[1] - a[1])
+                .slice(0, targetsMax);
+        },
+    };
+})()"##;
+
+/// The DOM mutations between two states, i.e. those caused by the action in between (or by
+/// the page itself, e.g. on timers).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MutationSummary {
+    /// Inserted, removed or updated nodes and attributes, as in `dom_mutations`.
+    pub count: u64,
+    /// The most frequently mutated elements, with their mutation counts.
+    pub targets: Vec<(String, u64)>,
+}
+
+pub async fn observe_mutations(page: &Page) -> Result<()> {
+    page.execute(page::AddScriptToEvaluateOnNewDocumentParams::new(
+        MUTATION_OBSERVER_SCRIPT,
+    ))
+    .await
+    .context("failed injecting mutation observer")?;
+    Ok(())
+}
+
+/// Takes the most frequently mutated elements since the last call. Documents loaded before
+/// the observer was installed have none.
+pub(crate) async fn take_mutation_targets(
+    page: &Page,
+    call_frame_id: &CallFrameId,
+) -> Result<Vec<(String, u64)>> {
+    let targets: Option<Vec<(String, u64)>> = evaluate_expression_in_debugger(
+        page,
+        call_frame_id,
+        format!("window.__bombadil_mutations__?.take({TARGETS_MAX}) ?? null"),
+    )
+    .await?;
+    Ok(targets.unwrap_or_default())
+}
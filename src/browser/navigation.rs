@@ -0,0 +1,150 @@
+use std::fmt::Display;
+use std::time::Duration;
+
+/// Content types of the documents Bombadil can test.
+const HTML_CONTENT_TYPES: &[&str] = &["text/html", "application/xhtml+xml"];
+
+/// Why the first navigation to the origin failed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NavigationFailure {
+    /// The origin's host name couldn't be resolved.
+    Dns,
+    /// Nothing accepted the connection to the origin.
+    ConnectionRefused,
+    /// The TLS handshake failed, e.g. on an untrusted certificate.
+    Tls,
+    /// The origin didn't load in time.
+    Timeout(Duration),
+    /// The origin loaded, but isn't an HTML document.
+    NotHtml {
+        content_type: String,
+    },
+    Other,
+}
+
+impl NavigationFailure {
+    /// Classifies a failed navigation by the network error Chrome reported, like
+    /// `net::ERR_NAME_NOT_RESOLVED`.
+    pub fn from_error(error: &str) -> Self {
+        let is_any =
+            |codes: &[&str]| codes.iter().any(|code| error.contains(code));
+        if is_any(&["ERR_NAME_NOT_RESOLVED", "ERR_NAME_RESOLUTION_FAILED"]) {
+            NavigationFailure::Dns
+        } else if is_any(&[
+            "ERR_CONNECTION_REFUSED",
+            "ERR_ADDRESS_UNREACHABLE",
+            "ERR_CONNECTION_RESET",
+            "ERR_EMPTY_RESPONSE",
+        ]) {
+            NavigationFailure::ConnectionRefused
+        } else if is_any(&["ERR_CERT_", "ERR_SSL_", "ERR_BAD_SSL_"]) {
+            NavigationFailure::Tls
+        } else {
+            NavigationFailure::Other
+        }
+    }
+
+    /// The failure of a navigation that loaded a document of this content type, if it
+    /// isn't HTML.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        if HTML_CONTENT_TYPES.contains(&content_type) {
+            None
+        } else {
+            Some(NavigationFailure::NotHtml {
+                content_type: content_type.to_string(),
+            })
+        }
+    }
+
+    /// What to check to fix it.
+    fn hint(&self) -> Option<String> {
+        match self {
+            NavigationFailure::Dns => Some(
+                "the host name couldn't be resolved, check the origin's spelling and that its host is reachable from this machine (e.g. from inside a container)".to_string(),
+            ),
+            NavigationFailure::ConnectionRefused => Some(
+                "nothing accepted the connection, check that the server is started and listening on the origin's port, or wait for it with --health-check or --navigation-retries".to_string(),
+            ),
+            NavigationFailure::Tls => Some(
+                "the TLS connection failed, e.g. because the certificate is self-signed or issued for another host, test over plain HTTP or have the certificate trusted by the system".to_string(),
+            ),
+            NavigationFailure::Timeout(timeout) => Some(format!(
+                "the origin didn't load within {:?}, raise --navigation-timeout if the server is slow to respond",
+                timeout
+            )),
+            NavigationFailure::NotHtml { content_type } => Some(format!(
+                "the origin is a {} document rather than an HTML page, use the URL of a page of the app, not e.g. of its API",
+                content_type
+            )),
+            NavigationFailure::Other => None,
+        }
+    }
+}
+
+/// The first navigation to the origin failed, before any state could be captured.
+#[derive(Clone, Debug)]
+pub struct InitialNavigationError {
+    pub origin: String,
+    pub attempts: u32,
+    pub failure: NavigationFailure,
+    /// The error reported by the browser.
+    pub message: String,
+}
+
+impl Display for InitialNavigationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "navigating to {} failed after {} attempt(s): {}",
+            self.origin, self.attempts, self.message
+        )?;
+        if let Some(hint) = self.failure.hint() {
+            write!(f, "\nhint: {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for InitialNavigationError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_navigation_failures() {
+        assert_eq!(
+            NavigationFailure::from_error("net::ERR_NAME_NOT_RESOLVED"),
+            NavigationFailure::Dns
+        );
+        assert_eq!(
+            NavigationFailure::from_error("net::ERR_CONNECTION_REFUSED"),
+            NavigationFailure::ConnectionRefused
+        );
+        assert_eq!(
+            NavigationFailure::from_error("net::ERR_CERT_AUTHORITY_INVALID"),
+            NavigationFailure::Tls
+        );
+        assert_eq!(
+            NavigationFailure::from_error("net::ERR_ABORTED"),
+            NavigationFailure::Other
+        );
+        assert_eq!(NavigationFailure::from_content_type("text/html"), None);
+        assert_eq!(
+            NavigationFailure::from_content_type("application/json"),
+            Some(NavigationFailure::NotHtml {
+                content_type: "application/json".to_string()
+            })
+        );
+
+        let error = InitialNavigationError {
+            origin: "http://localhost:3000/".to_string(),
+            attempts: 1,
+            failure: NavigationFailure::ConnectionRefused,
+            message: "net::ERR_CONNECTION_REFUSED".to_string(),
+        };
+        assert!(error.to_string().starts_with(
+            "navigating to http://localhost:3000/ failed after 1 attempt(s): net::ERR_CONNECTION_REFUSED\nhint: nothing accepted the connection"
+        ));
+    }
+}
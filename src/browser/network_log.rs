@@ -0,0 +1,403 @@
+use anyhow::Result;
+use chromiumoxide::Page;
+use chromiumoxide::cdp::browser_protocol::network::{
+    self, RequestId, ResourceType,
+};
+use futures::{StreamExt, stream};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::spawn;
+
+/// Requests completed between two states that are kept, the rest being counted as dropped.
+const NETWORK_REQUESTS_MAX: usize = 500;
+
+/// A request made by the page, as exposed to extractors once it has completed, i.e. loaded
+/// or failed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkRequest {
+    pub url: String,
+    pub method: String,
+    /// The HTTP status of the response, or none if the request failed before one.
+    pub status: Option<i64>,
+    pub resource_type: Option<String>,
+    /// What started the request: `parser`, `script`, `preload`, `preflight`, `other`, or
+    /// `SignedExchange`.
+    pub initiator: String,
+    /// When the request was sent, in milliseconds since the Unix epoch.
+    pub started_at: f64,
+    /// From sending the request until it loaded or failed.
+    pub duration_millis: f64,
+    /// Why the request failed, e.g. `net::ERR_CONNECTION_REFUSED`.
+    pub error: Option<String>,
+}
+
+/// A redirect of a document request, from `url`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RedirectHop {
+    pub url: String,
+    /// The 3xx status of the redirect.
+    pub status: i64,
+}
+
+/// The redirects a navigation went through, as exposed to extractors once it has loaded
+/// or failed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RedirectChain {
+    /// The URLs redirected from, in order.
+    pub hops: Vec<RedirectHop>,
+    /// The URL the navigation ended at.
+    pub url: String,
+    /// The HTTP status of the final response, or none if the navigation failed before one.
+    pub status: Option<i64>,
+    /// Why the navigation failed, e.g. `net::ERR_TOO_MANY_REDIRECTS`.
+    pub error: Option<String>,
+    /// Whether a URL came up twice in the chain, or the browser gave up after too many
+    /// redirects.
+    pub looped: bool,
+}
+
+impl RedirectChain {
+    fn new(
+        hops: Vec<RedirectHop>,
+        request: &NetworkRequest,
+        error: Option<String>,
+    ) -> Self {
+        let mut urls: Vec<&str> = hops
+            .iter()
+            .map(|hop| hop.url.as_str())
+            .chain([request.url.as_str()])
+            .collect();
+        let count = urls.len();
+        urls.sort_unstable();
+        urls.dedup();
+        let looped = urls.len() < count
+            || error.as_deref() == Some("net::ERR_TOO_MANY_REDIRECTS");
+        RedirectChain {
+            hops,
+            url: request.url.clone(),
+            status: request.status,
+            error,
+            looped,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PendingRequest {
+    request: NetworkRequest,
+    /// Monotonic time the request was sent at, in seconds.
+    sent: f64,
+}
+
+#[derive(Debug, Default)]
+struct NetworkLogInner {
+    pending: HashMap<RequestId, PendingRequest>,
+    completed: Vec<NetworkRequest>,
+    dropped: usize,
+    /// Redirects of the document requests still pending.
+    redirecting: HashMap<RequestId, Vec<RedirectHop>>,
+    redirects: Vec<RedirectChain>,
+}
+
+impl NetworkLogInner {
+    fn sent(&mut self, id: RequestId, request: NetworkRequest, sent: f64) {
+        self.pending.insert(id, PendingRequest { request, sent });
+    }
+
+    fn responded(&mut self, id: &RequestId, status: i64) {
+        if let Some(pending) = self.pending.get_mut(id) {
+            pending.request.status = Some(status);
+        }
+    }
+
+    fn redirected(&mut self, id: &RequestId, status: i64) {
+        let Some(pending) = self.pending.get(id) else {
+            return;
+        };
+        if pending.request.resource_type.as_deref() == Some("Document") {
+            self.redirecting
+                .entry(id.clone())
+                .or_default()
+                .push(RedirectHop {
+                    url: pending.request.url.clone(),
+                    status,
+                });
+        }
+    }
+
+    /// Ends the redirect chain of a document request, if it was redirected.
+    fn navigated(&mut self, id: &RequestId, error: Option<&str>) {
+        let Some(hops) = self.redirecting.remove(id) else {
+            return;
+        };
+        if let Some(pending) = self.pending.get(id)
+            && self.redirects.len() < NETWORK_REQUESTS_MAX
+        {
+            self.redirects.push(RedirectChain::new(
+                hops,
+                &pending.request,
+                error.map(str::to_string),
+            ));
+        }
+    }
+
+    fn completed(&mut self, id: &RequestId, time: f64, error: Option<String>) {
+        let Some(PendingRequest { mut request, sent }) =
+            self.pending.remove(id)
+        else {
+            return;
+        };
+        request.duration_millis = (time - sent) * 1000.0;
+        request.error = error;
+        if self.completed.len() < NETWORK_REQUESTS_MAX {
+            self.completed.push(request);
+        } else {
+            self.dropped += 1;
+        }
+    }
+}
+
+/// Tracks the requests of the page, kept up to date from CDP events.
+#[derive(Clone, Debug, Default)]
+pub struct NetworkLog {
+    inner: Arc<Mutex<NetworkLogInner>>,
+}
+
+impl NetworkLog {
+    /// Takes the requests completed since the last call, and the number of those dropped
+    /// beyond the limit.
+    pub fn take(&self) -> (Vec<NetworkRequest>, usize) {
+        let Ok(mut inner) = self.inner.lock() else {
+            return (vec![], 0);
+        };
+        let dropped = std::mem::take(&mut inner.dropped);
+        (std::mem::take(&mut inner.completed), dropped)
+    }
+
+    /// Takes the redirect chains of the navigations completed since the last call.
+    pub fn take_redirects(&self) -> Vec<RedirectChain> {
+        let Ok(mut inner) = self.inner.lock() else {
+            return vec![];
+        };
+        std::mem::take(&mut inner.redirects)
+    }
+}
+
+enum NetworkEvent {
+    Sent(RequestId, NetworkRequest, f64),
+    /// A redirect completes the request it redirects, under the same id.
+    Redirected(RequestId, i64, f64, NetworkRequest),
+    Responded(RequestId, i64),
+    Completed(RequestId, f64, Option<String>),
+}
+
+fn resource_type(resource_type: &ResourceType) -> String {
+    resource_type.as_ref().to_string()
+}
+
+/// Start tracking the requests of the page. The network domain is already enabled by
+/// chromiumoxide.
+pub async fn track_network(page: &Page) -> Result<NetworkLog> {
+    let sent = page
+        .event_listener::<network::EventRequestWillBeSent>()
+        .await?
+        .map(|event| {
+            let request = NetworkRequest {
+                url: event.request.url.clone(),
+                method: event.request.method.clone(),
+                status: None,
+                resource_type: event.r#type.as_ref().map(resource_type),
+                initiator: event.initiator.r#type.as_ref().to_string(),
+                started_at: *event.wall_time.inner() * 1000.0,
+                duration_millis: 0.0,
+                error: None,
+            };
+            let time = *event.timestamp.inner();
+            match &event.redirect_response {
+                Some(response) => NetworkEvent::Redirected(
+                    event.request_id.clone(),
+                    response.status,
+                    time,
+                    request,
+                ),
+                None => {
+                    NetworkEvent::Sent(event.request_id.clone(), request, time)
+                }
+            }
+        });
+    let responded = page
+        .event_listener::<network::EventResponseReceived>()
+        .await?
+        .map(|event| {
+            NetworkEvent::Responded(
+                event.request_id.clone(),
+                event.response.status,
+            )
+        });
+    let finished = page
+        .event_listener::<network::EventLoadingFinished>()
+        .await?
+        .map(|event| {
+            NetworkEvent::Completed(
+                event.request_id.clone(),
+                *event.timestamp.inner(),
+                None,
+            )
+        });
+    let failed = page
+        .event_listener::<network::EventLoadingFailed>()
+        .await?
+        .map(|event| {
+            NetworkEvent::Completed(
+                event.request_id.clone(),
+                *event.timestamp.inner(),
+                Some(event.error_text.clone()),
+            )
+        });
+
+    let network_log = NetworkLog::default();
+    let inner = network_log.inner.clone();
+    let mut events = stream::select_all(vec![
+        sent.boxed(),
+        responded.boxed(),
+        finished.boxed(),
+        failed.boxed(),
+    ]);
+    let _handle = spawn(async move {
+        while let Some(event) = events.next().await {
+            let Ok(mut inner) = inner.lock() else {
+                break;
+            };
+            match event {
+                NetworkEvent::Sent(id, request, time) => {
+                    inner.sent(id, request, time)
+                }
+                NetworkEvent::Redirected(id, status, time, request) => {
+                    inner.redirected(&id, status);
+                    inner.responded(&id, status);
+                    inner.completed(&id, time, None);
+                    inner.sent(id, request, time);
+                }
+                NetworkEvent::Responded(id, status) => {
+                    inner.responded(&id, status)
+                }
+                NetworkEvent::Completed(id, time, error) => {
+                    inner.navigated(&id, error.as_deref());
+                    inner.completed(&id, time, error)
+                }
+            }
+        }
+    });
+
+    Ok(network_log)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(url: &str) -> NetworkRequest {
+        NetworkRequest {
+            url: url.to_string(),
+            method: "GET".to_string(),
+            status: None,
+            resource_type: Some("Fetch".to_string()),
+            initiator: "script".to_string(),
+            started_at: 0.0,
+            duration_millis: 0.0,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_completed_requests() {
+        let log = NetworkLog::default();
+        {
+            let mut inner = log.inner.lock().unwrap();
+            let api = RequestId::from("1".to_string());
+            let image = RequestId::from("2".to_string());
+            let pending = RequestId::from("3".to_string());
+            inner.sent(api.clone(), request("http://a/api"), 1.0);
+            inner.sent(image.clone(), request("http://a/b.png"), 1.0);
+            inner.sent(pending, request("http://a/slow"), 1.0);
+            inner.responded(&api, 500);
+            inner.completed(&api, 1.25, None);
+            inner.completed(
+                &image,
+                2.0,
+                Some("net::ERR_CONNECTION_REFUSED".to_string()),
+            );
+        }
+
+        let (requests, dropped) = log.take();
+        assert_eq!(dropped, 0);
+        assert_eq!(
+            requests,
+            vec![
+                NetworkRequest {
+                    status: Some(500),
+                    duration_millis: 250.0,
+                    ..request("http://a/api")
+                },
+                NetworkRequest {
+                    duration_millis: 1000.0,
+                    error: Some("net::ERR_CONNECTION_REFUSED".to_string()),
+                    ..request("http://a/b.png")
+                },
+            ]
+        );
+        assert!(log.take().0.is_empty());
+    }
+
+    #[test]
+    fn test_redirect_chains() {
+        let log = NetworkLog::default();
+        {
+            let mut inner = log.inner.lock().unwrap();
+            let document = |url: &str| NetworkRequest {
+                resource_type: Some("Document".to_string()),
+                ..request(url)
+            };
+            let login = RequestId::from("1".to_string());
+            inner.sent(login.clone(), document("http://a/account"), 1.0);
+            inner.redirected(&login, 302);
+            inner.sent(login.clone(), document("http://sso/login"), 1.0);
+            inner.responded(&login, 200);
+            inner.navigated(&login, None);
+            inner.completed(&login, 2.0, None);
+
+            let looping = RequestId::from("2".to_string());
+            inner.sent(looping.clone(), document("http://a/x"), 3.0);
+            inner.redirected(&looping, 301);
+            inner.sent(looping.clone(), document("http://a/y"), 3.0);
+            inner.redirected(&looping, 301);
+            inner.sent(looping.clone(), document("http://a/x"), 3.0);
+            inner.navigated(&looping, None);
+
+            let api = RequestId::from("3".to_string());
+            inner.sent(api.clone(), request("http://a/api"), 4.0);
+            inner.redirected(&api, 307);
+        }
+
+        let redirects = log.take_redirects();
+        assert_eq!(
+            redirects[0],
+            RedirectChain {
+                hops: vec![RedirectHop {
+                    url: "http://a/account".to_string(),
+                    status: 302,
+                }],
+                url: "http://sso/login".to_string(),
+                status: Some(200),
+                error: None,
+                looped: false,
+            }
+        );
+        assert!(redirects[1].looped);
+        assert_eq!(redirects.len(), 2);
+    }
+}
@@ -0,0 +1,165 @@
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json as json;
+
+use crate::browser::actions::BrowserAction;
+use crate::browser::state::BrowserState;
+use crate::geometry::Point;
+
+const ENTER_KEY_CODE: u8 = 13;
+
+/// Evaluated in the page with the click points of the candidate actions. Payment fields are
+/// detected by their `autocomplete` tokens (`cc-number`, `cc-csc`, etc) or by common
+/// payment field names and IDs.
+pub(crate) const PAYMENT_GUARD_FUNCTION: &str = r#"(points) => {
+    const PAYMENT_NAME = /card.?num|cc.?num|cvv|cvc|csc|security.?code|expir|exp.?date/i;
+
+    function isPaymentField(element) {
+        if (!(element instanceof HTMLInputElement)) return false;
+        const tokens = (element.getAttribute("autocomplete") || "")
+            .toLowerCase()
+            .split(/\s+/);
+        if (tokens.some((token) => token.startsWith("cc-"))) return true;
+        return PAYMENT_NAME.test(element.name) || PAYMENT_NAME.test(element.id);
+    }
+
+    function hasPaymentFields(form) {
+        return !!form && Array.from(form.elements).some(isPaymentField);
+    }
+
+    function isSubmitControl(element) {
+        if (element instanceof HTMLButtonElement) return element.type === "submit";
+        if (element instanceof HTMLInputElement) {
+            return element.type === "submit" || element.type === "image";
+        }
+        return false;
+    }
+
+    const active = document.activeElement;
+    return {
+        activeIsPayment: isPaymentField(active),
+        activeInPaymentForm: hasPaymentFields(active && active.form),
+        clicksSubmitting: points.map(({ x, y }) => {
+            let element = document.elementFromPoint(x, y);
+            while (element && !isSubmitControl(element)) {
+                element = element.parentElement;
+            }
+            return !!element && hasPaymentFields(element.form);
+        }),
+    };
+}"#;
+
+/// Decides which actions would type into payment fields or submit forms containing them,
+/// to avoid accidental charges when exploring environments wired to payment gateways.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentGuard {
+    active_is_payment: bool,
+    active_in_payment_form: bool,
+    #[serde(skip)]
+    submitting_points: Vec<Point>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PaymentGuardResult {
+    #[serde(flatten)]
+    guard: PaymentGuard,
+    clicks_submitting: Vec<bool>,
+}
+
+impl PaymentGuard {
+    pub async fn evaluate(
+        state: &BrowserState,
+        actions: &[&BrowserAction],
+    ) -> Result<Self> {
+        let points: Vec<Point> = actions
+            .iter()
+            .filter_map(|action| match action {
+                BrowserAction::Click { point, .. }
+                | BrowserAction::DoubleClick { point, .. } => Some(*point),
+                _ => None,
+            })
+            .collect();
+        let result: PaymentGuardResult = state
+            .evaluate_function_call(
+                PAYMENT_GUARD_FUNCTION,
+                vec![json::to_value(&points)?],
+            )
+            .await?;
+        Ok(PaymentGuard {
+            submitting_points: points
+                .into_iter()
+                .zip(result.clicks_submitting)
+                .filter_map(|(point, submitting)| submitting.then_some(point))
+                .collect(),
+            ..result.guard
+        })
+    }
+
+    pub fn allows(&self, action: &BrowserAction) -> bool {
+        match action {
+            BrowserAction::TypeText { .. } => !self.active_is_payment,
+            BrowserAction::PressKey { code } => {
+                let submits =
+                    *code == ENTER_KEY_CODE && self.active_in_payment_form;
+                !(self.active_is_payment || submits)
+            }
+            BrowserAction::Click { point, .. }
+            | BrowserAction::DoubleClick { point, .. } => {
+                !self.submitting_points.iter().any(|submitting| {
+                    submitting.x == point.x && submitting.y == point.y
+                })
+            }
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard(
+        active_is_payment: bool,
+        active_in_payment_form: bool,
+    ) -> PaymentGuard {
+        PaymentGuard {
+            active_is_payment,
+            active_in_payment_form,
+            submitting_points: vec![Point { x: 10.0, y: 20.0 }],
+        }
+    }
+
+    #[test]
+    fn blocks_typing_into_payment_field() {
+        let action = BrowserAction::TypeText {
+            text: "4242".to_string(),
+            delay_millis: 1,
+        };
+        assert!(!guard(true, true).allows(&action));
+        assert!(guard(false, true).allows(&action));
+    }
+
+    #[test]
+    fn blocks_enter_in_payment_form() {
+        let enter = BrowserAction::PressKey {
+            code: ENTER_KEY_CODE,
+        };
+        let tab = BrowserAction::PressKey { code: 9 };
+        assert!(!guard(false, true).allows(&enter));
+        assert!(guard(false, true).allows(&tab));
+        assert!(guard(false, false).allows(&enter));
+    }
+
+    #[test]
+    fn blocks_clicking_submit_of_payment_form() {
+        let click = |x, y| BrowserAction::Click {
+            name: "BUTTON".to_string(),
+            content: None,
+            point: Point { x, y },
+        };
+        assert!(!guard(false, false).allows(&click(10.0, 20.0)));
+        assert!(guard(false, false).allows(&click(30.0, 20.0)));
+    }
+}
@@ -0,0 +1,218 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use chromiumoxide::detection::{DetectionOptions, default_executable};
+use tokio::io::AsyncWriteExt;
+
+/// The Chrome for Testing build to download when no Chrome is installed, pinned so that
+/// runs on CI images don't change with every Chrome release.
+pub const CHROME_FOR_TESTING_VERSION: &str = "131.0.6778.85";
+
+const CHROME_FOR_TESTING_URL: &str =
+    "https://storage.googleapis.com/chrome-for-testing-public";
+
+/// Environment variable with the path of the Chrome binary to launch, when none is picked on
+/// the command line.
+pub const CHROME_BINARY_VARIABLE: &str = "BOMBADIL_CHROME";
+
+/// Where Linux distributions and CI images commonly install Chromium, which the detection
+/// of Chrome doesn't look for.
+const CHROMIUM_PATHS: &[&str] = &[
+    "/usr/bin/chromium",
+    "/usr/bin/chromium-browser",
+    "/usr/lib/chromium/chromium",
+    "/snap/bin/chromium",
+    "/Applications/Chromium.app/Contents/MacOS/Chromium",
+];
+
+/// A platform Chrome for Testing is built for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Platform {
+    Linux64,
+    MacArm64,
+    MacX64,
+    Win64,
+}
+
+impl Platform {
+    fn current() -> Option<Self> {
+        match (std::env::consts::OS, std::env::consts::ARCH) {
+            ("linux", "x86_64") => Some(Platform::Linux64),
+            ("macos", "aarch64") => Some(Platform::MacArm64),
+            ("macos", "x86_64") => Some(Platform::MacX64),
+            ("windows", "x86_64") => Some(Platform::Win64),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Platform::Linux64 => "linux64",
+            Platform::MacArm64 => "mac-arm64",
+            Platform::MacX64 => "mac-x64",
+            Platform::Win64 => "win64",
+        }
+    }
+
+    fn archive_url(&self) -> String {
+        format!(
+            "{}/{}/{}/chrome-{}.zip",
+            CHROME_FOR_TESTING_URL,
+            CHROME_FOR_TESTING_VERSION,
+            self.name(),
+            self.name()
+        )
+    }
+
+    /// The path of the binary within the extracted archive.
+    fn binary_path(&self) -> PathBuf {
+        let directory = PathBuf::from(format!("chrome-{}", self.name()));
+        match self {
+            Platform::Linux64 => directory.join("chrome"),
+            Platform::MacArm64 | Platform::MacX64 => directory
+                .join("Google Chrome for Testing.app")
+                .join("Contents")
+                .join("MacOS")
+                .join("Google Chrome for Testing"),
+            Platform::Win64 => directory.join("chrome.exe"),
+        }
+    }
+}
+
+/// Finds a Chrome or Chromium binary to launch when none was picked: the one in the
+/// `BOMBADIL_CHROME` environment variable, an installed one, or a previously downloaded
+/// Chrome for Testing.
+pub fn find_chrome() -> Result<Option<PathBuf>> {
+    if let Some(binary) = std::env::var_os(CHROME_BINARY_VARIABLE) {
+        let binary = PathBuf::from(binary);
+        if !binary.is_file() {
+            bail!(
+                "Chrome binary {} (from {}) doesn't exist",
+                binary.display(),
+                CHROME_BINARY_VARIABLE
+            );
+        }
+        return Ok(Some(binary));
+    }
+    if let Ok(binary) = default_executable(DetectionOptions::default()) {
+        return Ok(Some(binary));
+    }
+    if let Some(binary) = CHROMIUM_PATHS
+        .iter()
+        .map(PathBuf::from)
+        .find(|path| path.is_file())
+    {
+        return Ok(Some(binary));
+    }
+    Ok(downloaded_chrome())
+}
+
+fn downloaded_chrome() -> Option<PathBuf> {
+    let platform = Platform::current()?;
+    let binary = cache_directory()?
+        .join(CHROME_FOR_TESTING_VERSION)
+        .join(platform.binary_path());
+    binary.is_file().then_some(binary)
+}
+
+/// Downloads the pinned Chrome for Testing build into the cache directory, unless it's
+/// already there, and returns its binary.
+pub async fn download_chrome() -> Result<PathBuf> {
+    let platform = Platform::current().with_context(|| {
+        format!(
+            "Chrome for Testing isn't built for {} {}",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        )
+    })?;
+    let directory = cache_directory()
+        .context("no cache directory to download Chrome into, set XDG_CACHE_HOME or HOME")?
+        .join(CHROME_FOR_TESTING_VERSION);
+    let binary = directory.join(platform.binary_path());
+    if binary.is_file() {
+        return Ok(binary);
+    }
+
+    // Download and extract next to the final directory, and only move it there once
+    // complete, so that an interrupted download isn't picked up later.
+    let partial = directory.with_extension("partial");
+    if partial.exists() {
+        tokio::fs::remove_dir_all(&partial).await?;
+    }
+    tokio::fs::create_dir_all(&partial).await?;
+
+    let url = platform.archive_url();
+    log::info!(
+        "downloading Chrome for Testing {} from {}",
+        CHROME_FOR_TESTING_VERSION,
+        url
+    );
+    let mut response = reqwest::get(&url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("failed downloading {}", url))?;
+    let archive_path = partial.join("chrome.zip");
+    let mut archive = tokio::fs::File::create(&archive_path).await?;
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .with_context(|| format!("failed downloading {}", url))?
+    {
+        archive.write_all(&chunk).await?;
+    }
+    archive.flush().await?;
+    drop(archive);
+
+    let extracted = partial.clone();
+    tokio::task::spawn_blocking(move || -> Result<()> {
+        let archive = std::fs::File::open(&archive_path)?;
+        zip::ZipArchive::new(archive)?.extract(&extracted)?;
+        std::fs::remove_file(&archive_path)?;
+        Ok(())
+    })
+    .await?
+    .context("failed extracting Chrome for Testing")?;
+    tokio::fs::rename(&partial, &directory).await?;
+
+    if !binary.is_file() {
+        bail!("downloaded archive {} has no {}", url, binary.display());
+    }
+    log::info!("downloaded Chrome for Testing to {}", binary.display());
+    Ok(binary)
+}
+
+fn cache_directory() -> Option<PathBuf> {
+    std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join(".cache"))
+        })
+        .map(|cache| cache.join("bombadil").join("chrome"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_layout() {
+        assert_eq!(
+            Platform::Linux64.archive_url(),
+            format!(
+                "https://storage.googleapis.com/chrome-for-testing-public/{}/linux64/chrome-linux64.zip",
+                CHROME_FOR_TESTING_VERSION
+            )
+        );
+        assert_eq!(
+            Platform::Win64.binary_path(),
+            PathBuf::from("chrome-win64").join("chrome.exe")
+        );
+        assert!(
+            Platform::MacArm64
+                .binary_path()
+                .starts_with("chrome-mac-arm64/Google Chrome for Testing.app")
+        );
+    }
+}
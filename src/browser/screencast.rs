@@ -0,0 +1,146 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use chromiumoxide::Page;
+use chromiumoxide::cdp::browser_protocol::page;
+use futures::StreamExt;
+use tokio::io::AsyncWriteExt;
+use tokio::spawn;
+
+/// JPEG quality of the frames, trading detail for disk space over long runs.
+const FRAME_QUALITY: i64 = 80;
+
+/// The list of frames with how long each is shown, in ffmpeg's concat format.
+pub const CONCAT_FILE_NAME: &str = "screencast.ffconcat";
+
+/// The lines of the concat list for a frame: how long the previous frame was shown, up to
+/// this one, and this frame's file.
+fn concat_entry(
+    previous: Option<f64>,
+    timestamp: f64,
+    file_name: &str,
+) -> String {
+    let mut entry = String::new();
+    if let Some(previous) = previous {
+        entry.push_str(&format!(
+            "duration {:.3}\n",
+            (timestamp - previous).max(0.0)
+        ));
+    }
+    entry.push_str(&format!("file '{}'\n", file_name));
+    entry
+}
+
+/// Writes frames to a directory as they come, along with the concat list timing them.
+struct ScreencastWriter {
+    directory: PathBuf,
+    list: tokio::fs::File,
+    frames: u64,
+    /// When the last frame was rendered, in seconds since the Unix epoch.
+    previous: Option<f64>,
+}
+
+impl ScreencastWriter {
+    async fn create(directory: &Path) -> Result<Self> {
+        tokio::fs::create_dir_all(directory).await?;
+        let mut list =
+            tokio::fs::File::create(directory.join(CONCAT_FILE_NAME)).await?;
+        list.write_all(b"ffconcat version 1.0\n").await?;
+        Ok(ScreencastWriter {
+            directory: directory.to_path_buf(),
+            list,
+            frames: 0,
+            previous: None,
+        })
+    }
+
+    async fn write(
+        &mut self,
+        frame: &page::EventScreencastFrame,
+    ) -> Result<()> {
+        let data = BASE64_STANDARD
+            .decode(AsRef::<[u8]>::as_ref(&frame.data))
+            .context("decode screencast frame")?;
+        let timestamp = match &frame.metadata.timestamp {
+            Some(timestamp) => *timestamp.inner(),
+            None => SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64(),
+        };
+        let file_name = format!("{:06}.jpg", self.frames);
+        tokio::fs::write(self.directory.join(&file_name), data).await?;
+        // Flushed on every frame, so that the list is usable however the run ends.
+        self.list
+            .write_all(
+                concat_entry(self.previous, timestamp, &file_name).as_bytes(),
+            )
+            .await?;
+        self.list.flush().await?;
+        self.frames += 1;
+        self.previous = Some(timestamp);
+        Ok(())
+    }
+}
+
+/// Records the page as JPEG frames in the directory, rendered whenever it changes, until
+/// the page is closed. The frames can be encoded into a video with e.g.
+/// `ffmpeg -f concat -i screencast.ffconcat screencast.webm`.
+pub async fn record_screencast(
+    page: Arc<Page>,
+    directory: &Path,
+) -> Result<()> {
+    let mut writer = ScreencastWriter::create(directory).await?;
+    let mut frames =
+        page.event_listener::<page::EventScreencastFrame>().await?;
+    page.execute(
+        page::StartScreencastParams::builder()
+            .format(page::StartScreencastFormat::Jpeg)
+            .quality(FRAME_QUALITY)
+            .build(),
+    )
+    .await?;
+
+    let _handle = spawn(async move {
+        while let Some(frame) = frames.next().await {
+            // Chrome sends no more frames until the last one is acknowledged.
+            if let Err(error) = page
+                .execute(page::ScreencastFrameAckParams::new(frame.session_id))
+                .await
+            {
+                log::debug!("failed acknowledging screencast frame: {}", error);
+                break;
+            }
+            if let Err(error) = writer.write(&frame).await {
+                log::warn!("failed writing screencast frame: {:#}", error);
+                break;
+            }
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concat_entries() {
+        assert_eq!(
+            concat_entry(None, 10.0, "000000.jpg"),
+            "file '000000.jpg'\n"
+        );
+        assert_eq!(
+            concat_entry(Some(10.0), 10.25, "000001.jpg"),
+            "duration 0.250\nfile '000001.jpg'\n"
+        );
+        // Timestamps going back, e.g. on a clock adjustment, don't make for negative
+        // durations.
+        assert_eq!(
+            concat_entry(Some(10.0), 9.0, "000002.jpg"),
+            "duration 0.000\nfile '000002.jpg'\n"
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, Weak};
+
+use anyhow::{Context, Result, anyhow, bail};
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use chromiumoxide::Page;
+use chromiumoxide::cdp::browser_protocol::page;
+use chromiumoxide::cdp::js_protocol::runtime;
+use serde_json as json;
+
+use crate::browser::state::{Screenshot, ScreenshotFormat};
+
+/// Buffers kept for reuse, beyond which released buffers are freed. A few states are in
+/// flight at any time (being captured, checked and written), so this covers the steady state.
+const POOL_SIZE_MAX: usize = 8;
+
+type FreeBuffers = Mutex<Vec<Vec<u8>>>;
+
+/// Blacks out the elements matching the selectors, returning the style sheet doing so. It's
+/// adopted rather than inserted, so that the page's mutation observers don't see it.
+/// Invalid selectors are skipped.
+const MASK_FUNCTION: &str = r#"(selectors) => {
+    const sheet = new CSSStyleSheet();
+    for (const selector of selectors) {
+        try {
+            sheet.insertRule(
+                `${selector} { background: #000 !important; filter: brightness(0) !important; }`,
+                sheet.cssRules.length,
+            );
+        } catch (error) {}
+    }
+    document.adoptedStyleSheets = [...document.adoptedStyleSheets, sheet];
+    return sheet;
+}"#;
+
+const UNMASK_FUNCTION: &str = r#"function() {
+    document.adoptedStyleSheets = document.adoptedStyleSheets.filter(
+        (sheet) => sheet !== this,
+    );
+}"#;
+
+/// Reuses the buffers of screenshots that are no longer referenced, instead of allocating
+/// new ones for every state.
+#[derive(Clone, Default)]
+pub struct ScreenshotPool {
+    free: Arc<FreeBuffers>,
+    /// Selectors of the elements blacked out in screenshots, e.g. dates, avatars or ads.
+    masks: Arc<[String]>,
+}
+
+impl ScreenshotPool {
+    pub fn new(masks: Vec<String>) -> Self {
+        ScreenshotPool {
+            free: Arc::default(),
+            masks: masks.into(),
+        }
+    }
+
+    /// Captures a screenshot of the page, decoding it into a pooled buffer. The default
+    /// background is expected to already be overridden as transparent.
+    pub async fn capture(
+        &self,
+        page: &Page,
+        format: ScreenshotFormat,
+    ) -> Result<Screenshot> {
+        page.activate().await?;
+        // Masking is best effort, as the page may be navigating away.
+        let mask = self
+            .mask(page)
+            .await
+            .inspect_err(|error| {
+                log::warn!("failed masking screenshot: {:#}", error)
+            })
+            .ok()
+            .flatten();
+        let result = page
+            .execute(
+                page::CaptureScreenshotParams::builder()
+                    .format(format)
+                    .build(),
+            )
+            .await;
+        if let Some(sheet) = mask {
+            unmask(page, sheet).await.unwrap_or_else(|error| {
+                log::debug!("failed unmasking screenshot: {:#}", error)
+            });
+        }
+        let result = result?.result;
+        let mut bytes = self.take();
+        BASE64_STANDARD
+            .decode_vec(AsRef::<[u8]>::as_ref(&result.data), &mut bytes)
+            .context("decode screenshot data")?;
+        Ok(Screenshot {
+            format,
+            data: self.wrap(bytes),
+        })
+    }
+
+    /// Adds the style sheet blacking out the masked elements, if there are any.
+    async fn mask(
+        &self,
+        page: &Page,
+    ) -> Result<Option<runtime::RemoteObjectId>> {
+        if self.masks.is_empty() {
+            return Ok(None);
+        }
+        let expression =
+            format!("({})({})", MASK_FUNCTION, json::to_string(&*self.masks)?);
+        let result = page
+            .execute(
+                runtime::EvaluateParams::builder()
+                    .expression(expression)
+                    .build()
+                    .map_err(|err| anyhow!(err))?,
+            )
+            .await?
+            .result;
+        if let Some(exception) = result.exception_details {
+            bail!("mask function threw: {}", exception.text);
+        }
+        Ok(result.result.object_id)
+    }
+
+    fn take(&self) -> Vec<u8> {
+        self.free
+            .lock()
+            .expect("screenshot pool lock poisoned")
+            .pop()
+            .unwrap_or_default()
+    }
+
+    fn wrap(&self, bytes: Vec<u8>) -> ScreenshotData {
+        ScreenshotData(Arc::new(PooledBuffer {
+            bytes,
+            pool: Arc::downgrade(&self.free),
+        }))
+    }
+}
+
+/// Captures the whole page rather than the viewport, as a PNG, e.g. to see all of a long
+/// form at once.
+pub async fn capture_full_page(page: &Page) -> Result<Screenshot> {
+    let size = page
+        .execute(page::GetLayoutMetricsParams::default())
+        .await?
+        .result
+        .css_content_size;
+    let result = page
+        .execute(
+            page::CaptureScreenshotParams::builder()
+                .format(ScreenshotFormat::Png)
+                .capture_beyond_viewport(true)
+                .clip(page::Viewport {
+                    x: 0.0,
+                    y: 0.0,
+                    width: size.width,
+                    height: size.height,
+                    scale: 1.0,
+                })
+                .build(),
+        )
+        .await?
+        .result;
+    let bytes = BASE64_STANDARD
+        .decode(AsRef::<[u8]>::as_ref(&result.data))
+        .context("decode screenshot data")?;
+    Ok(Screenshot {
+        format: ScreenshotFormat::Png,
+        data: bytes.into(),
+    })
+}
+
+async fn unmask(page: &Page, sheet: runtime::RemoteObjectId) -> Result<()> {
+    page.execute(
+        runtime::CallFunctionOnParams::builder()
+            .function_declaration(UNMASK_FUNCTION)
+            .object_id(sheet.clone())
+            .build()
+            .map_err(|err| anyhow!(err))?,
+    )
+    .await?;
+    page.execute(runtime::ReleaseObjectParams::new(sheet))
+        .await?;
+    Ok(())
+}
+
+/// A cheaply cloned handle to screenshot bytes, whose buffer goes back to its pool when the
+/// last handle is dropped.
+#[derive(Clone)]
+pub struct ScreenshotData(Arc<PooledBuffer>);
+
+/// Bytes not taken from a pool, e.g. of fixtures.
+impl From<Vec<u8>> for ScreenshotData {
+    fn from(bytes: Vec<u8>) -> Self {
+        ScreenshotData(Arc::new(PooledBuffer {
+            bytes,
+            pool: Weak::new(),
+        }))
+    }
+}
+
+impl Deref for ScreenshotData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0.bytes
+    }
+}
+
+struct PooledBuffer {
+    bytes: Vec<u8>,
+    pool: Weak<FreeBuffers>,
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        let Some(pool) = self.pool.upgrade() else {
+            return;
+        };
+        let Ok(mut free) = pool.lock() else {
+            return;
+        };
+        if free.len() < POOL_SIZE_MAX {
+            let mut bytes = std::mem::take(&mut self.bytes);
+            bytes.clear();
+            free.push(bytes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffers_are_reused_after_last_handle() {
+        let pool = ScreenshotPool::default();
+        let mut bytes = pool.take();
+        bytes.extend_from_slice(&[1; 1024]);
+        let data = pool.wrap(bytes);
+        let handle = data.clone();
+        drop(data);
+        assert!(pool.take().capacity() == 0);
+
+        drop(handle);
+        let reused = pool.take();
+        assert!(reused.is_empty());
+        assert!(reused.capacity() >= 1024);
+    }
+}
@@ -0,0 +1,251 @@
+use anyhow::{Context, Result, anyhow};
+use chromiumoxide::Page;
+use chromiumoxide::cdp::browser_protocol::service_worker::{
+    self, RegistrationId,
+};
+use chromiumoxide::cdp::browser_protocol::{network, storage};
+use futures::{StreamExt, stream};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::spawn;
+use url::Url;
+
+/// How service workers of the origin are treated at the start of a run. Stale service
+/// workers frequently serve old bundles, which makes coverage and property results
+/// misleading.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ServiceWorkerPolicy {
+    /// Leave service workers as they are.
+    #[default]
+    Keep,
+    /// Load all requests from the network, bypassing service workers.
+    Bypass,
+    /// Update service workers on every page load.
+    Update,
+    /// Unregister the origin's service workers before the test starts.
+    Unregister,
+}
+
+impl FromStr for ServiceWorkerPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(ServiceWorkerPolicy::Keep),
+            "bypass" => Ok(ServiceWorkerPolicy::Bypass),
+            "update" => Ok(ServiceWorkerPolicy::Update),
+            "unregister" => Ok(ServiceWorkerPolicy::Unregister),
+            other => Err(format!(
+                "invalid service worker policy `{other}`, expected one of: \
+                keep, bypass, update, unregister"
+            )),
+        }
+    }
+}
+
+/// Status of a service worker version, as exposed to extractors.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceWorkerStatus {
+    pub scope_url: Option<String>,
+    pub script_url: String,
+    pub status: String,
+    pub running_status: String,
+}
+
+#[derive(Debug, Default)]
+struct ServiceWorkersInner {
+    scopes: HashMap<RegistrationId, String>,
+    versions: HashMap<String, service_worker::ServiceWorkerVersion>,
+}
+
+/// Tracks the service workers known to the browser, kept up to date from CDP events.
+#[derive(Clone, Debug, Default)]
+pub struct ServiceWorkers {
+    inner: Arc<Mutex<ServiceWorkersInner>>,
+}
+
+impl ServiceWorkers {
+    /// Service workers that are not yet redundant, sorted by script URL.
+    pub fn statuses(&self) -> Vec<ServiceWorkerStatus> {
+        let Ok(inner) = self.inner.lock() else {
+            return vec![];
+        };
+        let mut statuses: Vec<ServiceWorkerStatus> = inner
+            .versions
+            .values()
+            .filter(|version| {
+                version.status
+                    != service_worker::ServiceWorkerVersionStatus::Redundant
+            })
+            .map(|version| ServiceWorkerStatus {
+                scope_url: inner.scopes.get(&version.registration_id).cloned(),
+                script_url: version.script_url.clone(),
+                status: version.status.as_ref().to_string(),
+                running_status: version.running_status.as_ref().to_string(),
+            })
+            .collect();
+        statuses.sort_by(|a, b| a.script_url.cmp(&b.script_url));
+        statuses
+    }
+}
+
+enum ServiceWorkerEvent {
+    Registrations(Vec<service_worker::ServiceWorkerRegistration>),
+    Versions(Vec<service_worker::ServiceWorkerVersion>),
+}
+
+/// Apply the service worker policy and start tracking service worker status.
+pub async fn manage_service_workers(
+    page: Arc<Page>,
+    origin: &Url,
+    policy: ServiceWorkerPolicy,
+) -> Result<ServiceWorkers> {
+    match policy {
+        ServiceWorkerPolicy::Keep => {}
+        ServiceWorkerPolicy::Bypass => {
+            page.execute(network::SetBypassServiceWorkerParams::new(true))
+                .await
+                .context("failed bypassing service workers")?;
+        }
+        ServiceWorkerPolicy::Update => {
+            page.execute(service_worker::SetForceUpdateOnPageLoadParams::new(
+                true,
+            ))
+            .await
+            .context("failed forcing service worker updates")?;
+        }
+        ServiceWorkerPolicy::Unregister => {
+            page.execute(
+                storage::ClearDataForOriginParams::builder()
+                    .origin(origin.origin().ascii_serialization())
+                    .storage_types("service_workers")
+                    .build()
+                    .map_err(|error| {
+                        anyhow!(
+                            "failed building ClearDataForOriginParams: {}",
+                            error
+                        )
+                    })?,
+            )
+            .await
+            .context("failed unregistering service workers")?;
+        }
+    }
+    log::debug!("applied service worker policy: {:?}", policy);
+
+    let registrations = page
+        .event_listener::<service_worker::EventWorkerRegistrationUpdated>()
+        .await?
+        .map(|event| {
+            ServiceWorkerEvent::Registrations(event.registrations.clone())
+        });
+    let versions = page
+        .event_listener::<service_worker::EventWorkerVersionUpdated>()
+        .await?
+        .map(|event| ServiceWorkerEvent::Versions(event.versions.clone()));
+
+    page.execute(service_worker::EnableParams {})
+        .await
+        .context("failed enabling service worker domain")?;
+
+    let service_workers = ServiceWorkers::default();
+    let inner = service_workers.inner.clone();
+    let mut events = stream::select(registrations, versions);
+    let _handle = spawn(async move {
+        while let Some(event) = events.next().await {
+            let Ok(mut inner) = inner.lock() else {
+                break;
+            };
+            match event {
+                ServiceWorkerEvent::Registrations(registrations) => {
+                    for registration in registrations {
+                        if registration.is_deleted {
+                            inner.scopes.remove(&registration.registration_id);
+                        } else {
+                            inner.scopes.insert(
+                                registration.registration_id,
+                                registration.scope_url,
+                            );
+                        }
+                    }
+                }
+                ServiceWorkerEvent::Versions(versions) => {
+                    for version in versions {
+                        log::debug!(
+                            "service worker {} is {}",
+                            version.script_url,
+                            version.status.as_ref()
+                        );
+                        inner
+                            .versions
+                            .insert(version.version_id.clone(), version);
+                    }
+                }
+            }
+        }
+    });
+
+    Ok(service_workers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn service_worker_policy_from_str() {
+        assert_eq!(
+            "bypass".parse::<ServiceWorkerPolicy>(),
+            Ok(ServiceWorkerPolicy::Bypass)
+        );
+        assert_eq!(
+            "unregister".parse::<ServiceWorkerPolicy>(),
+            Ok(ServiceWorkerPolicy::Unregister)
+        );
+        assert!("nope".parse::<ServiceWorkerPolicy>().is_err());
+    }
+
+    #[test]
+    fn statuses_skip_redundant_versions() {
+        let service_workers = ServiceWorkers::default();
+        {
+            let mut inner = service_workers.inner.lock().unwrap();
+            let registration_id = RegistrationId::from("1".to_string());
+            inner
+                .scopes
+                .insert(registration_id.clone(), "http://a/".to_string());
+            for (id, status) in [
+                ("1", service_worker::ServiceWorkerVersionStatus::Redundant),
+                ("2", service_worker::ServiceWorkerVersionStatus::Activated),
+            ] {
+                inner.versions.insert(
+                    id.to_string(),
+                    service_worker::ServiceWorkerVersion {
+                        version_id: id.to_string(),
+                        registration_id: registration_id.clone(),
+                        script_url: format!("http://a/sw-{id}.js"),
+                        running_status: service_worker::ServiceWorkerVersionRunningStatus::Running,
+                        status,
+                        script_last_modified: None,
+                        script_response_time: None,
+                        controlled_clients: None,
+                        target_id: None,
+                        router_rules: None,
+                    },
+                );
+            }
+        }
+        assert_eq!(
+            service_workers.statuses(),
+            vec![ServiceWorkerStatus {
+                scope_url: Some("http://a/".to_string()),
+                script_url: "http://a/sw-2.js".to_string(),
+                status: "activated".to_string(),
+                running_status: "running".to_string(),
+            }]
+        );
+    }
+}
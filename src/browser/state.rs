@@ -1,7 +1,7 @@
-use crate::instrumentation::js::{
+use crate::instrumentation::{
     EDGE_MAP_SIZE, EDGES_CURRENT, EDGES_PREVIOUS, NAMESPACE,
 };
-use anyhow::Result;
+use anyhow::{Result, bail};
 use chromiumoxide::{
     Page,
     cdp::{
@@ -11,30 +11,152 @@ use chromiumoxide::{
 };
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use serde_json as json;
-use std::{sync::Arc, time::SystemTime};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
 use url::Url;
 
+use crate::browser::accessibility::{AccessibilityNode, accessibility_tree};
+use crate::browser::checkpoints::{Checkpoint, capture_checkpoint};
+use crate::browser::dialogs::Dialog;
 use crate::browser::evaluation::{
-    evaluate_expression_in_debugger, evaluate_function_call_in_debugger,
+    IsolatedWorld, evaluate_expression_in_debugger,
+    evaluate_function_call_in_debugger, evaluate_function_call_in_world,
+    isolated_world,
 };
+use crate::browser::listeners::{ListenerTarget, listener_targets};
+use crate::browser::mock::Answers;
+use crate::browser::mutations::{MutationSummary, take_mutation_targets};
+use crate::browser::network_log::{NetworkRequest, RedirectChain};
+use crate::browser::screenshots::{ScreenshotData, capture_full_page};
+use crate::browser::service_workers::ServiceWorkerStatus;
+use crate::browser::websockets::WebSocketFrame;
+
+/// Where the scripts evaluated in a state run.
+#[derive(Clone)]
+enum StateContext {
+    /// The call frame the page is paused at.
+    Paused {
+        page: Arc<Page>,
+        call_frame_id: CallFrameId,
+    },
+    /// Fixtures answering the scripts in place of a page, for a `MockBrowser`.
+    Fixture(Arc<Answers>),
+}
+
+impl std::fmt::Debug for StateContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StateContext::Paused { call_frame_id, .. } => f
+                .debug_struct("Paused")
+                .field("call_frame_id", call_frame_id)
+                .finish(),
+            StateContext::Fixture(_) => f.write_str("Fixture"),
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct BrowserState {
-    page: Arc<Page>,
-    call_frame_id: CallFrameId,
+    context: StateContext,
 
     pub timestamp: SystemTime,
     pub url: Url,
     pub title: String,
     pub content_type: String,
     pub console_entries: Vec<ConsoleEntry>,
+    /// Console entries logged beyond the per-step limit.
+    pub console_entries_dropped: usize,
     pub navigation_history: NavigationHistory,
     pub exceptions: Vec<Exception>,
+    /// Exceptions thrown beyond the per-step limit.
+    pub exceptions_dropped: usize,
+    /// DOM mutations (inserted, removed or updated nodes and attributes) since the previous
+    /// state.
+    pub dom_mutations: u64,
+    /// The most frequently mutated elements since the previous state, with their mutation
+    /// counts.
+    pub mutation_targets: Vec<(String, u64)>,
+    pub navigation_timings: NavigationTimings,
+    pub service_workers: Vec<ServiceWorkerStatus>,
+    /// Requests completed since the previous state.
+    pub network_requests: Vec<NetworkRequest>,
+    /// Requests completed beyond the per-step limit.
+    pub network_requests_dropped: usize,
+    /// The latest WebSocket frames sent or received since the previous state.
+    pub web_socket_frames: Vec<WebSocketFrame>,
+    /// Earlier frames dropped beyond the per-step limit.
+    pub web_socket_frames_dropped: usize,
+    /// Dialogs opened since the previous state.
+    pub dialogs: Vec<Dialog>,
+    /// Redirect chains of the navigations completed since the previous state.
+    pub redirects: Vec<RedirectChain>,
+    /// Elements with listeners for clicks, double clicks and context menus, looked up with
+    /// `listener_targets` when the specification uses them.
+    pub listeners: Vec<ListenerTarget>,
     pub transition_hash: Option<u64>,
     pub coverage: Coverage,
     pub screenshot: Screenshot,
+    pub timings: CaptureTimings,
+}
+
+/// How long each stage of capturing a state took.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct CaptureTimings {
+    /// Taking the screenshot, before pausing.
+    pub screenshot: Duration,
+    /// From requesting the pause until the debugger paused.
+    pub pause: Duration,
+    /// Evaluating the state (URL, history, coverage, etc) in the paused page.
+    pub evaluation: Duration,
 }
 
+/// Timings of the navigation of the current document, from the Navigation Timing and Paint
+/// Timing APIs. Milliseconds since the navigation started, or none until reached.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NavigationTimings {
+    /// When the navigation started, in milliseconds since the Unix epoch, which identifies
+    /// it across states.
+    pub time_origin: f64,
+    /// `document.readyState` when the state was captured.
+    pub ready_state: String,
+    /// When `readyState` became `interactive`.
+    pub dom_interactive: Option<f64>,
+    pub dom_content_loaded: Option<f64>,
+    /// When `readyState` became `complete`.
+    pub dom_complete: Option<f64>,
+    pub load: Option<f64>,
+    pub first_paint: Option<f64>,
+    pub first_contentful_paint: Option<f64>,
+}
+
+const NAVIGATION_TIMINGS_EXPRESSION: &str = r#"(() => {
+    const [navigation] = performance.getEntriesByType("navigation");
+    const reached = (time) => (navigation && time > 0 ? time : null);
+    const paint = (name) => performance.getEntriesByName(name, "paint")[0]?.startTime ?? null;
+    return {
+        timeOrigin: performance.timeOrigin,
+        readyState: document.readyState,
+        domInteractive: reached(navigation?.domInteractive),
+        domContentLoaded: reached(navigation?.domContentLoadedEventEnd),
+        domComplete: reached(navigation?.domComplete),
+        load: reached(navigation?.loadEventEnd),
+        firstPaint: paint("first-paint"),
+        firstContentfulPaint: paint("first-contentful-paint"),
+    };
+})()"#;
+
+/// Serializes the document's markup, with its doctype. Attributes are serialized rather
+/// than properties, so e.g. the text typed into inputs is left out.
+const DOM_SNAPSHOT_EXPRESSION: &str = r#"(() => {
+    const doctype = document.doctype
+        ? new XMLSerializer().serializeToString(document.doctype) + "\n"
+        : "";
+    return doctype + (document.documentElement?.outerHTML ?? "");
+})()"#;
+
 pub type EdgeIndex = u32;
 pub type EdgeBucket = u8;
 
@@ -99,6 +221,67 @@ pub enum ConsoleEntryLevel {
     Error,
 }
 
+/// Per-step caps on collected console entries and exceptions, so that pages logging in a
+/// tight loop don't grow buffers and extractor payloads unboundedly.
+#[derive(Copy, Clone, Debug)]
+pub struct LogLimits {
+    pub console_entries: usize,
+    pub exceptions: usize,
+}
+
+impl Default for LogLimits {
+    fn default() -> Self {
+        LogLimits {
+            console_entries: 1000,
+            exceptions: 100,
+        }
+    }
+}
+
+/// Console entries, exceptions, network requests, WebSocket frames, dialogs and
+/// redirects collected between two states. Entries beyond the limits are dropped,
+/// keeping the earliest ones, and counted.
+#[derive(Debug, Default)]
+pub(crate) struct StepLogs {
+    pub console_entries: Vec<ConsoleEntry>,
+    pub console_entries_dropped: usize,
+    pub exceptions: Vec<Exception>,
+    pub exceptions_dropped: usize,
+    pub network_requests: Vec<NetworkRequest>,
+    pub network_requests_dropped: usize,
+    pub web_socket_frames: Vec<WebSocketFrame>,
+    pub web_socket_frames_dropped: usize,
+    pub dialogs: Vec<Dialog>,
+    pub redirects: Vec<RedirectChain>,
+}
+
+impl StepLogs {
+    pub fn push_console_entry(
+        &mut self,
+        entry: ConsoleEntry,
+        limits: &LogLimits,
+    ) {
+        if self.console_entries.len() < limits.console_entries {
+            self.console_entries.push(entry);
+        } else {
+            self.console_entries_dropped += 1;
+        }
+    }
+
+    pub fn push_exception(&mut self, exception: Exception, limits: &LogLimits) {
+        if self.exceptions.len() < limits.exceptions {
+            self.exceptions.push(exception);
+        } else {
+            self.exceptions_dropped += 1;
+        }
+    }
+
+    pub fn clear_console_entries(&mut self) {
+        self.console_entries.clear();
+        self.console_entries_dropped = 0;
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum ScreenshotFormat {
     Webp,
@@ -129,7 +312,7 @@ impl From<ScreenshotFormat> for CaptureScreenshotFormat {
 #[derive(Clone)]
 pub struct Screenshot {
     pub format: ScreenshotFormat,
-    pub data: Vec<u8>,
+    pub data: ScreenshotData,
 }
 
 impl std::fmt::Debug for Screenshot {
@@ -145,10 +328,14 @@ impl BrowserState {
     pub(crate) async fn current(
         page: Arc<Page>,
         call_frame_id: &CallFrameId,
-        console_entries: Vec<ConsoleEntry>,
-        exceptions: Vec<Exception>,
+        logs: StepLogs,
+        dom_mutations: u64,
+        service_workers: Vec<ServiceWorkerStatus>,
         screenshot: Screenshot,
+        timings: CaptureTimings,
     ) -> Result<Self> {
+        let started = Instant::now();
+
         log::trace!("BrowserState::current: evaluating url");
         let url = Url::parse(
             &evaluate_expression_in_debugger::<String>(
@@ -175,6 +362,15 @@ impl BrowserState {
         )
         .await?;
 
+        log::trace!("BrowserState::current: evaluating navigation timings");
+        let navigation_timings: NavigationTimings =
+            evaluate_expression_in_debugger(
+                &page,
+                call_frame_id,
+                NAVIGATION_TIMINGS_EXPRESSION,
+            )
+            .await?;
+
         log::trace!("BrowserState::current: getting navigation history");
         let navigation_history_result = page
             .execute(page::GetNavigationHistoryParams {})
@@ -208,6 +404,10 @@ impl BrowserState {
                 .collect(),
         };
 
+        log::trace!("BrowserState::current: taking mutation targets");
+        let mutation_targets =
+            take_mutation_targets(&page, call_frame_id).await?;
+
         log::trace!("BrowserState::current: evaluating coverage");
         let edges_new: Vec<(u32, u8)> = evaluate_expression_in_debugger(
             &page,
@@ -309,31 +509,270 @@ impl BrowserState {
         log::trace!("BrowserState::current: done");
         Ok(BrowserState {
             timestamp: SystemTime::now(),
-            page: page.clone(),
-            call_frame_id: call_frame_id.clone(),
+            context: StateContext::Paused {
+                page: page.clone(),
+                call_frame_id: call_frame_id.clone(),
+            },
             url,
             title,
             content_type,
-            console_entries,
+            console_entries: logs.console_entries,
+            console_entries_dropped: logs.console_entries_dropped,
             navigation_history,
-            exceptions,
+            exceptions: logs.exceptions,
+            exceptions_dropped: logs.exceptions_dropped,
+            dom_mutations,
+            mutation_targets,
+            navigation_timings,
+            service_workers,
+            network_requests: logs.network_requests,
+            network_requests_dropped: logs.network_requests_dropped,
+            web_socket_frames: logs.web_socket_frames,
+            web_socket_frames_dropped: logs.web_socket_frames_dropped,
+            dialogs: logs.dialogs,
+            redirects: logs.redirects,
+            listeners: vec![],
             coverage: Coverage { edges_new },
             transition_hash,
             screenshot,
+            timings: CaptureTimings {
+                evaluation: started.elapsed(),
+                ..timings
+            },
         })
     }
 
+    /// A state whose scripts are answered by fixtures rather than evaluated in a page.
+    pub(crate) fn fixture(
+        url: Url,
+        title: String,
+        content_type: String,
+        answers: Arc<Answers>,
+    ) -> Self {
+        let entry = NavigationEntry {
+            id: 0,
+            title: title.clone(),
+            url: url.clone(),
+        };
+        BrowserState {
+            context: StateContext::Fixture(answers),
+            timestamp: SystemTime::now(),
+            url,
+            title,
+            content_type,
+            console_entries: vec![],
+            console_entries_dropped: 0,
+            navigation_history: NavigationHistory {
+                back: vec![],
+                current: entry,
+                forward: vec![],
+            },
+            exceptions: vec![],
+            exceptions_dropped: 0,
+            dom_mutations: 0,
+            mutation_targets: vec![],
+            navigation_timings: NavigationTimings::default(),
+            service_workers: vec![],
+            network_requests: vec![],
+            network_requests_dropped: 0,
+            web_socket_frames: vec![],
+            web_socket_frames_dropped: 0,
+            dialogs: vec![],
+            redirects: vec![],
+            listeners: vec![],
+            transition_hash: None,
+            coverage: Coverage { edges_new: vec![] },
+            screenshot: Screenshot {
+                format: ScreenshotFormat::Png,
+                data: ScreenshotData::from(vec![]),
+            },
+            timings: CaptureTimings::default(),
+        }
+    }
+
     pub async fn evaluate_function_call<Output: DeserializeOwned>(
         &self,
         function_expression: impl Into<String>,
         arguments: Vec<json::Value>,
     ) -> Result<Output> {
-        evaluate_function_call_in_debugger(
-            &self.page,
-            &self.call_frame_id,
-            function_expression,
-            arguments,
-        )
-        .await
+        match &self.context {
+            StateContext::Paused {
+                page,
+                call_frame_id,
+            } => {
+                evaluate_function_call_in_debugger(
+                    page,
+                    call_frame_id,
+                    function_expression,
+                    arguments,
+                )
+                .await
+            }
+            StateContext::Fixture(answers) => {
+                let function: String = function_expression.into();
+                answers.answer(&function)
+            }
+        }
+    }
+
+    /// The isolated world of this state's document, reusing `previous` if possible.
+    pub async fn isolated_world(
+        &self,
+        previous: Option<&IsolatedWorld>,
+    ) -> Result<IsolatedWorld> {
+        match &self.context {
+            StateContext::Paused { page, .. } => {
+                isolated_world(page, previous).await
+            }
+            StateContext::Fixture(_) => {
+                bail!("fixture states have no isolated world")
+            }
+        }
+    }
+
+    /// Like `evaluate_function_call`, but in an isolated world rather than the paused
+    /// call frame of the page's main world.
+    pub async fn evaluate_function_call_in_world<Output: DeserializeOwned>(
+        &self,
+        world: &IsolatedWorld,
+        function_expression: impl Into<String>,
+        arguments: Vec<json::Value>,
+    ) -> Result<Output> {
+        match &self.context {
+            StateContext::Paused { page, .. } => {
+                evaluate_function_call_in_world(
+                    page,
+                    world,
+                    function_expression,
+                    arguments,
+                )
+                .await
+            }
+            StateContext::Fixture(answers) => {
+                let function: String = function_expression.into();
+                answers.answer(&function)
+            }
+        }
+    }
+
+    pub async fn accessibility_tree(
+        &self,
+    ) -> Result<Option<AccessibilityNode>> {
+        match &self.context {
+            StateContext::Paused { page, .. } => accessibility_tree(page).await,
+            StateContext::Fixture(_) => Ok(None),
+        }
+    }
+
+    /// A screenshot of the whole page, beyond the viewport, unless it's a fixture.
+    pub async fn full_page_screenshot(&self) -> Result<Option<Screenshot>> {
+        match &self.context {
+            StateContext::Paused { page, .. } => {
+                Ok(Some(capture_full_page(page).await?))
+            }
+            StateContext::Fixture(_) => Ok(None),
+        }
+    }
+
+    /// The serialized DOM of the page, unless it's a fixture.
+    pub async fn dom_snapshot(&self) -> Result<Option<String>> {
+        match &self.context {
+            StateContext::Paused {
+                page,
+                call_frame_id,
+            } => Ok(Some(
+                evaluate_expression_in_debugger(
+                    page,
+                    call_frame_id,
+                    DOM_SNAPSHOT_EXPRESSION,
+                )
+                .await?,
+            )),
+            StateContext::Fixture(_) => Ok(None),
+        }
+    }
+
+    /// The DOM mutations since the previous state.
+    pub fn mutation_summary(&self) -> MutationSummary {
+        MutationSummary {
+            count: self.dom_mutations,
+            targets: self.mutation_targets.clone(),
+        }
+    }
+
+    /// Looks up the elements with listeners for clicks, double clicks and context menus.
+    pub async fn listener_targets(&self) -> Result<Vec<ListenerTarget>> {
+        match &self.context {
+            StateContext::Paused {
+                page,
+                call_frame_id,
+            } => listener_targets(page, call_frame_id).await,
+            StateContext::Fixture(_) => Ok(vec![]),
+        }
+    }
+
+    pub async fn checkpoint(&self) -> Result<Checkpoint> {
+        match &self.context {
+            StateContext::Paused {
+                page,
+                call_frame_id,
+            } => capture_checkpoint(page, call_frame_id).await,
+            StateContext::Fixture(_) => Ok(Checkpoint {
+                url: self.url.to_string(),
+                cookies: vec![],
+                local_storage: Default::default(),
+                session_storage: Default::default(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn console_entry() -> ConsoleEntry {
+        ConsoleEntry {
+            timestamp: SystemTime::UNIX_EPOCH,
+            level: ConsoleEntryLevel::Error,
+            args: vec![],
+        }
+    }
+
+    #[test]
+    fn test_mutation_summary() {
+        let mut state = BrowserState::fixture(
+            Url::parse("http://localhost/").unwrap(),
+            String::new(),
+            "text/html".to_string(),
+            Arc::new(Answers::default()),
+        );
+        state.dom_mutations = 4;
+        state.mutation_targets = vec![("ul#cart".to_string(), 3)];
+        assert_eq!(
+            state.mutation_summary(),
+            MutationSummary {
+                count: 4,
+                targets: vec![("ul#cart".to_string(), 3)],
+            }
+        );
+    }
+
+    #[test]
+    fn test_step_logs_drop_beyond_limit() {
+        let limits = LogLimits {
+            console_entries: 2,
+            exceptions: 0,
+        };
+        let mut logs = StepLogs::default();
+        for _ in 0..5 {
+            logs.push_console_entry(console_entry(), &limits);
+        }
+        assert_eq!(logs.console_entries.len(), 2);
+        assert_eq!(logs.console_entries_dropped, 3);
+
+        logs.clear_console_entries();
+        assert!(logs.console_entries.is_empty());
+        assert_eq!(logs.console_entries_dropped, 0);
     }
 }
@@ -0,0 +1,210 @@
+use std::str::FromStr;
+
+use anyhow::{Context, Result, anyhow};
+use chromiumoxide::Page;
+use chromiumoxide::cdp::browser_protocol::emulation;
+use serde::{Deserialize, Serialize};
+
+const MOBILE_USER_AGENT: &str = "Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Mobile Safari/537.36";
+const TABLET_USER_AGENT: &str = "Mozilla/5.0 (Linux; Android 14; SM-X710) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36";
+
+/// Device metrics to emulate for a part of a run.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ViewportProfile {
+    pub name: String,
+    pub width: u16,
+    pub height: u16,
+    pub device_scale_factor: f64,
+    /// Emulate a mobile device: overlay scrollbars, and the meta viewport tag taken into
+    /// account.
+    pub mobile: bool,
+    /// Emulate a touch screen, e.g. for `ontouchstart` and `(pointer: coarse)`.
+    #[serde(default)]
+    pub touch: bool,
+    /// The user agent reported to the page, instead of the browser's own.
+    #[serde(default)]
+    pub user_agent: Option<String>,
+}
+
+impl ViewportProfile {
+    /// The profile of a named device: `mobile`, `tablet` or `desktop`.
+    pub fn device(name: &str) -> Option<Self> {
+        let (width, height, device_scale_factor, user_agent) = match name {
+            "mobile" => (412, 915, 2.625, Some(MOBILE_USER_AGENT)),
+            "tablet" => (800, 1280, 2.0, Some(TABLET_USER_AGENT)),
+            "desktop" => (1440, 900, 1.0, None),
+            _ => return None,
+        };
+        Some(ViewportProfile {
+            name: name.to_string(),
+            width,
+            height,
+            device_scale_factor,
+            mobile: user_agent.is_some(),
+            touch: user_agent.is_some(),
+            user_agent: user_agent.map(str::to_string),
+        })
+    }
+}
+
+/// Parses a device name (`mobile`, `tablet` or `desktop`) or
+/// `NAME=WIDTHxHEIGHT[@SCALE][:mobile][:touch]`, e.g. `phone=390x844@3:mobile:touch`.
+impl FromStr for ViewportProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if let Some(device) = ViewportProfile::device(s) {
+            return Ok(device);
+        }
+        let invalid = || {
+            format!(
+                "invalid viewport profile {:?}, expected mobile, tablet, desktop or NAME=WIDTHxHEIGHT[@SCALE][:mobile][:touch]",
+                s
+            )
+        };
+        let (name, metrics) = s.split_once('=').ok_or_else(invalid)?;
+        let (metrics, touch) = match metrics.strip_suffix(":touch") {
+            Some(metrics) => (metrics, true),
+            None => (metrics, false),
+        };
+        let (metrics, mobile) = match metrics.strip_suffix(":mobile") {
+            Some(metrics) => (metrics, true),
+            None => (metrics, false),
+        };
+        let (size, device_scale_factor) = match metrics.split_once('@') {
+            Some((size, scale)) => {
+                (size, scale.parse().map_err(|_| invalid())?)
+            }
+            None => (metrics, 1.0),
+        };
+        let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+        if name.is_empty() {
+            return Err(invalid());
+        }
+        Ok(ViewportProfile {
+            name: name.to_string(),
+            width: width.parse().map_err(|_| invalid())?,
+            height: height.parse().map_err(|_| invalid())?,
+            device_scale_factor,
+            mobile,
+            touch,
+            user_agent: None,
+        })
+    }
+}
+
+/// Emulates the device metrics, touch screen and user agent of the profile in the page.
+pub async fn emulate_viewport(
+    page: &Page,
+    profile: &ViewportProfile,
+) -> Result<()> {
+    page.execute(
+        emulation::SetDeviceMetricsOverrideParams::builder()
+            .width(profile.width)
+            .height(profile.height)
+            .device_scale_factor(profile.device_scale_factor)
+            .mobile(profile.mobile)
+            .scale(1)
+            .build()
+            .map_err(|err| {
+                anyhow!(err)
+                    .context("build SetDeviceMetricsOverrideParams failed")
+            })?,
+    )
+    .await?;
+    page.execute(emulation::SetTouchEmulationEnabledParams {
+        enabled: profile.touch,
+        max_touch_points: profile.touch.then_some(5),
+    })
+    .await
+    .context("failed emulating touch")?;
+    // An empty user agent removes the override of a previous profile.
+    page.execute(emulation::SetUserAgentOverrideParams::new(
+        profile.user_agent.clone().unwrap_or_default(),
+    ))
+    .await
+    .context("failed overriding the user agent")?;
+    Ok(())
+}
+
+/// Viewport profiles cycled through within a run, each for a number of states, to catch
+/// bugs at specific breakpoints without separate runs.
+#[derive(Clone, Debug)]
+pub struct ViewportSweep {
+    pub profiles: Vec<ViewportProfile>,
+    /// Number of states captured with each profile before switching to the next one.
+    pub interval: u64,
+}
+
+impl ViewportSweep {
+    /// The profile of the state with the given index.
+    pub fn profile_at(&self, step_index: u64) -> Option<&ViewportProfile> {
+        if self.profiles.is_empty() {
+            return None;
+        }
+        let segment = step_index / self.interval.max(1);
+        self.profiles
+            .get((segment % self.profiles.len() as u64) as usize)
+    }
+
+    /// The profile to switch to before capturing the state with the given index, if it's
+    /// not the one of the previous state.
+    pub fn switch_at(&self, step_index: u64) -> Option<&ViewportProfile> {
+        let previous = self.profile_at(step_index.checked_sub(1)?);
+        self.profile_at(step_index)
+            .filter(|profile| Some(*profile) != previous)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_profile() {
+        assert_eq!(
+            "phone=390x844@3:mobile".parse(),
+            Ok(ViewportProfile {
+                name: "phone".to_string(),
+                width: 390,
+                height: 844,
+                device_scale_factor: 3.0,
+                mobile: true,
+                touch: false,
+                user_agent: None,
+            })
+        );
+        let tablet: ViewportProfile = "tablet".parse().unwrap();
+        assert!(tablet.mobile && tablet.touch && tablet.user_agent.is_some());
+        let kiosk: ViewportProfile = "kiosk=1080x1920:touch".parse().unwrap();
+        assert!(kiosk.touch && !kiosk.mobile);
+        let desktop: ViewportProfile = "desktop=1440x900".parse().unwrap();
+        assert_eq!(desktop.device_scale_factor, 1.0);
+        assert!(!desktop.mobile);
+        assert!("1440x900".parse::<ViewportProfile>().is_err());
+        assert!("tablet=768".parse::<ViewportProfile>().is_err());
+    }
+
+    #[test]
+    fn test_sweep_switches() {
+        let sweep = ViewportSweep {
+            profiles: vec![
+                "default=1024x768".parse().unwrap(),
+                "phone=390x844@3:mobile".parse().unwrap(),
+            ],
+            interval: 2,
+        };
+        let names: Vec<&str> = (0..6)
+            .map(|index| sweep.profile_at(index).unwrap().name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["default", "default", "phone", "phone", "default", "default"]
+        );
+        let switches: Vec<u64> = (0..6)
+            .filter(|index| sweep.switch_at(*index).is_some())
+            .collect();
+        assert_eq!(switches, vec![2, 4]);
+    }
+}
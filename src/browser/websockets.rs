@@ -0,0 +1,179 @@
+use anyhow::Result;
+use chromiumoxide::Page;
+use chromiumoxide::cdp::browser_protocol::network::{
+    self, RequestId, WebSocketFrame as CdpWebSocketFrame,
+};
+use futures::{StreamExt, stream};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::spawn;
+
+/// Frames kept between two states, the oldest ones being dropped beyond that.
+const WEB_SOCKET_FRAMES_MAX: usize = 200;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FrameDirection {
+    Sent,
+    Received,
+}
+
+/// A WebSocket frame sent or received by the page, as exposed to extractors.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebSocketFrame {
+    /// The URL the WebSocket connected to.
+    pub url: String,
+    pub direction: FrameDirection,
+    /// 1 for text frames, 2 for binary ones.
+    pub opcode: u8,
+    /// The text of text frames, base64 encoded for binary ones.
+    pub payload: String,
+    /// When the frame was seen, in milliseconds since the Unix epoch.
+    pub timestamp: f64,
+}
+
+#[derive(Debug, Default)]
+struct WebSocketsInner {
+    urls: HashMap<RequestId, String>,
+    frames: VecDeque<WebSocketFrame>,
+    dropped: usize,
+}
+
+impl WebSocketsInner {
+    fn push(
+        &mut self,
+        id: &RequestId,
+        direction: FrameDirection,
+        frame: &CdpWebSocketFrame,
+        timestamp: f64,
+    ) {
+        if self.frames.len() >= WEB_SOCKET_FRAMES_MAX {
+            self.frames.pop_front();
+            self.dropped += 1;
+        }
+        self.frames.push_back(WebSocketFrame {
+            url: self.urls.get(id).cloned().unwrap_or_default(),
+            direction,
+            opcode: frame.opcode as u8,
+            payload: frame.payload_data.clone(),
+            timestamp,
+        });
+    }
+}
+
+/// Tracks the frames of the page's WebSockets, kept up to date from CDP events.
+#[derive(Clone, Debug, Default)]
+pub struct WebSockets {
+    inner: Arc<Mutex<WebSocketsInner>>,
+}
+
+impl WebSockets {
+    /// Takes the latest frames since the last call, and the number of earlier ones
+    /// dropped beyond the limit.
+    pub fn take(&self) -> (Vec<WebSocketFrame>, usize) {
+        let Ok(mut inner) = self.inner.lock() else {
+            return (vec![], 0);
+        };
+        let dropped = std::mem::take(&mut inner.dropped);
+        (std::mem::take(&mut inner.frames).into(), dropped)
+    }
+}
+
+enum WebSocketEvent {
+    Created(RequestId, String),
+    Frame(RequestId, FrameDirection, CdpWebSocketFrame),
+}
+
+/// Start tracking the WebSocket frames of the page. The network domain is already
+/// enabled by chromiumoxide.
+pub async fn track_web_sockets(page: &Page) -> Result<WebSockets> {
+    let created = page
+        .event_listener::<network::EventWebSocketCreated>()
+        .await?
+        .map(|event| {
+            WebSocketEvent::Created(event.request_id.clone(), event.url.clone())
+        });
+    let sent = page
+        .event_listener::<network::EventWebSocketFrameSent>()
+        .await?
+        .map(|event| {
+            WebSocketEvent::Frame(
+                event.request_id.clone(),
+                FrameDirection::Sent,
+                event.response.clone(),
+            )
+        });
+    let received = page
+        .event_listener::<network::EventWebSocketFrameReceived>()
+        .await?
+        .map(|event| {
+            WebSocketEvent::Frame(
+                event.request_id.clone(),
+                FrameDirection::Received,
+                event.response.clone(),
+            )
+        });
+
+    let web_sockets = WebSockets::default();
+    let inner = web_sockets.inner.clone();
+    let mut events = stream::select_all(vec![
+        created.boxed(),
+        sent.boxed(),
+        received.boxed(),
+    ]);
+    let _handle = spawn(async move {
+        while let Some(event) = events.next().await {
+            let Ok(mut inner) = inner.lock() else {
+                break;
+            };
+            match event {
+                WebSocketEvent::Created(id, url) => {
+                    inner.urls.insert(id, url);
+                }
+                WebSocketEvent::Frame(id, direction, frame) => {
+                    let timestamp = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs_f64()
+                        * 1000.0;
+                    inner.push(&id, direction, &frame, timestamp);
+                }
+            }
+        }
+    });
+
+    Ok(web_sockets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keeps_latest_frames() {
+        let web_sockets = WebSockets::default();
+        {
+            let mut inner = web_sockets.inner.lock().unwrap();
+            let id = RequestId::from("1".to_string());
+            inner.urls.insert(id.clone(), "ws://a/live".to_string());
+            for i in 0..WEB_SOCKET_FRAMES_MAX + 2 {
+                inner.push(
+                    &id,
+                    FrameDirection::Received,
+                    &CdpWebSocketFrame::new(1.0, false, i.to_string()),
+                    0.0,
+                );
+            }
+        }
+
+        let (frames, dropped) = web_sockets.take();
+        assert_eq!(dropped, 2);
+        assert_eq!(frames.len(), WEB_SOCKET_FRAMES_MAX);
+        assert_eq!(frames[0].payload, "2");
+        assert_eq!(frames[0].url, "ws://a/live");
+        assert!(web_sockets.take().0.is_empty());
+    }
+}
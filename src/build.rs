@@ -1,9 +1,13 @@
 use glob::glob;
+use std::path::Path;
 use std::process::Command;
 
 fn main() {
     println!("cargo:rerun-if-changed=src/specification/**/*.ts");
-    build_specification_modules();
+    // The modules are only embedded by the verifier.
+    if std::env::var_os("CARGO_FEATURE_VERIFIER").is_some() {
+        build_specification_modules();
+    }
 }
 
 fn build_specification_modules() {
@@ -22,4 +26,19 @@ fn build_specification_modules() {
     if !status.success() {
         panic!("esbuild failed with status: {}", status);
     }
+
+    // The TypeScript sources are embedded as well, for `bombadil eject-types`.
+    for entry_point in &entry_points {
+        let relative = entry_point
+            .strip_prefix("src/specification")
+            .expect("entry point outside of src/specification");
+        let destination =
+            Path::new("target/specification-types").join(relative);
+        std::fs::create_dir_all(
+            destination.parent().expect("destination has no parent"),
+        )
+        .expect("Failed to create specification types directory");
+        std::fs::copy(entry_point, &destination)
+            .expect("Failed to copy specification source");
+    }
 }
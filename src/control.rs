@@ -0,0 +1,387 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use axum::extract::State;
+use axum::http::{StatusCode, header};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Serialize;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+
+use crate::run_id::RunId;
+use crate::specification::render::render_violation;
+use crate::trace::PropertyViolation;
+
+/// Violations kept for `GET /violations`, the oldest ones being dropped beyond that.
+const VIOLATIONS_MAX: usize = 100;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TestState {
+    /// Waiting for `POST /run`.
+    #[default]
+    Waiting,
+    Running,
+    /// `POST /stop` was received, the runs in progress are stopping.
+    Stopping,
+    Finished,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunStatus {
+    pub seed: u64,
+    pub steps: u32,
+    pub violations: usize,
+    /// Set once the run is over.
+    pub exit_code: Option<i32>,
+    pub finished: bool,
+}
+
+/// A violation found by a run, as reported by the control API.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ViolationReport {
+    pub run_id: RunId,
+    pub property: String,
+    pub fingerprint: String,
+    pub url: String,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ControlStatus {
+    pub state: TestState,
+    pub runs: BTreeMap<RunId, RunStatus>,
+}
+
+#[derive(Debug, Default)]
+struct ControlInner {
+    status: ControlStatus,
+    violations: VecDeque<ViolationReport>,
+    /// The last value of each metric emitted by the specification, per run.
+    metrics: BTreeMap<RunId, BTreeMap<String, f64>>,
+}
+
+/// The state of a test driven through the control API, updated by its runs.
+#[derive(Clone, Debug)]
+pub struct Control {
+    inner: Arc<Mutex<ControlInner>>,
+    state: watch::Sender<TestState>,
+}
+
+impl Default for Control {
+    fn default() -> Self {
+        Control {
+            inner: Arc::default(),
+            state: watch::Sender::new(TestState::Waiting),
+        }
+    }
+}
+
+impl Control {
+    fn set_state(&self, state: TestState) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.status.state = state;
+        }
+        self.state.send_replace(state);
+    }
+
+    /// Waits for a client to start the test.
+    pub async fn started(&self) {
+        let _ = self
+            .state
+            .subscribe()
+            .wait_for(|state| *state != TestState::Waiting)
+            .await;
+    }
+
+    /// Waits for a client to stop the test.
+    pub async fn stopped(&self) {
+        let _ = self
+            .state
+            .subscribe()
+            .wait_for(|state| *state == TestState::Stopping)
+            .await;
+    }
+
+    pub fn run_started(&self, run_id: &RunId, seed: u64) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.status.runs.insert(
+                run_id.clone(),
+                RunStatus {
+                    seed,
+                    steps: 0,
+                    violations: 0,
+                    exit_code: None,
+                    finished: false,
+                },
+            );
+        }
+    }
+
+    /// Records a state checked by a run, with its unsuppressed violations and the metrics
+    /// emitted by the specification.
+    pub fn state_checked(
+        &self,
+        run_id: &RunId,
+        url: &str,
+        violations: &[PropertyViolation],
+        metrics: &[(String, f64)],
+    ) {
+        let Ok(mut inner) = self.inner.lock() else {
+            return;
+        };
+        if !metrics.is_empty() {
+            inner
+                .metrics
+                .entry(run_id.clone())
+                .or_default()
+                .extend(metrics.iter().cloned());
+        }
+        let violations: Vec<&PropertyViolation> = violations
+            .iter()
+            .filter(|violation| violation.suppressed.is_none())
+            .collect();
+        if let Some(run) = inner.status.runs.get_mut(run_id) {
+            run.steps += 1;
+            run.violations += violations.len();
+        }
+        for violation in violations {
+            if inner.violations.len() >= VIOLATIONS_MAX {
+                inner.violations.pop_front();
+            }
+            inner.violations.push_back(ViolationReport {
+                run_id: run_id.clone(),
+                property: violation.name.clone(),
+                fingerprint: violation.fingerprint.clone(),
+                url: url.to_string(),
+                message: render_violation(&violation.violation),
+            });
+        }
+    }
+
+    pub fn run_finished(&self, run_id: &RunId, exit_code: Option<i32>) {
+        if let Ok(mut inner) = self.inner.lock()
+            && let Some(run) = inner.status.runs.get_mut(run_id)
+        {
+            run.exit_code = exit_code;
+            run.finished = true;
+        }
+    }
+
+    /// Whether a client asked to stop the test, in which case no more runs start.
+    pub fn is_stopping(&self) -> bool {
+        *self.state.borrow() == TestState::Stopping
+    }
+
+    /// Marks the test as finished, once all of its runs are.
+    pub fn finish(&self) {
+        self.set_state(TestState::Finished);
+    }
+
+    fn status(&self) -> ControlStatus {
+        self.inner
+            .lock()
+            .map(|inner| inner.status.clone())
+            .unwrap_or_default()
+    }
+}
+
+fn router(control: Control) -> Router {
+    Router::new()
+        .route("/run", post(start_test))
+        .route("/status", get(get_status))
+        .route("/violations", get(get_violations))
+        .route("/metrics", get(get_metrics))
+        .route("/stop", post(stop_test))
+        .with_state(control)
+}
+
+async fn start_test(
+    State(control): State<Control>,
+) -> (StatusCode, Json<ControlStatus>) {
+    if *control.state.borrow() != TestState::Waiting {
+        return (StatusCode::CONFLICT, Json(control.status()));
+    }
+    log::info!("test started through the control API");
+    control.set_state(TestState::Running);
+    (StatusCode::ACCEPTED, Json(control.status()))
+}
+
+async fn get_status(State(control): State<Control>) -> Json<ControlStatus> {
+    Json(control.status())
+}
+
+async fn get_violations(
+    State(control): State<Control>,
+) -> Json<Vec<ViolationReport>> {
+    Json(
+        control
+            .inner
+            .lock()
+            .map(|inner| inner.violations.iter().cloned().collect())
+            .unwrap_or_default(),
+    )
+}
+
+/// Escapes a Prometheus label value.
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// The runs' steps and violations, and the last values of the metrics emitted by the
+/// specification, in the Prometheus text format.
+fn render_metrics(inner: &ControlInner) -> String {
+    let mut text = String::new();
+    text.push_str("# TYPE bombadil_steps_total counter\n");
+    for (run_id, run) in &inner.status.runs {
+        let _ = writeln!(
+            text,
+            "bombadil_steps_total{{run_id=\"{}\"}} {}",
+            label(run_id.as_str()),
+            run.steps
+        );
+    }
+    text.push_str("# TYPE bombadil_violations_total counter\n");
+    for (run_id, run) in &inner.status.runs {
+        let _ = writeln!(
+            text,
+            "bombadil_violations_total{{run_id=\"{}\"}} {}",
+            label(run_id.as_str()),
+            run.violations
+        );
+    }
+    text.push_str("# TYPE bombadil_metric gauge\n");
+    for (run_id, metrics) in &inner.metrics {
+        for (name, value) in metrics {
+            let _ = writeln!(
+                text,
+                "bombadil_metric{{run_id=\"{}\",name=\"{}\"}} {}",
+                label(run_id.as_str()),
+                label(name),
+                value
+            );
+        }
+    }
+    text
+}
+
+async fn get_metrics(
+    State(control): State<Control>,
+) -> ([(header::HeaderName, &'static str); 1], String) {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        control
+            .inner
+            .lock()
+            .map(|inner| render_metrics(&inner))
+            .unwrap_or_default(),
+    )
+}
+
+async fn stop_test(
+    State(control): State<Control>,
+) -> (StatusCode, Json<ControlStatus>) {
+    if *control.state.borrow() != TestState::Running {
+        return (StatusCode::CONFLICT, Json(control.status()));
+    }
+    log::info!("test stopped through the control API");
+    control.set_state(TestState::Stopping);
+    (StatusCode::ACCEPTED, Json(control.status()))
+}
+
+/// Serves the control API on the local `port`, letting other programs start the test,
+/// follow its runs and their violations, and stop it.
+pub async fn serve_control(port: u16) -> Result<Control> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .await
+        .with_context(|| format!("bind control API to port {}", port))?;
+    log::info!(
+        "control API listening on http://{}, waiting for POST /run",
+        listener.local_addr()?
+    );
+    let control = Control::default();
+    let app = router(control.clone());
+    tokio::spawn(async move {
+        if let Err(error) = axum::serve(listener, app).await {
+            log::error!("control API failed: {}", error);
+        }
+    });
+    Ok(control)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    async fn request(control: &Control, method: &str, uri: &str) -> StatusCode {
+        router(control.clone())
+            .oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn test_start_and_stop() {
+        let control = Control::default();
+        assert_eq!(
+            request(&control, "POST", "/stop").await,
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            request(&control, "POST", "/run").await,
+            StatusCode::ACCEPTED
+        );
+        control.started().await;
+        assert_eq!(
+            request(&control, "POST", "/run").await,
+            StatusCode::CONFLICT
+        );
+
+        let run_id = RunId::new(7);
+        control.run_started(&run_id, 7);
+        control.state_checked(
+            &run_id,
+            "http://localhost/",
+            &[],
+            &[("cart \"size\"".to_string(), 3.0)],
+        );
+        assert_eq!(control.status().runs[&run_id].steps, 1);
+        assert_eq!(request(&control, "GET", "/status").await, StatusCode::OK);
+        assert_eq!(request(&control, "GET", "/metrics").await, StatusCode::OK);
+        let metrics = render_metrics(&control.inner.lock().unwrap());
+        assert!(metrics.contains(&format!(
+            "bombadil_steps_total{{run_id=\"{}\"}} 1",
+            run_id
+        )));
+        assert!(metrics.contains(&format!(
+            "bombadil_metric{{run_id=\"{}\",name=\"cart \\\"size\\\"\"}} 3",
+            run_id
+        )));
+
+        assert_eq!(
+            request(&control, "POST", "/stop").await,
+            StatusCode::ACCEPTED
+        );
+        control.stopped().await;
+        assert_eq!(control.status().state, TestState::Stopping);
+    }
+}
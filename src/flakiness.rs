@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+/// How consistently a property was violated across the runs of a batch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Consistency {
+    /// Violated in every run, likely a deterministic regression.
+    Deterministic,
+    /// Violated in some of the runs only, e.g. because of a race condition.
+    Intermittent,
+}
+
+/// The violations of a property across the runs of a batch.
+#[derive(Clone, Debug, Serialize)]
+pub struct PropertyFlakiness {
+    /// Runs violating the property.
+    pub violated_runs: usize,
+    /// The fraction of runs violating the property, from 0 to 1.
+    pub violation_rate: f64,
+    /// The average index of the state where the property was first violated in a run.
+    pub average_first_violation_step: f64,
+    pub consistency: Consistency,
+    /// Seeds of the runs violating the property.
+    pub seeds: Vec<u64>,
+    #[serde(skip)]
+    first_violation_steps: u64,
+}
+
+/// Per-property statistics of the violations across the runs of a batch, telling
+/// deterministic regressions from rare failures.
+#[derive(Debug, Default, Serialize)]
+pub struct FlakinessReport {
+    pub runs: usize,
+    pub properties: BTreeMap<String, PropertyFlakiness>,
+}
+
+impl FlakinessReport {
+    /// Adds a run, with the index of the state each property was first violated in.
+    pub fn add(&mut self, seed: u64, first_violations: &BTreeMap<String, u32>) {
+        self.runs += 1;
+        for (name, step) in first_violations {
+            let property = self.properties.entry(name.clone()).or_insert(
+                PropertyFlakiness {
+                    violated_runs: 0,
+                    violation_rate: 0.0,
+                    average_first_violation_step: 0.0,
+                    consistency: Consistency::Intermittent,
+                    seeds: vec![],
+                    first_violation_steps: 0,
+                },
+            );
+            property.violated_runs += 1;
+            property.first_violation_steps += *step as u64;
+            property.average_first_violation_step =
+                property.first_violation_steps as f64
+                    / property.violated_runs as f64;
+            property.seeds.push(seed);
+        }
+        for property in self.properties.values_mut() {
+            property.violation_rate =
+                property.violated_runs as f64 / self.runs as f64;
+            property.consistency = if property.violated_runs == self.runs {
+                Consistency::Deterministic
+            } else {
+                Consistency::Intermittent
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consistency_across_runs() {
+        let mut report = FlakinessReport::default();
+        report.add(
+            1,
+            &BTreeMap::from([("a".to_string(), 4), ("b".to_string(), 10)]),
+        );
+        report.add(2, &BTreeMap::from([("a".to_string(), 8)]));
+        report.add(3, &BTreeMap::from([("a".to_string(), 6)]));
+
+        let a = &report.properties["a"];
+        assert_eq!(a.violated_runs, 3);
+        assert_eq!(a.average_first_violation_step, 6.0);
+        assert_eq!(a.consistency, Consistency::Deterministic);
+
+        let b = &report.properties["b"];
+        assert_eq!(b.seeds, vec![1]);
+        assert_eq!(b.violation_rate, 1.0 / 3.0);
+        assert_eq!(b.consistency, Consistency::Intermittent);
+    }
+}
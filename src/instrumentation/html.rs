@@ -1,4 +1,6 @@
 use anyhow::{Result, anyhow};
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
 use html5ever::{
     ParseOpts, parse_document, serialize, tendril::TendrilSink,
     tree_builder::TreeBuilderOpts,
@@ -42,60 +44,89 @@ pub fn instrument_inline_scripts(
 
 fn transform_inline_scripts(source_id: SourceId, dom: &RcDom) -> Result<()> {
     let mut scripts_count = 0;
+    // Sources nested in attributes (`srcdoc` documents, `data:` and `javascript:` URLs) get
+    // IDs derived separately from those of inline scripts.
+    let mut nested_count = 0;
     let mut stack: Vec<Handle> = Vec::new();
     stack.push(dom.document.clone());
 
     while let Some(node) = stack.pop() {
-        if let NodeData::Element { name, attrs, .. } = &node.data
-            && name.local.as_ref() == "script"
-        {
-            let attrs = attrs.borrow();
-            let script_src = attrs
-                .iter()
-                .find(|attr| attr.name.local.as_ref() == "src")
-                .map(|attr| attr.value.to_string());
-
+        if let NodeData::Element { name, attrs, .. } = &node.data {
+            let element: &str = name.local.as_ref();
             let script_type = attrs
+                .borrow()
                 .iter()
                 .find(|attr| attr.name.local.as_ref() == "type")
                 .map(|attr| attr.value.to_string())
                 .unwrap_or("".to_string());
-
-            let is_inline_javascript = script_src.is_none()
-                && (script_type.is_empty() || script_type == "text/javascript");
-
             let source_type = if script_type == "module" {
                 SourceType::mjs()
             } else {
                 SourceType::cjs()
             };
 
-            if is_inline_javascript {
-                let text_nodes: Vec<Handle> = node
-                    .children
-                    .borrow()
-                    .iter()
-                    .filter(|child| matches!(child.data, NodeData::Text { .. }))
-                    .cloned()
-                    .collect();
-
-                for child in text_nodes {
-                    if let NodeData::Text { contents } = &child.data {
-                        let original = {
-                            let c = contents.borrow();
-                            c.to_string()
-                        };
-
-                        let transformed = instrument_source_code(
-                            // Every inline scripts needs a unique ID.
-                            source_id.add(scripts_count),
-                            &original,
+            for attr in attrs.borrow_mut().iter_mut() {
+                let nested_id = source_id.add(("nested", nested_count));
+                let instrumented = match (element, attr.name.local.as_ref()) {
+                    ("iframe", "srcdoc") => {
+                        Some(instrument_inline_scripts(nested_id, &attr.value)?)
+                    }
+                    (_, "href" | "src" | "action" | "formaction") => {
+                        instrument_url(
+                            nested_id,
+                            element,
                             source_type,
-                        )?;
+                            &attr.value,
+                        )?
+                    }
+                    _ => None,
+                };
+                if let Some(instrumented) = instrumented {
+                    attr.value = instrumented.into();
+                    nested_count += 1;
+                }
+            }
+
+            if element == "script" {
+                let attrs = attrs.borrow();
+                let script_src = attrs
+                    .iter()
+                    .find(|attr| attr.name.local.as_ref() == "src")
+                    .map(|attr| attr.value.to_string());
+
+                let is_inline_javascript = script_src.is_none()
+                    && (script_type.is_empty()
+                        || script_type == "text/javascript");
+
+                if is_inline_javascript {
+                    let text_nodes: Vec<Handle> = node
+                        .children
+                        .borrow()
+                        .iter()
+                        .filter(|child| {
+                            matches!(child.data, NodeData::Text { .. })
+                        })
+                        .cloned()
+                        .collect();
+
+                    for child in text_nodes {
+                        if let NodeData::Text { contents } = &child.data {
+                            let original = {
+                                let c = contents.borrow();
+                                c.to_string()
+                            };
+
+                            let transformed = instrument_source_code(
+                                // Every inline scripts needs a unique ID.
+                                source_id.add(scripts_count),
+                                &original,
+                                source_type,
+                            )?;
 
-                        *contents.borrow_mut() = transformed.into();
+                            *contents.borrow_mut() = transformed.into();
+                        }
+                        scripts_count += 1;
                     }
-                    scripts_count += 1;
                 }
             }
         }
@@ -108,6 +139,149 @@ fn transform_inline_scripts(source_id: SourceId, dom: &RcDom) -> Result<()> {
     Ok(())
 }
 
+/// Instruments the scripts of a `javascript:` URL, or of a `data:` URL loaded as a script or
+/// frame, which aren't requested over the network and so can't be instrumented when
+/// intercepting responses. Returns None for other URLs, and for `javascript:` URLs that
+/// don't parse.
+fn instrument_url(
+    source_id: SourceId,
+    element: &str,
+    source_type: SourceType,
+    url: &str,
+) -> Result<Option<String>> {
+    let url = url.trim_start();
+    if let Some(code) = strip_prefix_ignore_case(url, "javascript:") {
+        let code = String::from_utf8_lossy(&percent_decode(code)).into_owned();
+        // Links like `javascript:;` aren't always valid scripts, which shouldn't fail the
+        // instrumentation of the whole document.
+        return Ok(instrument_source_code(source_id, &code, SourceType::cjs())
+            .inspect_err(|error| {
+                log::warn!("leaving javascript: URL uninstrumented: {}", error)
+            })
+            .ok()
+            .map(|instrumented| {
+                format!("javascript:{}", encode_javascript_url(&instrumented))
+            }));
+    }
+
+    if !matches!(element, "script" | "iframe" | "frame") {
+        return Ok(None);
+    }
+    let Some(data_url) = strip_prefix_ignore_case(url, "data:") else {
+        return Ok(None);
+    };
+    let Some(data_url) = DataUrl::parse(data_url) else {
+        return Ok(None);
+    };
+    let contents = String::from_utf8_lossy(&data_url.body).into_owned();
+    let instrumented = match (element, data_url.mime_type.as_str()) {
+        (
+            "script",
+            ""
+            | "text/javascript"
+            | "application/javascript"
+            | "text/ecmascript"
+            | "application/ecmascript",
+        ) => instrument_source_code(source_id, &contents, source_type)?,
+        ("iframe" | "frame", "text/html") => {
+            instrument_inline_scripts(source_id, &contents)?
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(format!(
+        "data:{};base64,{}",
+        data_url.metadata,
+        BASE64_STANDARD.encode(instrumented)
+    )))
+}
+
+fn strip_prefix_ignore_case<'a>(
+    value: &'a str,
+    prefix: &str,
+) -> Option<&'a str> {
+    value
+        .get(..prefix.len())
+        .filter(|start| start.eq_ignore_ascii_case(prefix))
+        .map(|_| &value[prefix.len()..])
+}
+
+/// The parts of a `data:` URL (after the scheme): its metadata without the `;base64` flag,
+/// the lower-cased MIME type, and the decoded body.
+struct DataUrl {
+    metadata: String,
+    mime_type: String,
+    body: Vec<u8>,
+}
+
+impl DataUrl {
+    fn parse(url: &str) -> Option<Self> {
+        let (metadata, body) = url.split_once(',')?;
+        let mut parameters: Vec<&str> =
+            metadata.split(';').map(str::trim).collect();
+        let is_base64 = parameters
+            .last()
+            .is_some_and(|last| last.eq_ignore_ascii_case("base64"));
+        if is_base64 {
+            parameters.pop();
+        }
+        let body = percent_decode(body);
+        let body = if is_base64 {
+            let body: Vec<u8> = body
+                .into_iter()
+                .filter(|byte| !byte.is_ascii_whitespace())
+                .collect();
+            BASE64_STANDARD.decode(body).ok()?
+        } else {
+            body
+        };
+        Some(DataUrl {
+            mime_type: parameters[0].to_ascii_lowercase(),
+            metadata: parameters.join(";"),
+            body,
+        })
+    }
+}
+
+fn percent_decode(input: &str) -> Vec<u8> {
+    let bytes = input.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                output.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                output.push(byte);
+                i += 1;
+            }
+        }
+    }
+    output
+}
+
+/// Encodes code for a `javascript:` URL, whose newlines and tabs would otherwise be stripped
+/// by URL parsing, and whose percent signs would be decoded.
+fn encode_javascript_url(code: &str) -> String {
+    let mut encoded = String::with_capacity(code.len());
+    for character in code.chars() {
+        match character {
+            '%' => encoded.push_str("%25"),
+            '\n' => encoded.push_str("%0A"),
+            '\r' => encoded.push_str("%0D"),
+            '\t' => encoded.push_str("%09"),
+            '#' => encoded.push_str("%23"),
+            other => encoded.push(other),
+        }
+    }
+    encoded
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -172,4 +346,41 @@ mod tests {
         let output = instrument_inline_scripts(SourceId(0), input).unwrap();
         assert_snapshot!(output);
     }
+
+    #[test]
+    fn test_instrument_html_nested_sources() {
+        let input = indoc! { r#"
+        <!DOCTYPE html>
+        <html>
+        <body>
+        <iframe srcdoc="<script>console.log(1 ? 2 : 3);</script>"></iframe>
+        <script src="data:text/javascript,console.log(1%20%3F%202%20%3A%203)"></script>
+        <a href="javascript:void(0)">link</a>
+        <img src="data:image/png;base64,iVBORw0KGgo=">
+        </body>
+        </html>
+        "# };
+
+        let output = instrument_inline_scripts(SourceId(0), input).unwrap();
+        let (_, iframe) = output.split_once("<iframe").unwrap();
+        let (srcdoc, _) = iframe.split_once("</iframe>").unwrap();
+        assert!(srcdoc.contains("window.__bombadil__"));
+        assert!(output.contains("<script src=\"data:text/javascript;base64,"));
+        assert!(output.contains("href=\"javascript:window.__bombadil__"));
+        assert!(output.contains("src=\"data:image/png;base64,iVBORw0KGgo=\""));
+    }
+
+    #[test]
+    fn test_parse_data_url() {
+        let data_url =
+            DataUrl::parse("Text/HTML;charset=utf-8;base64,PHA+aGk8L3A+")
+                .unwrap();
+        assert_eq!(data_url.metadata, "Text/HTML;charset=utf-8");
+        assert_eq!(data_url.mime_type, "text/html");
+        assert_eq!(data_url.body, b"<p>hi</p>");
+
+        let data_url = DataUrl::parse(",alert(%221%22)").unwrap();
+        assert_eq!(data_url.mime_type, "");
+        assert_eq!(data_url.body, b"alert(\"1\")");
+    }
 }
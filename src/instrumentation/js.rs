@@ -18,6 +18,9 @@ use oxc::{
 use oxc_traverse::{Traverse, TraverseCtx, traverse_mut};
 
 use crate::instrumentation::source_id::SourceId;
+use crate::instrumentation::{
+    EDGE_MAP_SIZE, EDGES_CURRENT, EDGES_PREVIOUS, NAMESPACE,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum InstrumentationError {
@@ -46,12 +49,6 @@ impl fmt::Display for InstrumentationError {
 
 pub type InstrumentationResult<T> = Result<T, InstrumentationError>;
 
-pub const NAMESPACE: &str = "__bombadil__";
-
-pub const EDGES_PREVIOUS: &str = "edges_previous";
-pub const EDGES_CURRENT: &str = "edges_current";
-pub const EDGE_MAP_SIZE: usize = 64 * 1024;
-
 const LOCATION_PREVIOUS: &str = "previous";
 
 const PRELUDE: &str = str_replace!(
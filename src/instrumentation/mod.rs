@@ -1,3 +1,13 @@
+#[cfg(feature = "instrumentation")]
 pub mod html;
+#[cfg(feature = "instrumentation")]
 pub mod js;
 pub mod source_id;
+
+/// The global object where instrumented scripts record the edges they hit, read back by
+/// the browser at each state.
+pub const NAMESPACE: &str = "__bombadil__";
+
+pub const EDGES_PREVIOUS: &str = "edges_previous";
+pub const EDGES_CURRENT: &str = "edges_current";
+pub const EDGE_MAP_SIZE: usize = 64 * 1024;
@@ -1,8 +1,16 @@
 pub mod browser;
+pub mod control;
+pub mod flakiness;
 pub mod geometry;
 pub mod instrumentation;
+pub mod run_id;
+#[cfg(feature = "verifier")]
 pub mod runner;
+pub mod soak;
 pub mod specification;
+#[cfg(feature = "verifier")]
+pub mod summary;
+pub mod suppressions;
 pub mod trace;
 pub mod tree;
 pub mod url;
@@ -1,14 +1,66 @@
 use ::url::Url;
 use anyhow::Result;
 use clap::{Args, Parser};
-use std::{path::PathBuf, str::FromStr};
+use futures::{StreamExt, future, stream};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, Instant, SystemTime};
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 use tempfile::TempDir;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::TcpListener;
 
 use bombadil::{
-    browser::{BrowserOptions, DebuggerOptions, Emulation, LaunchOptions},
-    runner::{Runner, RunnerOptions},
-    specification::{render::render_violation, verifier::Specification},
-    trace::writer::TraceWriter,
+    browser::{
+        BrowserOptions, DebuggerOptions, Emulation, InitialNavigation,
+        LaunchOptions,
+        actions::BrowserAction,
+        checkpoints::{CHECKPOINTS_FILE_NAME, Checkpointing, SavedCheckpoint},
+        chrome::{ChromeChannel, HeadlessVariant},
+        click_deny_list::ClickDenyList,
+        coverage::CoverageSource,
+        dialogs::BeforeUnloadPolicy,
+        faults::{DEFAULT_FAULT_DELAY, FaultInjection, FaultKind, FaultRule},
+        gpu::GpuPreset,
+        media::{FakeMedia, Permission},
+        provisioning, screencast,
+        service_workers::ServiceWorkerPolicy,
+        state::EdgeIndex,
+        viewports::{ViewportProfile, ViewportSweep},
+    },
+    control::{Control, serve_control},
+    flakiness::{Consistency, FlakinessReport},
+    run_id::RunId,
+    runner::{
+        PropertyVerdict, Runner, RunnerOptions, StepTimings,
+        context::{ContextEntry, load_context},
+        edges::EdgeMap,
+        exhaustive::{ExhaustiveOptions, SearchOrder},
+        novelty::NoveltyOptions,
+        policy::PolicyOptions,
+    },
+    soak::{SoakReport, SoakSegment, parse_duration},
+    specification::{
+        eject::eject_types,
+        remote::serve,
+        render::render_violation,
+        verifier::{Specification, Verifier},
+    },
+    summary::{RunSummary, StepLatency, Summary, ViolationSummary},
+    suppressions::{Suppression, Suppressions},
+    trace::{
+        clusters::ViolationCluster,
+        error_signatures::ErrorSignature,
+        replay::{read_actions, read_checkpoints},
+        report::render_report,
+        reproduction::Reproduction,
+        writer::{StateCaptures, TraceWriter},
+    },
 };
 
 /// Property-based testing for web UIs
@@ -27,12 +79,50 @@ struct TestSharedOptions {
     /// A custom specification in TypeScript or JavaScript, using the `@antithesishq/bombadil`
     /// package on NPM
     specification_file: Option<PathBuf>,
-    /// Where to store output data (trace, screenshots, etc)
-    #[arg(long)]
+    /// Another specification to check in the same run, e.g. one per feature, with the
+    /// properties and action generators of each file prefixed by its name, like
+    /// `checkout/noErrors` (can be repeated)
+    #[arg(long = "spec", value_name = "PATH")]
+    specification_files: Vec<PathBuf>,
+    /// A specification checked in its own JavaScript context alongside the others, e.g. an
+    /// invariant pack maintained by another team, with its properties prefixed by its name
+    /// and its action generators left out, the others driving the run (can be repeated)
+    #[arg(long = "isolated-spec", value_name = "PATH")]
+    isolated_specification_files: Vec<PathBuf>,
+    /// A module imported into every specification file, e.g. a company-wide baseline of
+    /// properties, whose exports are exported along with each file's own unless it exports
+    /// the same names
+    #[arg(long, value_name = "PATH")]
+    prelude: Option<PathBuf>,
+    /// Where to store output data (trace, screenshots, etc), in a subdirectory per run
+    #[arg(long, visible_alias = "trace-dir")]
     output_path: Option<PathBuf>,
+    /// Write the graph of the explored states, by URL and state hash, and the actions
+    /// between them to this file at the end of the run, as GraphML when it ends in
+    /// `.graphml` and in Graphviz's DOT language otherwise, or to `<RUN_ID>.dot` in it
+    /// when it's a directory
+    #[arg(long, value_name = "PATH")]
+    graph_out: Option<PathBuf>,
+    /// Write a machine-readable summary of the runs to this file when exiting, with the
+    /// verdict of each property, the violations, the coverage and the timings
+    #[arg(long, value_name = "PATH")]
+    json_summary: Option<PathBuf>,
     /// Whether to exit the test when first failing property is found (useful in development and CI)
     #[arg(long)]
     exit_on_violation: bool,
+    /// Stop each run after this many states
+    #[arg(long, value_name = "STEPS")]
+    max_steps: Option<u64>,
+    /// Stop each run after this long, e.g. `10m` or `1h30m`
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    max_duration: Option<Duration>,
+    /// Stop each run after this many states in a row without covering new edges
+    #[arg(long, value_name = "STEPS")]
+    coverage_plateau: Option<u64>,
+    /// Halve the hit buckets of the covered edges every this many states, uncovering those
+    /// hit only once, so that edges not covered in a while count as new again in long runs
+    #[arg(long, value_name = "STEPS")]
+    edge_decay: Option<u64>,
     /// Browser viewport width in pixels
     #[arg(long, default_value_t = 1024)]
     width: u16,
@@ -43,6 +133,275 @@ struct TestSharedOptions {
     /// mode
     #[arg(long, default_value_t = 2.0)]
     device_scale_factor: f64,
+    /// Device to emulate instead of `--width`, `--height` and `--device-scale-factor`:
+    /// `mobile`, `tablet` or `desktop` (each with its metrics, touch screen and user
+    /// agent), or a viewport profile like those of `--viewport-profile`
+    #[arg(long, value_name = "DEVICE")]
+    device: Option<ViewportProfile>,
+    /// User agent reported to the page, instead of the browser's own or the device's
+    #[arg(long, value_name = "USER_AGENT")]
+    user_agent: Option<String>,
+    /// Viewport profile (`mobile`, `tablet`, `desktop` or
+    /// `NAME=WIDTHxHEIGHT[@SCALE][:mobile][:touch]`) to switch to during the run, in turn
+    /// with the default viewport and the other profiles (can be repeated)
+    #[arg(long = "viewport-profile", value_name = "PROFILE")]
+    viewport_profiles: Vec<ViewportProfile>,
+    /// Number of states captured with each viewport profile before switching to the next one
+    #[arg(long, value_name = "STATES", default_value_t = 20)]
+    viewport_interval: u64,
+    /// Emulate `prefers-reduced-motion: reduce` and disable CSS animations and transitions,
+    /// for more stable states and screenshots
+    #[arg(long)]
+    reduce_motion: bool,
+    /// URL pattern (with `*` wildcards) of resources to forward without instrumentation,
+    /// preserving their original caching headers (can be repeated)
+    #[arg(long = "cache-pass-through", value_name = "PATTERN")]
+    cache_pass_through: Vec<String>,
+    /// URL pattern (with `*` wildcards) of requests to inject faults into, delaying,
+    /// dropping or failing them with a 500 at random (can be repeated)
+    #[arg(long = "fault-url", value_name = "PATTERN")]
+    fault_urls: Vec<String>,
+    /// Probability of a request matching a `--fault-url` getting a fault
+    #[arg(long, value_name = "RATE", default_value_t = 0.1)]
+    fault_rate: f64,
+    /// Seed for the random choices made during the test, for reproducing a previous run
+    #[arg(long)]
+    seed: Option<u64>,
+    /// How to treat the origin's service workers at the start of the test: keep, bypass,
+    /// update (on every page load), or unregister
+    #[arg(
+        long = "service-workers",
+        value_name = "POLICY",
+        default_value = "keep"
+    )]
+    service_worker_policy: ServiceWorkerPolicy,
+    /// How to answer the `beforeunload` confirmations ("Leave site?") blocking navigations
+    /// away from a page: accept or dismiss
+    #[arg(
+        long = "before-unload",
+        value_name = "POLICY",
+        default_value = "accept"
+    )]
+    before_unload: BeforeUnloadPolicy,
+    /// Only observe the browser while you drive it manually, checking properties and
+    /// recording the trace without generating any actions
+    #[arg(long)]
+    observe: bool,
+    /// Allow typing into payment fields (e.g. credit card numbers) and submitting forms
+    /// containing them, which is refused by default
+    #[arg(long)]
+    allow_payment_fields: bool,
+    /// Text of elements not to click (matched case-insensitively as whole words in their
+    /// text, aria-label, title or value), in addition to the defaults like "log out" or
+    /// "delete account" (can be repeated)
+    #[arg(long = "deny-click", value_name = "TEXT")]
+    deny_click: Vec<String>,
+    /// Text of elements to click even if denied, e.g. "log out" to test logging back in (can
+    /// be repeated)
+    #[arg(long = "allow-click", value_name = "TEXT")]
+    allow_click: Vec<String>,
+    /// Capture the accessibility tree of every state with a violation, next to its
+    /// screenshot
+    #[arg(long)]
+    accessibility_snapshots: bool,
+    /// For every state with a violation, write the screenshots of the last this many states
+    /// and a screenshot of the whole page, beyond the viewport, to the `violations`
+    /// directory of the run
+    #[arg(long, value_name = "COUNT")]
+    violation_screenshots: Option<usize>,
+    /// Record a screencast of every run, as frames in the `screencast` directory of the
+    /// run along with a list timing them, from which ffmpeg can encode a video
+    #[arg(long)]
+    record: bool,
+    /// Capture the serialized DOM of every state, written to the `dom` directory of the
+    /// run
+    #[arg(long)]
+    dom_snapshots: bool,
+    /// Evaluate extractors in an isolated world, sharing the page's DOM but not its
+    /// JavaScript globals, so that the page's scripts can't redefine what they use (or
+    /// notice them)
+    #[arg(long)]
+    isolated_extractors: bool,
+    /// Adapt the pace of actions to the page: act faster while it's static, and wait longer
+    /// while it's busy, coalescing bursts of DOM mutations into single states
+    #[arg(long)]
+    adaptive_pacing: bool,
+    /// Address (`host:port`) of a `bombadil verifier-server` to evaluate the specification
+    /// on, instead of in this process
+    #[arg(long, value_name = "ADDRESS")]
+    remote_verifier: Option<String>,
+    /// URL of an external policy to ask for the actions otherwise picked at random, given a
+    /// summary of the state and the candidate actions, picking at random when it fails or
+    /// doesn't answer in time
+    #[arg(long, value_name = "URL")]
+    policy_url: Option<Url>,
+    /// Milliseconds to wait for the external policy to pick an action
+    #[arg(long, value_name = "MILLISECONDS", default_value_t = 500)]
+    policy_timeout: u64,
+    /// JSON file of known violations (by property and optional fingerprint) that don't fail
+    /// the test until their expiry date, still reported as suppressed
+    #[arg(long, value_name = "PATH")]
+    suppressions: Option<PathBuf>,
+    /// Seconds to wait for each attempt of the initial navigation to the origin
+    #[arg(long, value_name = "SECONDS", default_value_t = 30)]
+    navigation_timeout: u64,
+    /// Number of times to retry the initial navigation to the origin when it fails, with
+    /// a backoff doubling from one second
+    #[arg(long, value_name = "RETRIES", default_value_t = 0)]
+    navigation_retries: u32,
+    /// URL to poll until it responds successfully before navigating to the origin, e.g. the
+    /// health endpoint of a backend that's still starting
+    #[arg(long, value_name = "URL")]
+    health_check: Option<Url>,
+    /// Seconds to poll the health check URL before failing the test
+    #[arg(long, value_name = "SECONDS", default_value_t = 60)]
+    health_check_timeout: u64,
+    /// Source of the coverage guiding the exploration: `instrumentation` (rewriting scripts
+    /// as they load) or `precise` (the V8 profiler, less precise but leaving scripts
+    /// untouched, for apps that break when rewritten)
+    #[arg(
+        long = "coverage",
+        value_name = "SOURCE",
+        default_value = "instrumentation"
+    )]
+    coverage_source: CoverageSource,
+    /// Pause at the first violation, keeping the (visible) browser open at the violating
+    /// state for debugging until Enter is pressed
+    #[arg(long)]
+    pause_on_violation: bool,
+    /// Every this many states, either save a checkpoint (URL, cookies and storage) of the
+    /// current state or branch off from a random earlier one, restoring it to explore other
+    /// actions from there
+    #[arg(long, value_name = "STATES")]
+    checkpoint_interval: Option<u64>,
+    /// Number of checkpoints kept, dropping the oldest ones
+    #[arg(long, value_name = "COUNT", default_value_t = 8)]
+    checkpoints: usize,
+    /// Write the checkpoints saved, cookies and storage included, to `checkpoints.jsonl`
+    /// in the run's directory, so that replaying the trace restores them
+    #[arg(long)]
+    save_checkpoints: bool,
+    /// Explore all action sequences up to `--exhaustive-depth` instead of picking actions at
+    /// random, in breadth-first (`bfs`) or depth-first (`dfs`) order, restoring a checkpoint
+    /// of the initial state to start each sequence over
+    #[arg(long, value_name = "ORDER")]
+    exhaustive: Option<SearchOrder>,
+    /// Length of the longest action sequences explored with `--exhaustive`
+    #[arg(long, value_name = "DEPTH", default_value_t = 3)]
+    exhaustive_depth: usize,
+    /// Prefer actions not known to lead to states already visited, identified by their URL
+    /// and the simhash of the edges covered to reach them
+    #[arg(long)]
+    prefer_new_states: bool,
+    /// Go back, or reload, when the last states all come down to a couple of them, e.g.
+    /// toggling a menu open and closed (implies `--prefer-new-states`)
+    #[arg(long)]
+    escape_loops: bool,
+    /// Value exposed to extractors in `state.context` (as JSON when it parses as JSON, e.g.
+    /// `beta=true`, and as a string otherwise), overriding `--context-file` (can be repeated)
+    #[arg(long = "context", value_name = "KEY=VALUE")]
+    context: Vec<ContextEntry>,
+    /// JSON object file exposed to extractors as `state.context`, e.g. the expected tenant
+    /// name and feature flags of the environment under test
+    #[arg(long, value_name = "PATH")]
+    context_file: Option<PathBuf>,
+    /// Permission granted to the origin, so the page isn't stuck behind its prompt:
+    /// `camera`, `microphone` or a DevTools protocol permission type such as `geolocation`
+    /// or `notifications` (can be repeated)
+    #[arg(long = "grant-permission", value_name = "PERMISSION")]
+    permissions: Vec<Permission>,
+    /// CSS selector of elements blacked out in screenshots, e.g. dates, avatars or ads, so
+    /// that screenshots are stable and free of personal data (can be repeated)
+    #[arg(long = "mask-screenshot", value_name = "SELECTOR")]
+    screenshot_masks: Vec<String>,
+    /// Serve an HTTP API on this local port for other programs to start the test (`POST
+    /// /run`), follow its runs (`GET /status`), their latest violations (`GET
+    /// /violations`) and metrics (`GET /metrics`), and stop it (`POST /stop`), waiting
+    /// for the start before running
+    #[arg(long, value_name = "PORT")]
+    serve_control: Option<u16>,
+}
+
+#[derive(Args)]
+struct ManagedBrowserOptions {
+    /// Whether the browser should run in a visible window or not
+    #[arg(long, default_value_t = false)]
+    headless: bool,
+    /// Headless mode variant when running headless: `new` (the regular browser without a
+    /// window) or `old` (the legacy implementation, e.g. in `chrome-headless-shell`)
+    #[arg(long, value_name = "MODE", default_value = "new")]
+    headless_mode: HeadlessVariant,
+    /// Path to the Chrome or Chromium binary to launch
+    #[arg(long, value_name = "PATH", conflicts_with = "chrome_channel")]
+    chrome_binary: Option<PathBuf>,
+    /// Release channel of the installed Chrome to launch: `stable`, `beta`, `dev` or
+    /// `canary`
+    #[arg(long, value_name = "CHANNEL")]
+    chrome_channel: Option<ChromeChannel>,
+    /// Launch flags for the GPU: `default`, `swiftshader` (WebGL rendered in software, for
+    /// environments without a GPU such as containers) or `disabled` (no GPU acceleration)
+    #[arg(long, value_name = "PRESET", default_value = "default")]
+    gpu: GpuPreset,
+    /// Download a pinned Chrome for Testing build into the cache directory when no Chrome
+    /// or Chromium is found, or reuse the one downloaded before
+    #[arg(long, default_value_t = false)]
+    download_chrome: bool,
+    /// Disable Chromium sandboxing
+    #[arg(long, default_value_t = false)]
+    no_sandbox: bool,
+    /// Path to an unpacked browser extension to load, whose pages can then be tested
+    /// using a `chrome-extension://` origin (can be repeated)
+    #[arg(long = "load-extension", value_name = "PATH")]
+    load_extensions: Vec<PathBuf>,
+    /// Give the browser a fake camera and microphone, accepting their prompts, so that
+    /// pages calling `getUserMedia` get a stream
+    #[arg(long, default_value_t = false)]
+    fake_media: bool,
+    /// `.y4m` or `.mjpeg` file played by the fake camera instead of a test pattern (implies
+    /// `--fake-media`)
+    #[arg(long, value_name = "PATH")]
+    fake_video_capture: Option<PathBuf>,
+    /// `.wav` file played by the fake microphone instead of a beep (implies `--fake-media`)
+    #[arg(long, value_name = "PATH")]
+    fake_audio_capture: Option<PathBuf>,
+}
+
+impl ManagedBrowserOptions {
+    /// Downloads Chrome when requested and none was picked or found, once for all runs.
+    async fn provision(&mut self) -> Result<()> {
+        if self.download_chrome
+            && self.chrome_binary.is_none()
+            && self.chrome_channel.is_none()
+            && provisioning::find_chrome()?.is_none()
+        {
+            self.chrome_binary = Some(provisioning::download_chrome().await?);
+        }
+        Ok(())
+    }
+
+    fn debugger_options(
+        &self,
+        user_data_directory: &TempDir,
+    ) -> DebuggerOptions {
+        DebuggerOptions::Managed {
+            launch_options: LaunchOptions {
+                headless: self.headless.then_some(self.headless_mode),
+                chrome_binary: self.chrome_binary.clone(),
+                chrome_channel: self.chrome_channel,
+                user_data_directory: user_data_directory.path().to_path_buf(),
+                no_sandbox: self.no_sandbox,
+                extensions: self.load_extensions.clone(),
+                gpu: self.gpu,
+                fake_media: (self.fake_media
+                    || self.fake_video_capture.is_some()
+                    || self.fake_audio_capture.is_some())
+                .then(|| FakeMedia {
+                    video: self.fake_video_capture.clone(),
+                    audio: self.fake_audio_capture.clone(),
+                }),
+            },
+        }
+    }
 }
 
 #[derive(clap::Subcommand)]
@@ -51,12 +410,42 @@ enum Command {
     Test {
         #[clap(flatten)]
         shared: TestSharedOptions,
-        /// Whether the browser should run in a visible window or not
-        #[arg(long, default_value_t = false)]
-        headless: bool,
-        /// Disable Chromium sandboxing
-        #[arg(long, default_value_t = false)]
-        no_sandbox: bool,
+        #[clap(flatten)]
+        browser: ManagedBrowserOptions,
+        /// Number of runs, each with a different seed, whose violations are aggregated
+        #[arg(long, default_value_t = 1)]
+        runs: usize,
+        /// Maximum number of runs executing at the same time
+        #[arg(long, default_value_t = 1)]
+        parallel: usize,
+        /// Number of browsers exploring concurrently in each run, each with its own seed
+        /// and trace, merging the edges they cover into one coverage map
+        #[arg(long, default_value_t = 1)]
+        sessions: usize,
+    },
+    /// Run a long unattended test with a browser managed by Bombadil, rotating seeds (and
+    /// browsers) at regular intervals, and consolidating the runs into a single report
+    Soak {
+        #[clap(flatten)]
+        shared: TestSharedOptions,
+        #[clap(flatten)]
+        browser: ManagedBrowserOptions,
+        /// Total duration of the test, e.g. `8h` or `1h30m`
+        #[arg(long, value_parser = parse_duration)]
+        duration: Duration,
+        /// How long each seed runs before rotating to the next one, e.g. `30m`
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration, default_value = "30m")]
+        rotate_seeds: Duration,
+    },
+    /// Replay the actions of a recorded trace against the origin with a browser managed by
+    /// Bombadil, checking the properties at every step, e.g. to confirm a fix
+    Replay {
+        /// The recorded `trace.jsonl`, or the directory of the run with it
+        trace: PathBuf,
+        #[clap(flatten)]
+        shared: TestSharedOptions,
+        #[clap(flatten)]
+        browser: ManagedBrowserOptions,
     },
     /// Run a test with an externally managed browser or Electron app (e.g. `chromium
     /// --remote-debugging-port=9992`)
@@ -71,6 +460,34 @@ enum Command {
         #[arg(long)]
         create_target: bool,
     },
+    /// Write the TypeScript sources of the embedded `@antithesishq/bombadil` modules, and a
+    /// tsconfig.json mapping their import paths, for editor support in specifications
+    EjectTypes {
+        /// Directory to write the sources to
+        directory: PathBuf,
+    },
+    /// Print a JSON description of a specification's properties, action generators and
+    /// extractors, without running a test
+    Inspect {
+        /// The specification in TypeScript or JavaScript
+        specification_file: PathBuf,
+    },
+    /// Render the trace of a run as a self-contained HTML report, with a timeline of its
+    /// actions, screenshots and violations
+    Report {
+        /// Directory of the run, with its `trace.jsonl` and screenshots
+        trace_directory: PathBuf,
+        /// Where to write the report (defaults to `report.html` in the run's directory)
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Evaluate specifications for tests run elsewhere with `--remote-verifier`, e.g. when
+    /// the browser runs on a resource-constrained machine
+    VerifierServer {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:9993")]
+        listen: SocketAddr,
+    },
 }
 
 #[derive(Clone)]
@@ -105,62 +522,698 @@ async fn main() -> Result<()> {
         .filter_module("html5ever", log::LevelFilter::Info)
         .init();
     let cli = Cli::parse();
-    match cli.command {
+    let control = match &cli.command {
+        Command::Test { shared, .. }
+        | Command::Soak { shared, .. }
+        | Command::Replay { shared, .. }
+        | Command::TestExternal { shared, .. } => start_control(shared).await?,
+        _ => None,
+    };
+    let json_summary = match &cli.command {
+        Command::Test { shared, .. }
+        | Command::Soak { shared, .. }
+        | Command::Replay { shared, .. }
+        | Command::TestExternal { shared, .. } => shared.json_summary.clone(),
+        _ => None,
+    };
+    let outcomes = match cli.command {
         Command::Test {
             shared,
-            headless,
-            no_sandbox,
+            mut browser,
+            runs,
+            parallel,
+            sessions,
         } => {
-            let user_data_directory = TempDir::with_prefix("user_data_")?;
-
-            let browser_options = BrowserOptions {
-                create_target: true,
-                emulation: Emulation {
-                    width: shared.width,
-                    height: shared.height,
-                    device_scale_factor: shared.device_scale_factor,
-                },
-            };
-            let debugger_options = DebuggerOptions::Managed {
-                launch_options: LaunchOptions {
-                    headless,
-                    user_data_directory: user_data_directory
-                        .path()
-                        .to_path_buf(),
-                    no_sandbox,
-                },
+            if browser.headless && shared.pause_on_violation {
+                anyhow::bail!(
+                    "--pause-on-violation needs a visible browser, it can't be used with --headless"
+                );
+            }
+            browser.provision().await?;
+            let outcomes = stream::iter(run_seeds(shared.seed, runs))
+                .map(|seed| {
+                    test_sessions(
+                        &shared,
+                        &browser,
+                        seed,
+                        sessions,
+                        runs,
+                        control.as_ref(),
+                    )
+                })
+                .buffer_unordered(parallel.max(1))
+                .collect::<Vec<_>>()
+                .await;
+            // Only a single run fails, the failures of several being recorded in the
+            // summary and the flakiness report.
+            let outcomes = outcomes
+                .into_iter()
+                .collect::<Result<Vec<_>>>()?
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>();
+            if outcomes.len() > 1 {
+                log_runs_summary(&outcomes);
+                write_flakiness_report(&shared, &outcomes).await?;
+            }
+            outcomes
+        }
+        Command::Soak {
+            mut shared,
+            mut browser,
+            duration,
+            rotate_seeds,
+        } => {
+            if shared.pause_on_violation {
+                anyhow::bail!(
+                    "--pause-on-violation can't be used with unattended soak tests"
+                );
+            }
+            browser.provision().await?;
+            // All segments are written to the same directory, next to the report.
+            let output_path = match &shared.output_path {
+                Some(path) => path.clone(),
+                None => TempDir::with_prefix("soak_")?.keep().to_path_buf(),
             };
-            test(shared, browser_options, debugger_options).await
+            shared.output_path = Some(output_path.clone());
+            soak(
+                &shared,
+                &browser,
+                duration,
+                rotate_seeds,
+                &output_path,
+                control.as_ref(),
+            )
+            .await?
+        }
+        Command::Replay {
+            trace,
+            shared,
+            mut browser,
+        } => {
+            if shared.observe {
+                anyhow::bail!("--observe can't be used when replaying a trace");
+            }
+            let actions = read_actions(&trace).await?;
+            let checkpoints = read_checkpoints(&trace).await?;
+            log::info!(
+                "replaying {} actions from {}",
+                actions.len(),
+                trace.display()
+            );
+            browser.provision().await?;
+            let seed = shared.seed.unwrap_or_else(rand::random);
+            let user_data_directory = TempDir::with_prefix("user_data_")?;
+            vec![
+                test(
+                    &shared,
+                    seed,
+                    browser_options(&shared, seed, true),
+                    browser.debugger_options(&user_data_directory),
+                    RunParameters {
+                        replay: Some(actions),
+                        checkpoints,
+                        control: control.clone(),
+                        ..RunParameters::default()
+                    },
+                )
+                .await?,
+            ]
         }
         Command::TestExternal {
             shared,
             remote_debugger,
             create_target,
         } => {
-            let browser_options = BrowserOptions {
-                create_target,
-                emulation: Emulation {
-                    width: shared.width,
-                    height: shared.height,
-                    device_scale_factor: shared.device_scale_factor,
-                },
-            };
+            let seed = shared.seed.unwrap_or_else(rand::random);
             let debugger_options =
                 DebuggerOptions::External { remote_debugger };
-            test(shared, browser_options, debugger_options).await
+            vec![
+                test(
+                    &shared,
+                    seed,
+                    browser_options(&shared, seed, create_target),
+                    debugger_options,
+                    RunParameters {
+                        control: control.clone(),
+                        ..RunParameters::default()
+                    },
+                )
+                .await?,
+            ]
+        }
+        Command::EjectTypes { directory } => {
+            let written = eject_types(&directory).await?;
+            log::info!(
+                "wrote {} files to {}, extend {} from your tsconfig.json",
+                written.len(),
+                directory.display(),
+                directory.join("tsconfig.json").display()
+            );
+            vec![]
+        }
+        Command::Inspect { specification_file } => {
+            let specification =
+                Specification::from_path(&specification_file).await?;
+            let metadata = Verifier::new(specification, 0)?.metadata();
+            println!("{}", serde_json::to_string_pretty(&metadata)?);
+            vec![]
+        }
+        Command::Report {
+            trace_directory,
+            output,
+        } => {
+            let report = render_report(&trace_directory).await?;
+            let output =
+                output.unwrap_or_else(|| trace_directory.join("report.html"));
+            tokio::fs::write(&output, report).await?;
+            log::info!("report written to {}", output.display());
+            vec![]
+        }
+        Command::VerifierServer { listen } => {
+            let listener = TcpListener::bind(listen).await?;
+            log::info!("verifier server listening on {}", listen);
+            serve(listener).await?;
+            vec![]
         }
+    };
+
+    if let Some(control) = &control {
+        control.finish();
+    }
+
+    // Errors take precedence over violations.
+    let exit_code = outcomes
+        .iter()
+        .filter_map(|outcome| outcome.exit_code)
+        .min_by_key(|code| *code != 1);
+    if let Some(path) = &json_summary {
+        write_json_summary(path, outcomes, exit_code.unwrap_or(0)).await?;
     }
+    if let Some(exit_code) = exit_code {
+        std::process::exit(exit_code);
+    }
+
+    Ok(())
+}
+
+/// How a run differs from a regular test run.
+#[derive(Default)]
+struct RunParameters {
+    /// Stop the run after this long.
+    time_limit: Option<Duration>,
+    /// Apply these actions instead of picking them.
+    replay: Option<Vec<BrowserAction>>,
+    /// The checkpoints restored by the replayed actions.
+    checkpoints: Vec<SavedCheckpoint>,
+    /// Coverage map shared with the other sessions of the run.
+    edge_map: Option<EdgeMap>,
+    /// The seed of the run this is a session of, reproducing it along with its other
+    /// sessions.
+    run_seed: Option<u64>,
+    /// The control API to report the run to, and to stop it from.
+    control: Option<Control>,
+}
+
+/// The result of a single seeded run.
+struct RunOutcome {
+    seed: u64,
+    run_id: RunId,
+    started: SystemTime,
+    duration: Duration,
+    steps: u32,
+    covered_edges: BTreeSet<EdgeIndex>,
+    /// Names of the properties violated during the run.
+    violated_properties: BTreeSet<String>,
+    /// The index of the state each violated property was first violated in.
+    first_violations: BTreeMap<String, u32>,
+    /// Properties violated during the run whose violations were suppressed.
+    suppressed_properties: BTreeMap<String, Suppression>,
+    /// What each property evaluated to by the end of the run.
+    verdicts: BTreeMap<String, PropertyVerdict>,
+    /// All violations of the run, suppressed ones included.
+    violations: Vec<ViolationSummary>,
+    step_latency: StepLatency,
+    exit_code: Option<i32>,
+}
+
+impl RunOutcome {
+    /// The outcome of a run that failed before finishing, e.g. as its browser crashed.
+    fn failed(seed: u64, run_id: RunId, started: SystemTime) -> Self {
+        RunOutcome {
+            seed,
+            run_id,
+            started,
+            duration: started.elapsed().unwrap_or_default(),
+            steps: 0,
+            covered_edges: BTreeSet::new(),
+            violated_properties: BTreeSet::new(),
+            first_violations: BTreeMap::new(),
+            suppressed_properties: BTreeMap::new(),
+            verdicts: BTreeMap::new(),
+            violations: vec![],
+            step_latency: StepLatency::default(),
+            exit_code: Some(1),
+        }
+    }
+}
+
+fn browser_options(
+    shared_options: &TestSharedOptions,
+    seed: u64,
+    create_target: bool,
+) -> BrowserOptions {
+    BrowserOptions {
+        run_id: RunId::new(seed),
+        create_target,
+        cache_pass_through: shared_options.cache_pass_through.clone(),
+        service_worker_policy: shared_options.service_worker_policy,
+        before_unload: shared_options.before_unload,
+        seed,
+        permissions: shared_options.permissions.clone(),
+        screenshot_masks: shared_options.screenshot_masks.clone(),
+        screencast: None,
+        faults: FaultInjection {
+            rules: shared_options
+                .fault_urls
+                .iter()
+                .map(|pattern| FaultRule {
+                    pattern: pattern.clone(),
+                    rate: shared_options.fault_rate,
+                    kinds: vec![
+                        FaultKind::Delay,
+                        FaultKind::Drop,
+                        FaultKind::ServerError,
+                    ],
+                    delay_millis: DEFAULT_FAULT_DELAY.as_millis() as u64,
+                })
+                .collect(),
+            seed,
+        },
+        emulation: {
+            let viewport = default_viewport(shared_options);
+            Emulation {
+                width: viewport.width,
+                height: viewport.height,
+                device_scale_factor: viewport.device_scale_factor,
+                mobile: viewport.mobile,
+                touch: viewport.touch,
+                user_agent: viewport.user_agent,
+                reduce_motion: shared_options.reduce_motion,
+            }
+        },
+    }
+}
+
+/// The viewport emulated at the start of each run, of `--device` if any.
+fn default_viewport(shared_options: &TestSharedOptions) -> ViewportProfile {
+    let mut viewport =
+        shared_options
+            .device
+            .clone()
+            .unwrap_or_else(|| ViewportProfile {
+                name: "default".to_string(),
+                width: shared_options.width,
+                height: shared_options.height,
+                device_scale_factor: shared_options.device_scale_factor,
+                mobile: false,
+                touch: false,
+                user_agent: None,
+            });
+    if let Some(user_agent) = &shared_options.user_agent {
+        viewport.user_agent = Some(user_agent.clone());
+    }
+    viewport
+}
+
+/// Runs the sessions of a run concurrently, each with its own browser, seed and trace,
+/// sharing the coverage map. The first session gets the run's seed, so that a single
+/// session is a regular run. Each session has its own verifier too, as its properties are
+/// about its own sequence of states. When there are several sessions, or several runs
+/// (`runs`), a failing session doesn't stop the others, and is logged and recorded as a
+/// failed outcome.
+async fn test_sessions(
+    shared_options: &TestSharedOptions,
+    browser: &ManagedBrowserOptions,
+    seed: u64,
+    sessions: usize,
+    runs: usize,
+    control: Option<&Control>,
+) -> Result<Vec<RunOutcome>> {
+    let edge_map = EdgeMap::default();
+    let results =
+        future::join_all(session_seeds(seed, sessions).into_iter().map(
+            |session_seed| {
+                let edge_map = edge_map.clone();
+                async move {
+                    let started = SystemTime::now();
+                    let browser_options =
+                        browser_options(shared_options, session_seed, true);
+                    let run_id = browser_options.run_id.clone();
+                    let outcome = async {
+                        let user_data_directory =
+                            TempDir::with_prefix("user_data_")?;
+                        test(
+                            shared_options,
+                            session_seed,
+                            browser_options,
+                            browser.debugger_options(&user_data_directory),
+                            RunParameters {
+                                edge_map: Some(edge_map),
+                                run_seed: Some(seed),
+                                control: control.cloned(),
+                                ..RunParameters::default()
+                            },
+                        )
+                        .await
+                    }
+                    .await;
+                    (session_seed, run_id, started, outcome)
+                }
+            },
+        ))
+        .await;
+    if sessions <= 1 && runs <= 1 {
+        return results
+            .into_iter()
+            .map(|(_, _, _, outcome)| outcome)
+            .collect();
+    }
+
+    let outcomes: Vec<RunOutcome> = results
+        .into_iter()
+        .map(|(session_seed, run_id, started, outcome)| {
+            outcome.unwrap_or_else(|error| {
+                log::error!(
+                    "{} with seed {} failed: {:#}",
+                    if sessions > 1 { "session" } else { "run" },
+                    session_seed,
+                    error
+                );
+                RunOutcome::failed(session_seed, run_id, started)
+            })
+        })
+        .collect();
+    if sessions > 1 {
+        let covered: BTreeSet<EdgeIndex> = outcomes
+            .iter()
+            .flat_map(|outcome| outcome.covered_edges.iter().copied())
+            .collect();
+        log::info!(
+            "{} sessions with seed {} covered {} edges together",
+            sessions,
+            seed,
+            covered.len()
+        );
+    }
+    Ok(outcomes)
+}
+
+/// Seeds of the sessions of a run, starting with the run's own seed.
+fn session_seeds(seed: u64, sessions: usize) -> Vec<u64> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    std::iter::once(seed)
+        .chain(std::iter::repeat_with(|| rng.random()))
+        .take(sessions.max(1))
+        .collect()
+}
+
+/// How long to wait after a failed soak segment before starting the next one.
+const SOAK_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// Runs consecutive segments, each with its own seed and browser, until the duration is
+/// up, and writes the consolidated report of the segments.
+async fn soak(
+    shared_options: &TestSharedOptions,
+    browser: &ManagedBrowserOptions,
+    duration: Duration,
+    rotate_seeds: Duration,
+    output_path: &Path,
+    control: Option<&Control>,
+) -> Result<Vec<RunOutcome>> {
+    let deadline = Instant::now()
+        .checked_add(duration)
+        .ok_or_else(|| anyhow::anyhow!("soak duration is too long"))?;
+    let first_seed = shared_options.seed.unwrap_or_else(rand::random);
+    let mut outcomes = Vec::new();
+    let mut report = SoakReport::default();
+
+    for segment in 0.. {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        let seed = first_seed.wrapping_add(segment);
+        log::info!(
+            "starting soak segment {} with seed {}, {}s left",
+            segment + 1,
+            seed,
+            remaining.as_secs()
+        );
+        let started = SystemTime::now();
+        let browser_options = browser_options(shared_options, seed, true);
+        let run_id = browser_options.run_id.clone();
+        let result = async {
+            let user_data_directory = TempDir::with_prefix("user_data_")?;
+            test(
+                shared_options,
+                seed,
+                browser_options,
+                browser.debugger_options(&user_data_directory),
+                RunParameters {
+                    time_limit: Some(rotate_seeds.min(remaining)),
+                    control: control.cloned(),
+                    ..RunParameters::default()
+                },
+            )
+            .await
+        }
+        .await;
+        // A failed segment is reported, and the soak test goes on with the next one.
+        let (outcome, error) = match result {
+            Ok(outcome) => (outcome, None),
+            Err(error) => {
+                log::error!(
+                    "soak segment {} with seed {} failed: {:#}",
+                    segment + 1,
+                    seed,
+                    error
+                );
+                (
+                    RunOutcome::failed(seed, run_id, started),
+                    Some(format!("{:#}", error)),
+                )
+            }
+        };
+
+        report.add(
+            SoakSegment {
+                seed,
+                run_id: outcome.run_id.clone(),
+                started: outcome.started,
+                duration: outcome.duration,
+                steps: outcome.steps,
+                edges_covered: 0,
+                edges_new: 0,
+                violated_properties: outcome.violated_properties.clone(),
+                exit_code: outcome.exit_code,
+                error: error.clone(),
+            },
+            &outcome.covered_edges,
+        );
+        tokio::fs::write(
+            output_path.join("soak_report.json"),
+            serde_json::to_string_pretty(&report)?,
+        )
+        .await?;
+
+        let stop = (outcome.exit_code == Some(2)
+            && shared_options.exit_on_violation)
+            || control.is_some_and(Control::is_stopping);
+        outcomes.push(outcome);
+        if stop {
+            break;
+        }
+        // Failing right away again, e.g. as the browser can't start, shouldn't spin.
+        if error.is_some() {
+            tokio::time::sleep(
+                SOAK_RETRY_DELAY
+                    .min(deadline.saturating_duration_since(Instant::now())),
+            )
+            .await;
+        }
+    }
+
+    log::info!(
+        "soak test ran {} segments, covering {} edges, report written to {}",
+        report.segments.len(),
+        report.edges_total,
+        output_path.join("soak_report.json").display()
+    );
+    log_runs_summary(&outcomes);
+    Ok(outcomes)
+}
+
+/// Seeds for `runs` consecutive runs, derived from the given seed if any so that the whole
+/// batch is reproducible, or random otherwise.
+fn run_seeds(seed: Option<u64>, runs: usize) -> Vec<u64> {
+    match seed {
+        Some(seed) => (0..runs as u64).map(|i| seed.wrapping_add(i)).collect(),
+        None => (0..runs).map(|_| rand::random()).collect(),
+    }
+}
+
+/// Log the distinct properties violated across all runs, along with commands reproducing
+/// each run that violated them.
+fn log_runs_summary(outcomes: &[RunOutcome]) {
+    let mut violations: BTreeMap<&str, Vec<&RunOutcome>> = BTreeMap::new();
+    let mut suppressed: BTreeMap<&str, (usize, &Suppression)> = BTreeMap::new();
+    for outcome in outcomes {
+        for name in &outcome.violated_properties {
+            violations.entry(name).or_default().push(outcome);
+        }
+        for (name, suppression) in &outcome.suppressed_properties {
+            suppressed.entry(name).or_insert((0, suppression)).0 += 1;
+        }
+    }
+
+    for (name, (count, suppression)) in suppressed {
+        log::warn!(
+            "property `{}` was violated in {} of {} runs, suppressed until {}: {}",
+            name,
+            count,
+            outcomes.len(),
+            suppression.expires,
+            suppression.reason
+        );
+    }
+
+    if violations.is_empty() {
+        log::info!("no violations found in {} runs", outcomes.len());
+        return;
+    }
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    for (name, violating) in violations {
+        let commands: Vec<String> = violating
+            .iter()
+            .map(|outcome| {
+                format!(
+                    "  {} (run {})",
+                    Reproduction::new(&args, outcome.seed).command,
+                    outcome.run_id
+                )
+            })
+            .collect();
+        log::error!(
+            "property `{}` was violated in {} of {} runs, reproduce with:\n{}",
+            name,
+            violating.len(),
+            outcomes.len(),
+            commands.join("\n")
+        );
+    }
+}
+
+/// Write the machine-readable summary of the runs, exiting with `exit_code`.
+async fn write_json_summary(
+    path: &Path,
+    outcomes: Vec<RunOutcome>,
+    exit_code: i32,
+) -> Result<()> {
+    let mut summary = Summary::default();
+    for outcome in outcomes {
+        summary.add(
+            RunSummary {
+                seed: outcome.seed,
+                run_id: outcome.run_id,
+                started: outcome.started,
+                duration: outcome.duration,
+                steps: outcome.steps,
+                edges_covered: outcome.covered_edges.len(),
+                step_latency: outcome.step_latency,
+                verdicts: outcome.verdicts,
+                violations: outcome.violations,
+                exit_code: outcome.exit_code,
+            },
+            &outcome.covered_edges,
+        );
+    }
+    summary.exit_code = exit_code;
+    tokio::fs::write(path, serde_json::to_string_pretty(&summary)?).await?;
+    log::info!("summary written to {}", path.display());
+    Ok(())
+}
+
+/// Log how consistently each property was violated across the runs, and write the report
+/// to `flakiness_report.json` at the root of the output path, if any.
+async fn write_flakiness_report(
+    shared_options: &TestSharedOptions,
+    outcomes: &[RunOutcome],
+) -> Result<()> {
+    let mut report = FlakinessReport::default();
+    for outcome in outcomes {
+        report.add(outcome.seed, &outcome.first_violations);
+    }
+    for (name, property) in &report.properties {
+        log::info!(
+            "property `{}` is {}: violated in {} of {} runs, first at state {:.1} on average",
+            name,
+            match property.consistency {
+                Consistency::Deterministic => "deterministic",
+                Consistency::Intermittent => "intermittent",
+            },
+            property.violated_runs,
+            report.runs,
+            property.average_first_violation_step
+        );
+    }
+    if let Some(output_path) = &shared_options.output_path {
+        tokio::fs::create_dir_all(output_path).await?;
+        tokio::fs::write(
+            output_path.join("flakiness_report.json"),
+            serde_json::to_string_pretty(&report)?,
+        )
+        .await?;
+    }
+    Ok(())
 }
 
 async fn test(
-    shared_options: TestSharedOptions,
+    shared_options: &TestSharedOptions,
+    seed: u64,
     browser_options: BrowserOptions,
     debugger_options: DebuggerOptions,
-) -> Result<()> {
-    // Load a user-provided specification, or use the defaults provided by Bombadil.
-    let specification = if let Some(path) = &shared_options.specification_file {
-        log::info!("loading specification from file: {}", path.display());
-        Specification::from_path(path.as_path()).await?
+    parameters: RunParameters,
+) -> Result<RunOutcome> {
+    let RunParameters {
+        time_limit,
+        replay,
+        checkpoints,
+        edge_map,
+        run_seed,
+        control,
+    } = parameters;
+    let run_id = browser_options.run_id.clone();
+    log::info!("starting run {} with seed {}", run_id, seed);
+    if let Some(control) = &control {
+        control.run_started(&run_id, seed);
+    }
+
+    // Load the user-provided specifications, or use the defaults provided by Bombadil.
+    let paths: Vec<&PathBuf> = shared_options
+        .specification_file
+        .iter()
+        .chain(&shared_options.specification_files)
+        .collect();
+    let specification = if !paths.is_empty() {
+        let mut specifications = Vec::with_capacity(paths.len());
+        for path in paths {
+            log::info!("loading specification from file: {}", path.display());
+            specifications
+                .push(Specification::from_path(path.as_path()).await?);
+        }
+        Specification::merge(specifications)?
     } else {
         log::info!("using default specification");
         Specification::from_string(
@@ -170,64 +1223,531 @@ async fn test(
             PathBuf::from("default_spec.js").as_path(),
         )?
     };
+    let specification = match &shared_options.prelude {
+        Some(path) => {
+            log::info!("loading prelude from file: {}", path.display());
+            specification
+                .with_prelude(Specification::from_path(path.as_path()).await?)
+        }
+        None => specification,
+    };
 
-    let output_path = match shared_options.output_path {
-        Some(path) => path,
-        None => TempDir::with_prefix("states_")?.keep().to_path_buf(),
+    let mut isolated_specifications = Vec::new();
+    for path in &shared_options.isolated_specification_files {
+        log::info!(
+            "loading isolated specification from file: {}",
+            path.display()
+        );
+        isolated_specifications
+            .push(Specification::from_path(path.as_path()).await?);
+    }
+
+    let suppressions = match &shared_options.suppressions {
+        Some(path) => Suppressions::from_path(path).await?,
+        None => Suppressions::default(),
     };
 
+    // Each run gets its own directory, so that several runs can share an output path.
+    let output_path = match &shared_options.output_path {
+        Some(path) => path.clone(),
+        None => TempDir::with_prefix("states_")?.keep().to_path_buf(),
+    }
+    .join(run_id.as_str());
+    let screencast = shared_options
+        .record
+        .then(|| output_path.join("screencast"));
+
     let runner = Runner::new(
-        shared_options.origin.url,
+        shared_options.origin.url.clone(),
         specification,
         RunnerOptions {
             stop_on_violation: shared_options.exit_on_violation,
+            seed,
+            observe_only: shared_options.observe,
+            allow_payment_fields: shared_options.allow_payment_fields,
+            click_deny_list: ClickDenyList::new(
+                shared_options.deny_click.clone(),
+                shared_options.allow_click.clone(),
+            ),
+            adaptive_pacing: shared_options.adaptive_pacing,
+            remote_verifier: shared_options.remote_verifier.clone(),
+            isolated_specifications,
+            suppressions,
+            pause_on_violation: shared_options.pause_on_violation,
+            capture_accessibility_tree: shared_options.accessibility_snapshots,
+            capture_full_page: shared_options.violation_screenshots.is_some(),
+            capture_dom: shared_options.dom_snapshots,
+            isolated_extractors: shared_options.isolated_extractors,
+            viewport_sweep: viewport_sweep(shared_options),
+            replay,
+            edge_map,
+            novelty: (shared_options.prefer_new_states
+                || shared_options.escape_loops)
+                .then_some(NoveltyOptions {
+                    escape_loops: shared_options.escape_loops,
+                }),
+            exhaustive: shared_options.exhaustive.map(|order| {
+                ExhaustiveOptions {
+                    order,
+                    depth: shared_options.exhaustive_depth,
+                }
+            }),
+            checkpointing: shared_options.checkpoint_interval.map(|interval| {
+                Checkpointing {
+                    interval,
+                    capacity: shared_options.checkpoints,
+                }
+            }),
+            saved_checkpoints: checkpoints,
+            checkpoints_file: shared_options
+                .save_checkpoints
+                .then(|| output_path.join(CHECKPOINTS_FILE_NAME)),
+            coverage_source: shared_options.coverage_source,
+            context: load_context(
+                shared_options.context_file.as_deref(),
+                &shared_options.context,
+            )
+            .await?,
+            initial_navigation: InitialNavigation {
+                timeout: Duration::from_secs(shared_options.navigation_timeout),
+                retries: shared_options.navigation_retries,
+                health_check: shared_options.health_check.clone(),
+                health_check_timeout: Duration::from_secs(
+                    shared_options.health_check_timeout,
+                ),
+            },
+            max_steps: shared_options.max_steps,
+            max_duration: shared_options.max_duration,
+            coverage_plateau: shared_options.coverage_plateau,
+            edge_decay: shared_options.edge_decay,
+            policy: shared_options.policy_url.clone().map(|url| {
+                PolicyOptions {
+                    url,
+                    timeout: Duration::from_millis(
+                        shared_options.policy_timeout,
+                    ),
+                }
+            }),
+            ..RunnerOptions::default()
+        },
+        BrowserOptions {
+            screencast: screencast.clone(),
+            ..browser_options
         },
-        browser_options,
         debugger_options,
     )
     .await?;
+    let caching_diagnostics = runner.caching_diagnostics();
+    let script_sizes = runner.script_sizes();
+    let gpu_info = runner.gpu_info();
     let mut events = runner.start();
-    let mut writer = TraceWriter::initialize(output_path).await?;
-
-    let exit_code: anyhow::Result<Option<i32>> = async {
-        loop {
-            match events.next().await {
-                Ok(Some(bombadil::runner::RunEvent::NewState {
-                    state,
-                    last_action,
-                    violations,
-                })) => {
-                    let has_violations = !violations.is_empty();
-
-                    for violation in &violations {
-                        log::error!(
-                            "violation of property `{}`:\n{}",
-                            violation.name,
-                            render_violation(&violation.violation)
-                        );
+    let mut writer = TraceWriter::initialize(output_path)
+        .await?
+        .with_violation_screenshots(
+            shared_options.violation_screenshots.unwrap_or(0),
+        );
+    let reproduction = Reproduction::new(
+        &std::env::args().skip(1).collect::<Vec<_>>(),
+        run_seed.unwrap_or(seed),
+    );
+    let mut violated_properties = BTreeSet::new();
+    let mut first_violations = BTreeMap::new();
+    let mut suppressed_properties = BTreeMap::new();
+    let mut violation_summaries = Vec::new();
+    let mut timings_total = StepTimings::default();
+    let mut steps: u32 = 0;
+    let mut covered_edges = BTreeSet::new();
+    let started_at = SystemTime::now();
+    let started = Instant::now();
+    let deadline = time_limit.map(|limit| tokio::time::Instant::now() + limit);
+
+    // The writer is finished however the run ends, so that the screenshots it writes in
+    // the background are all written, or their errors reported.
+    let written = async {
+        if let Some(gpu_info) = gpu_info {
+            writer.write_gpu_info(&gpu_info).await?;
+        }
+        writer.write_reproduction(&reproduction).await?;
+
+        let exit_code: anyhow::Result<Option<i32>> = async {
+            loop {
+                let time_limit_reached = async {
+                    match deadline {
+                        Some(deadline) => {
+                            tokio::time::sleep_until(deadline).await
+                        }
+                        None => future::pending().await,
+                    }
+                };
+                let stop_requested = async {
+                    match &control {
+                        Some(control) => control.stopped().await,
+                        None => future::pending().await,
                     }
+                };
+                let event = tokio::select! {
+                    event = events.next() => event,
+                    _ = time_limit_reached => {
+                        log::info!("time limit reached, stopping the run");
+                        break Ok(None);
+                    }
+                    _ = stop_requested => {
+                        log::info!("stop requested through the control API, stopping the run");
+                        break Ok(None);
+                    }
+                };
+                match event {
+                    Ok(Some(bombadil::runner::RunEvent::NewState {
+                        state,
+                        last_action,
+                        violations,
+                        transaction_failures,
+                        timings,
+                        accessibility_tree,
+                        metrics,
+                        full_page_screenshot,
+                        dom_snapshot,
+                        ..
+                    })) => {
+                        let has_violations = violations
+                            .iter()
+                            .any(|violation| violation.suppressed.is_none());
+                        timings_total += timings;
+                        steps += 1;
+                        covered_edges.extend(
+                            state
+                                .coverage
+                                .edges_new
+                                .iter()
+                                .map(|(index, _)| *index),
+                        );
 
-                    writer.write(last_action, state, violations).await?;
+                        for failure in &transaction_failures {
+                            log::error!(
+                                "transaction `{}` failed, its postcondition didn't hold (before: {}, after: {})",
+                                failure.name,
+                                failure.before,
+                                failure.after
+                            );
+                        }
 
-                    if has_violations && shared_options.exit_on_violation {
-                        break Ok(Some(2));
+                        for violation in &violations {
+                            violation_summaries.push(ViolationSummary::new(
+                                violation,
+                                steps - 1,
+                                Some(state.url.to_string()),
+                            ));
+                            if let Some(suppression) = &violation.suppressed {
+                                log::warn!(
+                                    "suppressed violation of property `{}` (fingerprint {}): {}",
+                                    violation.name,
+                                    violation.fingerprint,
+                                    suppression.reason
+                                );
+                                suppressed_properties.insert(
+                                    violation.name.clone(),
+                                    suppression.clone(),
+                                );
+                                continue;
+                            }
+                            log::error!(
+                                "violation of property `{}` (fingerprint {}):\n{}",
+                                violation.name,
+                                violation.fingerprint,
+                                render_violation(&violation.violation)
+                            );
+                            if first_violations.is_empty() {
+                                log_reproduction(&reproduction);
+                            }
+                            violated_properties.insert(violation.name.clone());
+                            first_violations
+                                .entry(violation.name.clone())
+                                .or_insert(steps - 1);
+                        }
+
+                        if let Some(control) = &control {
+                            control.state_checked(
+                                &run_id,
+                                state.url.as_str(),
+                                &violations,
+                                &metrics,
+                            );
+                        }
+
+                        let artifacts = writer
+                            .write(
+                                last_action,
+                                state,
+                                violations,
+                                transaction_failures,
+                                StateCaptures {
+                                    accessibility_tree,
+                                    full_page_screenshot,
+                                    dom_snapshot,
+                                },
+                                metrics,
+                            )
+                            .await?;
+                        if let Some(path) = artifacts {
+                            log::info!(
+                                "screenshots leading to the violation written to {}",
+                                path.display()
+                            );
+                        }
+
+                        if has_violations && shared_options.pause_on_violation {
+                            wait_for_enter(seed).await?;
+                            break Ok(Some(2));
+                        }
+                        if has_violations && shared_options.exit_on_violation {
+                            break Ok(Some(2));
+                        }
+                    }
+                    Ok(Some(bombadil::runner::RunEvent::NoActions(
+                        diagnostics,
+                    ))) => {
+                        writer.write_no_actions(&diagnostics).await?;
+                    }
+                    Ok(None) => break Ok(None),
+                    Err(err) => {
+                        eprintln!("next run event failure: {}", err);
+                        break Ok(Some(1));
                     }
-                }
-                Ok(None) => break Ok(None),
-                Err(err) => {
-                    eprintln!("next run event failure: {}", err);
-                    break Ok(Some(1));
                 }
             }
         }
+        .await;
+
+        let end_of_run = events.shutdown().await?;
+        for warning in &end_of_run.warnings {
+            log::warn!(
+                "property `{}` was undecided at the end of the run (fingerprint {}):\n{}",
+                warning.name,
+                warning.fingerprint,
+                render_violation(&warning.violation)
+            );
+        }
+        let mut end_violations = false;
+        for violation in &end_of_run.violations {
+            violation_summaries
+                .push(ViolationSummary::new(violation, steps, None));
+            if let Some(suppression) = &violation.suppressed {
+                log::warn!(
+                    "suppressed violation of property `{}` at the end of the run (fingerprint {}): {}",
+                    violation.name,
+                    violation.fingerprint,
+                    suppression.reason
+                );
+                suppressed_properties
+                    .insert(violation.name.clone(), suppression.clone());
+                continue;
+            }
+            log::error!(
+                "violation of property `{}` at the end of the run (fingerprint {}):\n{}",
+                violation.name,
+                violation.fingerprint,
+                render_violation(&violation.violation)
+            );
+            violated_properties.insert(violation.name.clone());
+            first_violations
+                .entry(violation.name.clone())
+                .or_insert(steps);
+            end_violations = true;
+        }
+        let exit_code = exit_code.map(|exit_code| match exit_code {
+            None if end_violations && shared_options.exit_on_violation => {
+                Some(2)
+            }
+            exit_code => exit_code,
+        });
+
+        log_step_latency(&timings_total, steps, started.elapsed());
+        log_reproduction(&reproduction);
+
+        let caching_report = caching_diagnostics.report();
+        if !caching_report.is_empty() {
+            log::info!(
+                "{} resources had their caching semantics altered by instrumentation",
+                caching_report.len()
+            );
+        }
+        writer.write_caching_report(&caching_report).await?;
+        let script_sizes = script_sizes.report();
+        if script_sizes.total_bytes > 0 {
+            log::info!(
+                "{} bytes of JavaScript loaded from {} origins",
+                script_sizes.total_bytes,
+                script_sizes.by_origin.len()
+            );
+        }
+        writer.write_script_sizes(&script_sizes).await?;
+        log_violation_clusters(writer.write_violation_clusters().await?);
+        log_error_signatures(writer.write_error_signatures().await?);
+        writer.write_coverage(&covered_edges).await?;
+        if let Some(graph_out) = &shared_options.graph_out {
+            let path = writer.write_graph(graph_out, run_id.as_str()).await?;
+            log::info!("wrote the graph of explored states to {}", path.display());
+        }
+        Ok::<_, anyhow::Error>((end_of_run, exit_code))
     }
     .await;
+    let finished = writer.finish().await;
+    let (end_of_run, exit_code) = match (written, finished) {
+        (Ok(written), finished) => {
+            finished?;
+            written
+        }
+        (Err(error), finished) => {
+            if let Err(finish_error) = finished {
+                log::error!("failed finishing the trace: {:#}", finish_error);
+            }
+            return Err(error);
+        }
+    };
+    if let Some(screencast) = &screencast {
+        log::info!(
+            "screencast of the run written to {}",
+            screencast.join(screencast::CONCAT_FILE_NAME).display()
+        );
+    }
 
-    events.shutdown().await?;
-
-    if let Some(exit_code) = exit_code? {
-        std::process::exit(exit_code);
+    let exit_code = exit_code?;
+    if let Some(control) = &control {
+        control.run_finished(&run_id, exit_code);
     }
+    Ok(RunOutcome {
+        seed,
+        run_id,
+        started: started_at,
+        duration: started.elapsed(),
+        steps,
+        covered_edges,
+        violated_properties,
+        first_violations,
+        suppressed_properties,
+        verdicts: end_of_run.verdicts,
+        violations: violation_summaries,
+        step_latency: StepLatency::average(&timings_total, steps),
+        exit_code,
+    })
+}
 
+/// Serves the control API when asked to, and waits for a client to start the test.
+async fn start_control(
+    shared_options: &TestSharedOptions,
+) -> Result<Option<Control>> {
+    let Some(port) = shared_options.serve_control else {
+        return Ok(None);
+    };
+    let control = serve_control(port).await?;
+    control.started().await;
+    Ok(Some(control))
+}
+
+fn log_reproduction(reproduction: &Reproduction) {
+    log::info!(
+        "reproduce this run (bombadil {}) with:\n  {}",
+        reproduction.version,
+        reproduction.command
+    );
+}
+
+async fn wait_for_enter(seed: u64) -> Result<()> {
+    log::warn!(
+        "paused at the violating state, with the browser kept open for debugging (reproduce with --seed {}), press Enter to terminate",
+        seed
+    );
+    let mut line = String::new();
+    BufReader::new(tokio::io::stdin())
+        .read_line(&mut line)
+        .await?;
     Ok(())
 }
+
+fn log_violation_clusters(clusters: &[ViolationCluster]) {
+    if clusters.is_empty() {
+        return;
+    }
+    log::info!(
+        "{} violations in {} distinct clusters:",
+        clusters.iter().map(|cluster| cluster.count).sum::<usize>(),
+        clusters.len()
+    );
+    for cluster in clusters {
+        log::info!(
+            "  `{}` on {} ({} times, first in {})",
+            cluster.property,
+            cluster.page,
+            cluster.count,
+            cluster.screenshot.display()
+        );
+    }
+}
+
+/// The viewport profiles to cycle through, starting with the default viewport, if any
+/// were given.
+fn viewport_sweep(shared_options: &TestSharedOptions) -> Option<ViewportSweep> {
+    if shared_options.viewport_profiles.is_empty() {
+        return None;
+    }
+    Some(ViewportSweep {
+        profiles: std::iter::once(default_viewport(shared_options))
+            .chain(shared_options.viewport_profiles.iter().cloned())
+            .collect(),
+        interval: shared_options.viewport_interval,
+    })
+}
+
+/// Error signatures logged in the summary, the rest only being in the JSON file.
+const ERROR_SIGNATURES_LOGGED_MAX: usize = 10;
+
+fn log_error_signatures(signatures: &[ErrorSignature]) {
+    if signatures.is_empty() {
+        return;
+    }
+    log::info!(
+        "{} console errors and uncaught exceptions with {} distinct signatures:",
+        signatures
+            .iter()
+            .map(|signature| signature.count)
+            .sum::<usize>(),
+        signatures.len()
+    );
+    let mut by_count: Vec<&ErrorSignature> = signatures.iter().collect();
+    by_count.sort_by_key(|signature| std::cmp::Reverse(signature.count));
+    for signature in by_count.iter().take(ERROR_SIGNATURES_LOGGED_MAX) {
+        log::info!(
+            "  {:>5}  {}{}",
+            signature.count,
+            signature.template,
+            signature
+                .top_frame
+                .as_ref()
+                .map(|frame| format!(" at {}", frame))
+                .unwrap_or_default()
+        );
+    }
+    if signatures.len() > ERROR_SIGNATURES_LOGGED_MAX {
+        log::info!(
+            "  ... and {} more",
+            signatures.len() - ERROR_SIGNATURES_LOGGED_MAX
+        );
+    }
+}
+
+fn log_step_latency(total: &StepTimings, steps: u32, elapsed: Duration) {
+    if steps == 0 {
+        return;
+    }
+    let average = |duration: Duration| (duration / steps).as_millis();
+    log::info!(
+        "{} steps ({:.1} per minute), average latency: {}ms screenshot, {}ms pause, {}ms state evaluation, {}ms extractors, {}ms verifier",
+        steps,
+        steps as f64 / elapsed.as_secs_f64().max(f64::EPSILON) * 60.0,
+        average(total.capture.screenshot),
+        average(total.capture.pause),
+        average(total.capture.evaluation),
+        average(total.extractors),
+        average(total.verifier),
+    );
+}
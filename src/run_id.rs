@@ -0,0 +1,80 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+/// Distinguishes runs created within the same process and millisecond.
+static RUN_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Identifies a single test run, used to namespace everything a run writes to disk (artifact
+/// directories, trace files, debug script dumps) so that concurrent runs on the same machine
+/// never collide.
+///
+/// The format is `<unix millis>-<seed>-<short hash>`, which sorts chronologically.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct RunId(String);
+
+impl RunId {
+    pub fn new(seed: u64) -> Self {
+        Self::from_parts(
+            SystemTime::now(),
+            seed,
+            std::process::id(),
+            RUN_COUNTER.fetch_add(1, Ordering::Relaxed),
+        )
+    }
+
+    fn from_parts(
+        timestamp: SystemTime,
+        seed: u64,
+        process_id: u32,
+        counter: u64,
+    ) -> Self {
+        let since_epoch =
+            timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut hasher = DefaultHasher::new();
+        (since_epoch.as_nanos(), seed, process_id, counter).hash(&mut hasher);
+        RunId(format!(
+            "{}-{:016x}-{:08x}",
+            since_epoch.as_millis(),
+            seed,
+            hasher.finish() as u32
+        ))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for RunId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_run_id_format() {
+        let timestamp = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+        let run_id = RunId::from_parts(timestamp, 42, 1, 0);
+        let parts: Vec<&str> = run_id.as_str().split('-').collect();
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0], "1700000000123");
+        assert_eq!(parts[1], "000000000000002a");
+        assert_eq!(parts[2].len(), 8);
+    }
+
+    #[test]
+    fn test_run_id_unique_with_same_seed() {
+        let first = RunId::new(42);
+        let second = RunId::new(42);
+        assert_ne!(first, second);
+    }
+}
@@ -1,44 +1,209 @@
+use crate::browser::accessibility::AccessibilityNode;
 use crate::browser::actions::BrowserAction;
-use crate::browser::{BrowserEvent, BrowserOptions};
-use crate::instrumentation::js::EDGE_MAP_SIZE;
-use crate::specification::verifier::Specification;
-use crate::specification::worker::{PropertyValue, VerifierWorker};
-use crate::trace::PropertyViolation;
+use crate::browser::checkpoints::{
+    Checkpoint, CheckpointStore, Checkpointing, SavedCheckpoint,
+};
+use crate::browser::click_deny_list::{ClickDenyList, DeniedClicks};
+use crate::browser::coverage::CoverageSource;
+use crate::browser::evaluation::IsolatedWorld;
+use crate::browser::gpu::GpuInfo;
+use crate::browser::instrumentation::{
+    CachingDiagnostics, ScriptSizeTotals, ScriptSizes,
+};
+use crate::browser::navigation::InitialNavigationError;
+use crate::browser::payment_guard::PaymentGuard;
+use crate::browser::viewports::{ViewportProfile, ViewportSweep};
+use crate::browser::{BrowserBackend, BrowserEvent, BrowserOptions};
+use crate::runner::diagnostics::{
+    ExcludedAction, Exclusion, NoActionsDiagnostics,
+};
+use crate::runner::edges::EdgeMap;
+use crate::runner::exhaustive::ExhaustiveOptions;
+use crate::runner::novelty::NoveltyOptions;
+use crate::runner::pacing::{Pacing, PacingDecision};
+use crate::runner::policy::{ExternalPolicy, PolicyOptions, StateSummary};
+use crate::runner::step::{
+    Exploration, NextAction, StepInputs, StopReason, advance, allowed_at,
+};
+use crate::runner::transactions::{PlannedAction, TransactionInProgress};
+use crate::specification::isolated::IsolatedVerifiers;
+use crate::specification::remote::RemoteVerifier;
+use crate::specification::stop::EndPolicy;
+use crate::specification::verifier::{ExtractorFunction, Specification};
+use crate::specification::worker::{
+    PropertyValue, VerifierBackend, VerifierWorker,
+};
+use crate::suppressions::{Suppressions, fingerprint};
+use crate::trace::{PropertyViolation, TransactionFailure};
 use ::url::Url;
+use serde::{Serialize, de::DeserializeOwned};
 use serde_json as json;
-use std::cmp::max;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::io::AsyncWriteExt;
 use tokio::sync::{broadcast, oneshot};
 use tokio::{select, spawn};
 
-use crate::browser::state::{BrowserState, Coverage};
-use crate::browser::{Browser, DebuggerOptions};
+use crate::browser::state::{
+    BrowserState, CaptureTimings, Coverage, LogLimits, Screenshot,
+};
+use crate::browser::{Browser, DebuggerOptions, InitialNavigation};
 use crate::url::is_within_domain;
 
+pub mod context;
+pub mod diagnostics;
+pub mod edges;
+pub mod exhaustive;
+pub mod novelty;
+pub mod pacing;
+pub mod policy;
+pub mod step;
+pub mod transactions;
+
+#[derive(Clone, Debug, Default)]
 pub struct RunnerOptions {
     pub stop_on_violation: bool,
+    /// Seed for the random choice of actions.
+    pub seed: u64,
+    /// Only observe the browser (e.g. driven manually by a human) without applying any
+    /// actions, still checking properties on every new state.
+    pub observe_only: bool,
+    /// Allow typing into payment fields (e.g. credit card numbers) and submitting forms
+    /// containing them, which is refused by default.
+    pub allow_payment_fields: bool,
+    /// Texts of elements not to click, e.g. logout buttons.
+    pub click_deny_list: ClickDenyList,
+    /// Per-step caps on console entries and exceptions exposed to extractors.
+    pub log_limits: LogLimits,
+    /// Adapt the pace of actions to the page activity, instead of using fixed timeouts.
+    pub adaptive_pacing: bool,
+    /// Address (`host:port`) of a `bombadil verifier-server` evaluating the specification,
+    /// instead of evaluating it in this process.
+    pub remote_verifier: Option<String>,
+    /// Specifications checked against the run in verifiers of their own, alongside the
+    /// main one generating the actions.
+    pub isolated_specifications: Vec<Specification>,
+    /// Known violations that don't fail the test.
+    pub suppressions: Suppressions,
+    /// Timeout, retries and health check of the navigation to the origin.
+    pub initial_navigation: InitialNavigation,
+    /// Where the coverage guiding the exploration comes from.
+    pub coverage_source: CoverageSource,
+    /// Stop exploring at the first violation, keeping the browser at the violating state
+    /// until shut down.
+    pub pause_on_violation: bool,
+    /// Capture the accessibility tree of states with violations.
+    pub capture_accessibility_tree: bool,
+    /// Evaluate extractors in an isolated world, where the page's scripts can't redefine
+    /// the globals they use, instead of the page's main world.
+    pub isolated_extractors: bool,
+    /// Viewport profiles to cycle through during the run.
+    pub viewport_sweep: Option<ViewportSweep>,
+    /// Actions to apply in order instead of picking them, e.g. those of a recorded trace.
+    /// The run stops once they've all been applied.
+    pub replay: Option<Vec<BrowserAction>>,
+    /// Save checkpoints during the run, and branch off from them.
+    pub checkpointing: Option<Checkpointing>,
+    /// Checkpoints saved by an earlier run, restored by the actions being replayed.
+    pub saved_checkpoints: Vec<SavedCheckpoint>,
+    /// Append the checkpoints saved, cookies and storage included, to this file, so that
+    /// replaying the run restores them.
+    pub checkpoints_file: Option<PathBuf>,
+    /// Coverage map shared with the other sessions of the run, which the edges covered are
+    /// merged into.
+    pub edge_map: Option<EdgeMap>,
+    /// Explore all action sequences up to a depth, instead of picking actions at random.
+    pub exhaustive: Option<ExhaustiveOptions>,
+    /// Steer the exploration away from the states already visited.
+    pub novelty: Option<NoveltyOptions>,
+    /// Values exposed to extractors as `state.context`, e.g. the expected tenant name.
+    pub context: json::Map<String, json::Value>,
+    /// Stop the run after this many states.
+    pub max_steps: Option<u64>,
+    /// Stop the run after this long, counting from when the origin has loaded.
+    pub max_duration: Option<Duration>,
+    /// Stop the run after this many states in a row without new coverage.
+    pub coverage_plateau: Option<u64>,
+    /// Capture a screenshot of the whole page for every state with a violation.
+    pub capture_full_page: bool,
+    /// Halve the buckets of the edge map every this many states, so that late novelty is
+    /// still noticed in long runs.
+    pub edge_decay: Option<u64>,
+    /// Ask an external policy over HTTP for the actions otherwise picked at random.
+    pub policy: Option<PolicyOptions>,
+    /// Capture the serialized DOM of every state.
+    pub capture_dom: bool,
 }
 
+/// How long to wait for a full-page screenshot, which renders the page beyond the viewport.
+const FULL_PAGE_SCREENSHOT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait for the page to change, when only observing, before capturing a new
+/// state regardless.
+const OBSERVATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for the page to react to a viewport switch, e.g. with a responsive
+/// layout, before capturing the state in the new viewport regardless.
+const VIEWPORT_SWITCH_TIMEOUT: Duration = Duration::from_secs(1);
+
 #[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum RunEvent {
     NewState {
         state: BrowserState,
         last_action: Option<BrowserAction>,
         violations: Vec<PropertyViolation>,
+        transaction_failures: Vec<TransactionFailure>,
+        /// The pace picked for the next action, when pacing is adaptive.
+        pacing: Option<PacingDecision>,
+        timings: StepTimings,
+        /// The accessibility tree of the state, if it has violations and capturing it is
+        /// enabled.
+        accessibility_tree: Option<AccessibilityNode>,
+        /// The metrics emitted by the specification in this state, in emission order.
+        metrics: Vec<(String, f64)>,
+        /// A screenshot of the whole page, if the state has violations and capturing it is
+        /// enabled.
+        full_page_screenshot: Option<Screenshot>,
+        /// The serialized DOM of the state, if capturing it is enabled.
+        dom_snapshot: Option<String>,
     },
+    /// The specification had no action for the last state, stopping the run.
+    NoActions(NoActionsDiagnostics),
+}
+
+/// How long each stage of a step took, from capturing the state to picking the next action.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StepTimings {
+    pub capture: CaptureTimings,
+    /// Running the extractors of the specification in the page.
+    pub extractors: Duration,
+    /// Stepping the properties and action generators.
+    pub verifier: Duration,
+}
+
+impl std::ops::AddAssign for StepTimings {
+    fn add_assign(&mut self, other: Self) {
+        self.capture.screenshot += other.capture.screenshot;
+        self.capture.pause += other.capture.pause;
+        self.capture.evaluation += other.capture.evaluation;
+        self.extractors += other.extractors;
+        self.verifier += other.verifier;
+    }
 }
 
 pub struct Runner {
     origin: Url,
     options: RunnerOptions,
-    browser: Browser,
-    verifier: Arc<VerifierWorker>,
+    browser: Box<dyn BrowserBackend>,
+    verifier: Arc<dyn VerifierBackend>,
     events: broadcast::Sender<RunEvent>,
     shutdown_sender: oneshot::Sender<()>,
     shutdown_receiver: oneshot::Receiver<()>,
-    done_sender: oneshot::Sender<anyhow::Result<()>>,
-    done_receiver: oneshot::Receiver<anyhow::Result<()>>,
+    done_sender: oneshot::Sender<anyhow::Result<EndOfRun>>,
+    done_receiver: oneshot::Receiver<anyhow::Result<EndOfRun>>,
 }
 
 impl Runner {
@@ -49,17 +214,44 @@ impl Runner {
         browser_options: BrowserOptions,
         debugger_options: DebuggerOptions,
     ) -> anyhow::Result<Self> {
+        let verifier = start_verifier(specification, &options).await?;
+        let browser = Browser::new(
+            origin,
+            browser_options,
+            debugger_options,
+            options.log_limits,
+            options.coverage_source,
+        )
+        .await?;
+        Ok(Runner::with_backends(
+            browser.origin().clone(),
+            options,
+            Box::new(browser),
+            verifier,
+        ))
+    }
+
+    /// A runner driving another browser than Chrome, e.g. a `MockBrowser` in tests.
+    pub async fn with_browser(
+        origin: Url,
+        specification: Specification,
+        options: RunnerOptions,
+        browser: Box<dyn BrowserBackend>,
+    ) -> anyhow::Result<Self> {
+        let verifier = start_verifier(specification, &options).await?;
+        Ok(Runner::with_backends(origin, options, browser, verifier))
+    }
+
+    pub fn with_backends(
+        origin: Url,
+        options: RunnerOptions,
+        browser: Box<dyn BrowserBackend>,
+        verifier: Arc<dyn VerifierBackend>,
+    ) -> Self {
         let (events, _) = broadcast::channel(16);
         let (done_sender, done_receiver) = oneshot::channel();
         let (shutdown_sender, shutdown_receiver) = oneshot::channel();
-
-        let verifier = VerifierWorker::start(specification).await?;
-
-        let browser =
-            Browser::new(origin.clone(), browser_options, debugger_options)
-                .await?;
-
-        Ok(Runner {
+        Runner {
             origin,
             options,
             browser,
@@ -69,7 +261,19 @@ impl Runner {
             shutdown_receiver,
             done_sender,
             done_receiver,
-        })
+        }
+    }
+
+    pub fn caching_diagnostics(&self) -> CachingDiagnostics {
+        self.browser.caching_diagnostics()
+    }
+
+    pub fn script_sizes(&self) -> ScriptSizes {
+        self.browser.script_sizes()
+    }
+
+    pub fn gpu_info(&self) -> Option<GpuInfo> {
+        self.browser.gpu_info()
     }
 
     pub fn start(self) -> RunEvents {
@@ -90,17 +294,22 @@ impl Runner {
 
         spawn(async move {
             let run = async || {
-                browser.initiate().await?;
+                browser.initiate(&options.initial_navigation).await?;
                 log::debug!("browser initiated");
+                let suppressions = options.suppressions.clone();
+                let mut verdicts = BTreeMap::new();
                 Runner::run_test(
                     &origin,
                     options,
-                    &mut browser,
-                    verifier,
+                    browser.as_mut(),
+                    verifier.clone(),
                     events,
                     shutdown_receiver,
+                    &mut verdicts,
                 )
-                .await
+                .await?;
+                EndOfRun::collect(verifier.as_ref(), &suppressions, verdicts)
+                    .await
             };
             let result = run().await;
             log::debug!("test finished");
@@ -125,92 +334,394 @@ impl Runner {
     async fn run_test(
         origin: &Url,
         options: RunnerOptions,
-        browser: &mut Browser,
-        verifier: Arc<VerifierWorker>,
+        browser: &mut dyn BrowserBackend,
+        verifier: Arc<dyn VerifierBackend>,
         events: broadcast::Sender<RunEvent>,
         mut shutdown: oneshot::Receiver<()>,
+        verdicts: &mut BTreeMap<String, PropertyVerdict>,
     ) -> anyhow::Result<()> {
         let mut last_action: Option<BrowserAction> = None;
-        let mut edges = [0u8; EDGE_MAP_SIZE];
+        let mut exploration = Exploration::new(&options);
+        let mut denied_click_labels: HashSet<String> = HashSet::new();
+        let mut step_index: u64 = 0;
+        let mut isolated_world: Option<IsolatedWorld> = None;
+        let mut first_timestamp: Option<SystemTime> = None;
+        let edges = options.edge_map.clone().unwrap_or_default();
+        let mut pacing = Pacing::default();
+        // The profile switched to by the last `SetViewport` action, until the sweep switches.
+        let mut viewport_set: Option<ViewportProfile> = None;
+
+        let script_sizes = browser.script_sizes();
+        let gpu_info = browser.gpu_info();
+        let mut network = NetworkStatus::default();
 
         let extractors = verifier.extractors().await?;
+        // Looking up the elements with listeners is costly, so only done for
+        // specifications extracting them.
+        let extract_listeners = extractors
+            .iter()
+            .any(|extractor| extractor.source.contains("listeners"));
+        // Recorded traces already start with the setup.
+        if options.replay.is_none() {
+            exploration.setup = verifier
+                .setup::<crate::specification::js::JsAction>()
+                .await?
+                .into_iter()
+                .map(|action| action.to_browser_action())
+                .collect::<anyhow::Result<_>>()?;
+        }
+        // The values extracted last, kept by extractors skipping states.
+        let mut snapshots_last = HashMap::new();
+        let deadline = options
+            .max_duration
+            .map(|duration| tokio::time::Instant::now() + duration);
+        let mut steps_without_new_coverage: u64 = 0;
+        let policy = options
+            .policy
+            .as_ref()
+            .map(ExternalPolicy::new)
+            .transpose()?;
 
         loop {
             let verifier = verifier.clone();
+            let max_duration_reached = async {
+                match deadline {
+                    Some(deadline) => tokio::time::sleep_until(deadline).await,
+                    None => std::future::pending().await,
+                }
+            };
             select! {
                 _ = &mut shutdown => {
                     return Ok(())
                 },
+                _ = max_duration_reached => {
+                    log::info!("maximum duration reached, stopping");
+                    return Ok(())
+                },
                 event = browser.next_event() => match event {
                     Some(event) => match event {
-                        BrowserEvent::StateChanged(state) => {
+                        BrowserEvent::StateChanged(mut state) => {
+                            if extract_listeners {
+                                state.listeners = state.listener_targets().await.unwrap_or_else(|error| {
+                                    log::warn!("failed getting event listeners: {:?}", error);
+                                    vec![]
+                                });
+                            }
                             // Step formulas and collect violations.
-                            let snapshots = run_extractors(&state, &extractors, &last_action).await?;
+                            let first_timestamp = *first_timestamp.get_or_insert(state.timestamp);
+                            let step = Step {
+                                index: step_index,
+                                elapsed: state.timestamp.duration_since(first_timestamp).unwrap_or_default(),
+                            };
+                            step_index += 1;
+                            let viewport = viewport_set.as_ref().or_else(|| options.viewport_sweep.as_ref().and_then(|sweep| sweep.profile_at(step.index)));
+                            let mut state_partial = state_partial(&state, &last_action, step, viewport, &script_sizes.totals(), gpu_info.as_ref(), network)?;
+                            state_partial["context"] = json::Value::Object(options.context.clone());
+                            let started = Instant::now();
+                            if options.isolated_extractors {
+                                isolated_world = Some(state.isolated_world(isolated_world.as_ref()).await?);
+                            }
+                            let snapshots = run_extractors(&state, &extractors, step.index, &mut snapshots_last, &state_partial, isolated_world.as_ref()).await?;
                             for (id, value) in &snapshots {
                                 log::debug!("snapshot {id}: {value}");
                             }
+                            let extractors_duration = started.elapsed();
+                            let started = Instant::now();
                             let step_result = verifier.step::<crate::specification::js::JsAction>(snapshots, state.timestamp).await?;
+                            let timings = StepTimings {
+                                capture: state.timings,
+                                extractors: extractors_duration,
+                                verifier: started.elapsed(),
+                            };
+                            log::debug!("step timings: {:?}", timings);
+                            if let Some(action) = &last_action && state.dom_mutations == 0 {
+                                log::debug!("no DOM mutations after {:?}", action);
+                            }
 
-                            // Convert JsAction tree to BrowserAction tree
-                            let action_tree = step_result.actions.try_map(&mut |js_action| {
-                                js_action.to_browser_action()
-                            })?;
+                            // Convert JsAction tree to PlannedAction tree
+                            let action_tree = step_result.actions.try_map(&mut PlannedAction::from_js)?;
 
-                            let mut violations = Vec::with_capacity(step_result.properties.len());
-                            let mut all_properties_definite = true;
-                            for (name, value) in step_result.properties {
-                                match value {
-                                    PropertyValue::False(violation) => {
-                                        violations.push(PropertyViolation{ name, violation });
-                                    }
-                                    PropertyValue::Residual => {
-                                        all_properties_definite = false;
-                                    }
-                                    PropertyValue::True => {
-                                        // Property is satisfied
+                            // Check the postcondition of a transaction whose steps have all been applied.
+                            let mut transaction_failures = vec![];
+                            if let Some(completed) = exploration.transaction.take_if(|transaction| transaction.is_complete())
+                                && let Some(failure) = completed.check(&state, &state_partial, isolated_world.as_ref()).await?
+                            {
+                                log::warn!("transaction `{}` failed", failure.name);
+                                transaction_failures.push(failure);
+                            }
+
+                            // Make sure we stay within origin.
+                            let within_origin = is_within_domain(&state.url, origin);
+                            let candidates: Vec<&BrowserAction> = action_tree.leaves().into_iter().filter(|action| allowed_at(action, within_origin)).map(PlannedAction::first).collect();
+
+                            // Refuse actions touching payment fields, unless allowed.
+                            let guard = if options.allow_payment_fields || options.observe_only {
+                                None
+                            } else {
+                                let mut candidates = candidates.clone();
+                                candidates.extend(exploration.transaction.as_ref().and_then(TransactionInProgress::next_step));
+                                Some(PaymentGuard::evaluate(&state, &candidates).await?)
+                            };
+                            // Refuse clicks on elements matching the deny-list, e.g. logout buttons.
+                            let denied_clicks = if options.observe_only {
+                                DeniedClicks::default()
+                            } else {
+                                options.click_deny_list.evaluate(&state, &candidates).await?
+                            };
+                            for denied in &denied_clicks.clicks {
+                                if denied_click_labels.insert(denied.label.clone()) {
+                                    log::info!("not clicking {:?}, matching denied text {:?} (use --allow-click to click it)", denied.label, denied.text);
+                                }
+                            }
+                            let allowed = |action: &BrowserAction| {
+                                let allowed = guard.as_ref().is_none_or(|guard| guard.allows(action));
+                                if !allowed {
+                                    log::debug!("payment guard refused action: {:?}", action);
+                                }
+                                allowed && denied_clicks.denial(action).is_none()
+                            };
+
+                            // The actions left out, kept to diagnose why there's none left.
+                            let exclusion = |action: &PlannedAction| Exclusion::of(action, within_origin, guard.as_ref(), &denied_clicks);
+                            let leaves = action_tree.leaves();
+                            let excluded = if leaves.iter().all(|action| exclusion(action).is_some()) {
+                                leaves.into_iter().filter_map(|action| {
+                                    exclusion(action).map(|reason| ExcludedAction { action: action.first().clone(), reason })
+                                }).collect()
+                            } else {
+                                vec![]
+                            };
+
+                            if let Some(search) = &mut exploration.exhaustive && exploration.setup.is_empty() && search.needs_root() {
+                                let checkpoint = state.checkpoint().await?;
+                                let url = checkpoint.url.clone();
+                                search.set_root(keep_checkpoint(&mut exploration.saved_checkpoints, checkpoint, options.checkpoints_file.as_deref()).await, url);
+                            }
+                            // Ask the external policy for the action that would be picked at random.
+                            let chosen = match &policy {
+                                Some(policy) if exploration.picks_at_random(&options) => {
+                                    let available: Vec<&PlannedAction> = action_tree.leaves().into_iter().filter(|action| allowed_at(action, within_origin) && allowed(action.first())).collect();
+                                    let summary = StateSummary {
+                                        index: step.index,
+                                        url: state.url.as_str(),
+                                        title: &state.title,
+                                        fingerprint: novelty::fingerprint(&state.url, state.transition_hash),
+                                        edges_changed: state.coverage.edges_new.len(),
+                                        violations: step_result.properties.iter().filter(|(_, value)| matches!(value, PropertyValue::False(_))).map(|(name, _)| name.as_str()).collect(),
+                                    };
+                                    policy.choose(&summary, &available).await.cloned()
+                                }
+                                _ => None,
+                            };
+                            // Switch to the next viewport profile when it's time to, only
+                            // observing the page then, so that the next action is picked in the
+                            // new layout.
+                            let viewport_switch = options.viewport_sweep.as_ref().and_then(|sweep| sweep.switch_at(step.index + 1));
+                            record_verdicts(verdicts, &step_result.properties);
+                            let decision = advance(StepInputs {
+                                index: step.index,
+                                timestamp: state.timestamp,
+                                fingerprint: novelty::fingerprint(&state.url, state.transition_hash),
+                                properties: step_result.properties,
+                                actions: action_tree,
+                                chosen,
+                                within_origin,
+                                switching_viewport: viewport_switch.is_some(),
+                                allowed: &allowed,
+                            }, &options, &mut exploration)?;
+                            let has_violations = decision.has_violations();
+
+                            if decision.save_checkpoint && let Some(checkpoints) = &mut exploration.checkpoints {
+                                match state.checkpoint().await {
+                                    Ok(checkpoint) => {
+                                        log::debug!("saved checkpoint at {}", checkpoint.url);
+                                        let url = checkpoint.url.clone();
+                                        let id = keep_checkpoint(&mut exploration.saved_checkpoints, checkpoint, options.checkpoints_file.as_deref()).await;
+                                        if let Some(dropped) = checkpoints.save(id, url) {
+                                            exploration.saved_checkpoints.remove(dropped);
+                                        }
                                     }
+                                    Err(error) => log::warn!("failed saving checkpoint: {:#}", error),
                                 }
                             }
-                            let has_violations = !violations.is_empty();
 
-                            // Make sure we stay within origin.
-                            let action_tree = if !is_within_domain(&state.url, origin) {
-                                action_tree.filter(&|a| matches!(a, BrowserAction::Back))
+                            let no_actions = if decision.next.is_err() {
+                                Some(NoActionsDiagnostics::collect(&state, excluded).await)
                             } else {
-                                action_tree
+                                None
+                            };
+
+                            // Start a picked transaction before handing the state over, as it
+                            // extracts from it.
+                            let next_action = match decision.next {
+                                Ok(NextAction::Observe) => None,
+                                Ok(NextAction::Apply(action)) => Some(Ok(action)),
+                                Ok(NextAction::Start(transaction)) => {
+                                    let mut in_progress = TransactionInProgress::start(transaction, &state, &state_partial, isolated_world.as_ref()).await?;
+                                    let step = in_progress.next_step().cloned();
+                                    in_progress.advance();
+                                    exploration.transaction = Some(in_progress);
+                                    Some(step.ok_or_else(|| anyhow::anyhow!("transaction has no steps")))
+                                }
+                                Err(error) => Some(Err(error)),
                             };
 
                             // Update global edges.
-                            for (index, bucket) in &state.coverage.edges_new {
-                                edges[*index as usize] =
-                                    max(edges[*index as usize], *bucket);
-                            }
-                            log_coverage_stats_increment(&state.coverage);
+                            let edges_new = edges.merge(&state.coverage);
+                            log_coverage_stats_increment(&state.coverage, edges_new);
                             log_coverage_stats_total(&edges);
 
+                            let pacing_decision = if options.adaptive_pacing {
+                                let decision = pacing.observe(&state);
+                                log::debug!("pacing: {:?}", decision);
+                                browser.set_settle_delay(decision.settle_delay)?;
+                                Some(decision)
+                            } else {
+                                None
+                            };
+
+                            let accessibility_tree = if has_violations && options.capture_accessibility_tree {
+                                state.accessibility_tree().await.unwrap_or_else(|error| {
+                                    log::warn!("{:#}", error);
+                                    None
+                                })
+                            } else {
+                                None
+                            };
+
+                            let full_page_screenshot = if has_violations && options.capture_full_page {
+                                match tokio::time::timeout(FULL_PAGE_SCREENSHOT_TIMEOUT, state.full_page_screenshot()).await {
+                                    Ok(Ok(screenshot)) => screenshot,
+                                    Ok(Err(error)) => {
+                                        log::warn!("failed taking a full-page screenshot: {:#}", error);
+                                        None
+                                    }
+                                    Err(_) => {
+                                        log::warn!("timed out taking a full-page screenshot");
+                                        None
+                                    }
+                                }
+                            } else {
+                                None
+                            };
+
+                            let dom_snapshot = if options.capture_dom {
+                                state.dom_snapshot().await.unwrap_or_else(|error| {
+                                    log::warn!("failed capturing the DOM: {:#}", error);
+                                    None
+                                })
+                            } else {
+                                None
+                            };
+
                             events.send(RunEvent::NewState {
                                 state,
                                 last_action,
-                                violations,
+                                violations: decision.violations,
+                                transaction_failures,
+                                pacing: pacing_decision,
+                                timings,
+                                accessibility_tree,
+                                metrics: step_result.metrics,
+                                full_page_screenshot,
+                                dom_snapshot,
                             })?;
-                            if has_violations && options.stop_on_violation {
+                            if let Some(diagnostics) = no_actions {
+                                log::error!("{}", diagnostics.summary());
+                                events.send(RunEvent::NoActions(diagnostics))?;
+                            }
+                            match decision.stop {
+                                Some(StopReason::PausedOnViolation) => {
+                                    log::info!("paused on violation");
+                                    let _ = (&mut shutdown).await;
+                                    return Ok(())
+                                }
+                                Some(StopReason::Violation) => return Ok(()),
+                                Some(StopReason::AllPropertiesDefinite) => {
+                                    log::info!("all properties are definite, stopping");
+                                    return Ok(())
+                                }
+                                Some(StopReason::Replayed) => {
+                                    log::info!("all recorded actions replayed, stopping");
+                                    return Ok(())
+                                }
+                                Some(StopReason::Explored(sequences)) => {
+                                    log::info!("explored all {} action sequences, stopping", sequences);
+                                    return Ok(())
+                                }
+                                None => {}
+                            }
+
+                            if let Some(interval) = options.edge_decay && (step.index + 1).is_multiple_of(interval) {
+                                let (before, after) = edges.decay();
+                                log::info!("decayed the edge map, {} of {} covered edges left", after, before);
+                            }
+
+                            // Stop once the budget of the run is spent.
+                            steps_without_new_coverage = if edges_new > 0 { 0 } else { steps_without_new_coverage + 1 };
+                            if let Some(max_steps) = options.max_steps && step.index + 1 >= max_steps {
+                                log::info!("reached the maximum of {} steps, stopping", max_steps);
                                 return Ok(())
                             }
-                            if all_properties_definite {
-                                log::info!("all properties are definite, stopping");
+                            if let Some(plateau) = options.coverage_plateau && steps_without_new_coverage >= plateau {
+                                log::info!("no new coverage in the last {} steps, stopping", plateau);
                                 return Ok(())
                             }
 
-                            let action_tree = action_tree.prune()
-                                .ok_or_else(|| anyhow::anyhow!("no actions available"))?;
+                            if let Some(profile) = viewport_switch {
+                                log::info!("switching to viewport profile `{}`", profile.name);
+                                browser.set_viewport(profile).await?;
+                                viewport_set = None;
+                                browser.observe(VIEWPORT_SWITCH_TIMEOUT)?;
+                                last_action = None;
+                                continue;
+                            }
+
+                            let Some(action) = next_action else {
+                                browser.observe(OBSERVATION_TIMEOUT)?;
+                                last_action = None;
+                                continue;
+                            };
 
-                            let action = action_tree.pick(&mut rand::rng())?.clone();
-                            let timeout = action_timeout(&action);
+                            let action = action?;
+                            let timeout = if options.adaptive_pacing {
+                                pacing.timeout(&action, action_timeout(&action))
+                            } else {
+                                action_timeout(&action)
+                            };
                             log::info!("picked action: {:?}", action);
-                            browser.apply(action.clone(), timeout)?;
+                            match action {
+                                BrowserAction::GoOffline => network = NetworkStatus { offline: true, changed_at: Some(step.index + 1), ..NetworkStatus::default() },
+                                BrowserAction::GoOnline => network = NetworkStatus { offline: false, changed_at: Some(step.index + 1), ..NetworkStatus::default() },
+                                BrowserAction::SetNetworkConditions { offline, latency_millis, throughput } => network = NetworkStatus {
+                                    offline,
+                                    changed_at: Some(step.index + 1),
+                                    latency_millis,
+                                    throughput: (throughput >= 0.0).then_some(throughput),
+                                },
+                                BrowserAction::SetViewport { ref profile } => viewport_set = Some(profile.clone()),
+                                _ => {}
+                            }
+                            match &action {
+                                BrowserAction::RestoreCheckpoint { id, url } => {
+                                    let checkpoint = match exploration.saved_checkpoints.get(*id) {
+                                        Some(checkpoint) => checkpoint.clone(),
+                                        None => {
+                                            log::warn!("checkpoint {} wasn't saved, e.g. when replaying a run without --save-checkpoints, only restoring its URL {}", id, url);
+                                            Checkpoint::at(url)
+                                        }
+                                    };
+                                    browser.restore_checkpoint(action.clone(), checkpoint, timeout)?;
+                                }
+                                _ => browser.apply(action.clone(), timeout)?,
+                            }
                             last_action = Some(action);
                         }
                         BrowserEvent::Error(error) => {
+                            // Keep the failure of the first navigation as is, with its hint.
+                            if let Some(error) = error.downcast_ref::<InitialNavigationError>() {
+                                return Err(error.clone().into());
+                            }
                             anyhow::bail!("state machine error: {}", error)
                         }
                     },
@@ -223,9 +734,131 @@ impl Runner {
     }
 }
 
+/// Keeps a checkpoint for actions to restore, returning its id, and appends it to the
+/// checkpoints file when there's one.
+async fn keep_checkpoint(
+    store: &mut CheckpointStore,
+    checkpoint: Checkpoint,
+    file: Option<&Path>,
+) -> u64 {
+    let saved = SavedCheckpoint {
+        id: store.insert(checkpoint.clone()),
+        checkpoint,
+    };
+    if let Some(path) = file
+        && let Err(error) = append_checkpoint(path, &saved).await
+    {
+        log::warn!(
+            "failed writing checkpoint to {}: {:#}",
+            path.display(),
+            error
+        );
+    }
+    saved.id
+}
+
+async fn append_checkpoint(
+    path: &Path,
+    saved: &SavedCheckpoint,
+) -> anyhow::Result<()> {
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(format!("{}\n", json::to_string(saved)?).as_bytes())
+        .await?;
+    Ok(())
+}
+
+/// What a property evaluated to in the last state of a run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "verdict", rename_all = "lowercase")]
+pub enum PropertyVerdict {
+    True,
+    /// False in some state, even if it later evaluated to something else.
+    False,
+    /// Still undecided, with what it would be were the run to stop there (its stop
+    /// default) and, when that's false, its end policy.
+    Residual {
+        stop_default: bool,
+        policy: Option<EndPolicy>,
+    },
+}
+
+/// Records the values of the properties in the last state, false ones staying false.
+fn record_verdicts(
+    verdicts: &mut BTreeMap<String, PropertyVerdict>,
+    properties: &[(String, PropertyValue)],
+) {
+    for (name, value) in properties {
+        let verdict = match value {
+            PropertyValue::True => PropertyVerdict::True,
+            PropertyValue::False(_) => PropertyVerdict::False,
+            PropertyValue::Residual => PropertyVerdict::Residual {
+                stop_default: true,
+                policy: None,
+            },
+        };
+        let recorded = verdicts.entry(name.clone()).or_insert(verdict);
+        if *recorded != PropertyVerdict::False {
+            *recorded = verdict;
+        }
+    }
+}
+
+/// The properties still undecided when the run ended, reported according to their end
+/// policies, and the verdicts of all properties.
+#[derive(Debug, Default)]
+pub struct EndOfRun {
+    pub violations: Vec<PropertyViolation>,
+    pub warnings: Vec<PropertyViolation>,
+    pub verdicts: BTreeMap<String, PropertyVerdict>,
+}
+
+impl EndOfRun {
+    async fn collect(
+        verifier: &dyn VerifierBackend,
+        suppressions: &Suppressions,
+        verdicts: BTreeMap<String, PropertyVerdict>,
+    ) -> anyhow::Result<Self> {
+        let time = SystemTime::now();
+        let mut end = EndOfRun {
+            verdicts,
+            ..EndOfRun::default()
+        };
+        for end_violation in verifier.finish(time).await? {
+            if let Some(PropertyVerdict::Residual {
+                stop_default,
+                policy,
+            }) = end.verdicts.get_mut(&end_violation.name)
+            {
+                *stop_default = false;
+                *policy = Some(end_violation.policy);
+            }
+            let fingerprint = fingerprint(&end_violation.violation);
+            let suppressed = suppressions
+                .find(&end_violation.name, &fingerprint, time)
+                .cloned();
+            let violation = PropertyViolation {
+                name: end_violation.name,
+                violation: end_violation.violation,
+                fingerprint,
+                suppressed,
+            };
+            match end_violation.policy {
+                EndPolicy::Fail => end.violations.push(violation),
+                EndPolicy::Warn => end.warnings.push(violation),
+                EndPolicy::Ignore => {}
+            }
+        }
+        Ok(end)
+    }
+}
+
 pub struct RunEvents {
     events: broadcast::Receiver<RunEvent>,
-    done: oneshot::Receiver<anyhow::Result<()>>,
+    done: oneshot::Receiver<anyhow::Result<EndOfRun>>,
     shutdown: oneshot::Sender<()>,
 }
 
@@ -239,21 +872,82 @@ impl RunEvents {
     }
 
     /// Shuts down the runner, waiting for it to finish and clean up. Returns an Err when some
-    /// non-recoverable error occured, as opposed to test violations which are sent in trace events.
-    pub async fn shutdown(mut self) -> anyhow::Result<()> {
+    /// non-recoverable error occured, as opposed to test violations which are sent in trace events,
+    /// or else the properties reported at the end of the run.
+    pub async fn shutdown(mut self) -> anyhow::Result<EndOfRun> {
         // If we can't send the signal, it means the receiver has already been dropped.
         let _ = self.shutdown.send(());
         (&mut self.done).await?
     }
 }
 
-async fn run_extractors(
+async fn start_verifier(
+    specification: Specification,
+    options: &RunnerOptions,
+) -> anyhow::Result<Arc<dyn VerifierBackend>> {
+    let driver = start_backend(specification, options).await?;
+    if options.isolated_specifications.is_empty() {
+        return Ok(driver);
+    }
+    let mut checked = Vec::new();
+    for specification in &options.isolated_specifications {
+        checked.push((
+            specification.name(),
+            start_backend(specification.clone().driven(), options).await?,
+        ));
+    }
+    Ok(IsolatedVerifiers::new(driver, checked).await?)
+}
+
+async fn start_backend(
+    specification: Specification,
+    options: &RunnerOptions,
+) -> anyhow::Result<Arc<dyn VerifierBackend>> {
+    Ok(match &options.remote_verifier {
+        Some(address) => {
+            log::info!("using remote verifier at {}", address);
+            RemoteVerifier::connect(
+                address.as_str(),
+                specification,
+                options.seed,
+            )
+            .await?
+        }
+        None => VerifierWorker::start(specification, options.seed).await?,
+    })
+}
+
+/// The position of a state in the run.
+#[derive(Clone, Copy, Debug)]
+struct Step {
+    /// Number of states before this one.
+    index: u64,
+    /// Time since the first state.
+    elapsed: Duration,
+}
+
+/// The emulated connectivity, changed by the `GoOffline`, `GoOnline` and
+/// `SetNetworkConditions` actions.
+#[derive(Clone, Copy, Debug, Default)]
+struct NetworkStatus {
+    offline: bool,
+    /// Index of the first state since the last change, if any.
+    changed_at: Option<u64>,
+    latency_millis: f64,
+    /// In bytes per second, or none if unlimited.
+    throughput: Option<f64>,
+}
+
+/// The part of the state passed to extractors that isn't read from the page itself.
+fn state_partial(
     state: &BrowserState,
-    extractors: &Vec<(u64, String)>,
     last_action: &Option<BrowserAction>,
-) -> anyhow::Result<Vec<(u64, json::Value)>> {
-    let mut results = Vec::with_capacity(extractors.len());
-
+    step: Step,
+    viewport: Option<&ViewportProfile>,
+    script_sizes: &ScriptSizeTotals,
+    gpu_info: Option<&GpuInfo>,
+    network: NetworkStatus,
+) -> anyhow::Result<json::Value> {
     let console_entries: Vec<json::Value> = state
         .console_entries
         .iter()
@@ -266,26 +960,102 @@ async fn run_extractors(
         })
         .collect();
 
-    let state_partial = json::json!({
+    Ok(json::json!({
         "errors": {
             "uncaughtExceptions": &state.exceptions,
+            "droppedCount": state.exceptions_dropped,
         },
         "console": console_entries,
+        "consoleDroppedCount": state.console_entries_dropped,
         "navigationHistory": &state.navigation_history,
+        "navigationTimings": &state.navigation_timings,
+        "serviceWorkers": &state.service_workers,
         "lastAction": json::to_value(last_action)?,
-    });
-
-    for (key, function) in extractors {
-        let json: json::Value = state
-            .evaluate_function_call(
-                format!(
-                    "(state) => ({})({{ ...state, document, window }})",
-                    function
-                ),
-                vec![state_partial.clone()],
-            )
-            .await?;
-        results.push((*key, json));
+        "mutations": state.mutation_summary(),
+        "listeners": &state.listeners,
+        "step": {
+            "index": step.index,
+            "elapsedMillis": step.elapsed.as_millis() as u64,
+        },
+        "viewportProfile": viewport,
+        "scriptSizes": script_sizes,
+        "gpu": gpu_info,
+        "dialogs": &state.dialogs,
+        "redirects": &state.redirects,
+        "network": {
+            "offline": network.offline,
+            "changedAt": network.changed_at,
+            "latencyMillis": network.latency_millis,
+            "throughput": network.throughput,
+            "requests": &state.network_requests,
+            "requestsDroppedCount": state.network_requests_dropped,
+            "webSocketFrames": &state.web_socket_frames,
+            "webSocketFramesDroppedCount": state.web_socket_frames_dropped,
+        },
+    }))
+}
+
+async fn evaluate_extractor(
+    state: &BrowserState,
+    function: &str,
+    state_partial: &json::Value,
+    world: Option<&IsolatedWorld>,
+) -> anyhow::Result<json::Value> {
+    let function = format!(
+        "(state) => ({})({{ ...state, document, window }})",
+        function
+    );
+    evaluate_function_call(state, world, function, vec![state_partial.clone()])
+        .await
+}
+
+/// Evaluates a function of the specification in the isolated world if there's one, or in
+/// the paused page otherwise.
+async fn evaluate_function_call<Output: DeserializeOwned>(
+    state: &BrowserState,
+    world: Option<&IsolatedWorld>,
+    function: String,
+    arguments: Vec<json::Value>,
+) -> anyhow::Result<Output> {
+    match world {
+        Some(world) => {
+            state
+                .evaluate_function_call_in_world(world, function, arguments)
+                .await
+        }
+        None => state.evaluate_function_call(function, arguments).await,
+    }
+}
+
+/// Runs the extractors due in the state at `step_index`, the others keeping the values
+/// they extracted last.
+async fn run_extractors(
+    state: &BrowserState,
+    extractors: &[ExtractorFunction],
+    step_index: u64,
+    snapshots_last: &mut HashMap<u64, json::Value>,
+    state_partial: &json::Value,
+    world: Option<&IsolatedWorld>,
+) -> anyhow::Result<Vec<(u64, json::Value)>> {
+    let mut results = Vec::with_capacity(extractors.len());
+    for extractor in extractors {
+        let json = match snapshots_last.get(&extractor.id) {
+            Some(json) if !step_index.is_multiple_of(extractor.every) => {
+                json.clone()
+            }
+            _ => {
+                let json = evaluate_extractor(
+                    state,
+                    &extractor.source,
+                    state_partial,
+                    world,
+                )
+                .await?;
+                snapshots_last.insert(extractor.id, json.clone());
+                json
+            }
+        };
+        results.push((extractor.id, json));
     }
     Ok(results)
 }
@@ -296,6 +1066,15 @@ fn action_timeout(action: &BrowserAction) -> Duration {
         BrowserAction::Forward => Duration::from_secs(2),
         BrowserAction::Reload => Duration::from_secs(2),
         BrowserAction::Click { .. } => Duration::from_millis(500),
+        BrowserAction::DoubleClick { .. } => Duration::from_millis(500),
+        BrowserAction::RightClick { .. } => Duration::from_millis(500),
+        // Leave time for menus and tooltips to open.
+        BrowserAction::Hover { .. } => Duration::from_millis(200),
+        BrowserAction::Focus { .. } => Duration::from_millis(100),
+        BrowserAction::SelectOption { .. } => Duration::from_millis(500),
+        BrowserAction::SetChecked { .. } => Duration::from_millis(500),
+        // The moves of the drag take 200ms.
+        BrowserAction::DragAndDrop { .. } => Duration::from_millis(500),
         BrowserAction::TypeText {
             text, delay_millis, ..
         } => {
@@ -307,10 +1086,25 @@ fn action_timeout(action: &BrowserAction) -> Duration {
         BrowserAction::PressKey { .. } => Duration::from_millis(50),
         BrowserAction::ScrollUp { .. } => Duration::from_millis(100),
         BrowserAction::ScrollDown { .. } => Duration::from_millis(100),
+        // Leave time for the page to react to the `online` and `offline` events.
+        BrowserAction::GoOffline => Duration::from_millis(500),
+        BrowserAction::GoOnline => Duration::from_millis(500),
+        BrowserAction::SetNetworkConditions { .. } => {
+            Duration::from_millis(500)
+        }
+        BrowserAction::InjectFaults { .. }
+        | BrowserAction::SetInstrumentation { .. }
+        | BrowserAction::SetDialogPolicy { .. } => Duration::from_millis(100),
+        BrowserAction::CdpCommand { .. } => Duration::from_millis(500),
+        // The timers have run by the time the clock is advanced.
+        BrowserAction::AdvanceClock { .. } => Duration::from_millis(100),
+        // Leave time for the layout to adapt to the new viewport.
+        BrowserAction::SetViewport { .. } => Duration::from_millis(500),
+        BrowserAction::RestoreCheckpoint { .. } => Duration::from_secs(2),
     }
 }
 
-fn log_coverage_stats_increment(coverage: &Coverage) {
+fn log_coverage_stats_increment(coverage: &Coverage, edges_new: usize) {
     if log::log_enabled!(log::Level::Debug) {
         let (added, removed) = coverage.edges_new.iter().fold(
             (0usize, 0usize),
@@ -322,20 +1116,19 @@ fn log_coverage_stats_increment(coverage: &Coverage) {
                 }
             },
         );
-        log::debug!("edge delta: +{}/-{}", added, removed);
+        log::debug!(
+            "edge delta: +{}/-{} ({} never covered before)",
+            added,
+            removed,
+            edges_new
+        );
     }
 }
 
-fn log_coverage_stats_total(edges: &[u8; EDGE_MAP_SIZE]) {
+fn log_coverage_stats_total(edges: &EdgeMap) {
     if log::log_enabled!(log::Level::Debug) {
-        let mut buckets = [0u64; 8];
-        let mut hits_total: u64 = 0;
-        for bucket in edges {
-            if *bucket > 0 {
-                buckets[*bucket as usize - 1] += 1;
-                hits_total += 1;
-            }
-        }
+        let buckets = edges.buckets();
+        let hits_total: u64 = buckets.iter().sum();
         log::debug!("total hits: {}", hits_total);
         log::debug!(
             "total edges (max bucket): {:04} {:04} {:04} {:04} {:04} {:04} {:04} {:04}",
@@ -350,3 +1143,234 @@ fn log_coverage_stats_total(edges: &[u8; EDGE_MAP_SIZE]) {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::mock::{MockBrowser, MockState};
+    use crate::specification::ltl;
+    use crate::specification::worker::{
+        EndViolation, PropertyValue, StepResult, WorkerError,
+    };
+    use crate::tree::Tree;
+    use futures::future::{self, BoxFuture};
+    use std::sync::Mutex;
+
+    /// Extracts the title and always presses enter, recording the snapshots it's given.
+    #[derive(Default)]
+    struct PressingEnter {
+        snapshots: Mutex<Vec<json::Value>>,
+    }
+
+    impl VerifierBackend for PressingEnter {
+        fn properties(
+            &self,
+        ) -> BoxFuture<'_, Result<Vec<String>, WorkerError>> {
+            Box::pin(future::ready(Ok(vec!["property".to_string()])))
+        }
+
+        fn extractors(
+            &self,
+        ) -> BoxFuture<'_, Result<Vec<ExtractorFunction>, WorkerError>>
+        {
+            Box::pin(future::ready(Ok(vec![ExtractorFunction {
+                id: 0,
+                source: "(state) => state.document.title".to_string(),
+                every: 1,
+            }])))
+        }
+
+        fn setup_json(
+            &self,
+        ) -> BoxFuture<'_, Result<Vec<json::Value>, WorkerError>> {
+            Box::pin(future::ready(Ok(vec![])))
+        }
+
+        fn step_json(
+            &self,
+            snapshots: Vec<(u64, json::Value)>,
+            _time: ltl::Time,
+        ) -> BoxFuture<'_, Result<StepResult<json::Value>, WorkerError>>
+        {
+            self.snapshots
+                .lock()
+                .unwrap()
+                .extend(snapshots.into_iter().map(|(_, value)| value));
+            Box::pin(future::ready(Ok(StepResult {
+                properties: vec![(
+                    "property".to_string(),
+                    PropertyValue::Residual,
+                )],
+                actions: Tree::Leaf {
+                    value: json::json!({ "PressKey": { "code": 13.0 } }),
+                },
+                metrics: vec![],
+            })))
+        }
+
+        fn finish(
+            &self,
+            _time: ltl::Time,
+        ) -> BoxFuture<'_, Result<Vec<EndViolation>, WorkerError>> {
+            Box::pin(future::ready(Ok(vec![])))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_with_mock_browser() {
+        let origin = Url::parse("http://localhost/").unwrap();
+        let states = ["first", "second", "third"]
+            .map(|title| {
+                MockState::new(origin.clone())
+                    .answer("document.title", json::json!(title))
+            })
+            .to_vec();
+        let browser = MockBrowser::new(states);
+        let applied = browser.applied();
+        let verifier = Arc::new(PressingEnter::default());
+        let runner = Runner::with_backends(
+            origin,
+            RunnerOptions::default(),
+            Box::new(browser),
+            verifier.clone(),
+        );
+
+        let mut events = runner.start();
+        for _ in 0..3 {
+            let Some(RunEvent::NewState { violations, .. }) =
+                events.next().await.unwrap()
+            else {
+                panic!("expected a new state");
+            };
+            assert!(violations.is_empty());
+        }
+        events.shutdown().await.unwrap();
+
+        assert_eq!(
+            *verifier.snapshots.lock().unwrap(),
+            vec![
+                json::json!("first"),
+                json::json!("second"),
+                json::json!("third")
+            ]
+        );
+        assert!(matches!(
+            applied.get()[..2],
+            [
+                BrowserAction::PressKey { code: 13 },
+                BrowserAction::PressKey { code: 13 }
+            ]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_step_timings() {
+        let origin = Url::parse("http://localhost/").unwrap();
+        let mut state = MockState::new(origin.clone())
+            .answer("document.title", json::json!("first"));
+        state.timings = CaptureTimings {
+            screenshot: Duration::from_millis(30),
+            pause: Duration::from_millis(5),
+            evaluation: Duration::from_millis(12),
+        };
+        let runner = Runner::with_backends(
+            origin,
+            RunnerOptions::default(),
+            Box::new(MockBrowser::new(vec![state.clone(), state])),
+            Arc::new(PressingEnter::default()),
+        );
+
+        let mut events = runner.start();
+        let mut total = StepTimings::default();
+        for _ in 0..2 {
+            let Some(RunEvent::NewState { timings, .. }) =
+                events.next().await.unwrap()
+            else {
+                panic!("expected a new state");
+            };
+            assert_eq!(timings.capture.screenshot, Duration::from_millis(30));
+            total += timings;
+        }
+        events.shutdown().await.unwrap();
+
+        assert_eq!(total.capture.screenshot, Duration::from_millis(60));
+        assert_eq!(total.capture.pause, Duration::from_millis(10));
+        assert_eq!(total.capture.evaluation, Duration::from_millis(24));
+    }
+
+    #[tokio::test]
+    async fn test_max_steps() {
+        let origin = Url::parse("http://localhost/").unwrap();
+        let states = ["first", "second", "third"]
+            .map(|title| {
+                MockState::new(origin.clone())
+                    .answer("document.title", json::json!(title))
+            })
+            .to_vec();
+        let runner = Runner::with_backends(
+            origin,
+            RunnerOptions {
+                max_steps: Some(2),
+                ..RunnerOptions::default()
+            },
+            Box::new(MockBrowser::new(states)),
+            Arc::new(PressingEnter::default()),
+        );
+
+        let mut events = runner.start();
+        for _ in 0..2 {
+            let Some(RunEvent::NewState { .. }) = events.next().await.unwrap()
+            else {
+                panic!("expected a new state");
+            };
+        }
+        assert!(events.next().await.unwrap().is_none());
+        let end_of_run = events.shutdown().await.unwrap();
+        assert_eq!(
+            end_of_run.verdicts,
+            BTreeMap::from([(
+                "property".to_string(),
+                PropertyVerdict::Residual {
+                    stop_default: true,
+                    policy: None
+                }
+            )])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_no_actions_diagnostics() {
+        let origin = Url::parse("http://localhost/").unwrap();
+        let elsewhere = Url::parse("http://example.com/").unwrap();
+        let browser = MockBrowser::new(vec![
+            MockState::new(elsewhere)
+                .answer("document.title", json::json!("elsewhere")),
+        ]);
+        let runner = Runner::with_backends(
+            origin,
+            RunnerOptions::default(),
+            Box::new(browser),
+            Arc::new(PressingEnter::default()),
+        );
+
+        let mut events = runner.start();
+        let Some(RunEvent::NewState { .. }) = events.next().await.unwrap()
+        else {
+            panic!("expected a new state");
+        };
+        let Some(RunEvent::NoActions(diagnostics)) =
+            events.next().await.unwrap()
+        else {
+            panic!("expected no actions");
+        };
+        assert_eq!(diagnostics.url, "http://example.com/");
+        assert!(matches!(
+            diagnostics.excluded[..],
+            [ExcludedAction {
+                action: BrowserAction::PressKey { code: 13 },
+                reason: Exclusion::OutsideOrigin,
+            }]
+        ));
+        assert!(events.shutdown().await.is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{Context, Result, bail};
+use serde_json as json;
+
+/// A `KEY=VALUE` entry of the run context. The value is parsed as JSON when it's valid
+/// JSON (e.g. `true` or `3`), and taken as a string otherwise.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContextEntry {
+    pub key: String,
+    pub value: json::Value,
+}
+
+impl FromStr for ContextEntry {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (key, value) = s
+            .split_once('=')
+            .filter(|(key, _)| !key.is_empty())
+            .ok_or_else(|| {
+                format!("invalid context entry {:?}, expected KEY=VALUE", s)
+            })?;
+        Ok(ContextEntry {
+            key: key.to_string(),
+            value: json::from_str(value)
+                .unwrap_or_else(|_| json::Value::String(value.to_string())),
+        })
+    }
+}
+
+/// The context of a run, exposed to extractors as `state.context`, letting the same
+/// specification adapt to different environments: the JSON object of `file`, if any,
+/// with `entries` set on top of it.
+pub async fn load_context(
+    file: Option<&Path>,
+    entries: &[ContextEntry],
+) -> Result<json::Map<String, json::Value>> {
+    let mut context = match file {
+        Some(path) => {
+            let contents = tokio::fs::read_to_string(path)
+                .await
+                .with_context(|| format!("read {}", path.display()))?;
+            context_from_json(&contents)
+                .with_context(|| format!("parse {}", path.display()))?
+        }
+        None => json::Map::new(),
+    };
+    for entry in entries {
+        context.insert(entry.key.clone(), entry.value.clone());
+    }
+    Ok(context)
+}
+
+fn context_from_json(contents: &str) -> Result<json::Map<String, json::Value>> {
+    match json::from_str(contents)? {
+        json::Value::Object(context) => Ok(context),
+        _ => bail!("context is not a JSON object"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_entries_override_file() {
+        let file = tempfile::NamedTempFile::with_suffix(".json").unwrap();
+        std::fs::write(file.path(), r#"{"tenant": "acme", "beta": false}"#)
+            .unwrap();
+        let entries: Vec<ContextEntry> = ["beta=true", "region=eu-west"]
+            .iter()
+            .map(|entry| entry.parse().unwrap())
+            .collect();
+
+        let context = load_context(Some(file.path()), &entries).await.unwrap();
+        assert_eq!(
+            json::Value::Object(context),
+            json::json!({"tenant": "acme", "beta": true, "region": "eu-west"})
+        );
+        assert!("=value".parse::<ContextEntry>().is_err());
+        assert!(context_from_json("[1]").is_err());
+    }
+}
@@ -0,0 +1,131 @@
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::browser::actions::BrowserAction;
+use crate::browser::click_deny_list::DeniedClicks;
+use crate::browser::payment_guard::PaymentGuard;
+use crate::browser::state::{BrowserState, Screenshot};
+use crate::runner::step::allowed_at;
+use crate::runner::transactions::PlannedAction;
+
+const VIEWPORT_FUNCTION: &str =
+    "() => ({ width: window.innerWidth, height: window.innerHeight })";
+
+/// Why an action generated by the specification was left out.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Exclusion {
+    /// Outside the origin, going back is the only action allowed.
+    OutsideOrigin,
+    /// It touches a payment field, see `--allow-payment-fields`.
+    PaymentField,
+    /// It clicks an element matching this denied text, see `--deny-click`.
+    DeniedClick(String),
+}
+
+impl Exclusion {
+    pub fn of(
+        action: &PlannedAction,
+        within_origin: bool,
+        guard: Option<&PaymentGuard>,
+        denied_clicks: &DeniedClicks,
+    ) -> Option<Exclusion> {
+        if !allowed_at(action, within_origin) {
+            Some(Exclusion::OutsideOrigin)
+        } else if guard.is_some_and(|guard| !guard.allows(action.first())) {
+            Some(Exclusion::PaymentField)
+        } else {
+            denied_clicks
+                .denial(action.first())
+                .map(|denied| Exclusion::DeniedClick(denied.text.clone()))
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExcludedAction {
+    pub action: BrowserAction,
+    pub reason: Exclusion,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Viewport {
+    pub width: f64,
+    pub height: f64,
+}
+
+/// What's known of a state the specification had no action for, to debug why without
+/// access to the browser.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoActionsDiagnostics {
+    pub timestamp: SystemTime,
+    pub url: String,
+    pub title: String,
+    pub content_type: String,
+    /// The size of the page's viewport, unless it couldn't be read.
+    pub viewport: Option<Viewport>,
+    /// The actions generated for the state, all excluded. There were none at all when
+    /// this is empty.
+    pub excluded: Vec<ExcludedAction>,
+    #[serde(skip)]
+    pub screenshot: Option<Screenshot>,
+}
+
+impl NoActionsDiagnostics {
+    pub async fn collect(
+        state: &BrowserState,
+        excluded: Vec<ExcludedAction>,
+    ) -> Self {
+        let viewport = state
+            .evaluate_function_call(VIEWPORT_FUNCTION, vec![])
+            .await
+            .inspect_err(|error| {
+                log::debug!("could not read the viewport: {:#}", error)
+            })
+            .ok();
+        NoActionsDiagnostics {
+            timestamp: state.timestamp,
+            url: state.url.to_string(),
+            title: state.title.clone(),
+            content_type: state.content_type.clone(),
+            viewport,
+            excluded,
+            screenshot: Some(state.screenshot.clone()),
+        }
+    }
+
+    /// A description of why there were no actions, for the logs.
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "no actions available at {} ({:?}, {})",
+            self.url, self.title, self.content_type
+        );
+        if let Some(viewport) = self.viewport {
+            summary.push_str(&format!(
+                " in a {}x{} viewport",
+                viewport.width, viewport.height
+            ));
+        }
+        if self.excluded.is_empty() {
+            summary.push_str(", the specification generated none");
+        }
+        for excluded in &self.excluded {
+            let reason = match &excluded.reason {
+                Exclusion::OutsideOrigin => "outside the origin".to_string(),
+                Exclusion::PaymentField => {
+                    "touches a payment field".to_string()
+                }
+                Exclusion::DeniedClick(text) => {
+                    format!("matches denied text {:?}", text)
+                }
+            };
+            summary.push_str(&format!(
+                "\n  excluded {:?}: {}",
+                excluded.action, reason
+            ));
+        }
+        summary
+    }
+}
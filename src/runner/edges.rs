@@ -0,0 +1,98 @@
+use std::cmp::max;
+use std::sync::{Arc, Mutex};
+
+use crate::browser::state::Coverage;
+use crate::instrumentation::EDGE_MAP_SIZE;
+
+/// The edges covered so far, each with the highest bucket of hits seen, shared by the
+/// sessions of a run so that their coverage adds up.
+#[derive(Clone, Debug)]
+pub struct EdgeMap(Arc<Mutex<Vec<u8>>>);
+
+impl Default for EdgeMap {
+    fn default() -> Self {
+        EdgeMap(Arc::new(Mutex::new(vec![0; EDGE_MAP_SIZE])))
+    }
+}
+
+impl EdgeMap {
+    /// Merges the coverage of a state, returning the number of edges that weren't covered
+    /// by any session before.
+    pub fn merge(&self, coverage: &Coverage) -> usize {
+        let mut edges = self.0.lock().expect("edge map lock poisoned");
+        let mut new = 0;
+        for (index, bucket) in &coverage.edges_new {
+            let edge = &mut edges[*index as usize];
+            if *edge == 0 && *bucket > 0 {
+                new += 1;
+            }
+            *edge = max(*edge, *bucket);
+        }
+        new
+    }
+
+    /// The number of edges covered, by their highest bucket (from 1 to 8).
+    pub fn buckets(&self) -> [u64; 8] {
+        let edges = self.0.lock().expect("edge map lock poisoned");
+        let mut buckets = [0u64; 8];
+        for bucket in edges.iter() {
+            if *bucket > 0 {
+                buckets[*bucket as usize - 1] += 1;
+            }
+        }
+        buckets
+    }
+
+    /// Halves the bucket of every edge, uncovering those with a single hit, so that edges
+    /// covered long ago count as new again instead of the map saturating. Returns the
+    /// number of edges covered before and after.
+    pub fn decay(&self) -> (usize, usize) {
+        let mut edges = self.0.lock().expect("edge map lock poisoned");
+        let before = edges.iter().filter(|bucket| **bucket > 0).count();
+        for bucket in edges.iter_mut() {
+            *bucket /= 2;
+        }
+        let after = edges.iter().filter(|bucket| **bucket > 0).count();
+        (before, after)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_sessions() {
+        let edges = EdgeMap::default();
+        let session = edges.clone();
+        assert_eq!(
+            edges.merge(&Coverage {
+                edges_new: vec![(1, 1), (2, 3)]
+            }),
+            2
+        );
+        assert_eq!(
+            session.merge(&Coverage {
+                edges_new: vec![(2, 1), (3, 2)]
+            }),
+            1
+        );
+        assert_eq!(edges.buckets(), [1, 1, 1, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_decay() {
+        let edges = EdgeMap::default();
+        edges.merge(&Coverage {
+            edges_new: vec![(1, 1), (2, 3), (3, 8)],
+        });
+        assert_eq!(edges.decay(), (3, 2));
+        assert_eq!(edges.buckets(), [1, 0, 0, 1, 0, 0, 0, 0]);
+        assert_eq!(
+            edges.merge(&Coverage {
+                edges_new: vec![(1, 1)]
+            }),
+            1
+        );
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::{HashSet, VecDeque};
+use std::str::FromStr;
+
+use anyhow::{Result, bail};
+use serde_json as json;
+
+use crate::browser::actions::BrowserAction;
+
+/// Sequences of actions waiting to be explored above which the action space is considered
+/// too large for exhaustive exploration.
+const FRONTIER_LIMIT: usize = 10_000;
+
+/// The order in which action sequences are enumerated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchOrder {
+    /// Shortest sequences first, restoring the initial state before most of them.
+    BreadthFirst,
+    /// Each sequence followed by its extensions, continuing from the current state when
+    /// possible.
+    DepthFirst,
+}
+
+impl FromStr for SearchOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "bfs" => Ok(SearchOrder::BreadthFirst),
+            "dfs" => Ok(SearchOrder::DepthFirst),
+            other => Err(format!(
+                "unknown search order {:?}, expected bfs or dfs",
+                other
+            )),
+        }
+    }
+}
+
+/// Enumerate all sequences of actions up to a depth, instead of picking actions at random.
+#[derive(Clone, Copy, Debug)]
+pub struct ExhaustiveOptions {
+    pub order: SearchOrder,
+    pub depth: usize,
+}
+
+/// Systematic exploration of the action sequences up to a depth. Each sequence is reached
+/// by restoring a checkpoint of the initial state and applying its actions again, unless it
+/// extends the sequence just applied by one action.
+#[derive(Debug)]
+pub struct ExhaustiveSearch {
+    options: ExhaustiveOptions,
+    /// The id and URL of the checkpoint of the initial state.
+    root: Option<(u64, String)>,
+    /// Sequences left to explore, the next one first.
+    frontier: VecDeque<Vec<BrowserAction>>,
+    /// The actions applied since the initial state, or its last restore.
+    path: Vec<BrowserAction>,
+    /// Actions left to apply to reach the sequence being explored.
+    pending: VecDeque<BrowserAction>,
+    explored: usize,
+}
+
+impl ExhaustiveSearch {
+    pub fn new(options: ExhaustiveOptions) -> Self {
+        ExhaustiveSearch {
+            options,
+            root: None,
+            frontier: VecDeque::new(),
+            path: vec![],
+            pending: VecDeque::new(),
+            explored: 0,
+        }
+    }
+
+    /// Whether the checkpoint of the initial state is still to be saved, at the first
+    /// state.
+    pub fn needs_root(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn set_root(&mut self, id: u64, url: String) {
+        self.root = Some((id, url));
+    }
+
+    /// The number of sequences explored so far.
+    pub fn explored(&self) -> usize {
+        self.explored
+    }
+
+    /// The next action to apply, given the actions available in the current state, or
+    /// `None` when all sequences have been explored.
+    pub fn next(
+        &mut self,
+        available: Vec<BrowserAction>,
+    ) -> Result<Option<BrowserAction>> {
+        if let Some(action) = self.pending.pop_front() {
+            self.path.push(action.clone());
+            return Ok(Some(action));
+        }
+
+        // The sequence in `path` has been reached, so its extensions can be enumerated.
+        self.explored += 1;
+        if self.path.len() < self.options.depth {
+            self.expand(available)?;
+        }
+
+        let Some(mut sequence) = self.frontier.pop_front() else {
+            return Ok(None);
+        };
+        if sequence.len() == self.path.len() + 1
+            && same_actions(&sequence[..self.path.len()], &self.path)
+        {
+            let action = sequence.pop().expect("extends the path");
+            self.path.push(action.clone());
+            return Ok(Some(action));
+        }
+        let Some((id, url)) = &self.root else {
+            bail!("no checkpoint of the initial state to restore");
+        };
+        self.path.clear();
+        self.pending = sequence.into();
+        Ok(Some(BrowserAction::RestoreCheckpoint {
+            id: *id,
+            url: url.clone(),
+        }))
+    }
+
+    fn expand(&mut self, available: Vec<BrowserAction>) -> Result<()> {
+        let mut seen = HashSet::new();
+        let extensions: Vec<Vec<BrowserAction>> = available
+            .into_iter()
+            .filter(|action| seen.insert(action_key(action)))
+            .map(|action| {
+                let mut sequence = self.path.clone();
+                sequence.push(action);
+                sequence
+            })
+            .collect();
+        match self.options.order {
+            SearchOrder::BreadthFirst => self.frontier.extend(extensions),
+            SearchOrder::DepthFirst => {
+                for sequence in extensions.into_iter().rev() {
+                    self.frontier.push_front(sequence);
+                }
+            }
+        }
+        if self.frontier.len() > FRONTIER_LIMIT {
+            bail!(
+                "more than {} action sequences to explore, the action space is too large for exhaustive exploration up to depth {}",
+                FRONTIER_LIMIT,
+                self.options.depth
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Identifies actions for deduplication, as several generators can yield the same one.
+fn action_key(action: &BrowserAction) -> String {
+    json::to_string(action).unwrap_or_else(|_| format!("{:?}", action))
+}
+
+fn same_actions(a: &[BrowserAction], b: &[BrowserAction]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).all(|(a, b)| action_key(a) == action_key(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: u8) -> BrowserAction {
+        BrowserAction::PressKey { code }
+    }
+
+    /// Explores a page where keys 1 and 2 are always available, returning the sequences
+    /// reached, by their key codes.
+    fn explore(order: SearchOrder) -> (Vec<Vec<u8>>, usize) {
+        let mut search =
+            ExhaustiveSearch::new(ExhaustiveOptions { order, depth: 2 });
+        search.set_root(0, "http://localhost/".to_string());
+        let mut path: Vec<u8> = vec![];
+        let mut reached = vec![];
+        let mut restores = 0;
+        while let Some(action) =
+            search.next(vec![key(1), key(2), key(1)]).unwrap()
+        {
+            match action {
+                BrowserAction::RestoreCheckpoint { .. } => {
+                    restores += 1;
+                    path.clear();
+                }
+                BrowserAction::PressKey { code } => path.push(code),
+                other => panic!("unexpected action {:?}", other),
+            }
+            if search.pending.is_empty() {
+                reached.push(path.clone());
+            }
+        }
+        (reached, restores)
+    }
+
+    #[test]
+    fn test_depth_first() {
+        let (reached, restores) = explore(SearchOrder::DepthFirst);
+        let reached: Vec<Vec<u8>> = reached
+            .into_iter()
+            .filter(|path| !path.is_empty())
+            .collect();
+        assert_eq!(
+            reached,
+            vec![
+                vec![1],
+                vec![1, 1],
+                vec![1, 2],
+                vec![2],
+                vec![2, 1],
+                vec![2, 2]
+            ]
+        );
+        assert_eq!(restores, 3);
+    }
+
+    #[test]
+    fn test_breadth_first() {
+        let (reached, _) = explore(SearchOrder::BreadthFirst);
+        let reached: Vec<Vec<u8>> = reached
+            .into_iter()
+            .filter(|path| !path.is_empty())
+            .collect();
+        assert_eq!(
+            reached,
+            vec![
+                vec![1],
+                vec![2],
+                vec![1, 1],
+                vec![1, 2],
+                vec![2, 1],
+                vec![2, 2]
+            ]
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Debug;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use anyhow::Result;
+use rand::Rng;
+use url::Url;
+
+use crate::tree::Tree;
+
+/// States in a row looked at to tell whether the exploration is stuck in a loop.
+const LOOP_WINDOW: usize = 12;
+/// Distinct states at most in the window for the exploration to be stuck in a loop.
+const LOOP_STATES_MAX: usize = 2;
+/// Actions drawn at most from a state, until one isn't known to lead to a visited state.
+const PICK_ATTEMPTS: usize = 4;
+
+/// Steer the exploration away from the states already visited.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NoveltyOptions {
+    /// Go back, or reload when there's no going back, once the last states all come down
+    /// to a couple of them.
+    pub escape_loops: bool,
+}
+
+/// Identifies a state by its URL and the simhash of the edges covered to reach it, so
+/// that revisiting a page the same way gives the same fingerprint.
+pub fn fingerprint(url: &Url, transition_hash: Option<u64>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    url.as_str().hash(&mut hasher);
+    transition_hash.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The fingerprints of the states visited in the run, and which states the actions taken
+/// led to.
+#[derive(Debug)]
+pub struct Novelty {
+    options: NoveltyOptions,
+    visits: HashMap<u64, u32>,
+    /// The state reached by an action (by its debug representation) from a state.
+    outcomes: HashMap<(u64, String), u64>,
+    /// The last state and the action taken from it, until the next state.
+    last: Option<(u64, String)>,
+    recent: VecDeque<u64>,
+}
+
+fn action_key(action: &impl Debug) -> String {
+    format!("{:?}", action)
+}
+
+impl Novelty {
+    pub fn new(options: NoveltyOptions) -> Self {
+        Novelty {
+            options,
+            visits: HashMap::new(),
+            outcomes: HashMap::new(),
+            last: None,
+            recent: VecDeque::with_capacity(LOOP_WINDOW),
+        }
+    }
+
+    /// Records a visit of the state, reached by the last action taken.
+    pub fn visit(&mut self, fingerprint: u64) {
+        *self.visits.entry(fingerprint).or_default() += 1;
+        if let Some(last) = self.last.take() {
+            self.outcomes.insert(last, fingerprint);
+        }
+        if self.recent.len() == LOOP_WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(fingerprint);
+    }
+
+    /// Visits of the state the action is known to lead to from the state, if known.
+    fn visits_after(&self, fingerprint: u64, action: &impl Debug) -> u32 {
+        self.outcomes
+            .get(&(fingerprint, action_key(action)))
+            .and_then(|outcome| self.visits.get(outcome))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Picks an action from the tree, drawing again a few times while the pick is known
+    /// to lead to a visited state, and keeping the one leading to the least visited.
+    pub fn pick<'a, T: Debug>(
+        &self,
+        fingerprint: u64,
+        tree: &'a Tree<T>,
+        rng: &mut impl Rng,
+    ) -> Result<&'a T> {
+        let mut picked = tree.pick(rng)?;
+        let mut visits = self.visits_after(fingerprint, picked);
+        for _ in 1..PICK_ATTEMPTS {
+            if visits == 0 {
+                break;
+            }
+            let other = tree.pick(rng)?;
+            let other_visits = self.visits_after(fingerprint, other);
+            if other_visits < visits {
+                picked = other;
+                visits = other_visits;
+            }
+        }
+        Ok(picked)
+    }
+
+    /// Records the action taken from the state.
+    pub fn acted(&mut self, fingerprint: u64, action: &impl Debug) {
+        self.last = Some((fingerprint, action_key(action)));
+    }
+
+    /// Whether the last states all come down to a couple of them, to escape from when
+    /// enabled. The loop is forgotten, so that escaping is given a chance to work.
+    pub fn escape_loop(&mut self) -> bool {
+        if !self.options.escape_loops || self.recent.len() < LOOP_WINDOW {
+            return false;
+        }
+        let distinct: HashSet<&u64> = self.recent.iter().collect();
+        if distinct.len() > LOOP_STATES_MAX {
+            return false;
+        }
+        self.recent.clear();
+        true
+    }
+
+    /// Number of distinct states visited so far.
+    pub fn visited(&self) -> usize {
+        self.visits.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn test_prefers_unvisited_outcomes() {
+        let mut novelty = Novelty::new(NoveltyOptions { escape_loops: true });
+        let tree = Tree::Branch {
+            branches: vec![
+                (1, Tree::Leaf { value: "stay" }),
+                (1, Tree::Leaf { value: "leave" }),
+            ],
+        };
+        for _ in 0..LOOP_WINDOW {
+            novelty.visit(1);
+            novelty.acted(1, &"stay");
+        }
+        assert_eq!(novelty.visited(), 1);
+
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let leaves = (0..100)
+            .filter(|_| novelty.pick(1, &tree, &mut rng).unwrap() == &"leave")
+            .count();
+        assert!(leaves > 80, "picked leave {} times", leaves);
+
+        assert!(novelty.escape_loop());
+        assert!(!novelty.escape_loop());
+    }
+}
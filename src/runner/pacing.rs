@@ -0,0 +1,149 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::browser::actions::BrowserAction;
+use crate::browser::state::BrowserState;
+
+/// DOM mutations in a single step above which the page is considered busy.
+const BUSY_DOM_MUTATIONS: u64 = 200;
+/// Changed coverage edges in a single step above which the page is considered busy.
+const BUSY_EDGES: usize = 500;
+
+const FACTOR_MIN: f64 = 0.25;
+const FACTOR_MAX: f64 = 4.0;
+const SPEED_UP: f64 = 0.75;
+const SLOW_DOWN: f64 = 1.5;
+
+/// Settle delay per unit of slowdown above the base pace.
+const SETTLE_DELAY_UNIT: Duration = Duration::from_millis(100);
+
+/// How much the page was doing between the previous state and the current one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Activity {
+    /// Nothing changed: no DOM mutations, no new coverage and the same transition hash.
+    Static,
+    Changing,
+    /// Heavy DOM mutation or script activity.
+    Busy,
+}
+
+impl Activity {
+    pub fn classify(
+        dom_mutations: u64,
+        edges_changed: usize,
+        hash_changed: bool,
+    ) -> Self {
+        if dom_mutations >= BUSY_DOM_MUTATIONS || edges_changed >= BUSY_EDGES {
+            Activity::Busy
+        } else if dom_mutations == 0 && edges_changed == 0 && !hash_changed {
+            Activity::Static
+        } else {
+            Activity::Changing
+        }
+    }
+}
+
+/// The pace picked after observing a state, exposed in run events for tuning.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct PacingDecision {
+    pub activity: Activity,
+    /// Multiplier applied to action timeouts, below 1 when speeding up.
+    pub timeout_factor: f64,
+    /// Delay after a DOM mutation before capturing the next state.
+    pub settle_delay: Duration,
+}
+
+/// Adapts the pace of actions to the activity of the page: speeding up while it's static,
+/// and slowing down (waiting longer and coalescing snapshots) while it's busy.
+#[derive(Debug)]
+pub struct Pacing {
+    factor: f64,
+    last_transition_hash: Option<u64>,
+}
+
+impl Default for Pacing {
+    fn default() -> Self {
+        Pacing {
+            factor: 1.0,
+            last_transition_hash: None,
+        }
+    }
+}
+
+impl Pacing {
+    pub fn observe(&mut self, state: &BrowserState) -> PacingDecision {
+        let hash_changed = state.transition_hash != self.last_transition_hash;
+        self.last_transition_hash = state.transition_hash;
+        self.update(Activity::classify(
+            state.dom_mutations,
+            state.coverage.edges_new.len(),
+            hash_changed,
+        ))
+    }
+
+    fn update(&mut self, activity: Activity) -> PacingDecision {
+        self.factor = match activity {
+            Activity::Static => (self.factor * SPEED_UP).max(FACTOR_MIN),
+            Activity::Busy => (self.factor * SLOW_DOWN).min(FACTOR_MAX),
+            // Drift back towards the base pace.
+            Activity::Changing => (self.factor + 1.0) / 2.0,
+        };
+        PacingDecision {
+            activity,
+            timeout_factor: self.factor,
+            settle_delay: SETTLE_DELAY_UNIT
+                .mul_f64((self.factor - 1.0).max(0.0)),
+        }
+    }
+
+    /// Scales the base timeout of an action to the current pace. Text entry is never sped
+    /// up, as that would abort typing before it's done.
+    pub fn timeout(&self, action: &BrowserAction, base: Duration) -> Duration {
+        match action {
+            BrowserAction::TypeText { .. } => {
+                base.mul_f64(self.factor.max(1.0))
+            }
+            _ => base.mul_f64(self.factor),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_activity() {
+        assert_eq!(Activity::classify(0, 0, false), Activity::Static);
+        assert_eq!(Activity::classify(0, 0, true), Activity::Changing);
+        assert_eq!(Activity::classify(3, 10, true), Activity::Changing);
+        assert_eq!(Activity::classify(1000, 0, true), Activity::Busy);
+        assert_eq!(Activity::classify(0, 1000, false), Activity::Busy);
+    }
+
+    #[test]
+    fn test_pacing_bounds_and_settling() {
+        let mut pacing = Pacing::default();
+        for _ in 0..20 {
+            pacing.update(Activity::Static);
+        }
+        assert_eq!(pacing.factor, FACTOR_MIN);
+        assert_eq!(
+            pacing.timeout(&BrowserAction::Reload, Duration::from_secs(2)),
+            Duration::from_millis(500)
+        );
+
+        let mut decision = pacing.update(Activity::Busy);
+        assert_eq!(decision.settle_delay, Duration::ZERO);
+        for _ in 0..20 {
+            decision = pacing.update(Activity::Busy);
+        }
+        assert_eq!(decision.timeout_factor, FACTOR_MAX);
+        assert_eq!(decision.settle_delay, Duration::from_millis(300));
+
+        let decision = pacing.update(Activity::Changing);
+        assert_eq!(decision.timeout_factor, 2.5);
+    }
+}
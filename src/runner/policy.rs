@@ -0,0 +1,194 @@
+use std::time::Duration;
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json as json;
+use url::Url;
+
+use crate::runner::transactions::PlannedAction;
+
+/// Where to ask for the next action, and how long to wait for an answer.
+#[derive(Clone, Debug)]
+pub struct PolicyOptions {
+    pub url: Url,
+    pub timeout: Duration,
+}
+
+/// What the policy is told of a state.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StateSummary<'a> {
+    /// Number of states before this one.
+    pub index: u64,
+    pub url: &'a str,
+    pub title: &'a str,
+    /// Identifies the state among those visited.
+    pub fingerprint: u64,
+    /// Edges covered in this state but not in the previous one.
+    pub edges_changed: usize,
+    /// Names of the properties violated in this state.
+    pub violations: Vec<&'a str>,
+}
+
+#[derive(Serialize)]
+struct PolicyRequest<'a> {
+    state: &'a StateSummary<'a>,
+    candidates: Vec<json::Value>,
+}
+
+#[derive(Deserialize)]
+struct PolicyResponse {
+    /// The index of the candidate picked, or none to pick at random.
+    choice: Option<usize>,
+}
+
+/// Delegates picking actions to an external policy over HTTP, e.g. a model guiding the
+/// exploration, and falls back to picking at random when it fails or is too slow.
+pub struct ExternalPolicy {
+    client: reqwest::Client,
+    url: Url,
+}
+
+/// A candidate as sent to the policy: a browser action as in traces, or a transaction
+/// with its name and steps.
+fn candidate_json(action: &PlannedAction) -> json::Value {
+    match action {
+        PlannedAction::Single(action) => {
+            json::to_value(action).unwrap_or(json::Value::Null)
+        }
+        PlannedAction::Transaction(transaction) => json::json!({
+            "Transaction": {
+                "name": transaction.name,
+                "steps": transaction.steps,
+            }
+        }),
+    }
+}
+
+impl ExternalPolicy {
+    pub fn new(options: &PolicyOptions) -> Result<Self> {
+        Ok(ExternalPolicy {
+            client: reqwest::Client::builder()
+                .timeout(options.timeout)
+                .build()?,
+            url: options.url.clone(),
+        })
+    }
+
+    /// The candidate picked by the policy, or none when it leaves the pick to chance or
+    /// can't be asked.
+    pub async fn choose<'a>(
+        &self,
+        state: &StateSummary<'_>,
+        candidates: &[&'a PlannedAction],
+    ) -> Option<&'a PlannedAction> {
+        if candidates.len() < 2 {
+            return None;
+        }
+        match self.request(state, candidates).await {
+            Ok(Some(index)) if index < candidates.len() => {
+                log::debug!("external policy picked candidate {}", index);
+                Some(candidates[index])
+            }
+            Ok(Some(index)) => {
+                log::warn!(
+                    "external policy picked candidate {} out of {}, picking at random",
+                    index,
+                    candidates.len()
+                );
+                None
+            }
+            Ok(None) => None,
+            Err(error) => {
+                log::warn!(
+                    "external policy failed, picking at random: {:#}",
+                    error
+                );
+                None
+            }
+        }
+    }
+
+    async fn request(
+        &self,
+        state: &StateSummary<'_>,
+        candidates: &[&PlannedAction],
+    ) -> Result<Option<usize>> {
+        let request = PolicyRequest {
+            state,
+            candidates: candidates
+                .iter()
+                .map(|action| candidate_json(action))
+                .collect(),
+        };
+        let response = self
+            .client
+            .post(self.url.clone())
+            .header("content-type", "application/json")
+            .body(json::to_vec(&request)?)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            bail!("status {}", response.status());
+        }
+        let response: PolicyResponse =
+            json::from_slice(&response.bytes().await?)?;
+        Ok(response.choice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::actions::BrowserAction;
+    use axum::{Json, Router, routing::post};
+
+    /// Serves a policy always picking the last candidate, after a delay.
+    async fn serve_policy(delay: Duration) -> Url {
+        let router = Router::new().route(
+            "/",
+            post(move |Json(request): Json<json::Value>| async move {
+                tokio::time::sleep(delay).await;
+                let candidates = request["candidates"].as_array().unwrap();
+                Json(json::json!({ "choice": candidates.len() - 1 }))
+            }),
+        );
+        let listener =
+            tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        Url::parse(&format!("http://{}/", address)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_choose_with_fallback() {
+        let back = PlannedAction::Single(BrowserAction::Back);
+        let reload = PlannedAction::Single(BrowserAction::Reload);
+        let candidates = [&back, &reload];
+        let state = StateSummary {
+            index: 0,
+            url: "http://localhost/",
+            title: "",
+            fingerprint: 0,
+            edges_changed: 0,
+            violations: vec![],
+        };
+
+        let policy = ExternalPolicy::new(&PolicyOptions {
+            url: serve_policy(Duration::ZERO).await,
+            timeout: Duration::from_secs(5),
+        })
+        .unwrap();
+        assert!(matches!(
+            policy.choose(&state, &candidates).await,
+            Some(PlannedAction::Single(BrowserAction::Reload))
+        ));
+
+        let slow = ExternalPolicy::new(&PolicyOptions {
+            url: serve_policy(Duration::from_secs(5)).await,
+            timeout: Duration::from_millis(50),
+        })
+        .unwrap();
+        assert!(slow.choose(&state, &candidates).await.is_none());
+    }
+}
@@ -0,0 +1,528 @@
+use std::collections::VecDeque;
+use std::time::SystemTime;
+
+use anyhow::{Result, anyhow};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::browser::actions::BrowserAction;
+use crate::browser::checkpoints::{
+    CheckpointDecision, CheckpointStore, Checkpoints,
+};
+use crate::runner::RunnerOptions;
+use crate::runner::exhaustive::ExhaustiveSearch;
+use crate::runner::novelty::Novelty;
+use crate::runner::transactions::{
+    PlannedAction, Transaction, TransactionInProgress,
+};
+use crate::specification::worker::PropertyValue;
+use crate::suppressions::fingerprint;
+use crate::trace::PropertyViolation;
+use crate::tree::Tree;
+
+/// What the exploration carries from one state to the next, besides the browser and the
+/// verifier.
+pub struct Exploration {
+    pub transaction: Option<TransactionInProgress>,
+    /// Actions of the specification's setup left to apply before exploring.
+    pub setup: VecDeque<BrowserAction>,
+    /// Recorded actions left to replay.
+    pub replay: Option<VecDeque<BrowserAction>>,
+    pub exhaustive: Option<ExhaustiveSearch>,
+    pub checkpoints: Option<Checkpoints>,
+    /// The checkpoints that actions may restore, by id.
+    pub saved_checkpoints: CheckpointStore,
+    pub novelty: Option<Novelty>,
+    pub rng: ChaCha8Rng,
+}
+
+impl Exploration {
+    pub fn new(options: &RunnerOptions) -> Self {
+        Exploration {
+            transaction: None,
+            setup: VecDeque::new(),
+            replay: options.replay.clone().map(VecDeque::from),
+            exhaustive: options.exhaustive.map(ExhaustiveSearch::new),
+            checkpoints: options.checkpointing.clone().map(Checkpoints::new),
+            saved_checkpoints: CheckpointStore::new(
+                options.saved_checkpoints.clone(),
+            ),
+            novelty: options.novelty.map(Novelty::new),
+            rng: ChaCha8Rng::seed_from_u64(options.seed),
+        }
+    }
+
+    /// Whether the next action is to be picked among those generated, rather than taken
+    /// from the setup, a recording, a search or a transaction in progress.
+    pub fn picks_at_random(&self, options: &RunnerOptions) -> bool {
+        !options.observe_only
+            && self.setup.is_empty()
+            && self.replay.is_none()
+            && self.exhaustive.is_none()
+            && self.transaction.is_none()
+    }
+}
+
+/// What is known of a state once its properties and action generators have been stepped.
+pub struct StepInputs<'a> {
+    /// Number of states before this one.
+    pub index: u64,
+    pub timestamp: SystemTime,
+    /// Identifies the state among those visited, see [`fingerprint`](crate::runner::novelty::fingerprint).
+    pub fingerprint: u64,
+    pub properties: Vec<(String, PropertyValue)>,
+    pub actions: Tree<PlannedAction>,
+    /// The action picked by an external policy, instead of at random.
+    pub chosen: Option<PlannedAction>,
+    pub within_origin: bool,
+    /// Whether the viewport switches after this state, in which case no action is picked
+    /// from its layout, and the page is only observed.
+    pub switching_viewport: bool,
+    /// Whether the payment guard and the click deny-list allow an action.
+    pub allowed: &'a dyn Fn(&BrowserAction) -> bool,
+}
+
+#[derive(Debug)]
+pub enum NextAction {
+    /// Resume the page without applying any action, until it changes.
+    Observe,
+    Apply(BrowserAction),
+    /// Start a transaction, which extracts from the state before its first step is
+    /// applied.
+    Start(Transaction),
+}
+
+/// Why the run stops after a state.
+#[derive(Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// Keep the browser at the violating state until shut down.
+    PausedOnViolation,
+    Violation,
+    AllPropertiesDefinite,
+    Replayed,
+    /// All action sequences up to the depth were explored, this many.
+    Explored(usize),
+}
+
+#[derive(Debug)]
+pub struct StepDecision {
+    pub violations: Vec<PropertyViolation>,
+    /// The action to take after the state, or why there's none.
+    pub next: Result<NextAction>,
+    /// Save a checkpoint of the state before taking the next action.
+    pub save_checkpoint: bool,
+    pub stop: Option<StopReason>,
+}
+
+impl StepDecision {
+    /// Whether some of the violations aren't suppressed.
+    pub fn has_violations(&self) -> bool {
+        self.violations
+            .iter()
+            .any(|violation| violation.suppressed.is_none())
+    }
+}
+
+/// Outside the origin, going back is the only action allowed.
+pub fn allowed_at(action: &PlannedAction, within_origin: bool) -> bool {
+    within_origin
+        || matches!(action, PlannedAction::Single(BrowserAction::Back))
+}
+
+/// Decides what to do after a state: which violations to report, the next action, and
+/// whether to stop. Everything that needs the page (extracting, saving checkpoints,
+/// starting transactions) is left to the caller.
+pub fn advance(
+    inputs: StepInputs,
+    options: &RunnerOptions,
+    exploration: &mut Exploration,
+) -> Result<StepDecision> {
+    let mut violations = Vec::with_capacity(inputs.properties.len());
+    let mut all_properties_definite = true;
+    for (name, value) in inputs.properties {
+        match value {
+            PropertyValue::False(violation) => {
+                let fingerprint = fingerprint(&violation);
+                let suppressed = options
+                    .suppressions
+                    .find(&name, &fingerprint, inputs.timestamp)
+                    .cloned();
+                violations.push(PropertyViolation {
+                    name,
+                    violation,
+                    fingerprint,
+                    suppressed,
+                });
+            }
+            PropertyValue::Residual => {
+                all_properties_definite = false;
+            }
+            PropertyValue::True => {}
+        }
+    }
+
+    if let Some(novelty) = &mut exploration.novelty {
+        novelty.visit(inputs.fingerprint);
+    }
+
+    let within_origin = inputs.within_origin;
+    let allowed = inputs.allowed;
+    let action_tree = inputs.actions.filter(&|action| {
+        allowed_at(action, within_origin) && allowed(action.first())
+    });
+
+    // Continue the transaction in progress, if its next step is allowed.
+    let transaction_step = match &mut exploration.transaction {
+        Some(_) if inputs.switching_viewport => None,
+        Some(in_progress) => match in_progress.next_step().cloned() {
+            Some(step) if within_origin && allowed(&step) => {
+                in_progress.advance();
+                Some(step)
+            }
+            _ => {
+                log::warn!(
+                    "abandoning transaction `{}`, its next step is not allowed",
+                    in_progress.name()
+                );
+                exploration.transaction = None;
+                None
+            }
+        },
+        None => None,
+    };
+
+    let mut stop = None;
+    let setting_up = !exploration.setup.is_empty();
+    let next = if options.observe_only || inputs.switching_viewport {
+        Ok(NextAction::Observe)
+    } else if let Some(action) = exploration.setup.pop_front() {
+        Ok(NextAction::Apply(action))
+    } else if let Some(replay) = &mut exploration.replay {
+        match replay.pop_front() {
+            Some(action) => Ok(NextAction::Apply(action)),
+            None => {
+                stop = Some(StopReason::Replayed);
+                Ok(NextAction::Observe)
+            }
+        }
+    } else if let Some(search) = &mut exploration.exhaustive {
+        // Transactions are left out, as their steps depend on the states in between.
+        let available = action_tree
+            .prune()
+            .map(|tree| {
+                tree.leaves()
+                    .into_iter()
+                    .filter_map(|action| match action {
+                        PlannedAction::Single(action) => Some(action.clone()),
+                        PlannedAction::Transaction(_) => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        match search.next(available)? {
+            Some(action) => Ok(NextAction::Apply(action)),
+            None => {
+                stop = Some(StopReason::Explored(search.explored()));
+                Ok(NextAction::Observe)
+            }
+        }
+    } else if let Some(step) = transaction_step {
+        Ok(NextAction::Apply(step))
+    } else if within_origin
+        && exploration
+            .novelty
+            .as_mut()
+            .is_some_and(|novelty| novelty.escape_loop())
+    {
+        let can_go_back = action_tree.leaves().into_iter().any(|action| {
+            matches!(action, PlannedAction::Single(BrowserAction::Back))
+        });
+        let action = if can_go_back {
+            BrowserAction::Back
+        } else {
+            BrowserAction::Reload
+        };
+        log::info!("stuck in a loop of states, escaping with {:?}", action);
+        Ok(NextAction::Apply(action))
+    } else {
+        match action_tree.prune() {
+            None => Err(anyhow!("no actions available")),
+            Some(action_tree) => {
+                let picked = match (&inputs.chosen, &exploration.novelty) {
+                    (Some(chosen), _) => chosen,
+                    (None, Some(novelty)) => novelty.pick(
+                        inputs.fingerprint,
+                        &action_tree,
+                        &mut exploration.rng,
+                    )?,
+                    (None, None) => action_tree.pick(&mut exploration.rng)?,
+                };
+                match picked.clone() {
+                    PlannedAction::Single(action) => {
+                        Ok(NextAction::Apply(action))
+                    }
+                    PlannedAction::Transaction(transaction) => {
+                        Ok(NextAction::Start(transaction))
+                    }
+                }
+            }
+        }
+    };
+
+    // Save a checkpoint of this state or, instead of the picked action, branch off from
+    // an earlier one, when it's time to.
+    let branching = exploration.checkpoints.as_mut().filter(|_| {
+        matches!(next, Ok(NextAction::Apply(_)))
+            && !setting_up
+            && exploration.transaction.is_none()
+            && exploration.replay.is_none()
+            && exploration.exhaustive.is_none()
+    });
+    let mut save_checkpoint = false;
+    let next = match branching.and_then(|checkpoints| {
+        checkpoints.decide(inputs.index, &mut exploration.rng)
+    }) {
+        Some(CheckpointDecision::Save) => {
+            save_checkpoint = true;
+            next
+        }
+        Some(CheckpointDecision::Restore { id, url }) => {
+            log::info!("branching off from checkpoint at {}", url);
+            Ok(NextAction::Apply(BrowserAction::RestoreCheckpoint {
+                id,
+                url,
+            }))
+        }
+        None => next,
+    };
+    if let Some(novelty) = &mut exploration.novelty {
+        match &next {
+            Ok(NextAction::Apply(action)) => novelty.acted(
+                inputs.fingerprint,
+                &PlannedAction::Single(action.clone()),
+            ),
+            Ok(NextAction::Start(transaction)) => novelty.acted(
+                inputs.fingerprint,
+                &PlannedAction::Transaction(transaction.clone()),
+            ),
+            Ok(NextAction::Observe) | Err(_) => {}
+        }
+    }
+
+    let has_violations = violations
+        .iter()
+        .any(|violation| violation.suppressed.is_none());
+    let stop = if has_violations && options.pause_on_violation {
+        Some(StopReason::PausedOnViolation)
+    } else if has_violations && options.stop_on_violation {
+        Some(StopReason::Violation)
+    } else if all_properties_definite {
+        Some(StopReason::AllPropertiesDefinite)
+    } else {
+        stop
+    };
+
+    Ok(StepDecision {
+        violations,
+        next,
+        save_checkpoint,
+        stop,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Point;
+    use crate::specification::ltl::Violation;
+    use proptest::prelude::*;
+
+    fn click(x: f64) -> BrowserAction {
+        BrowserAction::Click {
+            name: "A".to_string(),
+            content: None,
+            point: Point { x, y: 0.0 },
+        }
+    }
+
+    fn tree(actions: Vec<BrowserAction>) -> Tree<PlannedAction> {
+        Tree::Branch {
+            branches: actions
+                .into_iter()
+                .map(|action| {
+                    (
+                        1,
+                        Tree::Leaf {
+                            value: PlannedAction::Single(action),
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    fn inputs(
+        properties: Vec<(String, PropertyValue)>,
+        actions: Vec<BrowserAction>,
+        within_origin: bool,
+    ) -> StepInputs<'static> {
+        StepInputs {
+            index: 1,
+            timestamp: SystemTime::UNIX_EPOCH,
+            fingerprint: 0,
+            properties,
+            actions: tree(actions),
+            chosen: None,
+            within_origin,
+            switching_viewport: false,
+            allowed: &|_| true,
+        }
+    }
+
+    fn residual() -> (String, PropertyValue) {
+        ("residual".to_string(), PropertyValue::Residual)
+    }
+
+    fn violated() -> (String, PropertyValue) {
+        (
+            "violated".to_string(),
+            PropertyValue::False(Violation::False {
+                time: SystemTime::UNIX_EPOCH,
+                condition: "false".to_string(),
+            }),
+        )
+    }
+
+    #[test]
+    fn test_stopping_criteria() {
+        let options = RunnerOptions::default();
+        let mut exploration = Exploration::new(&options);
+        let decision = advance(
+            inputs(vec![residual()], vec![click(1.0)], true),
+            &options,
+            &mut exploration,
+        )
+        .unwrap();
+        assert_eq!(decision.stop, None);
+        assert!(matches!(decision.next, Ok(NextAction::Apply(_))));
+
+        let decision = advance(
+            inputs(vec![violated()], vec![click(1.0)], true),
+            &options,
+            &mut exploration,
+        )
+        .unwrap();
+        assert!(decision.has_violations());
+        assert_eq!(decision.stop, Some(StopReason::AllPropertiesDefinite));
+
+        let options = RunnerOptions {
+            stop_on_violation: true,
+            ..RunnerOptions::default()
+        };
+        let decision = advance(
+            inputs(vec![residual(), violated()], vec![], true),
+            &options,
+            &mut exploration,
+        )
+        .unwrap();
+        assert_eq!(decision.stop, Some(StopReason::Violation));
+        assert!(decision.next.is_err());
+    }
+
+    #[test]
+    fn test_replay_stops_when_done() {
+        let options = RunnerOptions {
+            replay: Some(vec![BrowserAction::Reload]),
+            ..RunnerOptions::default()
+        };
+        let mut exploration = Exploration::new(&options);
+        let decision = advance(
+            inputs(vec![residual()], vec![click(1.0)], true),
+            &options,
+            &mut exploration,
+        )
+        .unwrap();
+        assert!(matches!(
+            decision.next,
+            Ok(NextAction::Apply(BrowserAction::Reload))
+        ));
+        let decision = advance(
+            inputs(vec![residual()], vec![click(1.0)], true),
+            &options,
+            &mut exploration,
+        )
+        .unwrap();
+        assert!(matches!(decision.next, Ok(NextAction::Observe)));
+        assert_eq!(decision.stop, Some(StopReason::Replayed));
+    }
+
+    #[test]
+    fn test_setup_before_exploring() {
+        let options = RunnerOptions::default();
+        let mut exploration = Exploration::new(&options);
+        exploration.setup = VecDeque::from([BrowserAction::Reload]);
+        let decision = advance(
+            inputs(vec![residual()], vec![click(1.0)], true),
+            &options,
+            &mut exploration,
+        )
+        .unwrap();
+        assert!(matches!(
+            decision.next,
+            Ok(NextAction::Apply(BrowserAction::Reload))
+        ));
+        let decision = advance(
+            inputs(vec![residual()], vec![click(1.0)], true),
+            &options,
+            &mut exploration,
+        )
+        .unwrap();
+        assert!(matches!(
+            decision.next,
+            Ok(NextAction::Apply(BrowserAction::Click { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_observe_when_switching_viewport() {
+        let options = RunnerOptions::default();
+        let mut exploration = Exploration::new(&options);
+        let decision = advance(
+            StepInputs {
+                switching_viewport: true,
+                ..inputs(vec![residual()], vec![click(1.0)], true)
+            },
+            &options,
+            &mut exploration,
+        )
+        .unwrap();
+        assert!(matches!(decision.next, Ok(NextAction::Observe)));
+    }
+
+    proptest! {
+        #[test]
+        fn test_only_back_outside_origin(
+            clicks in prop::collection::vec(0.0..100.0f64, 0..8),
+            back in any::<bool>(),
+            seed in any::<u64>(),
+        ) {
+            let options = RunnerOptions { seed, ..RunnerOptions::default() };
+            let mut exploration = Exploration::new(&options);
+            let mut actions: Vec<BrowserAction> = clicks.into_iter().map(click).collect();
+            if back {
+                actions.push(BrowserAction::Back);
+            }
+            let decision = advance(
+                inputs(vec![residual()], actions, false),
+                &options,
+                &mut exploration,
+            )
+            .unwrap();
+            match decision.next {
+                Ok(NextAction::Apply(BrowserAction::Back)) => prop_assert!(back),
+                Err(_) => prop_assert!(!back),
+                other => prop_assert!(false, "unexpected next action {:?}", other),
+            }
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use anyhow::{Result, bail};
+use serde_json as json;
+
+use crate::browser::actions::BrowserAction;
+use crate::browser::evaluation::IsolatedWorld;
+use crate::browser::state::BrowserState;
+use crate::runner::{evaluate_extractor, evaluate_function_call};
+use crate::specification::js::JsAction;
+use crate::trace::TransactionFailure;
+
+/// An action picked from the action generators: either a single browser action, or a
+/// transaction whose steps are applied one per state.
+#[derive(Clone, Debug)]
+pub enum PlannedAction {
+    Single(BrowserAction),
+    Transaction(Transaction),
+}
+
+impl PlannedAction {
+    pub fn from_js(action: JsAction) -> Result<Self> {
+        Ok(match action {
+            JsAction::Transaction {
+                name,
+                steps,
+                extract,
+                postcondition,
+            } => {
+                if steps.is_empty() {
+                    bail!("transaction `{}` has no steps", name);
+                }
+                PlannedAction::Transaction(Transaction {
+                    name,
+                    steps: steps
+                        .into_iter()
+                        .map(JsAction::to_browser_action)
+                        .collect::<Result<_>>()?,
+                    extract,
+                    postcondition,
+                })
+            }
+            action => PlannedAction::Single(action.to_browser_action()?),
+        })
+    }
+
+    /// The browser action applied first when this action is picked.
+    pub fn first(&self) -> &BrowserAction {
+        match self {
+            PlannedAction::Single(action) => action,
+            PlannedAction::Transaction(transaction) => &transaction.steps[0],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Transaction {
+    pub name: String,
+    pub steps: Vec<BrowserAction>,
+    extract: String,
+    postcondition: String,
+}
+
+/// A transaction being applied, along with the extracted value from before its first step.
+pub struct TransactionInProgress {
+    transaction: Transaction,
+    before: json::Value,
+    applied: usize,
+}
+
+impl TransactionInProgress {
+    pub async fn start(
+        transaction: Transaction,
+        state: &BrowserState,
+        state_partial: &json::Value,
+        world: Option<&IsolatedWorld>,
+    ) -> Result<Self> {
+        let before = evaluate_extractor(
+            state,
+            &transaction.extract,
+            state_partial,
+            world,
+        )
+        .await?;
+        log::info!("starting transaction `{}`", transaction.name);
+        Ok(TransactionInProgress {
+            transaction,
+            before,
+            applied: 0,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.transaction.name
+    }
+
+    pub fn next_step(&self) -> Option<&BrowserAction> {
+        self.transaction.steps.get(self.applied)
+    }
+
+    pub fn advance(&mut self) {
+        self.applied += 1;
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.applied == self.transaction.steps.len()
+    }
+
+    /// Checks the postcondition against the values extracted before the first step and
+    /// after the last one.
+    pub async fn check(
+        self,
+        state: &BrowserState,
+        state_partial: &json::Value,
+        world: Option<&IsolatedWorld>,
+    ) -> Result<Option<TransactionFailure>> {
+        let after = evaluate_extractor(
+            state,
+            &self.transaction.extract,
+            state_partial,
+            world,
+        )
+        .await?;
+        // Evaluated where the extractors are, so that the page's scripts can't interfere
+        // with it either.
+        let holds: json::Value = evaluate_function_call(
+            state,
+            world,
+            format!(
+                "(before, after) => ({})(before, after)",
+                self.transaction.postcondition
+            ),
+            vec![self.before.clone(), after.clone()],
+        )
+        .await?;
+        if holds == json::Value::Bool(true) {
+            log::info!("transaction `{}` succeeded", self.transaction.name);
+            return Ok(None);
+        }
+        log::debug!("transaction postcondition returned: {}", holds);
+        Ok(Some(TransactionFailure {
+            name: self.transaction.name,
+            steps: self.transaction.steps,
+            before: self.before,
+            after,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transaction(steps: Vec<JsAction>) -> JsAction {
+        JsAction::Transaction {
+            name: "add to cart".to_string(),
+            steps,
+            extract: "(state) => 1".to_string(),
+            postcondition: "(before, after) => true".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_planned_transaction_starts_with_first_step() {
+        let planned =
+            PlannedAction::from_js(transaction(vec![JsAction::Reload]))
+                .unwrap();
+        assert!(matches!(planned, PlannedAction::Transaction(_)));
+        assert!(matches!(planned.first(), BrowserAction::Reload));
+    }
+
+    #[test]
+    fn test_planned_transaction_rejects_empty_and_nested() {
+        assert!(PlannedAction::from_js(transaction(vec![])).is_err());
+        assert!(
+            PlannedAction::from_js(transaction(vec![transaction(vec![
+                JsAction::Back
+            ])]))
+            .is_err()
+        );
+    }
+}
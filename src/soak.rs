@@ -0,0 +1,152 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+
+use crate::browser::state::EdgeIndex;
+use crate::run_id::RunId;
+
+/// Parses durations like `8h`, `30m`, `45s` or `1h30m`.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let invalid =
+        || format!("invalid duration {:?}, expected e.g. 8h, 30m or 1h30m", s);
+    let mut total = Duration::ZERO;
+    let mut number = String::new();
+    for c in s.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let value: u64 = number.parse().map_err(|_| invalid())?;
+        number.clear();
+        let unit = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(invalid()),
+        };
+        total = value
+            .checked_mul(unit)
+            .and_then(|seconds| total.checked_add(Duration::from_secs(seconds)))
+            .ok_or_else(invalid)?;
+    }
+    if !number.is_empty() || total.is_zero() {
+        return Err(invalid());
+    }
+    Ok(total)
+}
+
+/// A run of a soak test, with one seed, until it was rotated.
+#[derive(Clone, Debug, Serialize)]
+pub struct SoakSegment {
+    pub seed: u64,
+    pub run_id: RunId,
+    pub started: SystemTime,
+    pub duration: Duration,
+    pub steps: u32,
+    /// Edges covered during this segment.
+    pub edges_covered: usize,
+    /// Edges covered during this segment but none of the previous ones.
+    pub edges_new: usize,
+    pub violated_properties: BTreeSet<String>,
+    pub exit_code: Option<i32>,
+    /// Why the segment failed before its time was up, e.g. as its browser crashed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The consolidated report of the segments of a soak test.
+#[derive(Debug, Default, Serialize)]
+pub struct SoakReport {
+    pub segments: Vec<SoakSegment>,
+    /// Edges covered by any segment.
+    pub edges_total: usize,
+    /// The segments (by run ID) violating each property.
+    pub violated_properties: BTreeMap<String, Vec<RunId>>,
+    /// The segments (by run ID) that failed.
+    pub failed_segments: Vec<RunId>,
+    #[serde(skip)]
+    covered: BTreeSet<EdgeIndex>,
+}
+
+impl SoakReport {
+    /// Adds a segment, counting the edges it's the first to cover.
+    pub fn add(
+        &mut self,
+        mut segment: SoakSegment,
+        covered: &BTreeSet<EdgeIndex>,
+    ) {
+        segment.edges_covered = covered.len();
+        segment.edges_new = covered.difference(&self.covered).count();
+        self.covered.extend(covered);
+        self.edges_total = self.covered.len();
+        for name in &segment.violated_properties {
+            self.violated_properties
+                .entry(name.clone())
+                .or_default()
+                .push(segment.run_id.clone());
+        }
+        if segment.error.is_some() {
+            self.failed_segments.push(segment.run_id.clone());
+        }
+        self.segments.push(segment);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("8h"), Ok(Duration::from_secs(8 * 3600)));
+        assert_eq!(parse_duration("30m"), Ok(Duration::from_secs(30 * 60)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("45s"), Ok(Duration::from_secs(45)));
+        assert!(parse_duration("45").is_err());
+        assert!(parse_duration("0m").is_err());
+        assert!(parse_duration("2d").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration(&format!("{}h", u64::MAX / 60)).is_err());
+        assert!(parse_duration(&format!("{}s1s", u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn test_report_counts_new_edges() {
+        let segment = |seed| SoakSegment {
+            seed,
+            run_id: RunId::new(seed),
+            started: SystemTime::UNIX_EPOCH,
+            duration: Duration::from_secs(60),
+            steps: 10,
+            edges_covered: 0,
+            edges_new: 0,
+            violated_properties: BTreeSet::from(["a".to_string()]),
+            exit_code: None,
+            error: None,
+        };
+        let mut report = SoakReport::default();
+        report.add(segment(1), &BTreeSet::from([1, 2, 3]));
+        report.add(segment(2), &BTreeSet::from([2, 3, 4, 5]));
+
+        let counts: Vec<(usize, usize)> = report
+            .segments
+            .iter()
+            .map(|segment| (segment.edges_covered, segment.edges_new))
+            .collect();
+        assert_eq!(counts, vec![(3, 3), (4, 2)]);
+        assert_eq!(report.edges_total, 5);
+        assert_eq!(report.violated_properties["a"].len(), 2);
+        assert!(report.failed_segments.is_empty());
+
+        let failed = SoakSegment {
+            violated_properties: BTreeSet::new(),
+            exit_code: Some(1),
+            error: Some("browser crashed".to_string()),
+            ..segment(3)
+        };
+        report.add(failed.clone(), &BTreeSet::new());
+        assert_eq!(report.failed_segments, vec![failed.run_id]);
+        assert_eq!(report.edges_total, 5);
+    }
+}
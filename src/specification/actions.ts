@@ -1,13 +1,16 @@
 import { type Generator } from "@antithesishq/bombadil/random";
 
-export type { Generator } from "@antithesishq/bombadil/random";
+export type { Charset, Generator } from "@antithesishq/bombadil/random";
 export {
   from,
   strings,
+  INTERNATIONAL,
   emails,
   integers,
   keycodes,
   randomRange,
+  randomStream,
+  RandomStream,
 } from "@antithesishq/bombadil/random";
 
 export interface Point {
@@ -15,15 +18,63 @@ export interface Point {
   y: number;
 }
 
+/** What happens to a request picked for a fault: sent on late, failed, or answered with a 500. */
+export type FaultKind = "delay" | "drop" | "serverError";
+
+/**
+ * How `alert`, `confirm` and `prompt` dialogs are answered: accepted, dismissed, either at
+ * random, or accepted with a text typed into prompts.
+ */
+export type DialogPolicy = "accept" | "dismiss" | "random" | { text: string };
+
 export type Action =
   | "Back"
   | "Forward"
   | "Reload"
+  | "GoOffline"
+  | "GoOnline"
+  | {
+      SetNetworkConditions: {
+        offline: boolean;
+        latencyMillis: number;
+        throughput: number;
+      };
+    }
   | { Click: { name: string; content?: string; point: Point } }
+  | { DoubleClick: { name: string; content?: string; point: Point } }
+  | { RightClick: { name: string; content?: string; point: Point } }
+  | { Hover: { point: Point } }
+  | { Focus: { selector: string } }
+  | { SelectOption: { selector: string; value: string } }
+  | { SetChecked: { selector: string; checked: boolean } }
+  | { DragAndDrop: { from: Point; to: Point } }
   | { TypeText: { text: string; delayMillis: number } }
   | { PressKey: { code: number } }
   | { ScrollUp: { origin: Point; distance: number } }
-  | { ScrollDown: { origin: Point; distance: number } };
+  | { ScrollDown: { origin: Point; distance: number } }
+  | {
+      InjectFaults: {
+        rules: {
+          pattern: string;
+          rate: number;
+          kinds: FaultKind[];
+          delayMillis: number;
+        }[];
+      };
+    }
+  | { SetInstrumentation: { enabled: boolean } }
+  | { SetDialogPolicy: { policy: DialogPolicy } }
+  | { CdpCommand: { method: string; params: Record<string, unknown> } }
+  | { SetViewport: { profile: string } }
+  | { AdvanceClock: { millis: number } }
+  | {
+      Transaction: {
+        name: string;
+        steps: Action[];
+        extract: string;
+        postcondition: string;
+      };
+    };
 
 // Tree
 
@@ -76,3 +127,26 @@ export function weighted(
     );
   });
 }
+
+// Setup
+
+export class Setup {
+  constructor(public actions: Action[]) {}
+}
+
+/**
+ * Actions applied in order once the origin has loaded, before exploring, e.g. for logging
+ * in or dismissing a cookie banner:
+ *
+ * ```ts
+ * export const login = setup([
+ *   { Focus: { selector: "#email" } },
+ *   { TypeText: { text: "ada@example.com", delayMillis: 0 } },
+ * ]);
+ * ```
+ *
+ * Setups of several exports are applied in the order of their names.
+ */
+export function setup(actions: Action[]): Setup {
+  return new Setup(actions);
+}
@@ -3,11 +3,19 @@ export {
   noUncaughtExceptions,
   noUnhandledPromiseRejections,
   noConsoleErrors,
+  noUnexpectedRedirects,
 } from "@antithesishq/bombadil/defaults/properties";
 
 export {
   scroll,
   clicks,
+  doubleClicks,
+  rightClicks,
+  hovers,
+  focuses,
+  selects,
+  checkboxes,
+  drags,
   inputs,
   navigation,
 } from "@antithesishq/bombadil/defaults/actions";
@@ -3,6 +3,7 @@ import {
   weighted,
   extract,
   strings,
+  INTERNATIONAL,
   emails,
   integers,
   keycodes,
@@ -90,6 +91,14 @@ export const scroll = actions(() => {
 
 // Clicks
 
+type ClickTarget = {
+  name: string;
+  content: string;
+  point: { x: number; y: number };
+  /** Identifies the element across states, even when the DOM is re-rendered. */
+  key: string;
+};
+
 const clickablePoints = extract((state) => {
   if (!state.document.body) return [];
 
@@ -105,11 +114,6 @@ const clickablePoints = extract((state) => {
     "treeitem",
   ];
 
-  type ClickTarget = {
-    name: string;
-    content: string;
-    point: { x: number; y: number };
-  };
   const targets: ClickTarget[] = [];
   const added = new Set<Element>();
 
@@ -139,6 +143,46 @@ const clickablePoints = extract((state) => {
     );
   }
 
+  // Hashes the element's path of tag names (and ids) with its position among siblings of
+  // the same tag, and its text.
+  function stableKey(element: Element, content: string): string {
+    const path: string[] = [];
+    for (
+      let current: Element | null = element;
+      current && current !== state.document.body;
+      current = current.parentElement
+    ) {
+      let index = 0;
+      for (
+        let sibling = current.previousElementSibling;
+        sibling;
+        sibling = sibling.previousElementSibling
+      ) {
+        if (sibling.nodeName === current.nodeName) index++;
+      }
+      const id = current.id ? `#${current.id}` : "";
+      path.push(`${current.nodeName}${id}:${index}`);
+    }
+    let hash = 0x811c9dc5;
+    for (const c of path.reverse().join(">") + "|" + content) {
+      hash = Math.imul(hash ^ c.charCodeAt(0), 0x01000193) >>> 0;
+    }
+    return hash.toString(16);
+  }
+
+  function target(
+    element: Element,
+    point: { x: number; y: number },
+  ): ClickTarget {
+    const content = (element.textContent ?? "").trim().replace(/\s+/g, " ");
+    return {
+      name: element.nodeName,
+      content,
+      point,
+      key: stableKey(element, content),
+    };
+  }
+
   // Like querySelectorAll, but searches recursively into shadow roots and iframes.
   //
   // TODO: make this a part of the bombadil package so that others can use it (depends
@@ -193,11 +237,7 @@ const clickablePoints = extract((state) => {
     if (!point) continue;
     if (!inViewport(point)) continue;
 
-    targets.push({
-      name: anchor.nodeName,
-      content: (anchor.textContent ?? "").trim().replace(/\s+/g, " "),
-      point,
-    });
+    targets.push(target(anchor, point));
     added.add(anchor);
   }
 
@@ -223,11 +263,7 @@ const clickablePoints = extract((state) => {
       continue;
     }
 
-    targets.push({
-      name: element.nodeName,
-      content: (element.textContent ?? "").trim().replace(/\s+/g, " "),
-      point,
-    });
+    targets.push(target(element, point));
     added.add(element);
   }
 
@@ -243,24 +279,290 @@ const clickablePoints = extract((state) => {
     if (!point) continue;
     if (!inViewport(point)) continue;
 
-    targets.push({
-      name: element.nodeName,
-      content: (element.textContent ?? "").trim().replace(/\s+/g, " "),
-      point,
-    });
+    targets.push(target(element, point));
+    added.add(element);
+  }
+
+  // Elements with click listeners, found by the browser, that don't look clickable, e.g.
+  // a `div` with a handler. The element hit at the center of one is skipped when it's in
+  // an element found above.
+  for (const { types, point } of state.listeners) {
+    if (!types.includes("click")) continue;
+    if (!inViewport(point)) continue;
+
+    const element = state.document.elementFromPoint(point.x, point.y);
+    if (!element || !isVisible(element)) continue;
+    let covered = false;
+    for (
+      let current: Element | null = element;
+      current;
+      current = current.parentElement
+    ) {
+      if (added.has(current)) {
+        covered = true;
+        break;
+      }
+    }
+    if (covered) continue;
+
+    targets.push(target(element, point));
     added.add(element);
   }
 
   return targets;
 });
 
+const lastClickPoint = extract((state) => {
+  const action = state.lastAction;
+  if (action === null || typeof action !== "object" || !("Click" in action)) {
+    return null;
+  }
+  return action.Click.point;
+});
+
+// Clicks are biased towards elements not clicked yet, and elements that appeared in the
+// last few states, rather than clicking the same header links over and over.
+
+/** Number of states during which an element that appeared is favored. */
+const RECENT_STATES = 3;
+
+const clickCounts = new Map<string, number>();
+const firstSeen = new Map<string, number>();
+let previousTargets: ClickTarget[] = [];
+let stateIndex = 0;
+
+function clickWeight(target: ClickTarget): number {
+  const clicked = clickCounts.get(target.key) ?? 0;
+  const seen = firstSeen.get(target.key) ?? stateIndex;
+  const recent = stateIndex - seen < RECENT_STATES;
+  return Math.max(1, Math.round(8 / (1 + clicked))) + (recent ? 4 : 0);
+}
+
 export const clicks = actions(() => {
+  stateIndex++;
+  const clickedPoint = lastClickPoint.current;
+  const clicked = clickedPoint
+    ? previousTargets.find(
+        ({ point }) => point.x === clickedPoint.x && point.y === clickedPoint.y,
+      )
+    : undefined;
+  if (clicked) {
+    clickCounts.set(clicked.key, (clickCounts.get(clicked.key) ?? 0) + 1);
+  }
+
+  previousTargets =
+    contentType.current === "text/html" ? clickablePoints.current : [];
+  for (const { key } of previousTargets) {
+    if (!firstSeen.has(key)) firstSeen.set(key, stateIndex);
+  }
+  return weighted(
+    previousTargets.map(
+      (target) =>
+        [
+          clickWeight(target),
+          {
+            Click: {
+              name: target.name,
+              content: target.content,
+              point: target.point,
+            },
+          },
+        ] as [number, Action],
+    ),
+  ).generate();
+});
+
+// Double clicks and right clicks, on the elements listening for them
+
+const listeners = extract((state) =>
+  state.listeners.filter(
+    ({ point }) =>
+      point.x >= 0 &&
+      point.x <= state.window.innerWidth &&
+      point.y >= 0 &&
+      point.y <= state.window.innerHeight,
+  ),
+);
+
+export const doubleClicks = actions(() => {
+  if (contentType.current !== "text/html") return [];
+  return listeners.current
+    .filter(({ types }) => types.includes("dblclick"))
+    .map(({ name, point }) => ({ DoubleClick: { name, point } }) as Action);
+});
+
+export const rightClicks = actions(() => {
+  if (contentType.current !== "text/html") return [];
+  return listeners.current
+    .filter(({ types }) => types.includes("contextmenu"))
+    .map(({ name, point }) => ({ RightClick: { name, point } }) as Action);
+});
+
+// Hovers and focus
+
+export const hovers = actions(() => {
   if (contentType.current !== "text/html") return [];
   return clickablePoints.current.map(
-    ({ name, content, point }) =>
-      ({
-        Click: { name, content, point },
-      }) as Action,
+    ({ point }) => ({ Hover: { point } }) as Action,
+  );
+});
+
+// The focusable elements and form controls in the document, by selector. Elements in
+// shadow roots and iframes are left out, as `document.querySelector` doesn't reach them.
+const formElements = extract((state) => {
+  const elements = {
+    focusable: [] as string[],
+    selects: [] as { selector: string; values: string[] }[],
+    checkables: [] as { selector: string; checked: boolean; radio: boolean }[],
+  };
+  if (!state.document.body) return elements;
+
+  const FOCUSABLE =
+    "a[href],button,input,select,textarea,[tabindex],[contenteditable=true]";
+
+  function selector(element: Element): string {
+    const parts: string[] = [];
+    for (
+      let current: Element | null = element;
+      current && current !== state.document.documentElement;
+      current = current.parentElement
+    ) {
+      if (current.id) {
+        parts.push(`#${CSS.escape(current.id)}`);
+        break;
+      }
+      let index = 1;
+      for (
+        let sibling = current.previousElementSibling;
+        sibling;
+        sibling = sibling.previousElementSibling
+      ) {
+        if (sibling.nodeName === current.nodeName) index++;
+      }
+      parts.push(`${current.nodeName.toLowerCase()}:nth-of-type(${index})`);
+    }
+    return parts.reverse().join(" > ");
+  }
+
+  for (const element of Array.from(
+    state.document.body.querySelectorAll(FOCUSABLE),
+  )) {
+    if (element.getAttribute("tabindex") === "-1") continue;
+    if ((element as HTMLButtonElement).disabled) continue;
+    const rect = element.getBoundingClientRect();
+    if (rect.width === 0 || rect.height === 0) continue;
+
+    if (element instanceof HTMLSelectElement) {
+      const values = Array.from(element.options)
+        .filter((option) => !option.disabled && !option.selected)
+        .map((option) => option.value);
+      if (values.length > 0) {
+        elements.selects.push({ selector: selector(element), values });
+      }
+    }
+    if (
+      element instanceof HTMLInputElement &&
+      (element.type === "checkbox" || element.type === "radio")
+    ) {
+      elements.checkables.push({
+        selector: selector(element),
+        checked: element.checked,
+        radio: element.type === "radio",
+      });
+    }
+    if (element !== state.document.activeElement) {
+      elements.focusable.push(selector(element));
+    }
+  }
+  return elements;
+});
+
+export const focuses = actions(() => {
+  if (contentType.current !== "text/html") return [];
+  return formElements.current.focusable.map(
+    (selector) => ({ Focus: { selector } }) as Action,
+  );
+});
+
+// Selects, checkboxes and radio buttons, set directly, as clicks often miss native
+// dropdowns
+
+export const selects = actions(() => {
+  if (contentType.current !== "text/html") return [];
+  return formElements.current.selects.flatMap(({ selector, values }) =>
+    values.map((value) => ({ SelectOption: { selector, value } }) as Action),
+  );
+});
+
+export const checkboxes = actions(() => {
+  if (contentType.current !== "text/html") return [];
+  // Radio buttons are unchecked by checking another one in their group.
+  return formElements.current.checkables
+    .filter(({ checked, radio }) => !(radio && checked))
+    .map(
+      ({ selector, checked }) =>
+        ({ SetChecked: { selector, checked: !checked } }) as Action,
+    );
+});
+
+// Drag and drop
+
+// Draggable elements, each with the places it could be dropped: the other draggable
+// elements and the containers of draggable elements, e.g. the columns of a board.
+const dragTargets = extract((state) => {
+  if (!state.document.body) return [];
+
+  type Point = { x: number; y: number };
+
+  function center(element: Element): Point | null {
+    const rect = element.getBoundingClientRect();
+    if (rect.width === 0 || rect.height === 0) return null;
+    const point = {
+      x: rect.left + rect.width / 2,
+      y: rect.top + rect.height / 2,
+    };
+    if (
+      point.x < 0 ||
+      point.x > state.window.innerWidth ||
+      point.y < 0 ||
+      point.y > state.window.innerHeight
+    ) {
+      return null;
+    }
+    return point;
+  }
+
+  const draggables = Array.from(
+    state.document.body.querySelectorAll("[draggable=true]"),
+  );
+  const dropTargets = new Set<Element>(draggables);
+  for (const element of draggables) {
+    if (element.parentElement) dropTargets.add(element.parentElement);
+  }
+  for (const element of Array.from(
+    state.document.body.querySelectorAll("[dropzone]"),
+  )) {
+    dropTargets.add(element);
+  }
+
+  const drags: { from: Point; to: Point }[] = [];
+  for (const element of draggables) {
+    const from = center(element);
+    if (!from) continue;
+    for (const target of dropTargets) {
+      if (target === element || target.contains(element)) continue;
+      const to = center(target);
+      if (!to) continue;
+      drags.push({ from, to });
+    }
+  }
+  return drags;
+});
+
+export const drags = actions(() => {
+  if (contentType.current !== "text/html") return [];
+  return dragTargets.current.map(
+    ({ from, to }) => ({ DragAndDrop: { from, to } }) as Action,
   );
 });
 
@@ -281,6 +583,11 @@ const activeInput = extract((state) => {
   return null;
 });
 
+// Non-ASCII text, which input handling gets wrong more often than plain text.
+function internationalText(): string {
+  return strings().minSize(1).charsets(INTERNATIONAL).generate();
+}
+
 export const inputs = actions(() => {
   if (contentType.current !== "text/html") return [];
   const type = activeInput.current;
@@ -292,6 +599,7 @@ export const inputs = actions(() => {
     return weighted([
       [1, { PressKey: { code: keycodes().generate() } }],
       [3, { TypeText: { text: strings().minSize(1).generate(), delayMillis } }],
+      [1, { TypeText: { text: internationalText(), delayMillis } }],
     ]).generate();
   }
 
@@ -303,6 +611,7 @@ export const inputs = actions(() => {
           3,
           { TypeText: { text: strings().minSize(1).generate(), delayMillis } },
         ],
+        [1, { TypeText: { text: internationalText(), delayMillis } }],
       ]).generate();
     case "email":
       return weighted([
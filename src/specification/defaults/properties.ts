@@ -28,3 +28,41 @@ const consoleErrors = extract((state) =>
 export const noConsoleErrors = always(
   () => consoleErrors.current?.length === 0,
 );
+
+const unexpectedRedirects = extract((state) => {
+  const allowed = state.context.allowedRedirectOrigins;
+  const allowedOrigins = Array.isArray(allowed) ? allowed : [];
+  return state.redirects.filter((chain) => {
+    const urls = [...chain.hops.map((hop) => hop.url), chain.url];
+    const origins = urls.map((url) => new URL(url).origin);
+    return (
+      chain.looped ||
+      origins.some(
+        (origin, index) =>
+          index > 0 &&
+          origin !== origins[index - 1] &&
+          !allowedOrigins.includes(origin),
+      )
+    );
+  });
+});
+
+/**
+ * Redirect chains of navigations should neither loop nor go to another origin, unless
+ * it's one of the `allowedRedirectOrigins` of the run context (e.g. a sign-in provider).
+ */
+export const noUnexpectedRedirects = always(
+  () => unexpectedRedirects.current.length === 0,
+);
+
+const unloadBlockers = extract((state) =>
+  state.dialogs.filter((dialog) => dialog.type === "beforeunload"),
+);
+
+/**
+ * Not among the defaults, as plenty of pages ask before discarding unsaved changes on
+ * purpose. Export it for pages that shouldn't.
+ */
+export const noUnloadBlockers = always(
+  () => unloadBlockers.current.length === 0,
+);
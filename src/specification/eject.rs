@@ -0,0 +1,103 @@
+use std::path::{Path, PathBuf};
+
+use include_dir::{Dir, DirEntry, File, include_dir};
+use serde_json as json;
+
+use crate::specification::result::{Result, SpecificationError};
+
+static TS_DIR: Dir =
+    include_dir!("$CARGO_MANIFEST_DIR/target/specification-types");
+
+const PACKAGE_NAME: &str = "@antithesishq/bombadil";
+
+/// Writes the TypeScript sources of the embedded `@antithesishq/bombadil` modules to
+/// `directory`, along with a `tsconfig.json` mapping the module paths to them, so that
+/// editors type check specifications against the exact runtime version of this binary.
+/// Returns the paths of the written files.
+pub async fn eject_types(directory: &Path) -> Result<Vec<PathBuf>> {
+    let mut written = Vec::new();
+    let mut paths = json::Map::new();
+
+    for file in files(&TS_DIR) {
+        let destination = directory.join(file.path());
+        if let Some(parent) = destination.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&destination, file.contents()).await?;
+        written.push(destination);
+
+        let relative = file.path().to_string_lossy().replace('\\', "/");
+        paths.insert(
+            import_path(&relative)?,
+            json::json!([format!("./{}", relative)]),
+        );
+    }
+
+    let tsconfig = json::json!({
+        "compilerOptions": {
+            "module": "nodenext",
+            "target": "esnext",
+            "lib": ["es2021", "dom"],
+            "strict": true,
+            "skipLibCheck": true,
+            "paths": paths,
+        }
+    });
+    let tsconfig_path = directory.join("tsconfig.json");
+    tokio::fs::write(
+        &tsconfig_path,
+        format!(
+            "// Generated by bombadil {}, extend this from your own tsconfig.json.\n{}\n",
+            env!("CARGO_PKG_VERSION"),
+            json::to_string_pretty(&tsconfig).map_err(|error| {
+                SpecificationError::OtherError(error.to_string())
+            })?
+        ),
+    )
+    .await?;
+    written.push(tsconfig_path);
+
+    Ok(written)
+}
+
+fn files<'a>(dir: &'a Dir<'a>) -> Vec<&'a File<'a>> {
+    let mut found = Vec::new();
+    for entry in dir.entries() {
+        match entry {
+            DirEntry::Dir(dir) => found.extend(files(dir)),
+            DirEntry::File(file) => found.push(file),
+        }
+    }
+    found
+}
+
+/// The import path of a module, e.g. `defaults/actions.ts` is imported as
+/// `@antithesishq/bombadil/defaults/actions`.
+fn import_path(relative: &str) -> Result<String> {
+    let module = relative.strip_suffix(".ts").ok_or_else(|| {
+        SpecificationError::OtherError(format!(
+            "unexpected embedded file: {}",
+            relative
+        ))
+    })?;
+    Ok(if module == "index" {
+        PACKAGE_NAME.to_string()
+    } else {
+        format!("{}/{}", PACKAGE_NAME, module)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_path() {
+        assert_eq!(import_path("index.ts").unwrap(), "@antithesishq/bombadil");
+        assert_eq!(
+            import_path("defaults/actions.ts").unwrap(),
+            "@antithesishq/bombadil/defaults/actions"
+        );
+        assert!(import_path("tsconfig.json").is_err());
+    }
+}
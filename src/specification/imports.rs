@@ -0,0 +1,346 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use oxc::span::SourceType;
+use serde::{Deserialize, Serialize};
+use serde_json as json;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::specification::module_loader::{
+    imported_specifiers, is_local_import, resolve_import, transpile,
+};
+use crate::specification::result::{Result, SpecificationError};
+
+/// Name of the lockfile, next to the specification, recording the integrity of every module
+/// imported by URL.
+pub const LOCKFILE_NAME: &str = "bombadil.lock.json";
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A module imported by URL, transpiled to JavaScript.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RemoteModule {
+    pub url: String,
+    pub source: String,
+}
+
+/// A module imported by path, transpiled to JavaScript, bundled with the specification so
+/// that it loads the same wherever the specification is verified.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LocalModule {
+    /// The path of the module, as resolved from the file importing it.
+    pub path: PathBuf,
+    pub source: String,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Lockfile {
+    /// Integrity (`sha256-<base64>`, as in subresource integrity) of each module by URL.
+    modules: BTreeMap<String, String>,
+}
+
+/// Fetches the modules imported by URL from the sources of a specification and of the
+/// modules it imports by path, and those they import in turn. Their integrity is checked against the lockfile next to the specification, and recorded
+/// there for modules imported for the first time. Fetched modules are cached, so that locked
+/// modules are only fetched once per machine.
+pub async fn fetch_remote_modules(
+    sources: &[&str],
+    specification_path: &Path,
+) -> Result<Vec<RemoteModule>> {
+    let mut queue = VecDeque::new();
+    for source in sources {
+        queue.extend(remote_imports(source, None)?);
+    }
+    if queue.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let lockfile_path = specification_path.with_file_name(LOCKFILE_NAME);
+    let mut lockfile = match tokio::fs::read_to_string(&lockfile_path).await {
+        Ok(contents) => json::from_str(&contents).map_err(|error| {
+            SpecificationError::OtherError(format!(
+                "invalid {}: {}",
+                lockfile_path.display(),
+                error
+            ))
+        })?,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            Lockfile::default()
+        }
+        Err(error) => return Err(error.into()),
+    };
+    let mut lockfile_changed = false;
+
+    let client = reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .build()
+        .map_err(other_error)?;
+    let mut visited = BTreeSet::new();
+    let mut modules = Vec::new();
+    while let Some(url) = queue.pop_front() {
+        if !visited.insert(url.clone()) {
+            continue;
+        }
+        let locked = lockfile.modules.get(url.as_str()).cloned();
+        let contents = load(&client, &url, locked.as_deref()).await?;
+        let integrity = integrity(contents.as_bytes());
+        match locked {
+            Some(locked) if locked != integrity => {
+                return Err(SpecificationError::OtherError(format!(
+                    "integrity mismatch for {}: {} has {}, but fetched {} (update the lockfile if the change is expected)",
+                    url,
+                    lockfile_path.display(),
+                    locked,
+                    integrity
+                )));
+            }
+            Some(_) => {}
+            None => {
+                log::info!("locking {} ({})", url, integrity);
+                lockfile.modules.insert(url.to_string(), integrity);
+                lockfile_changed = true;
+            }
+        }
+
+        let source_type = url_source_type(&url);
+        let source =
+            if [SourceType::cjs(), SourceType::mjs()].contains(&source_type) {
+                contents
+            } else {
+                transpile(&contents, Path::new(url.path()), &source_type)?
+            };
+        queue.extend(remote_imports(&source, Some(&url))?);
+        modules.push(RemoteModule {
+            url: url.to_string(),
+            source,
+        });
+    }
+
+    if lockfile_changed {
+        tokio::fs::write(
+            &lockfile_path,
+            json::to_string_pretty(&lockfile).map_err(other_error)? + "\n",
+        )
+        .await?;
+        log::info!("updated {}", lockfile_path.display());
+    }
+    Ok(modules)
+}
+
+/// Reads the modules imported by path from a specification, and those they import in turn.
+pub async fn read_local_modules(
+    source: &str,
+    specification_path: &Path,
+) -> Result<Vec<LocalModule>> {
+    let mut queue = VecDeque::from([(
+        specification_path.to_path_buf(),
+        source.to_string(),
+    )]);
+    let mut visited = BTreeSet::new();
+    let mut modules = Vec::new();
+    while let Some((importer, source)) = queue.pop_front() {
+        for specifier in imported_specifiers(&source)? {
+            if !is_local_import(&specifier) {
+                continue;
+            }
+            let path = resolve_import(&importer, &specifier);
+            if !visited.insert(path.clone()) {
+                continue;
+            }
+            let contents =
+                tokio::fs::read_to_string(&path).await.map_err(|error| {
+                    other_error(format!(
+                        "{} imports {:?}: {}",
+                        importer.display(),
+                        specifier,
+                        error
+                    ))
+                })?;
+            let source_type =
+                SourceType::from_path(&path).map_err(|error| {
+                    other_error(format!("{}: {}", path.display(), error))
+                })?;
+            let source = if [SourceType::cjs(), SourceType::mjs()]
+                .contains(&source_type)
+            {
+                contents
+            } else {
+                transpile(&contents, &path, &source_type)?
+            };
+            queue.push_back((path.clone(), source.clone()));
+            modules.push(LocalModule { path, source });
+        }
+    }
+    Ok(modules)
+}
+
+/// The type of a module imported by URL, from the extension of its path. URLs without one,
+/// like those of many CDNs, are taken to be JavaScript modules.
+fn url_source_type(url: &Url) -> SourceType {
+    SourceType::from_path(url.path()).unwrap_or_else(|_| SourceType::mjs())
+}
+
+/// The URLs imported by a module. Modules imported by URL can only import other URLs (or
+/// the Bombadil modules), as there's no file system to resolve relative imports in.
+fn remote_imports(source: &str, importer: Option<&Url>) -> Result<Vec<Url>> {
+    let mut urls = Vec::new();
+    for specifier in imported_specifiers(source)? {
+        if specifier.starts_with("https://") {
+            urls.push(Url::parse(&specifier).map_err(|error| {
+                other_error(format!(
+                    "invalid import {:?}: {}",
+                    specifier, error
+                ))
+            })?);
+        } else if specifier.starts_with("http://") {
+            return Err(other_error(format!(
+                "import {:?} must use https",
+                specifier
+            )));
+        } else if let Some(importer) = importer
+            && !specifier.starts_with("@antithesishq/bombadil")
+        {
+            return Err(other_error(format!(
+                "{} imports {:?}, but modules imported by URL can only import other URLs",
+                importer, specifier
+            )));
+        }
+    }
+    Ok(urls)
+}
+
+/// Loads a module from the cache when it matches its locked integrity, or fetches it.
+async fn load(
+    client: &reqwest::Client,
+    url: &Url,
+    locked: Option<&str>,
+) -> Result<String> {
+    let cache_path = cache_directory().map(|directory| {
+        directory.join(format!("{:x}", Sha256::digest(url.as_str())))
+    });
+    if let (Some(cache_path), Some(locked)) = (&cache_path, locked)
+        && let Ok(contents) = tokio::fs::read_to_string(cache_path).await
+        && integrity(contents.as_bytes()) == locked
+    {
+        log::debug!("using cached {}", url);
+        return Ok(contents);
+    }
+
+    log::info!("fetching {}", url);
+    let contents = client
+        .get(url.clone())
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|error| other_error(format!("fetch {}: {}", url, error)))?
+        .text()
+        .await
+        .map_err(|error| other_error(format!("fetch {}: {}", url, error)))?;
+
+    if let Some(cache_path) = &cache_path {
+        let written = async {
+            if let Some(parent) = cache_path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(cache_path, &contents).await
+        };
+        if let Err(error) = written.await {
+            log::warn!("failed to cache {}: {}", url, error);
+        }
+    }
+    Ok(contents)
+}
+
+fn cache_directory() -> Option<PathBuf> {
+    std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join(".cache"))
+        })
+        .map(|cache| cache.join("bombadil").join("modules"))
+}
+
+fn integrity(contents: &[u8]) -> String {
+    format!(
+        "sha256-{}",
+        BASE64_STANDARD.encode(Sha256::digest(contents))
+    )
+}
+
+fn other_error(error: impl ToString) -> SpecificationError {
+    SpecificationError::OtherError(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_local_modules() {
+        let directory = tempfile::tempdir().unwrap();
+        std::fs::create_dir(directory.path().join("lib")).unwrap();
+        std::fs::write(
+            directory.path().join("lib/helpers.ts"),
+            "import { x } from \"../shared.js\";\nexport const y: number = x;\n",
+        )
+        .unwrap();
+        std::fs::write(
+            directory.path().join("shared.js"),
+            "import { y } from \"./lib/helpers.ts\";\nexport const x = 1;\n",
+        )
+        .unwrap();
+        let specification_path = directory.path().join("spec.ts");
+
+        let modules = read_local_modules(
+            "import { y } from \"./lib/helpers.ts\";\nimport { always } from \"@antithesishq/bombadil\";\n",
+            &specification_path,
+        )
+        .await
+        .unwrap();
+        let paths: Vec<&Path> =
+            modules.iter().map(|module| module.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            [
+                directory.path().join("lib/helpers.ts"),
+                directory.path().join("shared.js")
+            ]
+        );
+        assert!(!modules[0].source.contains(": number"));
+
+        let missing =
+            read_local_modules("import \"./missing.ts\";", &specification_path)
+                .await;
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_url_source_type() {
+        let url = |url: &str| Url::parse(url).unwrap();
+        assert_eq!(
+            url_source_type(&url("https://esm.sh/lodash-es@4")),
+            SourceType::mjs()
+        );
+        assert_eq!(
+            url_source_type(&url("https://specs.example.com/shared.ts?v=2")),
+            SourceType::from_path("shared.ts").unwrap()
+        );
+        assert_eq!(
+            url_source_type(&url("https://specs.example.com/shared.cjs")),
+            SourceType::cjs()
+        );
+    }
+
+    #[test]
+    fn test_integrity() {
+        assert_eq!(
+            integrity(b""),
+            "sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+        );
+    }
+}
@@ -5,27 +5,42 @@ import {
   type TimeUnit,
   type Cell,
 } from "@antithesishq/bombadil/internal";
+import { Schema } from "@antithesishq/bombadil/schema";
 
 /** @internal */
 export const runtimeDefault = new Runtime<State>();
 
 // Reexports
 export { time, type Cell } from "@antithesishq/bombadil/internal";
+export * as schema from "@antithesishq/bombadil/schema";
+export type { Schema, Infer } from "@antithesishq/bombadil/schema";
 export {
   actions,
   weighted,
   type Action,
+  type Charset,
+  type DialogPolicy,
+  type FaultKind,
   type Generator,
   type Point,
   ActionGenerator,
+  Setup,
+  setup,
   from,
   strings,
+  INTERNATIONAL,
   emails,
   integers,
   keycodes,
+  randomStream,
+  type RandomStream,
 } from "@antithesishq/bombadil/actions";
 
-import type { Action } from "@antithesishq/bombadil/actions";
+import type {
+  Action,
+  DialogPolicy,
+  FaultKind,
+} from "@antithesishq/bombadil/actions";
 
 export class Formula {
   not(): Formula {
@@ -40,6 +55,48 @@ export class Formula {
   implies(that: IntoFormula): Formula {
     return new Implies(this, now(that));
   }
+  /** Holds if `that` eventually holds, and this formula holds in every state before. */
+  until(that: IntoFormula): Formula {
+    return new Until(this, now(that));
+  }
+  /**
+   * Holds if `that` holds in every state up to and including the first one where this
+   * formula holds, or in every state if it never does.
+   */
+  release(that: IntoFormula): Formula {
+    return new Release(this, now(that));
+  }
+  /**
+   * Holds if `that` held in this state or an earlier one, and this formula in every state
+   * after it. Both must be decided in a single state.
+   */
+  since(that: IntoFormula): Formula {
+    return new Since(this, now(that));
+  }
+  /**
+   * What to make of this property if it's still undecided when the run ends, and would be
+   * false were the run to end there, e.g. an `eventually` that hasn't held yet: a
+   * violation (`"fail"`), a warning (`"warn"`), or nothing (`"ignore"`, the default).
+   * Only applies to exported properties, not to formulas within them.
+   */
+  atEnd(policy: EndPolicy): Formula {
+    return new AtEnd(this, policy);
+  }
+}
+
+export type EndPolicy = "fail" | "warn" | "ignore";
+
+export class AtEnd extends Formula {
+  constructor(
+    public subformula: Formula,
+    public policy: EndPolicy,
+  ) {
+    super();
+  }
+
+  override toString() {
+    return `${this.subformula}.atEnd(${JSON.stringify(this.policy)})`;
+  }
 }
 
 export class Pure extends Formula {
@@ -90,6 +147,65 @@ export class Implies extends Formula {
   }
 }
 
+export class Until extends Formula {
+  constructor(
+    public left: Formula,
+    public right: Formula,
+  ) {
+    super();
+  }
+
+  override toString() {
+    return `${this.left}.until(${this.right})`;
+  }
+}
+
+export class Release extends Formula {
+  constructor(
+    public left: Formula,
+    public right: Formula,
+  ) {
+    super();
+  }
+
+  override toString() {
+    return `${this.left}.release(${this.right})`;
+  }
+}
+
+export class Previously extends Formula {
+  constructor(public subformula: Formula) {
+    super();
+  }
+
+  override toString() {
+    return `previously(${this.subformula})`;
+  }
+}
+
+export class Once extends Formula {
+  constructor(public subformula: Formula) {
+    super();
+  }
+
+  override toString() {
+    return `once(${this.subformula})`;
+  }
+}
+
+export class Since extends Formula {
+  constructor(
+    public left: Formula,
+    public right: Formula,
+  ) {
+    super();
+  }
+
+  override toString() {
+    return `${this.left}.since(${this.right})`;
+  }
+}
+
 export class Not extends Formula {
   constructor(public subformula: Formula) {
     super();
@@ -113,12 +229,13 @@ export class Always extends Formula {
   constructor(
     public boundMillis: number | null,
     public subformula: Formula,
+    public boundSteps: number | null = null,
   ) {
     super();
   }
 
   within(n: number, unit: TimeUnit): Formula {
-    if (this.boundMillis !== null) {
+    if (this.boundMillis !== null || this.boundSteps !== null) {
       throw new Error("time bound is already set for `always`");
     }
     let durationMillis: number;
@@ -133,7 +250,24 @@ export class Always extends Formula {
     return new Always(durationMillis, this.subformula);
   }
 
+  /**
+   * Bounds the formula by a number of states rather than by wall-clock time,
+   * so that it doesn't depend on how fast the browser and the app are.
+   */
+  withinSteps(n: number): Formula {
+    if (this.boundMillis !== null || this.boundSteps !== null) {
+      throw new Error("time bound is already set for `always`");
+    }
+    if (!Number.isInteger(n) || n < 0) {
+      throw new Error(`withinSteps expects a non-negative integer: ${n}`);
+    }
+    return new Always(null, this.subformula, n);
+  }
+
   override toString() {
+    if (this.boundSteps !== null) {
+      return `always(${this.subformula}).withinSteps(${this.boundSteps})`;
+    }
     return this.boundMillis === null
       ? `always(${this.subformula})`
       : `always(${this.subformula}).within(${this.boundMillis}, "milliseconds")`;
@@ -144,12 +278,13 @@ export class Eventually extends Formula {
   constructor(
     public boundMillis: number | null,
     public subformula: Formula,
+    public boundSteps: number | null = null,
   ) {
     super();
   }
 
   within(n: number, unit: TimeUnit): Formula {
-    if (this.boundMillis !== null) {
+    if (this.boundMillis !== null || this.boundSteps !== null) {
       throw new Error("time bound is already set for `eventually`");
     }
     let durationMillis: number;
@@ -164,7 +299,24 @@ export class Eventually extends Formula {
     return new Eventually(durationMillis, this.subformula);
   }
 
+  /**
+   * Bounds the formula by a number of states rather than by wall-clock time,
+   * so that it doesn't depend on how fast the browser and the app are.
+   */
+  withinSteps(n: number): Formula {
+    if (this.boundMillis !== null || this.boundSteps !== null) {
+      throw new Error("time bound is already set for `eventually`");
+    }
+    if (!Number.isInteger(n) || n < 0) {
+      throw new Error(`withinSteps expects a non-negative integer: ${n}`);
+    }
+    return new Eventually(null, this.subformula, n);
+  }
+
   override toString() {
+    if (this.boundSteps !== null) {
+      return `eventually(${this.subformula}).withinSteps(${this.boundSteps})`;
+    }
     return this.boundMillis === null
       ? `eventually(${this.subformula})`
       : `eventually(${this.subformula}).within(${this.boundMillis}, "milliseconds")`;
@@ -211,6 +363,19 @@ export function next(x: IntoFormula): Formula {
   return new Next(now(x));
 }
 
+/**
+ * Holds if `x` held in the previous state, and never in the first one. `x` must be decided
+ * in a single state.
+ */
+export function previously(x: IntoFormula): Formula {
+  return new Previously(now(x));
+}
+
+/** Holds if `x` held in this state or an earlier one. `x` must be decided in a single state. */
+export function once(x: IntoFormula): Formula {
+  return new Once(now(x));
+}
+
 export function always(x: IntoFormula): Always {
   return new Always(null, now(x));
 }
@@ -219,18 +384,228 @@ export function eventually(x: IntoFormula): Eventually {
   return new Eventually(null, now(x));
 }
 
-export function extract<T extends JSON>(query: (state: State) => T): Cell<T> {
-  return new ExtractorCell<T, State>(runtimeDefault, query);
+export interface ExtractOptions<T> {
+  /**
+   * Extract in every `every`th state only, the states in between keeping the value
+   * extracted last, e.g. for extractors too slow to run in every state.
+   */
+  every?: number;
+  schema?: Schema<T>;
+}
+
+/**
+ * Extracts a value from every state. When a schema is given, the cell is typed by the schema
+ * and extracted values not matching it fail the test with an error naming the extractor.
+ */
+export function extract<T extends JSON>(query: (state: State) => T): Cell<T>;
+export function extract<T extends JSON>(
+  query: (state: State) => JSON | undefined,
+  schema: Schema<T>,
+): Cell<T>;
+export function extract<T extends JSON>(
+  query: (state: State) => T,
+  options: ExtractOptions<T>,
+): ExtractorCell<T, State>;
+export function extract<T extends JSON>(
+  query: (state: State) => JSON | undefined,
+  options?: Schema<T> | ExtractOptions<T>,
+): ExtractorCell<T, State> {
+  const { every = 1, schema = null } =
+    options instanceof Schema ? { schema: options } : (options ?? {});
+  if (!Number.isInteger(every) || every < 1) {
+    throw new Error(
+      `extract \`every\` must be a positive integer, got ${every}`,
+    );
+  }
+  return new ExtractorCell<T, State>(
+    runtimeDefault,
+    query as (state: State) => T,
+    schema,
+    every,
+  );
+}
+
+/**
+ * An action sequence applied as a unit, one step per state, after which `postcondition` is
+ * called with the values of `extract` from before the first step and after the last one.
+ * When it returns false, the test reports a failed transaction, e.g. for an add-to-cart flow
+ * that should either fully succeed or leave the cart as it was:
+ *
+ * ```ts
+ * transaction("add to cart", [click, confirm], {
+ *   extract: (state) => state.document.querySelectorAll(".cart li").length,
+ *   postcondition: (before, after) => after === before || after === before + 1,
+ * });
+ * ```
+ *
+ * Both functions run in the browser, so they can't refer to variables of the specification.
+ */
+export function transaction<T extends JSON>(
+  name: string,
+  steps: Action[],
+  check: {
+    extract: (state: State) => T;
+    postcondition: (before: T, after: T) => boolean;
+  },
+): Action {
+  if (steps.length === 0) {
+    throw new Error(`transaction \`${name}\` needs at least one step`);
+  }
+  return {
+    Transaction: {
+      name,
+      steps,
+      extract: check.extract.toString(),
+      postcondition: check.postcondition.toString(),
+    },
+  };
+}
+
+/**
+ * A transaction applying `steps` while the network is emulated as offline, and going back
+ * online after the last one, e.g. for checking that changes made offline are kept:
+ *
+ * ```ts
+ * offline("edit offline", [click, type], {
+ *   extract: (state) => state.document.querySelectorAll(".todo").length,
+ *   postcondition: (before, after) => after === before + 1,
+ * });
+ * ```
+ *
+ * Pending changes are usually synced some time after going online, which is a property of
+ * the states following it, using `state.network`.
+ */
+export function offline<T extends JSON>(
+  name: string,
+  steps: Action[],
+  check: {
+    extract: (state: State) => T;
+    postcondition: (before: T, after: T) => boolean;
+  },
+): Action {
+  return transaction(name, ["GoOffline", ...steps, "GoOnline"], check);
+}
+
+/**
+ * Requests whose URL matches `pattern` (with `*` wildcards) get one of `kinds` of faults,
+ * picked at random, with probability `rate`. Delayed requests are sent on after
+ * `delayMillis`.
+ */
+export interface FaultRule {
+  pattern: string;
+  rate: number;
+  kinds?: FaultKind[];
+  delayMillis?: number;
+}
+
+/**
+ * An action replacing the fault rules of the run, e.g. for checking that the app recovers
+ * from a flaky API:
+ *
+ * ```ts
+ * faults([{ pattern: "*\/api/*", rate: 0.2, kinds: ["drop", "serverError"] }]);
+ * ```
+ *
+ * Passing no rules stops injecting faults.
+ */
+export function faults(rules: FaultRule[]): Action {
+  return {
+    InjectFaults: {
+      rules: rules.map((rule) => ({
+        pattern: rule.pattern,
+        rate: rule.rate,
+        kinds: rule.kinds ?? ["delay", "drop", "serverError"],
+        delayMillis: rule.delayMillis ?? 3000,
+      })),
+    },
+  };
+}
+
+/**
+ * An action replacing how the `alert`, `confirm` and `prompt` dialogs opened from now on
+ * are answered, accepting them until then:
+ *
+ * ```ts
+ * export const answerPrompts = actions(() => [dialogs({ text: "Ada" })]);
+ * ```
+ */
+export function dialogs(policy: DialogPolicy): Action {
+  return { SetDialogPolicy: { policy } };
+}
+
+/**
+ * An action sending a raw Chrome DevTools Protocol command to the page, for what no other
+ * action covers, e.g. setting a cookie in a `setup()`:
+ *
+ * ```ts
+ * cdp("Network.setCookie", { name: "consent", value: "yes", url: "https://example.com" });
+ * ```
+ */
+export function cdp(
+  method: string,
+  params: Record<string, unknown> = {},
+): Action {
+  return { CdpCommand: { method, params } };
+}
+
+/**
+ * An action resizing the viewport to a device profile, `mobile`, `tablet` or `desktop`,
+ * or one in the `--viewport-profile` format, until the next sweep switch:
+ *
+ * ```ts
+ * export const rotate = actions(() => [viewport("kiosk=1080x1920:touch")]);
+ * ```
+ */
+export function viewport(profile: string): Action {
+  return { SetViewport: { profile } };
+}
+
+/**
+ * An action fast-forwarding the page's clock by `millis`, running the timers due in the
+ * meantime. From then on, the page's time only passes when the clock is advanced:
+ *
+ * ```ts
+ * export const expireSession = actions(() => [advanceClock(30 * 60 * 1000)]);
+ * ```
+ */
+export function advanceClock(millis: number): Action {
+  return { AdvanceClock: { millis } };
+}
+
+declare function __bombadil_metric(name: string, value: number): void;
+
+/**
+ * Emits a named numeric metric for the current state, recorded in the trace and
+ * summarized in the report. Call it where the specification is evaluated, e.g. in a
+ * property or an action generator:
+ *
+ * ```ts
+ * const cartSize = extract((state) => state.document.querySelectorAll(".cart li").length);
+ *
+ * export const smallCart = always(() => {
+ *   metric("cart_size", cartSize.current);
+ *   return cartSize.current < 100;
+ * });
+ * ```
+ */
+export function metric(name: string, value: number): void {
+  __bombadil_metric(name, value);
 }
 
 export interface State {
   document: HTMLDocument;
   window: Window;
+  /**
+   * The run context, given with `--context KEY=VALUE` or `--context-file`, e.g. the
+   * expected tenant name or feature flags of the environment under test.
+   */
+  context: Record<string, unknown>;
   navigationHistory: {
     back: NavigationEntry[];
     current: NavigationEntry;
     forward: NavigationEntry[];
   };
+  navigationTimings: NavigationTimings;
   errors: {
     uncaughtExceptions: {
       text: string;
@@ -241,11 +616,187 @@ export interface State {
         | { name: string; line: number; column: number; url: string }[]
         | null;
     }[];
+    /** Exceptions thrown in this step beyond the per-step limit. */
+    droppedCount: number;
   };
   console: ConsoleEntry[];
+  /** Console entries logged in this step beyond the per-step limit. */
+  consoleDroppedCount: number;
+  serviceWorkers: ServiceWorkerStatus[];
   lastAction: Action | null;
+  /** DOM mutations since the previous state, i.e. caused by `lastAction`. */
+  mutations: MutationSummary;
+  /**
+   * Elements with `click`, `dblclick` or `contextmenu` listeners, with the center of their
+   * box, including those that don't look clickable. Listeners delegated to the document
+   * or its body are left out. Only looked up when an extractor uses `state.listeners`,
+   * and empty otherwise.
+   */
+  listeners: { name: string; types: string[]; point: Point }[];
+  step: {
+    /** Number of states before this one in the run. */
+    index: number;
+    /** Milliseconds since the first state of the run. */
+    elapsedMillis: number;
+  };
+  /**
+   * The viewport profile of this state, when cycling through `--viewport-profile`s or
+   * after a `viewport()` action.
+   */
+  viewportProfile: ViewportProfile | null;
+  /**
+   * Total sizes of the scripts loaded so far in the run, as served (decompressed, before
+   * instrumentation), counting each URL once. The size of each script is in the run's
+   * `script_sizes.json`. Empty with `--coverage precise`.
+   */
+  scriptSizes: ScriptSizeTotals;
+  /** Whether WebGL is available in the browser, or null if detecting it failed. */
+  gpu: GpuInfo | null;
+  /**
+   * Dialogs the page opened since the previous state, answered as they opened, according
+   * to `--before-unload` for `beforeunload` confirmations ("Leave site?"), and to the
+   * policy set with `dialogs()` for the others.
+   */
+  dialogs: Dialog[];
+  /** Redirect chains of the navigations that loaded or failed since the previous state. */
+  redirects: RedirectChain[];
+  network: {
+    /**
+     * The emulated connectivity, changed by the `GoOffline`, `GoOnline` and
+     * `SetNetworkConditions` actions.
+     */
+    offline: boolean;
+    /** Index of the first state since the connectivity last changed, if ever. */
+    changedAt: number | null;
+    /** The emulated latency added to requests. */
+    latencyMillis: number;
+    /** The emulated throughput, in bytes per second, or null if unlimited. */
+    throughput: number | null;
+    /** Requests of the page that loaded or failed since the previous state. */
+    requests: NetworkRequest[];
+    /** Requests completed in this step beyond the per-step limit. */
+    requestsDroppedCount: number;
+    /** The latest WebSocket frames the page sent or received since the previous state. */
+    webSocketFrames: WebSocketFrame[];
+    /** Earlier frames of this step dropped beyond the per-step limit. */
+    webSocketFramesDroppedCount: number;
+  };
 }
 
+export type Dialog = {
+  type: "alert" | "confirm" | "prompt" | "beforeunload";
+  message: string;
+  /** The URL of the frame that opened the dialog. */
+  url: string;
+  /** Whether the dialog was accepted, rather than dismissed. */
+  accepted: boolean;
+  /** The text a prompt was answered with, if accepted. */
+  promptText: string | null;
+};
+
+export type RedirectChain = {
+  /** The URLs redirected from, in order, with the 3xx status of each redirect. */
+  hops: { url: string; status: number }[];
+  /** The URL the navigation ended at. */
+  url: string;
+  /** The HTTP status of the final response, or null if the navigation failed before one. */
+  status: number | null;
+  /** Why the navigation failed, e.g. `net::ERR_TOO_MANY_REDIRECTS`, if it did. */
+  error: string | null;
+  /** Whether a URL came up twice, or the browser gave up after too many redirects. */
+  looped: boolean;
+};
+
+export type WebSocketFrame = {
+  /** The URL the WebSocket connected to. */
+  url: string;
+  direction: "sent" | "received";
+  /** 1 for text frames, 2 for binary ones. */
+  opcode: number;
+  /** The text of text frames, base64 encoded for binary ones. */
+  payload: string;
+  /** When the frame was seen, in milliseconds since the Unix epoch. */
+  timestamp: number;
+};
+
+export type NetworkRequest = {
+  url: string;
+  method: string;
+  /** The HTTP status of the response, or null if the request failed before one. */
+  status: number | null;
+  /** E.g. `Document`, `Script`, `XHR` or `Fetch`. */
+  resourceType: string | null;
+  /** What started the request, e.g. `parser` or `script`. */
+  initiator: string;
+  /** When the request was sent, in milliseconds since the Unix epoch. */
+  startedAt: number;
+  durationMillis: number;
+  /** Why the request failed, e.g. `net::ERR_CONNECTION_REFUSED`, if it did. */
+  error: string | null;
+};
+
+export type GpuInfo = {
+  webgl: boolean;
+  webgl2: boolean;
+  vendor: string | null;
+  renderer: string | null;
+  /** Whether WebGL is rendered in software (e.g. SwiftShader) rather than by a GPU. */
+  software: boolean;
+};
+
+export type ScriptSizeTotals = {
+  totalBytes: number;
+  byOrigin: Record<string, { totalBytes: number }>;
+};
+
+export type ViewportProfile = {
+  name: string;
+  width: number;
+  height: number;
+  deviceScaleFactor: number;
+  mobile: boolean;
+  /** Whether a touch screen is emulated. */
+  touch: boolean;
+  /** The user agent reported to the page, if not the browser's own. */
+  userAgent: string | null;
+};
+
+export type MutationSummary = {
+  /** Inserted, removed or updated nodes and attributes. */
+  count: number;
+  /** The most frequently mutated elements (as `tag#id.class`) and their mutation counts. */
+  targets: [string, number][];
+};
+
+export type ServiceWorkerStatus = {
+  scopeUrl: string | null;
+  scriptUrl: string;
+  status:
+    | "new"
+    | "installing"
+    | "installed"
+    | "activating"
+    | "activated"
+    | "redundant";
+  runningStatus: "stopped" | "starting" | "running" | "stopping";
+};
+
+/**
+ * Timings of the navigation of the current document, in milliseconds since it started, or
+ * null until reached.
+ */
+export type NavigationTimings = {
+  /** When the navigation started, in milliseconds since the Unix epoch. */
+  timeOrigin: number;
+  readyState: "loading" | "interactive" | "complete";
+  domInteractive: number | null;
+  domContentLoaded: number | null;
+  domComplete: number | null;
+  load: number | null;
+  firstPaint: number | null;
+  firstContentfulPaint: number | null;
+};
+
 export type NavigationEntry = {
   id: number;
   title: string;
@@ -1,3 +1,5 @@
+import type { Schema } from "@antithesishq/bombadil/schema";
+
 export type Time = number;
 
 export type TimeUnit = "milliseconds" | "seconds";
@@ -22,6 +24,9 @@ export class ExtractorCell<T extends JSON, S> implements Cell<T> {
   constructor(
     runtime: Runtime<S>,
     private extract: (state: S) => T,
+    private schema: Schema<T> | null = null,
+    /** The extractor runs in every `every`th state, the others keeping its last value. */
+    readonly every: number = 1,
   ) {
     runtime.registerExtractor(this);
   }
@@ -30,6 +35,11 @@ export class ExtractorCell<T extends JSON, S> implements Cell<T> {
     this.snapshots.set(time, snapshot);
   }
 
+  /** Describes how a snapshot doesn't match the schema, if any, or returns null. */
+  validate(snapshot: unknown): string | null {
+    return this.schema === null ? null : this.schema.check(snapshot);
+  }
+
   get current(): T {
     const value = this.snapshots.get(time.current);
     if (value === undefined) {
@@ -55,6 +65,11 @@ export class ExtractorCell<T extends JSON, S> implements Cell<T> {
     }
   }
 
+  /** States since the current value was extracted, 0 if extracted in the current one. */
+  get staleSteps(): number {
+    return time.stepIndex % this.every;
+  }
+
   asJsFunction(): string {
     return this.extract.toString();
   }
@@ -62,10 +77,17 @@ export class ExtractorCell<T extends JSON, S> implements Cell<T> {
 
 export class TimeCell implements Cell<Time> {
   private time: Time | undefined = undefined;
+  private first: Time | undefined = undefined;
+  private index = 0;
   constructor() {}
 
-  update(_: {}, time: Time) {
+  /** Takes the index of the state, passed in by the runtime, as its snapshot. */
+  update(stepIndex: number, time: Time) {
+    if (this.first === undefined) {
+      this.first = time;
+    }
     this.time = time;
+    this.index = stepIndex;
   }
 
   get current(): Time {
@@ -78,9 +100,22 @@ export class TimeCell implements Cell<Time> {
   at(time: Time): Time {
     return time;
   }
+
+  /** Number of states before the current one, which doesn't depend on machine speed. */
+  get stepIndex(): number {
+    if (this.time === undefined) {
+      throw new Error("time has not been set");
+    }
+    return this.index;
+  }
+
+  /** Milliseconds since the first state. */
+  get elapsed(): number {
+    return this.current - this.first!;
+  }
 }
 
-export const time: Cell<Time> = new TimeCell();
+export const time = new TimeCell();
 
 export class Runtime<S> {
   extractors: ExtractorCell<any, S>[] = [];
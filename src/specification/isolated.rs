@@ -0,0 +1,325 @@
+use futures::future::{BoxFuture, try_join_all};
+use serde_json as json;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::specification::ltl;
+use crate::specification::result::SpecificationError;
+use crate::specification::verifier::ExtractorFunction;
+use crate::specification::worker::{
+    EndViolation, StepResult, VerifierBackend, WorkerError,
+};
+use crate::tree::Tree;
+
+/// Specifications checked against the same run in verifiers of their own, each in its own
+/// JavaScript context, e.g. invariant packs maintained by different teams. The first one,
+/// the driver, alone generates the actions and the setup. The properties of the others are
+/// prefixed by their name, like `a11y/noMissingLabels` for `noMissingLabels` in `a11y.ts`.
+pub struct IsolatedVerifiers {
+    /// The verifiers, the driver first, with the prefix of their properties.
+    verifiers: Vec<(Option<String>, Arc<dyn VerifierBackend>)>,
+    /// The extractors of all verifiers, those with the same source and sampling being run
+    /// once for all of them.
+    extractors: Vec<ExtractorFunction>,
+    /// The verifiers (by index) of each extractor, with its id in each of them.
+    routes: HashMap<u64, Vec<(usize, u64)>>,
+}
+
+fn prefixed(prefix: &Option<String>, name: String) -> String {
+    match prefix {
+        Some(prefix) => format!("{}/{}", prefix, name),
+        None => name,
+    }
+}
+
+impl IsolatedVerifiers {
+    pub async fn new(
+        driver: Arc<dyn VerifierBackend>,
+        checked: Vec<(String, Arc<dyn VerifierBackend>)>,
+    ) -> Result<Arc<Self>, WorkerError> {
+        let verifiers: Vec<(Option<String>, Arc<dyn VerifierBackend>)> =
+            std::iter::once((None, driver))
+                .chain(
+                    checked
+                        .into_iter()
+                        .map(|(name, verifier)| (Some(name), verifier)),
+                )
+                .collect();
+
+        let mut names = HashSet::new();
+        let mut extractors: Vec<ExtractorFunction> = Vec::new();
+        let mut routes: HashMap<u64, Vec<(usize, u64)>> = HashMap::new();
+        for (index, (prefix, verifier)) in verifiers.iter().enumerate() {
+            for name in verifier.properties().await? {
+                let name = prefixed(prefix, name);
+                if !names.insert(name.clone()) {
+                    return Err(WorkerError::SpecificationError(
+                        SpecificationError::OtherError(format!(
+                            "two specifications have a property named {:?}, rename one of them",
+                            name
+                        )),
+                    ));
+                }
+            }
+            for extractor in verifier.extractors().await? {
+                let id = match extractors.iter().find(|shared| {
+                    shared.source == extractor.source
+                        && shared.every == extractor.every
+                }) {
+                    Some(shared) => shared.id,
+                    None => {
+                        let id = extractors.len() as u64;
+                        extractors.push(ExtractorFunction {
+                            id,
+                            ..extractor.clone()
+                        });
+                        id
+                    }
+                };
+                routes.entry(id).or_default().push((index, extractor.id));
+            }
+        }
+
+        Ok(Arc::new(IsolatedVerifiers {
+            verifiers,
+            extractors,
+            routes,
+        }))
+    }
+}
+
+impl VerifierBackend for IsolatedVerifiers {
+    fn properties(&self) -> BoxFuture<'_, Result<Vec<String>, WorkerError>> {
+        Box::pin(async move {
+            let mut properties = Vec::new();
+            for (prefix, verifier) in &self.verifiers {
+                properties.extend(
+                    verifier
+                        .properties()
+                        .await?
+                        .into_iter()
+                        .map(|name| prefixed(prefix, name)),
+                );
+            }
+            Ok(properties)
+        })
+    }
+
+    fn extractors(
+        &self,
+    ) -> BoxFuture<'_, Result<Vec<ExtractorFunction>, WorkerError>> {
+        Box::pin(async move { Ok(self.extractors.clone()) })
+    }
+
+    fn setup_json(
+        &self,
+    ) -> BoxFuture<'_, Result<Vec<json::Value>, WorkerError>> {
+        self.verifiers[0].1.setup_json()
+    }
+
+    fn step_json(
+        &self,
+        snapshots: Vec<(u64, json::Value)>,
+        time: ltl::Time,
+    ) -> BoxFuture<'_, Result<StepResult<json::Value>, WorkerError>> {
+        Box::pin(async move {
+            let mut routed = vec![Vec::new(); self.verifiers.len()];
+            for (id, value) in snapshots {
+                for (index, own_id) in
+                    self.routes.get(&id).into_iter().flatten()
+                {
+                    routed[*index].push((*own_id, value.clone()));
+                }
+            }
+            let results = try_join_all(self.verifiers.iter().zip(routed).map(
+                |((_, verifier), snapshots)| {
+                    verifier.step_json(snapshots, time)
+                },
+            ))
+            .await?;
+
+            let mut properties = Vec::new();
+            let mut metrics = Vec::new();
+            let mut actions = Tree::Branch { branches: vec![] };
+            for (index, ((prefix, _), result)) in
+                self.verifiers.iter().zip(results).enumerate()
+            {
+                properties.extend(
+                    result
+                        .properties
+                        .into_iter()
+                        .map(|(name, value)| (prefixed(prefix, name), value)),
+                );
+                metrics.extend(
+                    result
+                        .metrics
+                        .into_iter()
+                        .map(|(name, value)| (prefixed(prefix, name), value)),
+                );
+                if index == 0 {
+                    actions = result.actions;
+                }
+            }
+            Ok(StepResult {
+                properties,
+                actions,
+                metrics,
+            })
+        })
+    }
+
+    fn finish(
+        &self,
+        time: ltl::Time,
+    ) -> BoxFuture<'_, Result<Vec<EndViolation>, WorkerError>> {
+        Box::pin(async move {
+            let results = try_join_all(
+                self.verifiers
+                    .iter()
+                    .map(|(_, verifier)| verifier.finish(time)),
+            )
+            .await?;
+            Ok(self
+                .verifiers
+                .iter()
+                .zip(results)
+                .flat_map(|((prefix, _), violations)| {
+                    violations.into_iter().map(|violation| EndViolation {
+                        name: prefixed(prefix, violation.name.clone()),
+                        ..violation
+                    })
+                })
+                .collect())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::specification::worker::PropertyValue;
+    use futures::future;
+    use std::sync::Mutex;
+    use std::time::SystemTime;
+
+    /// Extracts with the given sources and presses `code`, recording the snapshots it's
+    /// given.
+    struct Fake {
+        sources: Vec<&'static str>,
+        code: f64,
+        snapshots: Mutex<Vec<(u64, json::Value)>>,
+    }
+
+    impl Fake {
+        fn new(sources: Vec<&'static str>, code: f64) -> Arc<Self> {
+            Arc::new(Fake {
+                sources,
+                code,
+                snapshots: Mutex::default(),
+            })
+        }
+    }
+
+    impl VerifierBackend for Fake {
+        fn properties(
+            &self,
+        ) -> BoxFuture<'_, Result<Vec<String>, WorkerError>> {
+            Box::pin(future::ready(Ok(vec!["noErrors".to_string()])))
+        }
+
+        fn extractors(
+            &self,
+        ) -> BoxFuture<'_, Result<Vec<ExtractorFunction>, WorkerError>>
+        {
+            Box::pin(future::ready(Ok(self
+                .sources
+                .iter()
+                .enumerate()
+                .map(|(id, source)| ExtractorFunction {
+                    id: id as u64,
+                    source: source.to_string(),
+                    every: 1,
+                })
+                .collect())))
+        }
+
+        fn setup_json(
+            &self,
+        ) -> BoxFuture<'_, Result<Vec<json::Value>, WorkerError>> {
+            Box::pin(future::ready(Ok(vec![])))
+        }
+
+        fn step_json(
+            &self,
+            snapshots: Vec<(u64, json::Value)>,
+            _time: ltl::Time,
+        ) -> BoxFuture<'_, Result<StepResult<json::Value>, WorkerError>>
+        {
+            self.snapshots.lock().unwrap().extend(snapshots);
+            Box::pin(future::ready(Ok(StepResult {
+                properties: vec![("noErrors".to_string(), PropertyValue::True)],
+                actions: Tree::Leaf {
+                    value: json::json!({ "PressKey": { "code": self.code } }),
+                },
+                metrics: vec![("code".to_string(), self.code)],
+            })))
+        }
+
+        fn finish(
+            &self,
+            _time: ltl::Time,
+        ) -> BoxFuture<'_, Result<Vec<EndViolation>, WorkerError>> {
+            Box::pin(future::ready(Ok(vec![])))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shared_extractors_and_driver_actions() {
+        let driver = Fake::new(vec!["title", "url"], 13.0);
+        let a11y = Fake::new(vec!["labels", "title"], 27.0);
+        let verifiers = IsolatedVerifiers::new(
+            driver.clone(),
+            vec![("a11y".to_string(), a11y.clone())],
+        )
+        .await
+        .unwrap();
+
+        let extractors = verifiers.extractors().await.unwrap();
+        let sources: Vec<&str> = extractors
+            .iter()
+            .map(|extractor| extractor.source.as_str())
+            .collect();
+        assert_eq!(sources, vec!["title", "url", "labels"]);
+
+        let result = verifiers
+            .step_json(
+                vec![
+                    (0, json::json!("Home")),
+                    (1, json::json!("/")),
+                    (2, json::json!([])),
+                ],
+                SystemTime::UNIX_EPOCH,
+            )
+            .await
+            .unwrap();
+        let names: Vec<&str> = result
+            .properties
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, vec!["noErrors", "a11y/noErrors"]);
+        assert_eq!(
+            result.metrics,
+            vec![("code".to_string(), 13.0), ("a11y/code".to_string(), 27.0)]
+        );
+        assert!(matches!(
+            result.actions,
+            Tree::Leaf { value } if value["PressKey"]["code"] == 13.0
+        ));
+        assert_eq!(
+            *a11y.snapshots.lock().unwrap(),
+            vec![(1, json::json!("Home")), (0, json::json!([]))]
+        );
+        assert_eq!(driver.snapshots.lock().unwrap().len(), 2);
+    }
+}
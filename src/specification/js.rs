@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::fmt::{self, Display};
 use std::time::Duration;
 
 use boa_engine::{
@@ -10,10 +11,15 @@ use serde_json as json;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::browser::actions::BrowserAction;
+use crate::browser::dialogs::DialogPolicy;
+use crate::browser::faults::{FaultKind, FaultRule};
 use crate::geometry::Point;
 use crate::specification::{
+    ltl::Bound,
     result::{Result, SpecificationError},
+    stop::EndPolicy,
     syntax::Syntax,
+    verifier::ExtractorFunction,
 };
 
 /// TypeScript-friendly action representation with camelCase and f64 for numbers.
@@ -29,6 +35,41 @@ pub enum JsAction {
         point: Point,
     },
     #[serde(rename_all = "camelCase")]
+    DoubleClick {
+        name: String,
+        content: Option<String>,
+        point: Point,
+    },
+    #[serde(rename_all = "camelCase")]
+    RightClick {
+        name: String,
+        content: Option<String>,
+        point: Point,
+    },
+    #[serde(rename_all = "camelCase")]
+    Hover {
+        point: Point,
+    },
+    #[serde(rename_all = "camelCase")]
+    DragAndDrop {
+        from: Point,
+        to: Point,
+    },
+    #[serde(rename_all = "camelCase")]
+    Focus {
+        selector: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    SelectOption {
+        selector: String,
+        value: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    SetChecked {
+        selector: String,
+        checked: bool,
+    },
+    #[serde(rename_all = "camelCase")]
     TypeText {
         text: String,
         delay_millis: f64,
@@ -48,6 +89,54 @@ pub enum JsAction {
         distance: f64,
     },
     Reload,
+    GoOffline,
+    GoOnline,
+    #[serde(rename_all = "camelCase")]
+    SetNetworkConditions {
+        offline: bool,
+        latency_millis: f64,
+        throughput: f64,
+    },
+    #[serde(rename_all = "camelCase")]
+    InjectFaults {
+        rules: Vec<JsFaultRule>,
+    },
+    SetInstrumentation {
+        enabled: bool,
+    },
+    SetDialogPolicy {
+        policy: DialogPolicy,
+    },
+    CdpCommand {
+        method: String,
+        #[serde(default)]
+        params: json::Value,
+    },
+    AdvanceClock {
+        millis: f64,
+    },
+    /// A device name or a viewport profile, as parsed from `--viewport-profile`.
+    SetViewport {
+        profile: String,
+    },
+    /// Steps applied one per state, followed by a check of the postcondition. `extract` and
+    /// `postcondition` are the sources of functions evaluated in the page.
+    #[serde(rename_all = "camelCase")]
+    Transaction {
+        name: String,
+        steps: Vec<JsAction>,
+        extract: String,
+        postcondition: String,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JsFaultRule {
+    pattern: String,
+    rate: f64,
+    kinds: Vec<FaultKind>,
+    delay_millis: f64,
 }
 
 impl JsAction {
@@ -59,6 +148,59 @@ impl JsAction {
             JsAction::Back => BrowserAction::Back,
             JsAction::Forward => BrowserAction::Forward,
             JsAction::Reload => BrowserAction::Reload,
+            JsAction::GoOffline => BrowserAction::GoOffline,
+            JsAction::GoOnline => BrowserAction::GoOnline,
+            JsAction::SetNetworkConditions {
+                offline,
+                latency_millis,
+                throughput,
+            } => BrowserAction::SetNetworkConditions {
+                offline,
+                latency_millis,
+                throughput,
+            },
+            JsAction::InjectFaults { rules } => BrowserAction::InjectFaults {
+                rules: rules
+                    .into_iter()
+                    .map(|rule| FaultRule {
+                        pattern: rule.pattern,
+                        rate: rule.rate,
+                        kinds: rule.kinds,
+                        delay_millis: rule.delay_millis as u64,
+                    })
+                    .collect(),
+            },
+            JsAction::SetInstrumentation { enabled } => {
+                BrowserAction::SetInstrumentation { enabled }
+            }
+            JsAction::SetDialogPolicy { policy } => {
+                BrowserAction::SetDialogPolicy { policy }
+            }
+            JsAction::SetViewport { profile } => BrowserAction::SetViewport {
+                profile: profile.parse().map_err(anyhow::Error::msg)?,
+            },
+            JsAction::AdvanceClock { millis } => {
+                if !millis.is_finite() || millis < 0.0 {
+                    bail!(
+                        "millis must be a non-negative finite number, got {}",
+                        millis
+                    );
+                }
+                BrowserAction::AdvanceClock {
+                    millis: millis as u64,
+                }
+            }
+            JsAction::CdpCommand { method, params } => {
+                BrowserAction::CdpCommand {
+                    method,
+                    // Commands without parameters still take an object.
+                    params: if params.is_null() {
+                        json::json!({})
+                    } else {
+                        params
+                    },
+                }
+            }
             JsAction::Click {
                 name,
                 content,
@@ -68,6 +210,35 @@ impl JsAction {
                 content,
                 point,
             },
+            JsAction::DoubleClick {
+                name,
+                content,
+                point,
+            } => BrowserAction::DoubleClick {
+                name,
+                content,
+                point,
+            },
+            JsAction::RightClick {
+                name,
+                content,
+                point,
+            } => BrowserAction::RightClick {
+                name,
+                content,
+                point,
+            },
+            JsAction::Hover { point } => BrowserAction::Hover { point },
+            JsAction::DragAndDrop { from, to } => {
+                BrowserAction::DragAndDrop { from, to }
+            }
+            JsAction::Focus { selector } => BrowserAction::Focus { selector },
+            JsAction::SelectOption { selector, value } => {
+                BrowserAction::SelectOption { selector, value }
+            }
+            JsAction::SetChecked { selector, checked } => {
+                BrowserAction::SetChecked { selector, checked }
+            }
             JsAction::TypeText { text, delay_millis } => {
                 if !delay_millis.is_finite() || delay_millis < 0.0 {
                     bail!(
@@ -98,6 +269,9 @@ impl JsAction {
             JsAction::ScrollDown { origin, distance } => {
                 BrowserAction::ScrollDown { origin, distance }
             }
+            JsAction::Transaction { name, .. } => {
+                bail!("transaction `{}` can't be a step of a transaction", name)
+            }
         })
     }
 }
@@ -108,6 +282,12 @@ pub struct RuntimeFunction {
     pub pretty: String,
 }
 
+impl Display for RuntimeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.pretty)
+    }
+}
+
 impl Syntax<RuntimeFunction> {
     pub fn from_value(
         value: &JsValue,
@@ -189,6 +369,46 @@ impl Syntax<RuntimeFunction> {
             return Ok(Implies(Box::new(left), Box::new(right)));
         }
 
+        if value.instance_of(&bombadil.until, context)? {
+            let left_value = object.get(js_string!("left"), context)?;
+            let right_value = object.get(js_string!("right"), context)?;
+            let left = Self::from_value(&left_value, bombadil, context)?;
+            let right = Self::from_value(&right_value, bombadil, context)?;
+            return Ok(Until(Box::new(left), Box::new(right)));
+        }
+
+        if value.instance_of(&bombadil.release, context)? {
+            let left_value = object.get(js_string!("left"), context)?;
+            let right_value = object.get(js_string!("right"), context)?;
+            let left = Self::from_value(&left_value, bombadil, context)?;
+            let right = Self::from_value(&right_value, bombadil, context)?;
+            return Ok(Release(Box::new(left), Box::new(right)));
+        }
+
+        if value.instance_of(&bombadil.previously, context)? {
+            let subformula_value =
+                object.get(js_string!("subformula"), context)?;
+            let subformula =
+                Self::from_value(&subformula_value, bombadil, context)?;
+            return Ok(Previously(Box::new(subformula)));
+        }
+
+        if value.instance_of(&bombadil.once, context)? {
+            let subformula_value =
+                object.get(js_string!("subformula"), context)?;
+            let subformula =
+                Self::from_value(&subformula_value, bombadil, context)?;
+            return Ok(Once(Box::new(subformula)));
+        }
+
+        if value.instance_of(&bombadil.since, context)? {
+            let left_value = object.get(js_string!("left"), context)?;
+            let right_value = object.get(js_string!("right"), context)?;
+            let left = Self::from_value(&left_value, bombadil, context)?;
+            let right = Self::from_value(&right_value, bombadil, context)?;
+            return Ok(Since(Box::new(left), Box::new(right)));
+        }
+
         if value.instance_of(&bombadil.next, context)? {
             let subformula_value =
                 object.get(js_string!("subformula"), context)?;
@@ -202,8 +422,9 @@ impl Syntax<RuntimeFunction> {
                 object.get(js_string!("subformula"), context)?;
             let subformula =
                 Self::from_value(&subformula_value, bombadil, context)?;
-            let bound = optional_duration_from_js(
+            let bound = optional_bound_from_js(
                 object.get(js_string!("boundMillis"), context)?,
+                object.get(js_string!("boundSteps"), context)?,
             )?;
             return Ok(Always(Box::new(subformula), bound));
         }
@@ -213,17 +434,79 @@ impl Syntax<RuntimeFunction> {
                 object.get(js_string!("subformula"), context)?;
             let subformula =
                 Self::from_value(&subformula_value, bombadil, context)?;
-            let bound = optional_duration_from_js(
+            let bound = optional_bound_from_js(
                 object.get(js_string!("boundMillis"), context)?,
+                object.get(js_string!("boundSteps"), context)?,
             )?;
             return Ok(Eventually(Box::new(subformula), bound));
         }
 
+        if value.instance_of(&bombadil.at_end, context)? {
+            return Err(SpecificationError::OtherError(format!(
+                "atEnd only applies to exported properties, not to formulas within them: {}",
+                value.display()
+            )));
+        }
+
         Err(SpecificationError::OtherError(format!(
             "can't convert to formula: {}",
             value.display()
         )))
     }
+
+    /// Converts an exported property, with its end policy if given with `atEnd`.
+    pub fn property_from_value(
+        value: &JsValue,
+        bombadil: &BombadilExports,
+        context: &mut Context,
+    ) -> Result<(Self, EndPolicy)> {
+        if !value.instance_of(&bombadil.at_end, context)? {
+            return Ok((
+                Self::from_value(value, bombadil, context)?,
+                EndPolicy::default(),
+            ));
+        }
+        let object =
+            value.as_object().ok_or(SpecificationError::OtherError(
+                format!("formula is not an object: {}", value.display()),
+            ))?;
+        let policy = object
+            .get(js_string!("policy"), context)?
+            .as_string()
+            .ok_or(SpecificationError::OtherError(
+                "AtEnd.policy is not a string".to_string(),
+            ))?
+            .to_std_string_escaped()
+            .parse::<EndPolicy>()
+            .map_err(SpecificationError::OtherError)?;
+        let subformula_value = object.get(js_string!("subformula"), context)?;
+        Ok((
+            Self::from_value(&subformula_value, bombadil, context)?,
+            policy,
+        ))
+    }
+}
+
+/// The bound of `always` or `eventually`, in milliseconds or in states.
+fn optional_bound_from_js(
+    millis: JsValue,
+    steps: JsValue,
+) -> Result<Option<Bound>> {
+    if let Some(duration) = optional_duration_from_js(millis)? {
+        return Ok(Some(Bound::Duration(duration)));
+    }
+    if steps.is_null_or_undefined() {
+        return Ok(None);
+    }
+    match steps.as_number() {
+        Some(n) if n >= 0.0 && n.is_finite() && n.fract() == 0.0 => {
+            Ok(Some(Bound::Steps(n as u64)))
+        }
+        _ => Err(SpecificationError::OtherError(format!(
+            "steps is not a non-negative integer: {}",
+            steps.display()
+        ))),
+    }
 }
 
 fn optional_duration_from_js(value: JsValue) -> Result<Option<Duration>> {
@@ -260,12 +543,19 @@ pub struct BombadilExports {
     pub and: JsValue,
     pub or: JsValue,
     pub implies: JsValue,
+    pub until: JsValue,
+    pub release: JsValue,
+    pub previously: JsValue,
+    pub once: JsValue,
+    pub since: JsValue,
     pub next: JsValue,
     pub always: JsValue,
     pub eventually: JsValue,
+    pub at_end: JsValue,
     pub runtime_default: JsObject,
     pub time: JsObject,
     pub action_generator: JsValue,
+    pub setup: JsValue,
 }
 
 impl BombadilExports {
@@ -288,9 +578,15 @@ impl BombadilExports {
             and: get_export("And")?,
             or: get_export("Or")?,
             implies: get_export("Implies")?,
+            until: get_export("Until")?,
+            release: get_export("Release")?,
+            previously: get_export("Previously")?,
+            once: get_export("Once")?,
+            since: get_export("Since")?,
             next: get_export("Next")?,
             always: get_export("Always")?,
             eventually: get_export("Eventually")?,
+            at_end: get_export("AtEnd")?,
             runtime_default: get_export("runtimeDefault")?.as_object().ok_or(
                 SpecificationError::OtherError(
                     "runtimeDefault is not an object".to_string(),
@@ -302,6 +598,7 @@ impl BombadilExports {
                 ),
             )?,
             action_generator: get_export("ActionGenerator")?,
+            setup: get_export("Setup")?,
         })
     }
 }
@@ -344,17 +641,27 @@ impl Extractors {
         self.instances.get(&id)
     }
 
+    /// The extractors' functions, sorted by id.
     pub fn extract_functions(
         &self,
         context: &mut Context,
-    ) -> Result<HashMap<u64, String>> {
-        let mut functions = HashMap::new();
+    ) -> Result<Vec<ExtractorFunction>> {
+        let mut functions = Vec::with_capacity(self.instances.len());
 
         for (&id, obj) in &self.instances {
             let func = obj.get(js_string!("extract"), context)?;
-            functions
-                .insert(id, func.to_string(context)?.to_std_string_lossy());
+            let every = obj.get(js_string!("every"), context)?;
+            functions.push(ExtractorFunction {
+                id,
+                source: func.to_string(context)?.to_std_string_lossy(),
+                every: if every.is_undefined() {
+                    1
+                } else {
+                    (every.to_number(context)? as u64).max(1)
+                },
+            });
         }
+        functions.sort_by_key(|function| function.id);
 
         Ok(functions)
     }
@@ -363,6 +670,7 @@ impl Extractors {
         &self,
         results: Vec<(u64, json::Value)>,
         time: SystemTime,
+        step_index: u64,
         context: &mut Context,
     ) -> Result<()> {
         let update = |extractor: &JsObject,
@@ -384,6 +692,35 @@ impl Extractors {
             Ok(())
         };
 
+        let validate = |extractor: &JsObject,
+                        value: &JsValue,
+                        context: &mut Context|
+         -> Result<()> {
+            let method = extractor
+                .get(js_string!("validate"), context)?
+                .as_callable()
+                .ok_or(SpecificationError::OtherError(
+                    "validate is not callable".to_string(),
+                ))?;
+            let error = method.call(
+                &JsValue::from(extractor.clone()),
+                std::slice::from_ref(value),
+                context,
+            )?;
+            if let Some(error) = error.as_string() {
+                let function = extractor
+                    .get(js_string!("extract"), context)?
+                    .to_string(context)?
+                    .to_std_string_lossy();
+                return Err(SpecificationError::OtherError(format!(
+                    "extractor `{}` returned a value not matching its schema: {}",
+                    function,
+                    error.to_std_string_lossy()
+                )));
+            }
+            Ok(())
+        };
+
         let time = JsValue::from_json(
             &json::Value::Number(
                 json::Number::from_u128(
@@ -396,11 +733,17 @@ impl Extractors {
             context,
         )?;
 
-        update(&self.time, JsValue::null(), time.clone(), context)?;
+        update(
+            &self.time,
+            JsValue::from(step_index as f64),
+            time.clone(),
+            context,
+        )?;
 
         for (id, json_result) in results {
             if let Some(obj) = self.get(id) {
                 let js_value = JsValue::from_json(&json_result, context)?;
+                validate(obj, &js_value, context)?;
                 update(obj, js_value, time.clone(), context)?;
             }
         }
@@ -1,20 +1,128 @@
+use std::collections::HashMap;
+use std::fmt::Display;
 use std::time::{Duration, SystemTime};
 
+use crate::specification::render::SExpr;
 use crate::specification::result::{Result, SpecificationError};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 /// A formula in negation normal form (NNF), up to thunks. Note that `Implies` is preserved for
 /// better error messages.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Formula<Function> {
-    Pure { value: bool, pretty: String },
-    Thunk { function: Function, negated: bool },
+    Pure {
+        value: bool,
+        pretty: String,
+    },
+    Thunk {
+        function: Function,
+        negated: bool,
+    },
     And(Box<Formula<Function>>, Box<Formula<Function>>),
     Or(Box<Formula<Function>>, Box<Formula<Function>>),
     Implies(Box<Formula<Function>>, Box<Formula<Function>>),
     Next(Box<Formula<Function>>),
-    Always(Box<Formula<Function>>, Option<Duration>),
-    Eventually(Box<Formula<Function>>, Option<Duration>),
+    Always(Box<Formula<Function>>, Option<Bound>),
+    Eventually(Box<Formula<Function>>, Option<Bound>),
+    /// The right formula holds at some point, and the left one at every state before.
+    Until(Box<Formula<Function>>, Box<Formula<Function>>),
+    /// The right formula holds at every state up to and including the first one where the
+    /// left one holds, or forever if it never does.
+    Release(Box<Formula<Function>>, Box<Formula<Function>>),
+    /// A past-time formula, decided in every state by the ones before it, so that it's
+    /// negated like a thunk.
+    Past {
+        formula: Past<Function>,
+        negated: bool,
+    },
+}
+
+/// How far a bounded `always` or `eventually` reaches from the state it starts in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Bound {
+    /// A duration of wall-clock time.
+    Duration(Duration),
+    /// A number of states after the first one, which doesn't depend on machine speed.
+    Steps(u64),
+}
+
+/// Where a bounded `always` or `eventually` ends, as of the state it's evaluated in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum End {
+    Time(Time),
+    /// States left to evaluate, including the current one.
+    Steps(u64),
+}
+
+impl End {
+    fn start(bound: Bound, time: Time) -> Result<Self> {
+        match bound {
+            Bound::Duration(duration) => {
+                Ok(End::Time(time.checked_add(duration).ok_or(
+                    SpecificationError::OtherError(
+                        "failed to add bound to time".to_string(),
+                    ),
+                )?))
+            }
+            Bound::Steps(steps) => Ok(End::Steps(steps.saturating_add(1))),
+        }
+    }
+
+    fn passed(&self, time: Time) -> bool {
+        match self {
+            End::Time(end) => *end < time,
+            End::Steps(left) => *left == 0,
+        }
+    }
+
+    /// The end as of the next state.
+    fn next(self) -> Self {
+        match self {
+            End::Time(end) => End::Time(end),
+            End::Steps(left) => End::Steps(left.saturating_sub(1)),
+        }
+    }
+
+    /// The time of the end, if bounded in time.
+    pub fn time(self) -> Option<Time> {
+        match self {
+            End::Time(end) => Some(end),
+            End::Steps(_) => None,
+        }
+    }
+}
+
+/// Past-time operators. Their subformulas must be decided in a single state, e.g. thunks
+/// or other past-time formulas.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Past<Function> {
+    /// The subformula held in the previous state, which is false in the first one.
+    Previously(Box<Formula<Function>>),
+    /// The subformula held in this state or an earlier one.
+    Once(Box<Formula<Function>>),
+    /// The right subformula held in this state or an earlier one, and the left one in
+    /// every state after it.
+    Since(Box<Formula<Function>>, Box<Formula<Function>>),
+}
+
+impl<Function: Clone> Past<Function> {
+    fn map_function_ref<Result>(
+        &self,
+        f: &impl Fn(&Function) -> Result,
+    ) -> Past<Result> {
+        match self {
+            Past::Previously(formula) => {
+                Past::Previously(Box::new(formula.map_function_ref(f)))
+            }
+            Past::Once(formula) => {
+                Past::Once(Box::new(formula.map_function_ref(f)))
+            }
+            Past::Since(left, right) => Past::Since(
+                Box::new(left.map_function_ref(f)),
+                Box::new(right.map_function_ref(f)),
+            ),
+        }
+    }
 }
 
 impl<Function: Clone> Formula<Function> {
@@ -61,6 +169,18 @@ impl<Function: Clone> Formula<Function> {
                 Box::new(formula.clone().map_function_ref(f)),
                 *bound,
             ),
+            Formula::Until(left, right) => Formula::Until(
+                Box::new(left.clone().map_function_ref(f)),
+                Box::new(right.clone().map_function_ref(f)),
+            ),
+            Formula::Release(left, right) => Formula::Release(
+                Box::new(left.clone().map_function_ref(f)),
+                Box::new(right.clone().map_function_ref(f)),
+            ),
+            Formula::Past { formula, negated } => Formula::Past {
+                formula: formula.map_function_ref(f),
+                negated: *negated,
+            },
         }
     }
 }
@@ -74,12 +194,17 @@ pub enum Value<Function> {
     Residual(Residual<Function>),
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Violation<Function> {
     False {
         time: Time,
         condition: String,
     },
+    /// A past-time formula didn't hold, given the states before.
+    Past {
+        time: Time,
+        formula: Box<Formula<Function>>,
+    },
     Eventually {
         subformula: Box<Formula<Function>>,
         reason: EventuallyViolation,
@@ -103,9 +228,24 @@ pub enum Violation<Function> {
         left: Formula<Function>,
         right: Box<Violation<Function>>,
     },
+    /// The left formula of an `until` stopped holding before the right one held, or the
+    /// test ended first, when there's no violation.
+    Until {
+        left: Box<Formula<Function>>,
+        right: Box<Formula<Function>>,
+        start: Time,
+        violation: Option<Box<Violation<Function>>>,
+    },
+    /// The right formula of a `release` stopped holding before the left one held.
+    Release {
+        left: Box<Formula<Function>>,
+        right: Box<Formula<Function>>,
+        start: Time,
+        violation: Box<Violation<Function>>,
+    },
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum EventuallyViolation {
     TimedOut(Time),
     TestEnded,
@@ -128,6 +268,10 @@ impl<Function: Clone> Violation<Function> {
                 time: *time,
                 condition: condition.clone(),
             },
+            Violation::Past { time, formula } => Violation::Past {
+                time: *time,
+                formula: Box::new(formula.map_function_ref(f)),
+            },
             Violation::Eventually { subformula, reason } => {
                 Violation::Eventually {
                     subformula: Box::new(subformula.map_function_ref(f)),
@@ -159,6 +303,30 @@ impl<Function: Clone> Violation<Function> {
                 left: left.map_function_ref(f),
                 right: Box::new(right.map_function_ref(f)),
             },
+            Violation::Until {
+                left,
+                right,
+                start,
+                violation,
+            } => Violation::Until {
+                left: Box::new(left.map_function_ref(f)),
+                right: Box::new(right.map_function_ref(f)),
+                start: *start,
+                violation: violation
+                    .as_ref()
+                    .map(|violation| Box::new(violation.map_function_ref(f))),
+            },
+            Violation::Release {
+                left,
+                right,
+                start,
+                violation,
+            } => Violation::Release {
+                left: Box::new(left.map_function_ref(f)),
+                right: Box::new(right.map_function_ref(f)),
+                start: *start,
+                violation: Box::new(violation.map_function_ref(f)),
+            },
         }
     }
 }
@@ -190,14 +358,14 @@ pub enum Residual<Function> {
     OrEventually {
         subformula: Box<Formula<Function>>,
         start: Time,
-        end: Option<Time>,
+        end: Option<End>,
         left: Box<Residual<Function>>,
         right: Box<Residual<Function>>,
     },
     AndAlways {
         subformula: Box<Formula<Function>>,
         start: Time,
-        end: Option<Time>,
+        end: Option<End>,
         left: Box<Residual<Function>>,
         right: Box<Residual<Function>>,
     },
@@ -211,26 +379,215 @@ pub enum Derived<Function> {
     },
     Always {
         start: Time,
-        end: Option<Time>,
+        end: Option<End>,
         subformula: Box<Formula<Function>>,
     },
     Eventually {
         start: Time,
-        end: Option<Time>,
+        end: Option<End>,
         subformula: Box<Formula<Function>>,
     },
+    Until {
+        start: Time,
+        left: Box<Formula<Function>>,
+        right: Box<Formula<Function>>,
+    },
+    Release {
+        start: Time,
+        left: Box<Formula<Function>>,
+        right: Box<Formula<Function>>,
+    },
+}
+
+impl<Function: Clone> Value<Function> {
+    pub fn map_function<Result>(
+        &self,
+        f: impl Fn(&Function) -> Result,
+    ) -> Value<Result> {
+        match self {
+            Value::True => Value::True,
+            Value::False(violation) => {
+                Value::False(violation.map_function_ref(&f))
+            }
+            Value::Residual(residual) => {
+                Value::Residual(residual.map_function_ref(&f))
+            }
+        }
+    }
+}
+
+impl<Function: Clone> Residual<Function> {
+    pub fn map_function<Result>(
+        &self,
+        f: impl Fn(&Function) -> Result,
+    ) -> Residual<Result> {
+        self.map_function_ref(&f)
+    }
+
+    fn map_function_ref<Result>(
+        &self,
+        f: &impl Fn(&Function) -> Result,
+    ) -> Residual<Result> {
+        match self {
+            Residual::True => Residual::True,
+            Residual::False(violation) => {
+                Residual::False(violation.map_function_ref(f))
+            }
+            Residual::Derived(derived, leaning) => Residual::Derived(
+                derived.map_function_ref(f),
+                match leaning {
+                    Leaning::AssumeTrue => Leaning::AssumeTrue,
+                    Leaning::AssumeFalse(violation) => {
+                        Leaning::AssumeFalse(violation.map_function_ref(f))
+                    }
+                },
+            ),
+            Residual::And { left, right } => Residual::And {
+                left: Box::new(left.map_function_ref(f)),
+                right: Box::new(right.map_function_ref(f)),
+            },
+            Residual::Or { left, right } => Residual::Or {
+                left: Box::new(left.map_function_ref(f)),
+                right: Box::new(right.map_function_ref(f)),
+            },
+            Residual::Implies {
+                left_formula,
+                left,
+                right,
+            } => Residual::Implies {
+                left_formula: left_formula.map_function_ref(f),
+                left: Box::new(left.map_function_ref(f)),
+                right: Box::new(right.map_function_ref(f)),
+            },
+            Residual::OrEventually {
+                subformula,
+                start,
+                end,
+                left,
+                right,
+            } => Residual::OrEventually {
+                subformula: Box::new(subformula.map_function_ref(f)),
+                start: *start,
+                end: *end,
+                left: Box::new(left.map_function_ref(f)),
+                right: Box::new(right.map_function_ref(f)),
+            },
+            Residual::AndAlways {
+                subformula,
+                start,
+                end,
+                left,
+                right,
+            } => Residual::AndAlways {
+                subformula: Box::new(subformula.map_function_ref(f)),
+                start: *start,
+                end: *end,
+                left: Box::new(left.map_function_ref(f)),
+                right: Box::new(right.map_function_ref(f)),
+            },
+        }
+    }
+}
+
+impl<Function: Clone> Derived<Function> {
+    fn map_function_ref<Result>(
+        &self,
+        f: &impl Fn(&Function) -> Result,
+    ) -> Derived<Result> {
+        match self {
+            Derived::Once { start, subformula } => Derived::Once {
+                start: *start,
+                subformula: Box::new(subformula.map_function_ref(f)),
+            },
+            Derived::Always {
+                start,
+                end,
+                subformula,
+            } => Derived::Always {
+                start: *start,
+                end: *end,
+                subformula: Box::new(subformula.map_function_ref(f)),
+            },
+            Derived::Eventually {
+                start,
+                end,
+                subformula,
+            } => Derived::Eventually {
+                start: *start,
+                end: *end,
+                subformula: Box::new(subformula.map_function_ref(f)),
+            },
+            Derived::Until { start, left, right } => Derived::Until {
+                start: *start,
+                left: Box::new(left.map_function_ref(f)),
+                right: Box::new(right.map_function_ref(f)),
+            },
+            Derived::Release { start, left, right } => Derived::Release {
+                start: *start,
+                left: Box::new(left.map_function_ref(f)),
+                right: Box::new(right.map_function_ref(f)),
+            },
+        }
+    }
 }
 
 pub type EvaluateThunk<'a, Function> =
     &'a mut dyn FnMut(&'_ Function, bool) -> Result<Formula<Function>>;
 
+/// What past-time formulas remember of the states before, keyed by their S-expression, so
+/// that they're shared between properties and survive thunks building them anew in every
+/// state.
+pub struct PastHistory<Function> {
+    entries: HashMap<String, PastEntry<Function>>,
+}
+
+impl<Function> Default for PastHistory<Function> {
+    fn default() -> Self {
+        PastHistory {
+            entries: HashMap::new(),
+        }
+    }
+}
+
+struct PastEntry<Function> {
+    formula: Past<Function>,
+    /// The last state the formula was evaluated in.
+    time: Time,
+    /// The summary of the states before `time`, if there were any.
+    before: Option<bool>,
+    /// The summary of the states up to and including `time`.
+    current: bool,
+}
+
 pub struct Evaluator<'a, Function> {
     evaluate_thunk: EvaluateThunk<'a, Function>,
+    history: &'a mut PastHistory<Function>,
 }
 
-impl<'a, Function: Clone> Evaluator<'a, Function> {
-    pub fn new(evaluate_thunk: EvaluateThunk<'a, Function>) -> Self {
-        Evaluator { evaluate_thunk }
+impl<'a, Function: Clone + Display> Evaluator<'a, Function> {
+    pub fn new(
+        evaluate_thunk: EvaluateThunk<'a, Function>,
+        history: &'a mut PastHistory<Function>,
+    ) -> Self {
+        Evaluator {
+            evaluate_thunk,
+            history,
+        }
+    }
+
+    /// Brings the past-time formulas evaluated so far up to the state at `time`, so that
+    /// they see it even when nothing evaluates them in it.
+    pub fn update_past(&mut self, time: Time) -> Result<()> {
+        let formulas: Vec<Past<Function>> = self
+            .history
+            .entries
+            .values()
+            .map(|entry| entry.formula.clone())
+            .collect();
+        for formula in formulas {
+            self.evaluate_past(&formula, time)?;
+        }
+        Ok(())
     }
 
     pub fn evaluate(
@@ -274,29 +631,94 @@ impl<'a, Function: Clone> Evaluator<'a, Function> {
                 Leaning::AssumeTrue, // TODO: expose true/false leaning in TS layer?
             ))),
             Formula::Always(formula, bound) => {
-                let end = if let Some(duration) = bound {
-                    Some(time.checked_add(*duration).ok_or(
-                        SpecificationError::OtherError(
-                            "failed to add bound to time".to_string(),
-                        ),
-                    )?)
-                } else {
-                    None
-                };
+                let end =
+                    bound.map(|bound| End::start(bound, time)).transpose()?;
                 self.evaluate_always(formula.clone(), time, end, time)
             }
             Formula::Eventually(formula, bound) => {
-                let end = if let Some(duration) = bound {
-                    Some(time.checked_add(*duration).ok_or(
-                        SpecificationError::OtherError(
-                            "failed to add bound to time".to_string(),
-                        ),
-                    )?)
-                } else {
-                    None
-                };
+                let end =
+                    bound.map(|bound| End::start(bound, time)).transpose()?;
                 self.evaluate_eventually(formula.clone(), time, end, time)
             }
+            Formula::Until(left, right) => {
+                self.evaluate_until(left.clone(), right.clone(), time, time)
+            }
+            Formula::Release(left, right) => {
+                self.evaluate_release(left.clone(), right.clone(), time, time)
+            }
+            Formula::Past { formula, negated } => {
+                if self.evaluate_past(formula, time)? != *negated {
+                    Ok(Value::True)
+                } else {
+                    Ok(Value::False(Violation::Past {
+                        time,
+                        formula: Box::new(Formula::Past {
+                            formula: formula.clone(),
+                            negated: *negated,
+                        }),
+                    }))
+                }
+            }
+        }
+    }
+
+    fn evaluate_past(
+        &mut self,
+        formula: &Past<Function>,
+        time: Time,
+    ) -> Result<bool> {
+        let key = SExpr(formula).to_string();
+        let before = match self.history.entries.get(&key) {
+            // Evaluated again in the same state, e.g. by another property.
+            Some(entry) if entry.time == time => entry.before,
+            Some(entry) => Some(entry.current),
+            None => None,
+        };
+        let held_before = before.unwrap_or(false);
+        // All subformulas are evaluated, so that the past-time ones among them see this
+        // state.
+        let (current, value) = match formula {
+            Past::Previously(subformula) => {
+                let now = self.evaluate_in_state(subformula, time)?;
+                (now, held_before)
+            }
+            Past::Once(subformula) => {
+                let once =
+                    self.evaluate_in_state(subformula, time)? || held_before;
+                (once, once)
+            }
+            Past::Since(left, right) => {
+                let left = self.evaluate_in_state(left, time)?;
+                let right = self.evaluate_in_state(right, time)?;
+                let since = right || (left && held_before);
+                (since, since)
+            }
+        };
+        self.history.entries.insert(
+            key,
+            PastEntry {
+                formula: formula.clone(),
+                time,
+                before,
+                current,
+            },
+        );
+        Ok(value)
+    }
+
+    /// Evaluates a subformula of a past-time formula, which must be decided in this state.
+    fn evaluate_in_state(
+        &mut self,
+        formula: &Formula<Function>,
+        time: Time,
+    ) -> Result<bool> {
+        match self.evaluate(formula, time)? {
+            Value::True => Ok(true),
+            Value::False(_) => Ok(false),
+            Value::Residual(_) => Err(SpecificationError::OtherError(format!(
+                "past-time operators need subformulas decided in a single state, but {} isn't",
+                formula
+            ))),
         }
     }
 
@@ -394,20 +816,21 @@ impl<'a, Function: Clone> Evaluator<'a, Function> {
         &mut self,
         subformula: Box<Formula<Function>>,
         start: Time,
-        end: Option<Time>,
+        end: Option<End>,
         time: Time,
     ) -> Result<Value<Function>> {
         if let Some(end) = end
-            && end < time
+            && end.passed(time)
         {
             return Ok(Value::True);
         }
+        let next_end = end.map(End::next);
 
         let residual = Residual::Derived(
             Derived::Always {
                 subformula: subformula.clone(),
                 start,
-                end,
+                end: next_end,
             },
             Leaning::AssumeTrue,
         );
@@ -418,13 +841,13 @@ impl<'a, Function: Clone> Evaluator<'a, Function> {
                 violation: Box::new(violation),
                 subformula: subformula.clone(),
                 start,
-                end,
+                end: end.and_then(End::time),
                 time,
             }),
             Value::Residual(left) => Value::Residual(Residual::AndAlways {
                 subformula: subformula.clone(),
                 start,
-                end,
+                end: next_end,
                 left: Box::new(left),
                 right: Box::new(residual),
             }),
@@ -435,16 +858,17 @@ impl<'a, Function: Clone> Evaluator<'a, Function> {
         &mut self,
         subformula: Box<Formula<Function>>,
         start: Time,
-        end: Option<Time>,
+        end: Option<End>,
         time: Time,
         left: Value<Function>,
         right: Value<Function>,
     ) -> Result<Value<Function>> {
         if let Some(end) = end
-            && end < time
+            && end.passed(time)
         {
             return Ok(Value::True);
         }
+        let next_end = end.map(End::next);
 
         Ok(match (left, right) {
             (Value::True, Value::True) => Value::True,
@@ -452,21 +876,21 @@ impl<'a, Function: Clone> Evaluator<'a, Function> {
                 violation: Box::new(violation.clone()),
                 subformula,
                 start,
-                end,
+                end: end.and_then(End::time),
                 time,
             }),
             (_, Value::False(violation)) => Value::False(Violation::Always {
                 violation: Box::new(violation.clone()),
                 subformula,
                 start,
-                end,
+                end: end.and_then(End::time),
                 time,
             }),
             (Value::Residual(left), Value::True) => {
                 Value::Residual(Residual::AndAlways {
                     subformula,
                     start,
-                    end,
+                    end: next_end,
                     left: Box::new(left),
                     right: Box::new(Residual::True),
                 })
@@ -475,7 +899,7 @@ impl<'a, Function: Clone> Evaluator<'a, Function> {
                 Value::Residual(Residual::AndAlways {
                     subformula,
                     start,
-                    end,
+                    end: next_end,
                     left: Box::new(Residual::True),
                     right: Box::new(right),
                 })
@@ -484,7 +908,7 @@ impl<'a, Function: Clone> Evaluator<'a, Function> {
                 Value::Residual(Residual::AndAlways {
                     subformula,
                     start,
-                    end,
+                    end: next_end,
                     left: Box::new(left),
                     right: Box::new(right),
                 })
@@ -496,23 +920,24 @@ impl<'a, Function: Clone> Evaluator<'a, Function> {
         &mut self,
         subformula: Box<Formula<Function>>,
         start: Time,
-        end: Option<Time>,
+        end: Option<End>,
         time: Time,
     ) -> Result<Value<Function>> {
         if let Some(end) = end
-            && end < time
+            && end.passed(time)
         {
             return Ok(Value::False(Violation::Eventually {
                 subformula: subformula.clone(),
                 reason: EventuallyViolation::TimedOut(time),
             }));
         }
+        let next_end = end.map(End::next);
 
         let residual = Residual::Derived(
             Derived::Eventually {
                 subformula: subformula.clone(),
                 start,
-                end,
+                end: next_end,
             },
             Leaning::AssumeFalse(Violation::Eventually {
                 subformula: subformula.clone(),
@@ -525,7 +950,7 @@ impl<'a, Function: Clone> Evaluator<'a, Function> {
             Value::False(_violation) => Value::Residual(residual),
             Value::Residual(left) => Value::Residual(Residual::OrEventually {
                 subformula,
-                end,
+                end: next_end,
                 start,
                 left: Box::new(left),
                 right: Box::new(residual),
@@ -537,19 +962,20 @@ impl<'a, Function: Clone> Evaluator<'a, Function> {
         &mut self,
         subformula: Box<Formula<Function>>,
         start: Time,
-        end: Option<Time>,
+        end: Option<End>,
         time: Time,
         left: Value<Function>,
         right: Value<Function>,
     ) -> Result<Value<Function>> {
         if let Some(end) = end
-            && end < time
+            && end.passed(time)
         {
             return Ok(Value::False(Violation::Eventually {
                 subformula,
                 reason: EventuallyViolation::TimedOut(time),
             }));
         }
+        let next_end = end.map(End::next);
 
         Ok(match (left, right) {
             (Value::True, _) => Value::True,
@@ -571,7 +997,7 @@ impl<'a, Function: Clone> Evaluator<'a, Function> {
                 Value::Residual(Residual::OrEventually {
                     subformula,
                     start,
-                    end,
+                    end: next_end,
                     left: Box::new(left.clone()),
                     right: Box::new(right.clone()),
                 })
@@ -579,6 +1005,78 @@ impl<'a, Function: Clone> Evaluator<'a, Function> {
         })
     }
 
+    /// Unfolds `left U right` into `right ∨ (left ∧ X(left U right))`.
+    fn evaluate_until(
+        &mut self,
+        left: Box<Formula<Function>>,
+        right: Box<Formula<Function>>,
+        start: Time,
+        time: Time,
+    ) -> Result<Value<Function>> {
+        let right_value = self.evaluate(&right, time)?;
+        if let Value::True = right_value {
+            return Ok(Value::True);
+        }
+        let left_value = self.evaluate(&left, time)?;
+        if let (Value::False(_), Value::False(violation)) =
+            (&right_value, &left_value)
+        {
+            return Ok(Value::False(Violation::Until {
+                left,
+                right,
+                start,
+                violation: Some(Box::new(violation.clone())),
+            }));
+        }
+
+        let next = Value::Residual(Residual::Derived(
+            Derived::Until {
+                start,
+                left: left.clone(),
+                right: right.clone(),
+            },
+            Leaning::AssumeFalse(Violation::Until {
+                left,
+                right,
+                start,
+                violation: None,
+            }),
+        ));
+        let continued = self.evaluate_and(&left_value, &next);
+        Ok(self.evaluate_or(&right_value, &continued))
+    }
+
+    /// Unfolds `left R right` into `right ∧ (left ∨ X(left R right))`.
+    fn evaluate_release(
+        &mut self,
+        left: Box<Formula<Function>>,
+        right: Box<Formula<Function>>,
+        start: Time,
+        time: Time,
+    ) -> Result<Value<Function>> {
+        let right_value = self.evaluate(&right, time)?;
+        if let Value::False(violation) = right_value {
+            return Ok(Value::False(Violation::Release {
+                left,
+                right,
+                start,
+                violation: Box::new(violation),
+            }));
+        }
+        let left_value = self.evaluate(&left, time)?;
+
+        let next = Value::Residual(Residual::Derived(
+            Derived::Release {
+                start,
+                left: left.clone(),
+                right: right.clone(),
+            },
+            Leaning::AssumeTrue,
+        ));
+        let released = self.evaluate_or(&left_value, &next);
+        Ok(self.evaluate_and(&right_value, &released))
+    }
+
     pub fn step(
         &mut self,
         residual: &Residual<Function>,
@@ -634,6 +1132,19 @@ impl<'a, Function: Clone> Evaluator<'a, Function> {
                     *deadline,
                     time,
                 )?,
+                Derived::Until { start, left, right } => self.evaluate_until(
+                    left.clone(),
+                    right.clone(),
+                    *start,
+                    time,
+                )?,
+                Derived::Release { start, left, right } => self
+                    .evaluate_release(
+                        left.clone(),
+                        right.clone(),
+                        *start,
+                        time,
+                    )?,
             },
             Residual::OrEventually {
                 subformula,
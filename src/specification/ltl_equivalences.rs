@@ -38,8 +38,13 @@ fn variable() -> BoxedStrategy<Variable> {
     prop_oneof![Just(X), Just(Y)].boxed()
 }
 
-fn bound() -> BoxedStrategy<Option<Duration>> {
-    prop::option::of((0..10u64).prop_map(Duration::from_millis)).boxed()
+fn bound() -> BoxedStrategy<Option<Bound>> {
+    prop::option::of(prop_oneof![
+        (0..10u64)
+            .prop_map(|millis| Bound::Duration(Duration::from_millis(millis))),
+        (0..3u64).prop_map(Bound::Steps),
+    ])
+    .boxed()
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -48,6 +53,40 @@ enum Thunk {
     Subformula(Box<Syntax<Thunk>>),
 }
 
+impl std::fmt::Display for Thunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+fn atomic() -> BoxedStrategy<Syntax<Thunk>> {
+    variable()
+        .prop_map(|value| Syntax::Thunk(Thunk::Atomic(value)))
+        .boxed()
+}
+
+/// Past-time formulas, over formulas decided in a single state.
+fn past() -> BoxedStrategy<Syntax<Thunk>> {
+    atomic()
+        .prop_recursive(3, 16, 2, |inner| {
+            prop_oneof![
+                inner
+                    .clone()
+                    .prop_map(|subformula| Syntax::Not(Box::new(subformula))),
+                inner.clone().prop_map(|subformula| {
+                    Syntax::Previously(Box::new(subformula))
+                }),
+                inner
+                    .clone()
+                    .prop_map(|subformula| Syntax::Once(Box::new(subformula))),
+                (inner.clone(), inner.clone()).prop_map(|(left, right)| {
+                    Syntax::Since(Box::new(left), Box::new(right))
+                }),
+            ]
+        })
+        .boxed()
+}
+
 fn syntax() -> BoxedStrategy<Syntax<Thunk>> {
     let leaf = prop_oneof![
         // leaf nodes
@@ -55,7 +94,8 @@ fn syntax() -> BoxedStrategy<Syntax<Thunk>> {
             value,
             pretty: format!("{}", value)
         }),
-        variable().prop_map(|value| Syntax::Thunk(Thunk::Atomic(value))),
+        atomic(),
+        past(),
     ]
     .boxed();
 
@@ -86,6 +126,12 @@ fn syntax() -> BoxedStrategy<Syntax<Thunk>> {
             (inner.clone(), bound()).prop_map(|(subformula, bound)| {
                 Syntax::Eventually(Box::new(subformula), bound)
             }),
+            (inner.clone(), inner.clone()).prop_map(|(left, right)| {
+                Syntax::Until(Box::new(left), Box::new(right))
+            }),
+            (inner.clone(), inner.clone()).prop_map(|(left, right)| {
+                Syntax::Release(Box::new(left), Box::new(right))
+            }),
         ]
     })
     .boxed()
@@ -164,7 +210,8 @@ fn check_equivalence(
             Ok(syntax.nnf())
         }
     };
-    let mut evaluator = Evaluator::new(&mut evaluate_thunk);
+    let mut history = PastHistory::default();
+    let mut evaluator = Evaluator::new(&mut evaluate_thunk, &mut history);
 
     let mut time = UNIX_EPOCH;
 
@@ -178,6 +225,7 @@ fn check_equivalence(
         if let Value::Residual(left) = &value_left
             && let Value::Residual(right) = &value_right
         {
+            evaluator.update_past(time).unwrap();
             value_left = evaluator.step(left, time).unwrap();
             value_right = evaluator.step(right, time).unwrap();
         } else {
@@ -275,3 +323,107 @@ proptest! {
         check_equivalence(formula_left, formula_right, trace, ValueEqMode::UpToViolations);
     }
 }
+
+// Until and release
+proptest! {
+    // ¬(φ U ψ) ⇔ (¬φ R ¬ψ)
+    #[test]
+    fn test_until_release_duality(φ in syntax(), ψ in syntax(), trace in trace()) {
+        let formula_left =
+            Syntax::Not(Box::new(Syntax::Until(Box::new(φ.clone()), Box::new(ψ.clone())))).nnf();
+        let formula_right =
+            Syntax::Release(Box::new(Syntax::Not(Box::new(φ.clone()))), Box::new(Syntax::Not(Box::new(ψ.clone())))).nnf();
+        check_equivalence(formula_left, formula_right, trace, ValueEqMode::Strict);
+    }
+
+    // F(φ) ⇔ true U φ
+    #[test]
+    fn test_eventually_as_until(φ in syntax(), trace in trace()) {
+        let formula_left =
+            Syntax::Eventually(Box::new(φ.clone()), None).nnf();
+        let formula_right =
+            Syntax::Until(Box::new(Syntax::Pure { value: true, pretty: "true".to_string() }), Box::new(φ.clone())).nnf();
+        check_equivalence(formula_left, formula_right, trace, ValueEqMode::UpToViolations);
+    }
+
+    // G(φ) ⇔ false R φ
+    #[test]
+    fn test_always_as_release(φ in syntax(), trace in trace()) {
+        let formula_left =
+            Syntax::Always(Box::new(φ.clone()), None).nnf();
+        let formula_right =
+            Syntax::Release(Box::new(Syntax::Pure { value: false, pretty: "false".to_string() }), Box::new(φ.clone())).nnf();
+        check_equivalence(formula_left, formula_right, trace, ValueEqMode::UpToViolations);
+    }
+}
+
+/// Evaluates a formula in each state of the trace on its own, with the history of the
+/// states before.
+fn evaluate_in_each_state(
+    formula: &Formula<Thunk>,
+    trace: &[State],
+) -> Vec<bool> {
+    let current = RefCell::new(0);
+    let mut evaluate_thunk = |thunk: &Thunk, negated: bool| match thunk {
+        Thunk::Atomic(variable) => {
+            let state = &trace[*current.borrow()];
+            let value = match variable {
+                Variable::X => state.x,
+                Variable::Y => state.y,
+            };
+            Ok(Formula::Pure {
+                value: value != negated,
+                pretty: format!("{}", value),
+            })
+        }
+        Thunk::Subformula(_) => {
+            unreachable!("only atomic thunks are generated")
+        }
+    };
+    let mut history = PastHistory::default();
+    let mut evaluator = Evaluator::new(&mut evaluate_thunk, &mut history);
+    let mut values = vec![];
+    for index in 0..trace.len() {
+        *current.borrow_mut() = index;
+        let time = UNIX_EPOCH + Duration::from_millis(index as u64);
+        evaluator.update_past(time).unwrap();
+        values.push(matches!(
+            evaluator.evaluate(formula, time).unwrap(),
+            Value::True
+        ));
+    }
+    values
+}
+
+// Past-time operators
+proptest! {
+    // once(φ) ⇔ true S φ
+    #[test]
+    fn test_once_as_since(φ in past(), trace in trace()) {
+        let formula_left =
+            Syntax::Once(Box::new(φ.clone())).nnf();
+        let formula_right =
+            Syntax::Since(Box::new(Syntax::Pure { value: true, pretty: "true".to_string() }), Box::new(φ.clone())).nnf();
+        prop_assert_eq!(evaluate_in_each_state(&formula_left, &trace), evaluate_in_each_state(&formula_right, &trace));
+    }
+
+    #[test]
+    fn test_past_operators_definitions(trace in trace()) {
+        let x = || Box::new(Syntax::Thunk(Thunk::Atomic(Variable::X)));
+        let y = || Box::new(Syntax::Thunk(Thunk::Atomic(Variable::Y)));
+
+        let previously = evaluate_in_each_state(&Syntax::Previously(x()).nnf(), &trace);
+        let once = evaluate_in_each_state(&Syntax::Once(x()).nnf(), &trace);
+        let since = evaluate_in_each_state(&Syntax::Since(x(), y()).nnf(), &trace);
+        let not_once = evaluate_in_each_state(&Syntax::Not(Box::new(Syntax::Once(x()))).nnf(), &trace);
+        for index in 0..trace.len() {
+            prop_assert_eq!(previously[index], index > 0 && trace[index - 1].x);
+            prop_assert_eq!(once[index], trace[..=index].iter().any(|state| state.x));
+            prop_assert_eq!(
+                since[index],
+                (0..=index).any(|start| trace[start].y && trace[start + 1..=index].iter().all(|state| state.x))
+            );
+            prop_assert_eq!(not_once[index], !once[index]);
+        }
+    }
+}
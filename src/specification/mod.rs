@@ -1,14 +1,28 @@
+#[cfg(feature = "verifier")]
+pub mod eject;
+#[cfg(feature = "verifier")]
+pub mod imports;
+#[cfg(feature = "verifier")]
+pub mod isolated;
+#[cfg(feature = "verifier")]
 pub mod js;
 pub mod ltl;
+#[cfg(feature = "verifier")]
 pub(crate) mod module_loader;
+pub mod monitor;
+pub mod random_streams;
+#[cfg(feature = "verifier")]
+pub mod remote;
 pub mod render;
 pub mod result;
 pub mod stop;
 pub mod syntax;
+#[cfg(feature = "verifier")]
 pub mod verifier;
+#[cfg(feature = "verifier")]
 pub mod worker;
 
 #[cfg(test)]
 mod ltl_equivalences;
-#[cfg(test)]
+#[cfg(all(test, feature = "verifier"))]
 mod random_test;
@@ -1,13 +1,15 @@
 use std::{
+    cell::RefCell,
+    collections::HashMap,
     fs,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
     rc::Rc,
 };
 
 use crate::specification::result::{Result, SpecificationError};
 use boa_engine::{
     Context, JsError, JsResult, JsString, Module, Source,
-    module::{MapModuleLoader, ModuleLoader, Referrer, SimpleModuleLoader},
+    module::{MapModuleLoader, ModuleLoader, Referrer},
 };
 use include_dir::{Dir, include_dir};
 use oxc::{
@@ -19,16 +21,26 @@ use oxc::{codegen::Codegen, semantic::SemanticBuilder};
 
 static JS_DIR: Dir = include_dir!("$CARGO_MANIFEST_DIR/target/specification");
 
+/// The specifier of the prelude imported into every file of a specification, which files
+/// may also import explicitly.
+pub const PRELUDE_SPECIFIER: &str = "bombadil:prelude";
+
 pub struct HybridModuleLoader {
     map_loader: Rc<MapModuleLoader>,
-    file_loader: Rc<SimpleModuleLoader>,
+    /// Modules imported by path, bundled with the specification, by resolved path.
+    local_modules: RefCell<HashMap<PathBuf, Module>>,
+    /// Whether modules imported by path that weren't bundled are read from the file
+    /// system, which a remote verifier doesn't do as it may not be the one the
+    /// specification was loaded from.
+    read_files: bool,
 }
 
 impl HybridModuleLoader {
-    pub fn new() -> Result<Self> {
+    pub fn new(read_files: bool) -> Result<Self> {
         Ok(HybridModuleLoader {
             map_loader: Rc::new(MapModuleLoader::new()),
-            file_loader: Rc::new(SimpleModuleLoader::new(".")?),
+            local_modules: RefCell::new(HashMap::new()),
+            read_files,
         })
     }
 
@@ -36,9 +48,12 @@ impl HybridModuleLoader {
         self.map_loader.insert(path, module);
     }
 
-    fn specifier_source_type(&self, spec: &JsString) -> JsResult<SourceType> {
-        let s = spec.to_std_string_escaped();
-        SourceType::from_path(s).map_err(JsError::from_rust)
+    pub fn insert_local_module(
+        &self,
+        path: impl Into<PathBuf>,
+        module: Module,
+    ) {
+        self.local_modules.borrow_mut().insert(path.into(), module);
     }
 
     fn resolve_path(
@@ -52,10 +67,10 @@ impl HybridModuleLoader {
                 specifier, referrer
             )),
         ))?;
-        Ok(referrer_path
-            .parent()
-            .expect("referrer path has no parent directory")
-            .join(specifier.to_std_string_lossy()))
+        Ok(resolve_import(
+            referrer_path,
+            &specifier.to_std_string_lossy(),
+        ))
     }
 }
 
@@ -75,25 +90,32 @@ impl ModuleLoader for HybridModuleLoader {
         {
             Ok(module) => Ok(module),
             Err(_) => {
-                let source_type = self.specifier_source_type(&specifier)?;
-                // If it looks like JS, use the regular file loader.
-                if [SourceType::cjs(), SourceType::mjs()].contains(&source_type)
-                {
-                    return self
-                        .file_loader
-                        .clone()
-                        .load_imported_module(referrer, specifier, context)
-                        .await;
+                let path = self.resolve_path(&referrer, &specifier)?;
+                if let Some(module) = self.local_modules.borrow().get(&path) {
+                    return Ok(module.clone());
+                }
+                if !self.read_files {
+                    return Err(JsError::from_rust(
+                        SpecificationError::OtherError(format!(
+                            "cannot import {}, only modules imported statically by path or URL are bundled with the specification",
+                            path.display()
+                        )),
+                    ));
                 }
 
-                // Otherwise we transpile to JS and load that in-memory.
-                let path = self.resolve_path(&referrer, &specifier)?;
-                let ts_source =
+                let source_type =
+                    SourceType::from_path(&path).map_err(JsError::from_rust)?;
+                let contents =
                     fs::read_to_string(&path).map_err(JsError::from_rust)?;
-
-                let js_source =
-                    transpile(&ts_source, path.as_path(), &source_type)
-                        .map_err(JsError::from_rust)?;
+                // Transpiled to JS unless it already is.
+                let js_source = if [SourceType::cjs(), SourceType::mjs()]
+                    .contains(&source_type)
+                {
+                    contents
+                } else {
+                    transpile(&contents, &path, &source_type)
+                        .map_err(JsError::from_rust)?
+                };
 
                 let context = &mut context.borrow_mut();
                 let source =
@@ -104,6 +126,35 @@ impl ModuleLoader for HybridModuleLoader {
     }
 }
 
+/// Whether a module is imported by path, rather than by URL or as a Bombadil module.
+pub fn is_local_import(specifier: &str) -> bool {
+    specifier.starts_with("./")
+        || specifier.starts_with("../")
+        || specifier.starts_with('/')
+}
+
+/// The path of a module imported by path from the module at `importer`, normalized so that
+/// a module has the same path however it's imported.
+pub fn resolve_import(importer: &Path, specifier: &str) -> PathBuf {
+    let path = importer.parent().unwrap_or(Path::new("")).join(specifier);
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
 pub fn load_bombadil_module(
     name: impl AsRef<Path>,
     context: &mut Context,
@@ -144,6 +195,39 @@ pub fn load_modules(context: &mut Context, modules: &[Module]) -> Result<()> {
     Ok(())
 }
 
+/// The source of a module exporting what the prelude and the module at `specifier` export,
+/// the module's exports (`names`) shadowing those of the prelude with the same names.
+pub fn prelude_wrapper(specifier: &str, names: &[String]) -> String {
+    let mut source = format!("export * from {:?};\n", PRELUDE_SPECIFIER);
+    if !names.is_empty() {
+        source.push_str(&format!(
+            "export {{ {} }} from {:?};\n",
+            names.join(", "),
+            specifier
+        ));
+    }
+    source
+}
+
+/// The specifiers of the modules imported or re-exported by a JavaScript module.
+pub fn imported_specifiers(source_code: &str) -> Result<Vec<String>> {
+    let allocator = Allocator::default();
+    let result =
+        oxc::parser::Parser::new(&allocator, source_code, SourceType::mjs())
+            .parse();
+    if result.panicked {
+        return Err(SpecificationError::TranspilationError(
+            result.errors.to_vec(),
+        ));
+    }
+    Ok(result
+        .module_record
+        .requested_modules
+        .keys()
+        .map(|specifier| specifier.to_string())
+        .collect())
+}
+
 pub fn transpile(
     source_code: &str,
     path: &Path,
@@ -0,0 +1,339 @@
+//! Checking formulas over arbitrary streams of timestamped JSON snapshots, e.g. the lines
+//! of a log or the events of a backend, with the same temporal logic as specifications.
+//! Conditions are Rust functions of the snapshot, so neither a browser nor a JavaScript
+//! runtime is involved.
+//!
+//! ```
+//! use std::time::{Duration, SystemTime};
+//!
+//! use bombadil::specification::ltl::Value;
+//! use bombadil::specification::monitor::{Monitor, always, eventually, now};
+//! use serde_json::json;
+//!
+//! let recovers = always(
+//!     now("down", |s| s["status"] == "down").implies(
+//!         eventually(now("up", |s| s["status"] == "up"))
+//!             .within(Duration::from_secs(30)),
+//!     ),
+//! );
+//! let mut monitor = Monitor::new([("recovers", recovers)]);
+//!
+//! let time = SystemTime::UNIX_EPOCH;
+//! monitor.step(&json!({ "status": "down" }), time).unwrap();
+//! let properties = monitor
+//!     .step(&json!({ "status": "up" }), time + Duration::from_secs(40))
+//!     .unwrap();
+//! assert!(matches!(properties[0].1, Value::False(_)));
+//! ```
+
+use std::fmt::{self, Debug, Display};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json as json;
+
+use crate::specification::ltl::{
+    Bound, Evaluator, Formula, PastHistory, Residual, Time, Value, Violation,
+};
+use crate::specification::result::Result;
+use crate::specification::stop::{StopDefault, stop_default};
+use crate::specification::syntax::Syntax;
+
+/// A named condition on a snapshot. Formulas and violations are rendered with its name.
+#[derive(Clone)]
+pub struct Predicate {
+    name: String,
+    function: Arc<dyn Fn(&json::Value) -> bool + Send + Sync>,
+}
+
+impl Predicate {
+    pub fn new(
+        name: impl Into<String>,
+        function: impl Fn(&json::Value) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Predicate {
+            name: name.into(),
+            function: Arc::new(function),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn holds(&self, snapshot: &json::Value) -> bool {
+        (self.function)(snapshot)
+    }
+}
+
+impl Debug for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Predicate({:?})", self.name)
+    }
+}
+
+impl Display for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+// Builders mirroring those of the TypeScript package.
+
+pub fn now(
+    name: impl Into<String>,
+    function: impl Fn(&json::Value) -> bool + Send + Sync + 'static,
+) -> Syntax<Predicate> {
+    Syntax::Thunk(Predicate::new(name, function))
+}
+
+pub fn not(syntax: Syntax<Predicate>) -> Syntax<Predicate> {
+    Syntax::Not(Box::new(syntax))
+}
+
+pub fn next(syntax: Syntax<Predicate>) -> Syntax<Predicate> {
+    Syntax::Next(Box::new(syntax))
+}
+
+pub fn always(syntax: Syntax<Predicate>) -> Syntax<Predicate> {
+    Syntax::Always(Box::new(syntax), None)
+}
+
+pub fn eventually(syntax: Syntax<Predicate>) -> Syntax<Predicate> {
+    Syntax::Eventually(Box::new(syntax), None)
+}
+
+pub fn previously(syntax: Syntax<Predicate>) -> Syntax<Predicate> {
+    Syntax::Previously(Box::new(syntax))
+}
+
+pub fn once(syntax: Syntax<Predicate>) -> Syntax<Predicate> {
+    Syntax::Once(Box::new(syntax))
+}
+
+impl Syntax<Predicate> {
+    pub fn and(self, that: Syntax<Predicate>) -> Self {
+        Syntax::And(Box::new(self), Box::new(that))
+    }
+
+    pub fn or(self, that: Syntax<Predicate>) -> Self {
+        Syntax::Or(Box::new(self), Box::new(that))
+    }
+
+    pub fn implies(self, that: Syntax<Predicate>) -> Self {
+        Syntax::Implies(Box::new(self), Box::new(that))
+    }
+
+    pub fn until(self, that: Syntax<Predicate>) -> Self {
+        Syntax::Until(Box::new(self), Box::new(that))
+    }
+
+    pub fn release(self, that: Syntax<Predicate>) -> Self {
+        Syntax::Release(Box::new(self), Box::new(that))
+    }
+
+    pub fn since(self, that: Syntax<Predicate>) -> Self {
+        Syntax::Since(Box::new(self), Box::new(that))
+    }
+
+    /// Bounds an `always` or `eventually` formula in time, leaving others as they are.
+    pub fn within(self, bound: Duration) -> Self {
+        match self {
+            Syntax::Always(syntax, _) => {
+                Syntax::Always(syntax, Some(Bound::Duration(bound)))
+            }
+            Syntax::Eventually(syntax, _) => {
+                Syntax::Eventually(syntax, Some(Bound::Duration(bound)))
+            }
+            other => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum PropertyState {
+    Initial(Formula<Predicate>),
+    Residual(Residual<Predicate>),
+    DefinitelyTrue,
+    DefinitelyFalse(Violation<Predicate>),
+}
+
+#[derive(Debug, Clone)]
+struct Property {
+    name: String,
+    state: PropertyState,
+}
+
+/// Named properties checked against a stream of snapshots, one step per snapshot, in the
+/// order of their times.
+pub struct Monitor {
+    properties: Vec<Property>,
+    past_history: PastHistory<Predicate>,
+}
+
+impl Monitor {
+    pub fn new(
+        properties: impl IntoIterator<Item = (impl Into<String>, Syntax<Predicate>)>,
+    ) -> Self {
+        Monitor {
+            properties: properties
+                .into_iter()
+                .map(|(name, syntax)| Property {
+                    name: name.into(),
+                    state: PropertyState::Initial(syntax.nnf()),
+                })
+                .collect(),
+            past_history: PastHistory::default(),
+        }
+    }
+
+    /// Evaluates the properties in the next snapshot, returning their values in the order
+    /// they were given. Properties already decided keep their value.
+    pub fn step(
+        &mut self,
+        snapshot: &json::Value,
+        time: Time,
+    ) -> Result<Vec<(String, Value<Predicate>)>> {
+        let mut evaluate_thunk = |predicate: &Predicate,
+                                  negated: bool|
+         -> Result<Formula<Predicate>> {
+            Ok(Formula::Pure {
+                value: predicate.holds(snapshot) != negated,
+                pretty: predicate.name.clone(),
+            })
+        };
+        let mut evaluator =
+            Evaluator::new(&mut evaluate_thunk, &mut self.past_history);
+        evaluator.update_past(time)?;
+
+        let mut values = Vec::with_capacity(self.properties.len());
+        for property in &mut self.properties {
+            let value = match &property.state {
+                PropertyState::Initial(formula) => {
+                    evaluator.evaluate(formula, time)?
+                }
+                PropertyState::Residual(residual) => {
+                    evaluator.step(residual, time)?
+                }
+                PropertyState::DefinitelyTrue => Value::True,
+                PropertyState::DefinitelyFalse(violation) => {
+                    Value::False(violation.clone())
+                }
+            };
+            property.state = match &value {
+                Value::True => PropertyState::DefinitelyTrue,
+                Value::False(violation) => {
+                    PropertyState::DefinitelyFalse(violation.clone())
+                }
+                Value::Residual(residual) => {
+                    PropertyState::Residual(residual.clone())
+                }
+            };
+            values.push((property.name.clone(), value));
+        }
+        Ok(values)
+    }
+
+    /// Decides the properties once the stream has ended, like a test ending: those still
+    /// undecided take the value they lean towards, e.g. an `eventually` that never held is
+    /// false. `None` for properties not stepped yet.
+    pub fn finish(
+        &self,
+        time: Time,
+    ) -> Vec<(String, Option<StopDefault<Predicate>>)> {
+        self.properties
+            .iter()
+            .map(|property| {
+                let default = match &property.state {
+                    PropertyState::Initial(_) => None,
+                    PropertyState::Residual(residual) => {
+                        stop_default(residual, time)
+                    }
+                    PropertyState::DefinitelyTrue => Some(StopDefault::True),
+                    PropertyState::DefinitelyFalse(violation) => {
+                        Some(StopDefault::False(violation.clone()))
+                    }
+                };
+                (property.name.clone(), default)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+
+    fn at(seconds: u64) -> Time {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    fn count_below(limit: i64) -> Syntax<Predicate> {
+        now(format!("count < {}", limit), move |snapshot| {
+            snapshot["count"]
+                .as_i64()
+                .is_some_and(|count| count < limit)
+        })
+    }
+
+    #[test]
+    fn test_always_violated() {
+        let mut monitor = Monitor::new([("bounded", always(count_below(3)))]);
+        for (second, count) in [0, 1, 2].into_iter().enumerate() {
+            let values = monitor
+                .step(&json::json!({ "count": count }), at(second as u64))
+                .unwrap();
+            assert!(matches!(values[0].1, Value::Residual(_)));
+        }
+        let values = monitor.step(&json::json!({ "count": 3 }), at(3)).unwrap();
+        match &values[0].1 {
+            Value::False(violation) => {
+                assert_eq!(
+                    violation.to_string(),
+                    "as of 0ms, it should always be the case that\n\ncount < 3\n\nbut at 3000ms\n\n!(count < 3)"
+                )
+            }
+            other => panic!("expected a violation, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_eventually_at_end_of_stream() {
+        let mut monitor = Monitor::new([
+            ("reached", eventually(not(count_below(2)))),
+            ("bounded", always(count_below(10))),
+        ]);
+        assert!(
+            monitor
+                .finish(at(0))
+                .iter()
+                .all(|(_, default)| default.is_none())
+        );
+        for (second, count) in [0, 1].into_iter().enumerate() {
+            monitor
+                .step(&json::json!({ "count": count }), at(second as u64))
+                .unwrap();
+        }
+        let defaults = monitor.finish(at(2));
+        assert!(matches!(defaults[0].1, Some(StopDefault::False(_))));
+        assert!(matches!(defaults[1].1, Some(StopDefault::True)));
+    }
+
+    #[test]
+    fn test_past_operators() {
+        let started = now("started", |snapshot| snapshot["started"] == true);
+        let mut monitor = Monitor::new([(
+            "started first",
+            always(not(count_below(1)).implies(once(started))),
+        )]);
+        monitor
+            .step(&json::json!({ "count": 0, "started": true }), at(0))
+            .unwrap();
+        let values = monitor
+            .step(&json::json!({ "count": 1, "started": false }), at(1))
+            .unwrap();
+        assert!(matches!(values[0].1, Value::Residual(_)));
+    }
+}
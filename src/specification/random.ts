@@ -5,9 +5,17 @@ export interface Generator<T> {
 // Random helpers (backed by Rust's rand crate via __bombadil_random_bytes)
 
 declare function __bombadil_random_bytes(n: number): Uint8Array;
+declare function __bombadil_random_stream_bytes(
+  key: string,
+  n: number,
+): Uint8Array;
+
+// Where random bytes currently come from, swapped by `RandomStream.generate`.
+let randomBytes: (n: number) => Uint8Array = (n) =>
+  __bombadil_random_bytes(n);
 
 function randomU32(): number {
-  return new DataView(__bombadil_random_bytes(4).buffer).getUint32(0);
+  return new DataView(randomBytes(4).buffer).getUint32(0);
 }
 
 /** @internal */
@@ -21,7 +29,7 @@ export function randomRange(min: number, max: number): number {
   }
   // For ranges exceeding 32 bits, generate a uniform float in [0, 1) with
   // 53 bits of precision (the maximum for a JS number) and scale it.
-  const view = new DataView(__bombadil_random_bytes(8).buffer);
+  const view = new DataView(randomBytes(8).buffer);
   const uniform =
     ((view.getUint32(0) >>> 5) * 0x4000000 + (view.getUint32(4) >>> 6)) /
     0x20000000000000;
@@ -35,6 +43,44 @@ function randomChoice<T>(items: T[]): T {
   return items[randomU32() % items.length]!;
 }
 
+// Keyed streams
+
+/**
+ * A deterministic source of randomness identified by a key. Given the same seed, a stream
+ * produces the same values regardless of what other streams or generators have produced,
+ * so adding a new generator doesn't shift existing random decisions.
+ */
+export class RandomStream {
+  constructor(private key: string) {}
+
+  bytes(n: number): Uint8Array {
+    return __bombadil_random_stream_bytes(this.key, n);
+  }
+
+  range(min: number, max: number): number {
+    return this.generate({ generate: () => randomRange(min, max) });
+  }
+
+  choice<T>(items: T[]): T {
+    return this.generate({ generate: () => randomChoice(items) });
+  }
+
+  /** Generate a value with any generator, drawing randomness from this stream. */
+  generate<T>(generator: Generator<T>): T {
+    const previous = randomBytes;
+    randomBytes = (n) => this.bytes(n);
+    try {
+      return generator.generate();
+    } finally {
+      randomBytes = previous;
+    }
+  }
+}
+
+export function randomStream(key: string): RandomStream {
+  return new RandomStream(key);
+}
+
 // Generators
 
 export class From<T> implements Generator<T> {
@@ -54,13 +100,63 @@ export function from<T>(elements: T[]): From<T> {
 
 const ALPHANUMERIC = "abcdefghijklmnopqrstuvwxyz0123456789";
 
+/** Kinds of characters drawn by `strings()`, each a corpus of characters input handling often gets wrong. */
+export type Charset =
+  | "alphanumeric"
+  | "latin"
+  | "cjk"
+  | "arabic"
+  | "diacritics"
+  | "surrogatePairs"
+  | "zeroWidth";
+
+// Characters made of several code points, or of a surrogate pair, are kept whole so
+// that they're never split into invalid or partial ones.
+const CHARSETS: Record<Charset, string[]> = {
+  alphanumeric: [...ALPHANUMERIC],
+  latin: [..."àáâäãåæçèéêëìíîïñòóôöõøœßùúûüýÿ"],
+  cjk: [..."日本語中文漢字你好世界東京かなカナがぎゃゅょアイウ한국어안녕하세요"],
+  arabic: [..."العربيةمرحبابكمسلام٠١٢٣"].concat(["\u200f"]),
+  // Base letters followed by combining marks, and lone combining marks.
+  diacritics: ["e\u0301", "a\u0308", "n\u0303", "o\u0302\u0323", "\u0301", "\u0327"],
+  surrogatePairs: ["😀", "👍🏽", "👨‍👩‍👧", "🇫🇷", "𝒜", "𠜎", "🀄"],
+  zeroWidth: ["\u200b", "\u200c", "\u200d", "\u2060", "\ufeff"],
+};
+
+/** A distribution of all charsets, mostly alphanumeric, for text exercising input handling. */
+export const INTERNATIONAL: Partial<Record<Charset, number>> = {
+  alphanumeric: 8,
+  latin: 2,
+  cjk: 2,
+  arabic: 2,
+  diacritics: 1,
+  surrogatePairs: 1,
+  zeroWidth: 1,
+};
+
 class StringGenerator implements Generator<string> {
   private size = { min: 0, max: 16 };
+  private distribution: [number, string[]][] | null = null;
+
   generate() {
     const len = randomRange(this.size.min, this.size.max);
-    return Array.from({ length: len }, () =>
-      randomChoice([...ALPHANUMERIC]),
-    ).join("");
+    const distribution = this.distribution;
+    if (distribution === null) {
+      return Array.from({ length: len }, () =>
+        randomChoice([...ALPHANUMERIC]),
+      ).join("");
+    }
+    const total = distribution.reduce((sum, [weight]) => sum + weight, 0);
+    return Array.from({ length: len }, () => {
+      let pick = randomRange(0, total);
+      for (const [weight, characters] of distribution) {
+        if (pick < weight) {
+          return randomChoice(characters);
+        }
+        pick -= weight;
+      }
+      throw new Error("unreachable");
+    }).join("");
   }
 
   minSize(value: number): StringGenerator {
@@ -72,6 +168,34 @@ class StringGenerator implements Generator<string> {
     this.size.max = value;
     return this;
   }
+
+  /**
+   * Draws each character from a charset picked by weight, like
+   * `{ alphanumeric: 3, cjk: 1 }`, instead of only alphanumeric ones. Sizes count
+   * characters, some of which are several code points.
+   */
+  charsets(weights: Partial<Record<Charset, number>>): StringGenerator {
+    const distribution: [number, string[]][] = [];
+    for (const [name, weight] of Object.entries(weights)) {
+      const characters = CHARSETS[name as Charset];
+      if (characters === undefined) {
+        throw new Error(`unknown charset: ${name}`);
+      }
+      if (!Number.isInteger(weight) || weight! < 0) {
+        throw new Error(
+          `weight of charset ${name} must be a non-negative integer, got ${weight}`,
+        );
+      }
+      if (weight! > 0) {
+        distribution.push([weight!, characters]);
+      }
+    }
+    if (distribution.length === 0) {
+      throw new Error("`charsets` needs a charset with a positive weight");
+    }
+    this.distribution = distribution;
+    return this;
+  }
 }
 
 export function strings(): StringGenerator {
@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// Independent random streams, one per key, all derived from a single seed.
+///
+/// A stream only depends on the seed and its key, not on how many bytes other streams have
+/// produced, so adding a new generator to a specification doesn't shift the decisions of
+/// existing ones.
+pub struct RandomStreams {
+    seed: u64,
+    streams: HashMap<String, ChaCha8Rng>,
+}
+
+impl RandomStreams {
+    pub fn new(seed: u64) -> Self {
+        RandomStreams {
+            seed,
+            streams: HashMap::new(),
+        }
+    }
+
+    pub fn fill(&mut self, key: &str, buffer: &mut [u8]) {
+        let seed = self.seed;
+        self.streams
+            .entry(key.to_string())
+            .or_insert_with(|| {
+                ChaCha8Rng::seed_from_u64(stream_seed(seed, key))
+            })
+            .fill_bytes(buffer);
+    }
+}
+
+/// Derives the seed of a keyed stream using FNV-1a, which (unlike the standard library's
+/// hashers) is stable across Rust versions, so recorded seeds keep reproducing.
+fn stream_seed(seed: u64, key: &str) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+    seed.to_le_bytes()
+        .iter()
+        .chain(key.as_bytes())
+        .fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(streams: &mut RandomStreams, key: &str) -> [u8; 8] {
+        let mut buffer = [0u8; 8];
+        streams.fill(key, &mut buffer);
+        buffer
+    }
+
+    #[test]
+    fn test_stream_independent_of_other_streams() {
+        let mut alone = RandomStreams::new(42);
+        let expected = [bytes(&mut alone, "a"), bytes(&mut alone, "a")];
+
+        let mut interleaved = RandomStreams::new(42);
+        bytes(&mut interleaved, "b");
+        let first = bytes(&mut interleaved, "a");
+        bytes(&mut interleaved, "c");
+        let second = bytes(&mut interleaved, "a");
+
+        assert_eq!([first, second], expected);
+    }
+
+    #[test]
+    fn test_streams_differ_by_key_and_seed() {
+        let mut streams = RandomStreams::new(42);
+        assert_ne!(bytes(&mut streams, "a"), bytes(&mut streams, "b"));
+        assert_ne!(
+            bytes(&mut RandomStreams::new(1), "a"),
+            bytes(&mut RandomStreams::new(2), "a")
+        );
+    }
+}
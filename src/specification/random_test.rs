@@ -21,7 +21,7 @@ thread_local! {
 fn load_random_module(random_bytes: Vec<u8>) -> (Context, Module) {
     RANDOM_BYTES.with(|buf| *buf.borrow_mut() = VecDeque::from(random_bytes));
 
-    let loader = Rc::new(HybridModuleLoader::new().unwrap());
+    let loader = Rc::new(HybridModuleLoader::new(true).unwrap());
     let mut context = ContextBuilder::default()
         .module_loader(loader.clone())
         .build()
@@ -0,0 +1,276 @@
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json as json;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::Mutex;
+
+use crate::specification::ltl;
+use crate::specification::result::SpecificationError;
+use crate::specification::verifier::{ExtractorFunction, Specification};
+use crate::specification::worker::{
+    EndViolation, StepResult, VerifierBackend, VerifierWorker, WorkerError,
+};
+
+#[derive(Serialize, Deserialize)]
+enum Request {
+    Start {
+        specification: Specification,
+        seed: u64,
+    },
+    Properties,
+    Extractors,
+    Setup,
+    Step {
+        snapshots: Vec<(u64, json::Value)>,
+        time: ltl::Time,
+    },
+    Finish {
+        time: ltl::Time,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+enum Response {
+    Started,
+    Properties(Vec<String>),
+    Extractors(Vec<ExtractorFunction>),
+    Setup(Vec<json::Value>),
+    Step(StepResult<json::Value>),
+    Finish(Vec<EndViolation>),
+    Error(String),
+}
+
+struct Connection {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+}
+
+impl Connection {
+    async fn send<T: Serialize>(&mut self, message: &T) -> std::io::Result<()> {
+        let mut line = json::to_vec(message)?;
+        line.push(b'\n');
+        self.writer.write_all(&line).await
+    }
+
+    /// Receives the next message, or None when the connection was closed.
+    async fn receive<T: for<'de> Deserialize<'de>>(
+        &mut self,
+    ) -> std::io::Result<Option<T>> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(json::from_str(&line)?))
+    }
+}
+
+impl From<TcpStream> for Connection {
+    fn from(stream: TcpStream) -> Self {
+        let (reader, writer) = stream.into_split();
+        Connection {
+            reader: BufReader::new(reader),
+            writer,
+        }
+    }
+}
+
+/// A verifier evaluated by a `bombadil verifier-server` over TCP, e.g. when the browser runs
+/// on a resource-constrained device farm node. Requests and responses are JSON values on
+/// their own lines, starting with a `Start` request carrying the (transpiled) specification
+/// and seed, which the server evaluates in a worker dedicated to the connection.
+pub struct RemoteVerifier {
+    connection: Mutex<Connection>,
+}
+
+impl RemoteVerifier {
+    pub async fn connect(
+        address: impl ToSocketAddrs,
+        specification: Specification,
+        seed: u64,
+    ) -> Result<Arc<Self>, WorkerError> {
+        let stream = TcpStream::connect(address)
+            .await
+            .map_err(|error| WorkerError::Remote(error.to_string()))?;
+        let verifier = RemoteVerifier {
+            connection: Mutex::new(Connection::from(stream)),
+        };
+        match verifier
+            .call(Request::Start {
+                specification,
+                seed,
+            })
+            .await?
+        {
+            Response::Started => Ok(Arc::new(verifier)),
+            _ => Err(unexpected_response()),
+        }
+    }
+
+    async fn call(&self, request: Request) -> Result<Response, WorkerError> {
+        let mut connection = self.connection.lock().await;
+        connection
+            .send(&request)
+            .await
+            .map_err(|error| WorkerError::Remote(error.to_string()))?;
+        match connection
+            .receive()
+            .await
+            .map_err(|error| WorkerError::Remote(error.to_string()))?
+        {
+            None => Err(WorkerError::WorkerGone),
+            Some(Response::Error(message)) => {
+                Err(WorkerError::SpecificationError(
+                    SpecificationError::OtherError(message),
+                ))
+            }
+            Some(response) => Ok(response),
+        }
+    }
+}
+
+fn unexpected_response() -> WorkerError {
+    WorkerError::Remote("unexpected response".to_string())
+}
+
+impl VerifierBackend for RemoteVerifier {
+    fn properties(&self) -> BoxFuture<'_, Result<Vec<String>, WorkerError>> {
+        Box::pin(async move {
+            match self.call(Request::Properties).await? {
+                Response::Properties(properties) => Ok(properties),
+                _ => Err(unexpected_response()),
+            }
+        })
+    }
+
+    fn extractors(
+        &self,
+    ) -> BoxFuture<'_, Result<Vec<ExtractorFunction>, WorkerError>> {
+        Box::pin(async move {
+            match self.call(Request::Extractors).await? {
+                Response::Extractors(extractors) => Ok(extractors),
+                _ => Err(unexpected_response()),
+            }
+        })
+    }
+
+    fn setup_json(
+        &self,
+    ) -> BoxFuture<'_, Result<Vec<json::Value>, WorkerError>> {
+        Box::pin(async move {
+            match self.call(Request::Setup).await? {
+                Response::Setup(actions) => Ok(actions),
+                _ => Err(unexpected_response()),
+            }
+        })
+    }
+
+    fn step_json(
+        &self,
+        snapshots: Vec<(u64, json::Value)>,
+        time: ltl::Time,
+    ) -> BoxFuture<'_, Result<StepResult<json::Value>, WorkerError>> {
+        Box::pin(async move {
+            match self.call(Request::Step { snapshots, time }).await? {
+                Response::Step(result) => Ok(result),
+                _ => Err(unexpected_response()),
+            }
+        })
+    }
+
+    fn finish(
+        &self,
+        time: ltl::Time,
+    ) -> BoxFuture<'_, Result<Vec<EndViolation>, WorkerError>> {
+        Box::pin(async move {
+            match self.call(Request::Finish { time }).await? {
+                Response::Finish(violations) => Ok(violations),
+                _ => Err(unexpected_response()),
+            }
+        })
+    }
+}
+
+/// Accepts connections from remote verifier clients, each evaluating its own specification.
+pub async fn serve(listener: TcpListener) -> std::io::Result<()> {
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        log::info!("verifier client connected: {}", peer);
+        tokio::spawn(async move {
+            match handle_client(Connection::from(stream)).await {
+                Ok(()) => log::info!("verifier client disconnected: {}", peer),
+                Err(error) => {
+                    log::error!("verifier client {} failed: {}", peer, error)
+                }
+            }
+        });
+    }
+}
+
+async fn handle_client(mut connection: Connection) -> std::io::Result<()> {
+    let Some(Request::Start {
+        specification,
+        seed,
+    }) = connection.receive().await?
+    else {
+        return connection
+            .send(&Response::Error("expected a start request".to_string()))
+            .await;
+    };
+    let worker = match VerifierWorker::start(specification, seed).await {
+        Ok(worker) => worker,
+        Err(error) => {
+            return connection.send(&Response::Error(error.to_string())).await;
+        }
+    };
+    connection.send(&Response::Started).await?;
+
+    while let Some(request) = connection.receive::<Request>().await? {
+        let response = match request {
+            Request::Start { .. } => {
+                Err(WorkerError::Remote("already started".to_string()))
+            }
+            Request::Properties => {
+                worker.properties().await.map(Response::Properties)
+            }
+            Request::Extractors => {
+                worker.extractors().await.map(Response::Extractors)
+            }
+            Request::Setup => worker.setup_json().await.map(Response::Setup),
+            Request::Step { snapshots, time } => {
+                worker.step_json(snapshots, time).await.map(Response::Step)
+            }
+            Request::Finish { time } => {
+                worker.finish(time).await.map(Response::Finish)
+            }
+        };
+        connection
+            .send(
+                &response
+                    .unwrap_or_else(|error| Response::Error(error.to_string())),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_are_single_lines() {
+        let request = Request::Step {
+            snapshots: vec![(1, json::json!({ "text": "a\nb" }))],
+            time: std::time::UNIX_EPOCH,
+        };
+        let line = json::to_string(&request).unwrap();
+        assert!(!line.contains('\n'));
+        assert!(matches!(
+            json::from_str::<Request>(&line).unwrap(),
+            Request::Step { snapshots, .. } if snapshots[0].0 == 1
+        ));
+    }
+}
@@ -1,24 +1,30 @@
+use std::fmt::{self, Display};
 use std::time::UNIX_EPOCH;
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-use crate::specification::{
-    js::RuntimeFunction,
-    ltl::{EventuallyViolation, Formula, Time, Violation},
+#[cfg(feature = "verifier")]
+use crate::specification::js::RuntimeFunction;
+use crate::specification::ltl::{
+    Bound, Derived, End, EventuallyViolation, Formula, Leaning, Past, Residual,
+    Time, Value, Violation,
 };
 
 pub fn render_violation(violation: &Violation<PrettyFunction>) -> String {
     format!("{}", RenderedViolation(violation))
 }
 
-struct RenderedViolation<'a>(&'a Violation<PrettyFunction>);
+struct RenderedViolation<'a, F>(&'a Violation<F>);
 
-impl<'a> std::fmt::Display for RenderedViolation<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl<'a, F: Display> Display for RenderedViolation<'a, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self.0 {
             Violation::False { condition, .. } => {
                 write!(f, "!({})", condition)?;
             }
+            Violation::Past { formula, .. } => {
+                write!(f, "!({})", RenderedFormula(formula))?;
+            }
             Violation::Eventually { subformula, reason } => {
                 match reason {
                     EventuallyViolation::TimedOut(time) => {
@@ -54,6 +60,49 @@ impl<'a> std::fmt::Display for RenderedViolation<'a> {
                     RenderedFormula(left),
                 )?;
             }
+            Violation::Until {
+                left,
+                right,
+                start,
+                violation: Some(violation),
+            } => {
+                write!(
+                    f,
+                    "as of {}ms, it should be the case that\n\n{}\n\nuntil\n\n{}\n\nbut before that\n\n{}",
+                    time_to_ms(start),
+                    RenderedFormula(left),
+                    RenderedFormula(right),
+                    RenderedViolation(violation),
+                )?;
+            }
+            Violation::Until {
+                left,
+                right,
+                violation: None,
+                ..
+            } => {
+                write!(
+                    f,
+                    "failed at test end: {}.until({})",
+                    RenderedFormula(left),
+                    RenderedFormula(right),
+                )?;
+            }
+            Violation::Release {
+                left,
+                right,
+                start,
+                violation,
+            } => {
+                write!(
+                    f,
+                    "as of {}ms, it should be the case that\n\n{}\n\nuntil released by\n\n{}\n\nbut before that\n\n{}",
+                    time_to_ms(start),
+                    RenderedFormula(right),
+                    RenderedFormula(left),
+                    RenderedViolation(violation),
+                )?;
+            }
             Violation::Always {
                 violation,
                 subformula,
@@ -92,10 +141,10 @@ impl<'a> std::fmt::Display for RenderedViolation<'a> {
     }
 }
 
-struct RenderedFormula<'a>(&'a Formula<PrettyFunction>);
+struct RenderedFormula<'a, F>(&'a Formula<F>);
 
-impl<'a> std::fmt::Display for RenderedFormula<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl<'a, F: Display> Display for RenderedFormula<'a, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self.0 {
             Formula::Pure { value: _, pretty } => write!(f, "{}", pretty),
             Formula::Thunk { function, negated } => {
@@ -129,6 +178,30 @@ impl<'a> std::fmt::Display for RenderedFormula<'a> {
                     RenderedFormula(right)
                 )
             }
+            Formula::Until(left, right) => {
+                write!(
+                    f,
+                    "{}.until({})",
+                    RenderedFormula(left),
+                    RenderedFormula(right)
+                )
+            }
+            Formula::Release(left, right) => {
+                write!(
+                    f,
+                    "{}.release({})",
+                    RenderedFormula(left),
+                    RenderedFormula(right)
+                )
+            }
+            Formula::Past {
+                formula,
+                negated: true,
+            } => write!(f, "not({})", RenderedPast(formula)),
+            Formula::Past {
+                formula,
+                negated: false,
+            } => write!(f, "{}", RenderedPast(formula)),
             Formula::Next(formula) => {
                 write!(f, "next({})", RenderedFormula(formula))
             }
@@ -138,9 +211,9 @@ impl<'a> std::fmt::Display for RenderedFormula<'a> {
             Formula::Always(formula, Some(bound)) => {
                 write!(
                     f,
-                    "always({}).within({}, \"milliseconds\")",
+                    "always({}){}",
                     RenderedFormula(formula),
-                    bound.as_millis()
+                    RenderedBound(bound)
                 )
             }
             Formula::Eventually(formula, None) => {
@@ -149,11 +222,538 @@ impl<'a> std::fmt::Display for RenderedFormula<'a> {
             Formula::Eventually(formula, Some(bound)) => {
                 write!(
                     f,
-                    "eventually({}).within({}, \"milliseconds\")",
+                    "eventually({}){}",
                     RenderedFormula(formula),
-                    bound.as_millis()
+                    RenderedBound(bound)
+                )
+            }
+        }
+    }
+}
+
+struct RenderedBound<'a>(&'a Bound);
+
+impl<'a> Display for RenderedBound<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Bound::Duration(duration) => {
+                write!(f, ".within({}, \"milliseconds\")", duration.as_millis())
+            }
+            Bound::Steps(steps) => write!(f, ".withinSteps({})", steps),
+        }
+    }
+}
+
+struct RenderedPast<'a, F>(&'a Past<F>);
+
+impl<'a, F: Display> Display for RenderedPast<'a, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Past::Previously(formula) => {
+                write!(f, "previously({})", RenderedFormula(formula))
+            }
+            Past::Once(formula) => {
+                write!(f, "once({})", RenderedFormula(formula))
+            }
+            Past::Since(left, right) => write!(
+                f,
+                "{}.since({})",
+                RenderedFormula(left),
+                RenderedFormula(right)
+            ),
+        }
+    }
+}
+
+struct RenderedResidual<'a, F>(&'a Residual<F>);
+
+impl<'a, F: Display> Display for RenderedResidual<'a, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Residual::True => write!(f, "true"),
+            Residual::False(violation) => {
+                write!(f, "false: {}", RenderedViolation(violation))
+            }
+            Residual::Derived(derived, _) => match derived {
+                Derived::Once { subformula, .. } => {
+                    write!(f, "next({})", RenderedFormula(subformula))
+                }
+                Derived::Always {
+                    end: None,
+                    subformula,
+                    ..
+                } => write!(f, "always({})", RenderedFormula(subformula)),
+                Derived::Always {
+                    end: Some(End::Time(end)),
+                    subformula,
+                    ..
+                } => write!(
+                    f,
+                    "always({}) until {}ms",
+                    RenderedFormula(subformula),
+                    time_to_ms(end)
+                ),
+                Derived::Always {
+                    end: Some(End::Steps(steps)),
+                    subformula,
+                    ..
+                } => write!(
+                    f,
+                    "always({}) for {} more states",
+                    RenderedFormula(subformula),
+                    steps
+                ),
+                Derived::Eventually {
+                    end: None,
+                    subformula,
+                    ..
+                } => write!(f, "eventually({})", RenderedFormula(subformula)),
+                Derived::Eventually {
+                    end: Some(End::Time(end)),
+                    subformula,
+                    ..
+                } => write!(
+                    f,
+                    "eventually({}) by {}ms",
+                    RenderedFormula(subformula),
+                    time_to_ms(end)
+                ),
+                Derived::Eventually {
+                    end: Some(End::Steps(steps)),
+                    subformula,
+                    ..
+                } => write!(
+                    f,
+                    "eventually({}) within {} more states",
+                    RenderedFormula(subformula),
+                    steps
+                ),
+                Derived::Until { left, right, .. } => write!(
+                    f,
+                    "{}.until({})",
+                    RenderedFormula(left),
+                    RenderedFormula(right)
+                ),
+                Derived::Release { left, right, .. } => write!(
+                    f,
+                    "{}.release({})",
+                    RenderedFormula(left),
+                    RenderedFormula(right)
+                ),
+            },
+            Residual::And { left, right }
+            | Residual::AndAlways { left, right, .. } => write!(
+                f,
+                "({}) and ({})",
+                RenderedResidual(left),
+                RenderedResidual(right)
+            ),
+            Residual::Or { left, right }
+            | Residual::OrEventually { left, right, .. } => write!(
+                f,
+                "({}) or ({})",
+                RenderedResidual(left),
+                RenderedResidual(right)
+            ),
+            Residual::Implies { left, right, .. } => write!(
+                f,
+                "({}) implies ({})",
+                RenderedResidual(left),
+                RenderedResidual(right)
+            ),
+        }
+    }
+}
+
+impl<F: Display> Display for Formula<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        RenderedFormula(self).fmt(f)
+    }
+}
+
+impl<F: Display> Display for Violation<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        RenderedViolation(self).fmt(f)
+    }
+}
+
+/// Renders what the property is still waiting for, e.g. `eventually(x) by
+/// 1500ms`. Times are milliseconds since the Unix epoch.
+impl<F: Display> Display for Residual<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        RenderedResidual(self).fmt(f)
+    }
+}
+
+impl<F: Display> Display for Value<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::True => write!(f, "true"),
+            Value::False(violation) => {
+                write!(f, "false: {}", RenderedViolation(violation))
+            }
+            Value::Residual(residual) => RenderedResidual(residual).fmt(f),
+        }
+    }
+}
+
+/// A compact S-expression rendering, meant for tools that need to show or
+/// diff formulas, violations, residuals and values without depending on the
+/// `ltl` types. The grammar is stable:
+///
+/// ```text
+/// formula   = (pure BOOL STRING) | (thunk STRING) | (not (thunk STRING))
+///           | (and formula formula) | (or formula formula)
+///           | (implies formula formula) | (next formula)
+///           | (always formula [BOUND]) | (eventually formula [BOUND])
+///           | (until formula formula) | (release formula formula)
+///           | past | (not past)
+/// past      = (previously formula) | (once formula) | (since formula formula)
+/// violation = (false MS STRING) | (past-false MS formula)
+///           | (eventually-violated (timed-out MS) formula)
+///           | (eventually-violated test-ended formula)
+///           | (always-violated MS END MS formula violation)
+///           | (until-violated MS formula formula violation)
+///           | (until-violated MS formula formula test-ended)
+///           | (release-violated MS formula formula violation)
+///           | (and violation violation) | (or violation violation)
+///           | (implies formula violation)
+/// residual  = true | (violated violation)
+///           | (next MS formula leaning)
+///           | (always MS END formula leaning)
+///           | (eventually MS END formula leaning)
+///           | (until MS formula formula leaning)
+///           | (release MS formula formula leaning)
+///           | (and residual residual) | (or residual residual)
+///           | (implies formula residual residual)
+///           | (or-eventually MS END formula residual residual)
+///           | (and-always MS END formula residual residual)
+/// leaning   = assume-true | (assume-false violation)
+/// value     = true | (false violation) | (residual residual)
+/// BOUND     = MS | (steps N)
+/// END       = MS | (steps N) | nil
+/// ```
+///
+/// `MS` is a time in milliseconds since the Unix epoch, or a duration in
+/// milliseconds for formula bounds. `N` is a number of states, in bounds,
+/// or the number of states left, in residuals. Strings are double-quoted with Rust
+/// escaping.
+pub struct SExpr<'a, T>(pub &'a T);
+
+impl<'a, F: Display> Display for SExpr<'a, Formula<F>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Formula::Pure { value, pretty } => {
+                write!(f, "(pure {} {:?})", value, pretty)
+            }
+            Formula::Thunk {
+                function,
+                negated: false,
+            } => write!(f, "(thunk {:?})", function.to_string()),
+            Formula::Thunk {
+                function,
+                negated: true,
+            } => write!(f, "(not (thunk {:?}))", function.to_string()),
+            Formula::And(left, right) => {
+                write!(f, "(and {} {})", SExpr(&**left), SExpr(&**right))
+            }
+            Formula::Or(left, right) => {
+                write!(f, "(or {} {})", SExpr(&**left), SExpr(&**right))
+            }
+            Formula::Implies(left, right) => {
+                write!(f, "(implies {} {})", SExpr(&**left), SExpr(&**right))
+            }
+            Formula::Until(left, right) => {
+                write!(f, "(until {} {})", SExpr(&**left), SExpr(&**right))
+            }
+            Formula::Release(left, right) => {
+                write!(f, "(release {} {})", SExpr(&**left), SExpr(&**right))
+            }
+            Formula::Past {
+                formula,
+                negated: false,
+            } => write!(f, "{}", SExpr(formula)),
+            Formula::Past {
+                formula,
+                negated: true,
+            } => write!(f, "(not {})", SExpr(formula)),
+            Formula::Next(formula) => write!(f, "(next {})", SExpr(&**formula)),
+            Formula::Always(formula, None) => {
+                write!(f, "(always {})", SExpr(&**formula))
+            }
+            Formula::Always(formula, Some(bound)) => write!(
+                f,
+                "(always {} {})",
+                SExpr(&**formula),
+                SExprBound(bound)
+            ),
+            Formula::Eventually(formula, None) => {
+                write!(f, "(eventually {})", SExpr(&**formula))
+            }
+            Formula::Eventually(formula, Some(bound)) => write!(
+                f,
+                "(eventually {} {})",
+                SExpr(&**formula),
+                SExprBound(bound)
+            ),
+        }
+    }
+}
+
+impl<'a, F: Display> Display for SExpr<'a, Past<F>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Past::Previously(formula) => {
+                write!(f, "(previously {})", SExpr(&**formula))
+            }
+            Past::Once(formula) => write!(f, "(once {})", SExpr(&**formula)),
+            Past::Since(left, right) => {
+                write!(f, "(since {} {})", SExpr(&**left), SExpr(&**right))
+            }
+        }
+    }
+}
+
+impl<'a, F: Display> Display for SExpr<'a, Violation<F>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Violation::False { time, condition } => {
+                write!(f, "(false {} {:?})", time_to_ms(time), condition)
+            }
+            Violation::Past { time, formula } => {
+                write!(
+                    f,
+                    "(past-false {} {})",
+                    time_to_ms(time),
+                    SExpr(&**formula)
                 )
             }
+            Violation::Eventually {
+                subformula,
+                reason: EventuallyViolation::TimedOut(time),
+            } => write!(
+                f,
+                "(eventually-violated (timed-out {}) {})",
+                time_to_ms(time),
+                SExpr(&**subformula)
+            ),
+            Violation::Eventually {
+                subformula,
+                reason: EventuallyViolation::TestEnded,
+            } => write!(
+                f,
+                "(eventually-violated test-ended {})",
+                SExpr(&**subformula)
+            ),
+            Violation::Always {
+                violation,
+                subformula,
+                start,
+                end,
+                time,
+            } => write!(
+                f,
+                "(always-violated {} {} {} {} {})",
+                time_to_ms(start),
+                SExprEnd(&end.map(End::Time)),
+                time_to_ms(time),
+                SExpr(&**subformula),
+                SExpr(&**violation)
+            ),
+            Violation::Until {
+                left,
+                right,
+                start,
+                violation: Some(violation),
+            } => write!(
+                f,
+                "(until-violated {} {} {} {})",
+                time_to_ms(start),
+                SExpr(&**left),
+                SExpr(&**right),
+                SExpr(&**violation)
+            ),
+            Violation::Until {
+                left,
+                right,
+                start,
+                violation: None,
+            } => write!(
+                f,
+                "(until-violated {} {} {} test-ended)",
+                time_to_ms(start),
+                SExpr(&**left),
+                SExpr(&**right)
+            ),
+            Violation::Release {
+                left,
+                right,
+                start,
+                violation,
+            } => write!(
+                f,
+                "(release-violated {} {} {} {})",
+                time_to_ms(start),
+                SExpr(&**left),
+                SExpr(&**right),
+                SExpr(&**violation)
+            ),
+            Violation::And { left, right } => {
+                write!(f, "(and {} {})", SExpr(&**left), SExpr(&**right))
+            }
+            Violation::Or { left, right } => {
+                write!(f, "(or {} {})", SExpr(&**left), SExpr(&**right))
+            }
+            Violation::Implies { left, right } => {
+                write!(f, "(implies {} {})", SExpr(left), SExpr(&**right))
+            }
+        }
+    }
+}
+
+impl<'a, F: Display> Display for SExpr<'a, Residual<F>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Residual::True => write!(f, "true"),
+            Residual::False(violation) => {
+                write!(f, "(violated {})", SExpr(violation))
+            }
+            Residual::Derived(derived, leaning) => {
+                match derived {
+                    Derived::Once { start, subformula } => write!(
+                        f,
+                        "(next {} {} ",
+                        time_to_ms(start),
+                        SExpr(&**subformula)
+                    )?,
+                    Derived::Always {
+                        start,
+                        end,
+                        subformula,
+                    } => write!(
+                        f,
+                        "(always {} {} {} ",
+                        time_to_ms(start),
+                        SExprEnd(end),
+                        SExpr(&**subformula)
+                    )?,
+                    Derived::Eventually {
+                        start,
+                        end,
+                        subformula,
+                    } => write!(
+                        f,
+                        "(eventually {} {} {} ",
+                        time_to_ms(start),
+                        SExprEnd(end),
+                        SExpr(&**subformula)
+                    )?,
+                    Derived::Until { start, left, right } => write!(
+                        f,
+                        "(until {} {} {} ",
+                        time_to_ms(start),
+                        SExpr(&**left),
+                        SExpr(&**right)
+                    )?,
+                    Derived::Release { start, left, right } => write!(
+                        f,
+                        "(release {} {} {} ",
+                        time_to_ms(start),
+                        SExpr(&**left),
+                        SExpr(&**right)
+                    )?,
+                }
+                match leaning {
+                    Leaning::AssumeTrue => write!(f, "assume-true)"),
+                    Leaning::AssumeFalse(violation) => {
+                        write!(f, "(assume-false {}))", SExpr(violation))
+                    }
+                }
+            }
+            Residual::And { left, right } => {
+                write!(f, "(and {} {})", SExpr(&**left), SExpr(&**right))
+            }
+            Residual::Or { left, right } => {
+                write!(f, "(or {} {})", SExpr(&**left), SExpr(&**right))
+            }
+            Residual::Implies {
+                left_formula,
+                left,
+                right,
+            } => write!(
+                f,
+                "(implies {} {} {})",
+                SExpr(left_formula),
+                SExpr(&**left),
+                SExpr(&**right)
+            ),
+            Residual::OrEventually {
+                subformula,
+                start,
+                end,
+                left,
+                right,
+            } => write!(
+                f,
+                "(or-eventually {} {} {} {} {})",
+                time_to_ms(start),
+                SExprEnd(end),
+                SExpr(&**subformula),
+                SExpr(&**left),
+                SExpr(&**right)
+            ),
+            Residual::AndAlways {
+                subformula,
+                start,
+                end,
+                left,
+                right,
+            } => write!(
+                f,
+                "(and-always {} {} {} {} {})",
+                time_to_ms(start),
+                SExprEnd(end),
+                SExpr(&**subformula),
+                SExpr(&**left),
+                SExpr(&**right)
+            ),
+        }
+    }
+}
+
+impl<'a, F: Display> Display for SExpr<'a, Value<F>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Value::True => write!(f, "true"),
+            Value::False(violation) => {
+                write!(f, "(false {})", SExpr(violation))
+            }
+            Value::Residual(residual) => {
+                write!(f, "(residual {})", SExpr(residual))
+            }
+        }
+    }
+}
+
+struct SExprBound<'a>(&'a Bound);
+
+impl<'a> Display for SExprBound<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Bound::Duration(duration) => write!(f, "{}", duration.as_millis()),
+            Bound::Steps(steps) => write!(f, "(steps {})", steps),
+        }
+    }
+}
+
+struct SExprEnd<'a>(&'a Option<End>);
+
+impl<'a> Display for SExprEnd<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(End::Time(end)) => write!(f, "{}", time_to_ms(end)),
+            Some(End::Steps(steps)) => write!(f, "(steps {})", steps),
+            None => write!(f, "nil"),
         }
     }
 }
@@ -164,23 +764,122 @@ fn time_to_ms(time: &Time) -> u128 {
         .as_millis()
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PrettyFunction(String);
 
-impl std::fmt::Display for PrettyFunction {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl Display for PrettyFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.0)
     }
 }
 
+#[cfg(feature = "verifier")]
 impl Formula<RuntimeFunction> {
     pub fn with_pretty_functions(&self) -> Formula<PrettyFunction> {
         self.map_function(|f| PrettyFunction(f.pretty.clone()))
     }
 }
 
+#[cfg(feature = "verifier")]
 impl Violation<RuntimeFunction> {
     pub fn with_pretty_functions(&self) -> Violation<PrettyFunction> {
         self.map_function(|f| PrettyFunction(f.pretty.clone()))
     }
 }
+
+#[cfg(feature = "verifier")]
+impl Residual<RuntimeFunction> {
+    pub fn with_pretty_functions(&self) -> Residual<PrettyFunction> {
+        self.map_function(|f| PrettyFunction(f.pretty.clone()))
+    }
+}
+
+#[cfg(feature = "verifier")]
+impl Value<RuntimeFunction> {
+    pub fn with_pretty_functions(&self) -> Value<PrettyFunction> {
+        self.map_function(|f| PrettyFunction(f.pretty.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn at(millis: u64) -> Time {
+        UNIX_EPOCH + Duration::from_millis(millis)
+    }
+
+    fn thunk(pretty: &str) -> Box<Formula<PrettyFunction>> {
+        Box::new(Formula::Thunk {
+            function: PrettyFunction(pretty.to_string()),
+            negated: false,
+        })
+    }
+
+    #[test]
+    fn test_residual_display() {
+        let residual: Residual<PrettyFunction> = Residual::And {
+            left: Box::new(Residual::True),
+            right: Box::new(Residual::Derived(
+                Derived::Eventually {
+                    start: at(0),
+                    end: Some(End::Time(at(1500))),
+                    subformula: thunk("loaded"),
+                },
+                Leaning::AssumeTrue,
+            )),
+        };
+        assert_eq!(
+            residual.to_string(),
+            "(true) and (eventually(loaded) by 1500ms)"
+        );
+    }
+
+    #[test]
+    fn test_residual_sexpr() {
+        let residual: Residual<PrettyFunction> = Residual::Or {
+            left: Box::new(Residual::Derived(
+                Derived::Always {
+                    start: at(10),
+                    end: None,
+                    subformula: thunk("x < 5"),
+                },
+                Leaning::AssumeTrue,
+            )),
+            right: Box::new(Residual::Derived(
+                Derived::Once {
+                    start: at(20),
+                    subformula: thunk("\"quoted\""),
+                },
+                Leaning::AssumeFalse(Violation::False {
+                    time: at(20),
+                    condition: "ready".to_string(),
+                }),
+            )),
+        };
+        assert_eq!(
+            SExpr(&residual).to_string(),
+            r#"(or (always 10 nil (thunk "x < 5") assume-true) (next 20 (thunk "\"quoted\"") (assume-false (false 20 "ready"))))"#
+        );
+    }
+
+    #[test]
+    fn test_until_violation() {
+        let violation: Violation<PrettyFunction> = Violation::Until {
+            left: thunk("loading"),
+            right: thunk("loaded"),
+            start: at(10),
+            violation: None,
+        };
+        assert_eq!(
+            violation.to_string(),
+            "failed at test end: loading.until(loaded)"
+        );
+        assert_eq!(
+            SExpr(&violation).to_string(),
+            r#"(until-violated 10 (thunk "loading") (thunk "loaded") test-ended)"#
+        );
+    }
+}
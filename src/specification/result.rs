@@ -1,12 +1,15 @@
 use std::{fmt::Display, io, time::SystemTimeError};
 
+#[cfg(feature = "verifier")]
 use boa_engine::JsError;
+#[cfg(feature = "verifier")]
 use oxc::diagnostics::OxcDiagnostic;
 
 #[derive(Debug)]
 pub enum SpecificationError {
     JS(String),
     IO(io::Error),
+    #[cfg(feature = "verifier")]
     TranspilationError(Vec<OxcDiagnostic>),
     SystemTimeError(SystemTimeError),
     OtherError(String),
@@ -21,6 +24,7 @@ impl Display for SpecificationError {
                 system_time_error.fmt(f)
             }
             SpecificationError::OtherError(message) => message.fmt(f),
+            #[cfg(feature = "verifier")]
             SpecificationError::TranspilationError(diagnostics) => {
                 for diagnostic in diagnostics {
                     writeln!(f, "{}", diagnostic)?;
@@ -33,6 +37,7 @@ impl Display for SpecificationError {
 
 impl std::error::Error for SpecificationError {}
 
+#[cfg(feature = "verifier")]
 impl From<JsError> for SpecificationError {
     fn from(value: JsError) -> Self {
         SpecificationError::JS(format!("{}", value))
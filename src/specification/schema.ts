@@ -0,0 +1,126 @@
+// Schemas for extractor values, checked whenever a new state is extracted
+
+type Check = (value: unknown, path: string) => string | null;
+
+/**
+ * The expected shape of values of type `T`. When passed to `extract`, every extracted
+ * value is checked against it, failing the test with an error naming the extractor
+ * instead of letting `undefined` silently propagate into properties.
+ */
+export class Schema<T> {
+  declare readonly type: T;
+
+  constructor(
+    private description: string,
+    private checkValue: Check,
+  ) {}
+
+  /** Describes where and how `value` doesn't match the schema, or returns null. */
+  check(value: unknown, path: string = "value"): string | null {
+    return this.checkValue(value, path);
+  }
+
+  toString() {
+    return this.description;
+  }
+}
+
+export type Infer<S> = S extends Schema<infer T> ? T : never;
+
+const DESCRIBED_LENGTH_MAX = 80;
+
+function describe(value: unknown): string {
+  if (value === undefined) {
+    return "undefined";
+  }
+  const json = JSON.stringify(value);
+  return json.length > DESCRIBED_LENGTH_MAX
+    ? `${json.slice(0, DESCRIBED_LENGTH_MAX)}...`
+    : json;
+}
+
+function mismatch(path: string, expected: string, value: unknown): string {
+  return `${path} should be ${expected}, got ${describe(value)}`;
+}
+
+function primitive<T>(name: "number" | "string" | "boolean"): Schema<T> {
+  return new Schema(name, (value, path) =>
+    typeof value === name ? null : mismatch(path, `a ${name}`, value),
+  );
+}
+
+export function number(): Schema<number> {
+  return primitive("number");
+}
+
+export function string(): Schema<string> {
+  return primitive("string");
+}
+
+export function boolean(): Schema<boolean> {
+  return primitive("boolean");
+}
+
+export function literal<T extends string | number | boolean | null>(
+  expected: T,
+): Schema<T> {
+  const description = JSON.stringify(expected);
+  return new Schema(description, (value, path) =>
+    value === expected ? null : mismatch(path, description, value),
+  );
+}
+
+export function nullable<T>(schema: Schema<T>): Schema<T | null> {
+  return new Schema(`${schema} | null`, (value, path) =>
+    value === null ? null : schema.check(value, path),
+  );
+}
+
+export function array<T>(items: Schema<T>): Schema<T[]> {
+  return new Schema(`${items}[]`, (value, path) => {
+    if (!Array.isArray(value)) {
+      return mismatch(path, "an array", value);
+    }
+    for (let i = 0; i < value.length; i++) {
+      const error = items.check(value[i], `${path}[${i}]`);
+      if (error !== null) {
+        return error;
+      }
+    }
+    return null;
+  });
+}
+
+export function object<S extends { [key: string]: Schema<unknown> }>(
+  shape: S,
+): Schema<{ [K in keyof S]: Infer<S[K]> }> {
+  const description = `{ ${Object.entries(shape)
+    .map(([key, schema]) => `${key}: ${schema}`)
+    .join(", ")} }`;
+  return new Schema(description, (value, path) => {
+    if (typeof value !== "object" || value === null || Array.isArray(value)) {
+      return mismatch(path, "an object", value);
+    }
+    for (const [key, schema] of Object.entries(shape)) {
+      const error = schema.check(
+        (value as { [key: string]: unknown })[key],
+        `${path}.${key}`,
+      );
+      if (error !== null) {
+        return error;
+      }
+    }
+    return null;
+  });
+}
+
+export function union<S extends Schema<unknown>[]>(
+  ...schemas: S
+): Schema<Infer<S[number]>> {
+  const description = schemas.join(" | ");
+  return new Schema(description, (value, path) =>
+    schemas.some((schema) => schema.check(value, path) === null)
+      ? null
+      : mismatch(path, description, value),
+  );
+}
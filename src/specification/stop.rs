@@ -1,4 +1,41 @@
-use crate::specification::ltl::{Formula, Leaning, Residual, Time, Violation};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::specification::ltl::{
+    End, Formula, Leaning, Residual, Time, Violation,
+};
+
+/// What to make of a property still undecided when a run ends, if it would be false were
+/// the run to end there, e.g. an `eventually` that hasn't held yet.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum EndPolicy {
+    /// Report it as a violation.
+    Fail,
+    /// Report it as a warning.
+    Warn,
+    #[default]
+    Ignore,
+}
+
+impl FromStr for EndPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "fail" => Ok(EndPolicy::Fail),
+            "warn" => Ok(EndPolicy::Warn),
+            "ignore" => Ok(EndPolicy::Ignore),
+            other => Err(format!(
+                "unknown end policy {:?}, expected fail, warn or ignore",
+                other
+            )),
+        }
+    }
+}
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum StopDefault<Function> {
@@ -43,7 +80,12 @@ pub fn stop_default<Function: Clone>(
         } => stop_default(left, time).and_then(|s1| {
             stop_default(right, time).map(|s2| {
                 stop_and_always_default(
-                    subformula, *start, *end, time, &s1, &s2,
+                    subformula,
+                    *start,
+                    end.and_then(End::time),
+                    time,
+                    &s1,
+                    &s2,
                 )
             })
         }),
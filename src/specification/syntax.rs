@@ -1,6 +1,4 @@
-use std::time::Duration;
-
-use crate::specification::ltl::Formula;
+use crate::specification::ltl::{Bound, Formula, Past};
 
 /// A formula in its syntactic form, "parsed" from JavaScript runtime objects.
 #[derive(Debug, Clone, PartialEq)]
@@ -12,8 +10,13 @@ pub enum Syntax<Function> {
     Or(Box<Syntax<Function>>, Box<Syntax<Function>>),
     Implies(Box<Syntax<Function>>, Box<Syntax<Function>>),
     Next(Box<Syntax<Function>>),
-    Always(Box<Syntax<Function>>, Option<Duration>),
-    Eventually(Box<Syntax<Function>>, Option<Duration>),
+    Always(Box<Syntax<Function>>, Option<Bound>),
+    Eventually(Box<Syntax<Function>>, Option<Bound>),
+    Until(Box<Syntax<Function>>, Box<Syntax<Function>>),
+    Release(Box<Syntax<Function>>, Box<Syntax<Function>>),
+    Previously(Box<Syntax<Function>>),
+    Once(Box<Syntax<Function>>),
+    Since(Box<Syntax<Function>>, Box<Syntax<Function>>),
 }
 
 impl<Function: Clone> Syntax<Function> {
@@ -32,6 +35,23 @@ impl<Function: Clone> Syntax<Function> {
                     negated,
                 },
                 Syntax::Not(syntax) => go(syntax, !negated),
+                // Past-time formulas are decided in every state, so their negation is
+                // kept outside, like that of thunks.
+                Syntax::Previously(sub) => Formula::Past {
+                    formula: Past::Previously(Box::new(go(sub, false))),
+                    negated,
+                },
+                Syntax::Once(sub) => Formula::Past {
+                    formula: Past::Once(Box::new(go(sub, false))),
+                    negated,
+                },
+                Syntax::Since(left, right) => Formula::Past {
+                    formula: Past::Since(
+                        Box::new(go(left, false)),
+                        Box::new(go(right, false)),
+                    ),
+                    negated,
+                },
                 Syntax::And(left, right) => {
                     if negated {
                         //   ¬(l ∧ r)
@@ -93,6 +113,28 @@ impl<Function: Clone> Syntax<Function> {
                         Formula::Eventually(Box::new(go(sub, negated)), *bound)
                     }
                 }
+                Syntax::Until(left, right) => {
+                    let left = Box::new(go(left, negated));
+                    let right = Box::new(go(right, negated));
+                    if negated {
+                        //   ¬(l U r)
+                        // ⇔ ¬l R ¬r
+                        Formula::Release(left, right)
+                    } else {
+                        Formula::Until(left, right)
+                    }
+                }
+                Syntax::Release(left, right) => {
+                    let left = Box::new(go(left, negated));
+                    let right = Box::new(go(right, negated));
+                    if negated {
+                        //   ¬(l R r)
+                        // ⇔ ¬l U ¬r
+                        Formula::Until(left, right)
+                    } else {
+                        Formula::Release(left, right)
+                    }
+                }
             }
         }
         go(self, false)
@@ -1,10 +1,17 @@
+use std::cell::RefCell;
 use std::path::{Path, PathBuf};
 use std::{collections::HashMap, rc::Rc};
 
+use crate::specification::imports::{
+    LocalModule, RemoteModule, fetch_remote_modules, read_local_modules,
+};
 use crate::specification::js::{
     BombadilExports, Extractors, RuntimeFunction, module_exports,
 };
-use crate::specification::module_loader::transpile;
+use crate::specification::module_loader::{
+    PRELUDE_SPECIFIER, prelude_wrapper, transpile,
+};
+use crate::specification::random_streams::RandomStreams;
 use crate::specification::result::Result;
 use crate::specification::syntax::Syntax;
 use crate::specification::{ltl, module_loader::load_modules};
@@ -18,18 +25,44 @@ use boa_engine::{
 };
 use boa_engine::{JsError, JsObject, JsValue};
 use oxc::span::SourceType;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
 use serde_json as json;
 
 use crate::specification::{
-    ltl::{Evaluator, Formula, Residual, Violation},
+    ltl::{Evaluator, Formula, PastHistory, Residual, Violation},
     module_loader::{HybridModuleLoader, load_bombadil_module},
     result::SpecificationError,
+    stop::{EndPolicy, StopDefault, stop_default},
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Specification {
     contents: Vec<u8>,
     path: PathBuf,
+    /// Modules imported by URL, fetched when loading the specification.
+    #[serde(default)]
+    remote_modules: Vec<RemoteModule>,
+    /// Modules imported by path, read when loading the specification.
+    #[serde(default)]
+    local_modules: Vec<LocalModule>,
+    /// Specifications from other files merged into this one.
+    #[serde(default)]
+    merged: Vec<Specification>,
+    /// Whether another specification generates the actions, this one only checking its
+    /// properties.
+    #[serde(default)]
+    driven: bool,
+    /// A module imported into every file of the specification, e.g. a company-wide
+    /// baseline of properties, its exports being exported along with those of each file.
+    #[serde(default)]
+    prelude: Option<Box<Specification>>,
+    /// Whether modules imported by path that weren't read when loading the specification
+    /// (e.g. imported dynamically) may be read when verifying it. Not sent to remote
+    /// verifiers, which don't read their own file system.
+    #[serde(skip)]
+    read_files: bool,
 }
 
 impl Specification {
@@ -38,7 +71,20 @@ impl Specification {
         let contents = tokio::fs::read_to_string(path)
             .await
             .map_err(SpecificationError::IO)?;
-        Self::from_string(&contents, path)
+        let mut specification = Self::from_string(&contents, path)?;
+        let source = String::from_utf8_lossy(&specification.contents);
+        specification.local_modules = read_local_modules(&source, path).await?;
+        let sources: Vec<&str> = std::iter::once(source.as_ref())
+            .chain(
+                specification
+                    .local_modules
+                    .iter()
+                    .map(|module| module.source.as_str()),
+            )
+            .collect();
+        specification.remote_modules =
+            fetch_remote_modules(&sources, path).await?;
+        Ok(specification)
     }
     pub fn from_string(contents: &str, path: impl AsRef<Path>) -> Result<Self> {
         let path: &Path = path.as_ref();
@@ -59,14 +105,96 @@ impl Specification {
         Ok(Specification {
             contents: contents.into_bytes(),
             path: path.to_path_buf(),
+            remote_modules: vec![],
+            local_modules: vec![],
+            merged: vec![],
+            driven: false,
+            prelude: None,
+            read_files: true,
         })
     }
+
+    /// The specification checked alongside another one generating the actions, so that it
+    /// may export no action generators, and those it exports are left out.
+    pub fn driven(self) -> Self {
+        Specification {
+            driven: true,
+            ..self
+        }
+    }
+
+    /// The specification with `prelude` imported into each of its files, which export what
+    /// it exports unless they export the same names themselves.
+    pub fn with_prelude(self, prelude: Specification) -> Self {
+        Specification {
+            prelude: Some(Box::new(prelude)),
+            ..self
+        }
+    }
+
+    /// The name of the specification's file, without its extension, e.g. `checkout` for
+    /// `checkout.ts`.
+    pub fn name(&self) -> String {
+        self.path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Merges the specifications of several files into one, whose properties and action
+    /// generators are namespaced by the name of their file, e.g. `checkout/noErrors` for
+    /// `noErrors` in `checkout.ts`. A single specification is left as is.
+    pub fn merge(specifications: Vec<Specification>) -> Result<Self> {
+        let mut files = specifications
+            .into_iter()
+            .flat_map(|mut specification| {
+                let merged = std::mem::take(&mut specification.merged);
+                std::iter::once(specification).chain(merged)
+            })
+            .collect::<Vec<_>>()
+            .into_iter();
+        let mut merged = files.next().ok_or_else(|| {
+            SpecificationError::OtherError(
+                "no specifications to merge".to_string(),
+            )
+        })?;
+        merged.merged = files.collect();
+
+        let mut namespaces = HashMap::new();
+        for (namespace, file) in merged.files() {
+            if let Some(other) =
+                namespaces.insert(namespace.clone(), file.path.clone())
+            {
+                return Err(SpecificationError::OtherError(format!(
+                    "specifications {} and {} have the same name {:?}, rename one of them",
+                    other.display(),
+                    file.path.display(),
+                    namespace.unwrap_or_default()
+                )));
+            }
+        }
+        Ok(merged)
+    }
+
+    /// The files of the specification, with the namespace of their exports when there are
+    /// several.
+    fn files(&self) -> Vec<(Option<String>, &Specification)> {
+        if self.merged.is_empty() {
+            return vec![(None, self)];
+        }
+        std::iter::once(self)
+            .chain(&self.merged)
+            .map(|file| (Some(file.name()), file))
+            .collect()
+    }
 }
 
 #[derive(Clone)]
 pub struct StepResult<A> {
     pub properties: Vec<(String, ltl::Value<RuntimeFunction>)>,
     pub actions: Tree<A>,
+    /// The metrics emitted by `metric` since the last step, by name, in emission order.
+    pub metrics: Vec<(String, f64)>,
 }
 
 pub struct Verifier {
@@ -75,14 +203,33 @@ pub struct Verifier {
     properties: HashMap<String, Property>,
     action_generators: HashMap<String, ActionGenerator>,
     extractors: Extractors,
-    extractor_functions: HashMap<u64, String>,
+    extractor_functions: Vec<ExtractorFunction>,
+    setup: Vec<json::Value>,
+    past_history: PastHistory<RuntimeFunction>,
+    /// Number of states evaluated so far.
+    steps: u64,
 }
 
 const RANDOM_BYTES_COUNT_MAX: usize = 4096;
 
+thread_local! {
+    /// Random bytes of the verifier running on this thread, derived from the seed, used by
+    /// the `__bombadil_random_bytes` builtin.
+    static RANDOM_BYTES: RefCell<ChaCha8Rng> =
+        RefCell::new(ChaCha8Rng::seed_from_u64(0));
+    /// Keyed random streams of the verifier running on this thread, used by the
+    /// `__bombadil_random_stream_bytes` builtin.
+    static RANDOM_STREAMS: RefCell<RandomStreams> =
+        RefCell::new(RandomStreams::new(0));
+    /// Metrics emitted through the `__bombadil_metric` builtin by the verifier running on
+    /// this thread, since its last step.
+    static METRICS: RefCell<Vec<(String, f64)>> = const { RefCell::new(Vec::new()) };
+}
+
 impl Verifier {
-    pub fn new(specification: Specification) -> Result<Self> {
-        let loader = Rc::new(HybridModuleLoader::new()?);
+    pub fn new(specification: Specification, seed: u64) -> Result<Self> {
+        let loader =
+            Rc::new(HybridModuleLoader::new(specification.read_files)?);
 
         // Instantiate the execution context
         let mut context = ContextBuilder::default()
@@ -90,7 +237,9 @@ impl Verifier {
             .build()
             .map_err(|error| SpecificationError::JS(error.to_string()))?;
 
-        // Expose random byte generation to JS
+        // Expose random byte generation, derived from the seed, to JS
+        RANDOM_BYTES
+            .with(|rng| *rng.borrow_mut() = ChaCha8Rng::seed_from_u64(seed));
         context.register_global_builtin_callable(
             js_string!("__bombadil_random_bytes"),
             1,
@@ -108,13 +257,76 @@ impl Verifier {
                     )));
                 }
                 let mut buf = vec![0u8; n];
-                rand::fill(&mut buf[..]);
+                RANDOM_BYTES.with(|rng| rng.borrow_mut().fill_bytes(&mut buf));
                 Ok(JsUint8Array::from_iter(buf, context)?.into())
             }),
         )?;
 
+        // Expose keyed random streams, derived from the seed, to JS
+        RANDOM_STREAMS
+            .with(|streams| *streams.borrow_mut() = RandomStreams::new(seed));
+        context.register_global_builtin_callable(
+            js_string!("__bombadil_random_stream_bytes"),
+            2,
+            NativeFunction::from_copy_closure(|_this, args, context| {
+                let key = args
+                    .first()
+                    .map(|v| v.to_string(context))
+                    .transpose()?
+                    .map(|key| key.to_std_string_escaped())
+                    .unwrap_or_default();
+                let n = args
+                    .get(1)
+                    .map(|v| v.to_u32(context))
+                    .transpose()?
+                    .unwrap_or(0) as usize;
+                if n > RANDOM_BYTES_COUNT_MAX {
+                    return Err(JsError::from_rust(SpecificationError::JS(
+                        format!(
+                            "n cannot be larger than {RANDOM_BYTES_COUNT_MAX}"
+                        ),
+                    )));
+                }
+                let mut buf = vec![0u8; n];
+                RANDOM_STREAMS
+                    .with(|streams| streams.borrow_mut().fill(&key, &mut buf));
+                Ok(JsUint8Array::from_iter(buf, context)?.into())
+            }),
+        )?;
+
+        // Collect the named metrics emitted by the specification
+        METRICS.with(|metrics| metrics.borrow_mut().clear());
+        context.register_global_builtin_callable(
+            js_string!("__bombadil_metric"),
+            2,
+            NativeFunction::from_copy_closure(|_this, args, context| {
+                let name = args
+                    .first()
+                    .map(|v| v.to_string(context))
+                    .transpose()?
+                    .map(|name| name.to_std_string_escaped())
+                    .unwrap_or_default();
+                let value = args
+                    .get(1)
+                    .map(|v| v.to_number(context))
+                    .transpose()?
+                    .unwrap_or(f64::NAN);
+                if name.is_empty() || !value.is_finite() {
+                    return Err(JsError::from_rust(SpecificationError::JS(
+                        format!(
+                            "metric needs a name and a finite number, got {:?} and {}",
+                            name, value
+                        ),
+                    )));
+                }
+                METRICS.with(|metrics| metrics.borrow_mut().push((name, value)));
+                Ok(JsValue::undefined())
+            }),
+        )?;
+
         // Non-special modules loaded in dependency order
         let modules = [
+            ("schema.js", "@antithesishq/bombadil/schema"),
             ("internal.js", "@antithesishq/bombadil/internal"),
             ("random.js", "@antithesishq/bombadil/random"),
             ("actions.js", "@antithesishq/bombadil/actions"),
@@ -149,40 +361,122 @@ impl Verifier {
             loader.insert_mapped_module(import_path, module);
         }
 
-        let specification_module = {
-            let specification_bytes: &[u8] = &specification.contents;
-            Module::parse(
+        // Modules imported by URL, already fetched
+        let files = specification.files();
+        let prelude = specification.prelude.as_deref();
+        for remote_module in files
+            .iter()
+            .map(|(_, file)| *file)
+            .chain(prelude)
+            .flat_map(|file| &file.remote_modules)
+        {
+            let module = Module::parse(
+                Source::from_bytes(remote_module.source.as_bytes()),
+                None,
+                &mut context,
+            )?;
+            loader.insert_mapped_module(&remote_module.url, module);
+        }
+
+        // Modules imported by path, already read
+        for local_module in files
+            .iter()
+            .map(|(_, file)| *file)
+            .chain(prelude)
+            .flat_map(|file| &file.local_modules)
+        {
+            let module = Module::parse(
                 Source::from_reader(
-                    specification_bytes,
-                    Some(&specification.path),
+                    local_module.source.as_bytes(),
+                    Some(&local_module.path),
                 ),
                 None,
                 &mut context,
-            )?
-        };
-        load_modules(
-            &mut context,
-            std::slice::from_ref(&specification_module),
-        )?;
+            )?;
+            loader.insert_local_module(&local_module.path, module);
+        }
+
+        if let Some(prelude) = prelude {
+            let prelude_bytes: &[u8] = &prelude.contents;
+            let module = Module::parse(
+                Source::from_reader(prelude_bytes, Some(&prelude.path)),
+                None,
+                &mut context,
+            )?;
+            loader.insert_mapped_module(PRELUDE_SPECIFIER, module.clone());
+            load_modules(&mut context, &[module])?;
+        }
+
+        let mut specification_modules = Vec::with_capacity(files.len());
+        for (_, file) in &files {
+            let specification_bytes: &[u8] = &file.contents;
+            specification_modules.push(Module::parse(
+                Source::from_reader(specification_bytes, Some(&file.path)),
+                None,
+                &mut context,
+            )?);
+        }
+        load_modules(&mut context, &specification_modules)?;
+
+        // Each file is replaced by a module re-exporting it along with the prelude.
+        if prelude.is_some() {
+            let mut wrappers = Vec::with_capacity(specification_modules.len());
+            for (index, module) in specification_modules.iter().enumerate() {
+                let specifier = format!("bombadil:specification/{}", index);
+                loader.insert_mapped_module(&specifier, module.clone());
+                let names: Vec<String> = module_exports(module, &mut context)?
+                    .into_keys()
+                    .filter_map(|key| match key {
+                        PropertyKey::String(name) => {
+                            Some(name.to_std_string_escaped())
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                wrappers.push(Module::parse(
+                    Source::from_bytes(
+                        prelude_wrapper(&specifier, &names).as_bytes(),
+                    ),
+                    None,
+                    &mut context,
+                )?);
+            }
+            load_modules(&mut context, &wrappers)?;
+            specification_modules = wrappers;
+        }
 
-        let specification_exports =
-            module_exports(&specification_module, &mut context)?;
+        let mut specification_exports = Vec::new();
+        for ((namespace, _), module) in files.iter().zip(&specification_modules)
+        {
+            for (key, value) in module_exports(module, &mut context)? {
+                specification_exports.push((namespace.clone(), key, value));
+            }
+        }
         let bombadil_exports =
             BombadilExports::from_module(&bombadil_module_index, &mut context)?;
 
         let mut properties: HashMap<String, Property> = HashMap::new();
         let mut action_generators: HashMap<String, ActionGenerator> =
             HashMap::new();
-        for (key, value) in specification_exports.iter() {
+        let mut setups: Vec<(String, Vec<json::Value>)> = Vec::new();
+        for (namespace, key, value) in &specification_exports {
+            let name = match namespace {
+                Some(namespace) => format!("{}/{}", namespace, key),
+                None => key.to_string(),
+            };
             if value.instance_of(&bombadil_exports.formula, &mut context)? {
-                let syntax =
-                    Syntax::from_value(value, &bombadil_exports, &mut context)?;
+                let (syntax, at_end) = Syntax::property_from_value(
+                    value,
+                    &bombadil_exports,
+                    &mut context,
+                )?;
                 let formula = syntax.nnf();
                 properties.insert(
-                    key.to_string(),
+                    name.clone(),
                     Property {
-                        name: key.to_string(),
+                        name,
                         state: PropertyState::Initial(formula),
+                        at_end,
                     },
                 );
             } else if value
@@ -191,7 +485,7 @@ impl Verifier {
                 let object = value.as_object().ok_or(
                     SpecificationError::OtherError(format!(
                         "action generator {} is not an object, it is {}",
-                        key,
+                        name,
                         value.type_of()
                     )),
                 )?;
@@ -201,17 +495,41 @@ impl Verifier {
                     .as_object()
                     .ok_or(SpecificationError::OtherError(format!(
                         "action {} is not a function, it is {}",
-                        key,
+                        name,
                         value.type_of()
                     )))?;
                 action_generators.insert(
-                    key.to_string(),
+                    name.clone(),
                     ActionGenerator {
-                        name: key.to_string(),
+                        name,
                         this: value.clone(),
                         function,
                     },
                 );
+            } else if value
+                .instance_of(&bombadil_exports.setup, &mut context)?
+            {
+                let object = value.as_object().ok_or(
+                    SpecificationError::OtherError(format!(
+                        "setup {} is not an object, it is {}",
+                        name,
+                        value.type_of()
+                    )),
+                )?;
+                match object
+                    .get(js_string!("actions"), &mut context)?
+                    .to_json(&mut context)?
+                {
+                    Some(json::Value::Array(actions)) => {
+                        setups.push((name, actions));
+                    }
+                    _ => {
+                        return Err(SpecificationError::OtherError(format!(
+                            "setup {} has no array of actions",
+                            name
+                        )));
+                    }
+                }
             } else if let PropertyKey::Symbol(symbol) = key
                 && let Some(description) = symbol.description()
                 && IGNORED_SYMBOL_EXPORTS.contains(&description)
@@ -220,14 +538,23 @@ impl Verifier {
             } else {
                 return Err(SpecificationError::OtherError(format!(
                     "export {:?} is of unknown type ({}): {}",
-                    key.to_string(),
+                    name,
                     value.type_of(),
                     value.display()
                 )));
             }
         }
 
-        if action_generators.is_empty() {
+        // The setups of several exports are applied in the order of their names.
+        setups.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let setup = setups
+            .into_iter()
+            .flat_map(|(_, actions)| actions)
+            .collect();
+
+        if specification.driven {
+            action_generators.clear();
+        } else if action_generators.is_empty() {
             return Err(SpecificationError::OtherError(
                 "specification exports no action generators".to_string(),
             ));
@@ -266,6 +593,9 @@ impl Verifier {
             bombadil_exports,
             extractors,
             extractor_functions,
+            setup,
+            past_history: PastHistory::default(),
+            steps: 0,
         })
     }
 
@@ -273,12 +603,57 @@ impl Verifier {
         self.properties.keys().cloned().collect()
     }
 
-    pub fn extractors(&self) -> Result<Vec<(u64, String)>> {
-        let mut results = Vec::with_capacity(self.extractor_functions.len());
-        for (key, value) in &self.extractor_functions {
-            results.push((*key, value.clone()));
+    pub fn extractors(&self) -> Result<Vec<ExtractorFunction>> {
+        Ok(self.extractor_functions.clone())
+    }
+
+    /// The actions of the exported setups, as JSON, to apply before exploring.
+    pub fn setup(&self) -> Vec<json::Value> {
+        self.setup.clone()
+    }
+
+    /// Describes the exported properties and action generators, and the registered
+    /// extractors, sorted by name or id.
+    pub fn metadata(&self) -> SpecificationMetadata {
+        let mut properties: Vec<PropertyMetadata> = self
+            .properties
+            .values()
+            .filter_map(|property| match &property.state {
+                PropertyState::Initial(formula) => Some(PropertyMetadata {
+                    name: property.name.clone(),
+                    formula: formula
+                        .map_function(|function| function.pretty.clone()),
+                }),
+                _ => None,
+            })
+            .collect();
+        properties.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut action_generators: Vec<ActionGeneratorMetadata> = self
+            .action_generators
+            .keys()
+            .map(|name| ActionGeneratorMetadata {
+                name: name.clone(),
+                // All exported generators are weighted equally.
+                weight: 1,
+            })
+            .collect();
+        action_generators.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let extractors: Vec<ExtractorMetadata> = self
+            .extractor_functions
+            .iter()
+            .map(|extractor| ExtractorMetadata {
+                id: extractor.id,
+                source: extractor.source.clone(),
+            })
+            .collect();
+
+        SpecificationMetadata {
+            properties,
+            action_generators,
+            extractors,
         }
-        Ok(results)
     }
 
     pub fn step<A: serde::de::DeserializeOwned>(
@@ -286,13 +661,60 @@ impl Verifier {
         snapshots: Vec<(u64, json::Value)>,
         time: ltl::Time,
     ) -> Result<StepResult<A>> {
+        let properties = self.evaluate_properties(snapshots, time)?;
+
+        let mut generator_branches: Vec<(u16, Tree<A>)> = Vec::new();
+        for action_generator in self.action_generators.values() {
+            // All exported generators are weighted equally.
+            generator_branches
+                .push((1, action_generator.generate(&mut self.context)?));
+        }
+
+        let action_tree = Tree::Branch {
+            branches: generator_branches,
+        };
+
+        Ok(StepResult {
+            properties,
+            actions: action_tree,
+            metrics: METRICS.with(|metrics| metrics.take()),
+        })
+    }
+
+    /// Folds a recorded sequence of snapshots through property evaluation,
+    /// returning the property values after the last snapshot. Action
+    /// generators are not run, and folding stops early once every property
+    /// has a definite value. Returns no properties for an empty sequence.
+    pub fn replay(
+        &mut self,
+        snapshots: Vec<(ltl::Time, Vec<(u64, json::Value)>)>,
+    ) -> Result<Vec<(String, ltl::Value<RuntimeFunction>)>> {
+        let mut properties = Vec::new();
+        for (time, snapshots) in snapshots {
+            properties = self.evaluate_properties(snapshots, time)?;
+            if properties
+                .iter()
+                .all(|(_, value)| !matches!(value, ltl::Value::Residual(_)))
+            {
+                break;
+            }
+        }
+        Ok(properties)
+    }
+
+    fn evaluate_properties(
+        &mut self,
+        snapshots: Vec<(u64, json::Value)>,
+        time: ltl::Time,
+    ) -> Result<Vec<(String, ltl::Value<RuntimeFunction>)>> {
         self.extractors.update_from_snapshots(
             snapshots,
             time,
+            self.steps,
             &mut self.context,
         )?;
+        self.steps += 1;
         let mut result_properties = Vec::with_capacity(self.properties.len());
-        let mut generator_branches: Vec<(u16, Tree<A>)> = Vec::new();
 
         let context = &mut self.context;
         let mut evaluate_thunk = |function: &RuntimeFunction,
@@ -309,7 +731,9 @@ impl Verifier {
             })
             .nnf())
         };
-        let mut evaluator = Evaluator::new(&mut evaluate_thunk);
+        let mut evaluator =
+            Evaluator::new(&mut evaluate_thunk, &mut self.past_history);
+        evaluator.update_past(time)?;
 
         for property in self.properties.values_mut() {
             let value = match &property.state {
@@ -345,28 +769,80 @@ impl Verifier {
             ));
         }
 
-        for action_generator in self.action_generators.values() {
-            // All exported generators are weighted equally.
-            generator_branches.push((1, action_generator.generate(context)?));
-        }
-
-        let action_tree = Tree::Branch {
-            branches: generator_branches,
-        };
+        Ok(result_properties)
+    }
 
-        Ok(StepResult {
-            properties: result_properties,
-            actions: action_tree,
-        })
+    /// The properties left undecided when the run ends that would be false if it stopped
+    /// at `time`, e.g. an `eventually` that never held, for those whose end policy isn't
+    /// to ignore them.
+    pub fn finish(
+        &self,
+        time: ltl::Time,
+    ) -> Vec<(String, EndPolicy, Violation<RuntimeFunction>)> {
+        self.properties
+            .values()
+            .filter(|property| property.at_end != EndPolicy::Ignore)
+            .filter_map(|property| match &property.state {
+                PropertyState::Residual(residual) => {
+                    match stop_default(residual, time) {
+                        Some(StopDefault::False(violation)) => Some((
+                            property.name.clone(),
+                            property.at_end,
+                            violation,
+                        )),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            })
+            .collect()
     }
 }
 
 const IGNORED_SYMBOL_EXPORTS: &[JsString] = &[js_string!("Symbol.toStringTag")];
 
+/// What a specification exports, for tooling that doesn't run it (editors, documentation
+/// generators, CI checks).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SpecificationMetadata {
+    pub properties: Vec<PropertyMetadata>,
+    pub action_generators: Vec<ActionGeneratorMetadata>,
+    pub extractors: Vec<ExtractorMetadata>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PropertyMetadata {
+    pub name: String,
+    /// The formula in negation normal form, with thunks as their source.
+    pub formula: Formula<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ActionGeneratorMetadata {
+    pub name: String,
+    pub weight: u16,
+}
+
+/// An extractor's function, run in every `every`th state, with the states in between
+/// keeping the value extracted last.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExtractorFunction {
+    pub id: u64,
+    pub source: String,
+    pub every: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExtractorMetadata {
+    pub id: u64,
+    pub source: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct Property {
     pub name: String,
     state: PropertyState,
+    at_end: EndPolicy,
 }
 
 #[derive(Debug, Clone)]
@@ -422,10 +898,19 @@ mod tests {
     use super::*;
 
     fn verifier(specification: &str) -> Verifier {
-        Verifier::new(Specification {
-            path: PathBuf::from("fake.ts"),
-            contents: specification.to_string().into_bytes(),
-        })
+        Verifier::new(
+            Specification {
+                path: PathBuf::from("fake.ts"),
+                contents: specification.to_string().into_bytes(),
+                remote_modules: vec![],
+                local_modules: vec![],
+                merged: vec![],
+                driven: false,
+                prelude: None,
+                read_files: true,
+            },
+            0,
+        )
         .unwrap()
     }
 
@@ -474,7 +959,7 @@ mod tests {
             .extractors()
             .unwrap()
             .iter()
-            .map(|(_, value)| value.clone())
+            .map(|extractor| extractor.source.clone())
             .collect();
 
         extractors.sort();
@@ -489,6 +974,164 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_setup() {
+        let verifier = verifier(
+            r#"
+            import { actions, cdp, setup } from "@antithesishq/bombadil";
+            export const _actions = actions(() => []);
+
+            export const b_login = setup([
+              { Focus: { selector: "input[type=email]" } },
+              { TypeText: { text: "ada@example.com", delayMillis: 0 } },
+            ]);
+            export const a_cookies = setup([
+              cdp("Network.setCookie", { name: "consent", value: "yes" }),
+            ]);
+            "#,
+        );
+
+        let setup = verifier.setup();
+        assert_eq!(setup.len(), 3);
+        assert_eq!(setup[0]["CdpCommand"]["method"], "Network.setCookie");
+        assert_eq!(setup[1]["Focus"]["selector"], "input[type=email]");
+        assert_eq!(setup[2]["TypeText"]["text"], "ada@example.com");
+    }
+
+    #[test]
+    fn test_metadata() {
+        let verifier = verifier(
+            r#"
+            import { actions, always, extract, now } from "@antithesishq/bombadil";
+            export const clicks = actions(() => []);
+            export const inputs = actions(() => []);
+
+            const count = extract((state) => state.count);
+
+            export const positive = always(now(() => count.current > 0));
+            "#,
+        );
+        let metadata = verifier.metadata();
+
+        let generators: Vec<&str> = metadata
+            .action_generators
+            .iter()
+            .map(|generator| generator.name.as_str())
+            .collect();
+        assert_eq!(generators, vec!["clicks", "inputs"]);
+        assert_eq!(metadata.extractors.len(), 1);
+        assert_eq!(metadata.extractors[0].source, "(state) => state.count");
+        assert_eq!(metadata.properties.len(), 1);
+        assert_eq!(metadata.properties[0].name, "positive");
+        assert!(matches!(
+            &metadata.properties[0].formula,
+            Formula::Always(subformula, None)
+                if matches!(**subformula, Formula::Thunk { negated: false, .. })
+        ));
+    }
+
+    #[test]
+    fn test_extractor_every() {
+        let verifier = verifier(
+            r#"
+            import { actions, extract, schema } from "@antithesishq/bombadil";
+            export const clicks = actions(() => []);
+
+            const rows = extract((state) => state.rows, { every: 5 });
+            const count = extract((state) => state.count, schema.number());
+            "#,
+        );
+        let every: Vec<u64> = verifier
+            .extractors()
+            .unwrap()
+            .iter()
+            .map(|extractor| extractor.every)
+            .collect();
+        assert_eq!(every, vec![5, 1]);
+    }
+
+    #[test]
+    fn test_merged_specifications() {
+        let specification = |path: &str| Specification {
+            path: PathBuf::from(path),
+            contents: r#"
+                import { actions, always } from "@antithesishq/bombadil";
+                export const clicks = actions(() => []);
+                export const ok = always(() => true);
+                "#
+            .to_string()
+            .into_bytes(),
+            remote_modules: vec![],
+            local_modules: vec![],
+            merged: vec![],
+            driven: false,
+            prelude: None,
+            read_files: true,
+        };
+        let merged = Specification::merge(vec![
+            specification("specs/checkout.ts"),
+            specification("specs/search.ts"),
+        ])
+        .unwrap();
+        let verifier = Verifier::new(merged, 0).unwrap();
+
+        let mut properties = verifier.properties();
+        properties.sort();
+        assert_eq!(properties, vec!["checkout/ok", "search/ok"]);
+        let generators: Vec<String> = verifier
+            .metadata()
+            .action_generators
+            .into_iter()
+            .map(|generator| generator.name)
+            .collect();
+        assert_eq!(generators, vec!["checkout/clicks", "search/clicks"]);
+
+        assert!(
+            Specification::merge(vec![
+                specification("a/checkout.ts"),
+                specification("b/checkout.ts"),
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_prelude() {
+        let prelude = Specification::from_string(
+            r#"
+            import { always, now } from "@antithesishq/bombadil";
+            export const baseline = always(() => true);
+            export const overridden = now(() => true);
+            "#,
+            "prelude.ts",
+        )
+        .unwrap();
+        let specification = Specification::from_string(
+            r#"
+            import { actions, now } from "@antithesishq/bombadil";
+            export const clicks = actions(() => []);
+            export const overridden = now(() => false);
+            "#,
+            "spec.ts",
+        )
+        .unwrap()
+        .with_prelude(prelude);
+        let mut verifier = Verifier::new(specification, 0).unwrap();
+
+        let mut properties = verifier.properties();
+        properties.sort();
+        assert_eq!(properties, vec!["baseline", "overridden"]);
+
+        let result: StepResult<json::Value> =
+            verifier.step(vec![], SystemTime::UNIX_EPOCH).unwrap();
+        let (_, overridden) = result
+            .properties
+            .iter()
+            .find(|(name, _)| name == "overridden")
+            .unwrap();
+        assert!(matches!(overridden, ltl::Value::False(_)));
+    }
+
     #[test]
     fn test_property_evaluation_not() {
         let mut verifier = verifier(
@@ -503,7 +1146,7 @@ mod tests {
         );
 
         let extractors = verifier.extractors().unwrap();
-        let extractor_foo_id = extractors.first().unwrap().0;
+        let extractor_foo_id = extractors.first().unwrap().id;
 
         let time = SystemTime::UNIX_EPOCH
             .checked_add(Duration::from_millis(0))
@@ -518,6 +1161,68 @@ mod tests {
         assert!(matches!(value, ltl::Value::True));
     }
 
+    #[test]
+    fn test_extractor_schema_violation() {
+        let mut verifier = verifier(
+            r#"
+            import { actions, extract, now, schema } from "@antithesishq/bombadil";
+            export const _actions = actions(() => []);
+
+            const foo = extract(
+              (state) => state.foo,
+              schema.object({ count: schema.number() }),
+            );
+
+            export const my_prop = now(() => foo.current.count > 0);
+            "#,
+        );
+
+        let extractor_id = verifier.extractors().unwrap().first().unwrap().id;
+        let time = SystemTime::UNIX_EPOCH;
+
+        let result: Result<StepResult<json::Value>> = verifier
+            .step(vec![(extractor_id, json::json!({ "count": "3" }))], time);
+
+        let error = result.err().unwrap().to_string();
+        assert!(error.contains("state.foo"), "{}", error);
+        assert!(
+            error.contains(r#"value.count should be a number, got "3""#),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_metrics() {
+        let mut verifier = verifier(
+            r#"
+            import { actions, always, extract, metric } from "@antithesishq/bombadil";
+            export const _actions = actions(() => []);
+
+            const items = extract((state) => state.items);
+
+            export const small_cart = always(() => {
+              metric("cart_size", items.current);
+              return items.current < 10;
+            });
+            "#,
+        );
+
+        let extractor_id = verifier.extractors().unwrap().first().unwrap().id;
+        for count in [2, 3] {
+            let result: StepResult<json::Value> = verifier
+                .step(
+                    vec![(extractor_id, json::json!(count))],
+                    SystemTime::UNIX_EPOCH,
+                )
+                .unwrap();
+            assert_eq!(
+                result.metrics,
+                vec![("cart_size".to_string(), count as f64)]
+            );
+        }
+    }
+
     #[test]
     fn test_property_evaluation_and() {
         let mut verifier = verifier(
@@ -533,8 +1238,8 @@ mod tests {
         );
 
         let extractors = verifier.extractors().unwrap();
-        let extractor_foo_id = extractors.first().unwrap().0;
-        let extractor_bar_id = extractors.get(1).unwrap().0;
+        let extractor_foo_id = extractors.first().unwrap().id;
+        let extractor_bar_id = extractors.get(1).unwrap().id;
 
         let time = SystemTime::UNIX_EPOCH
             .checked_add(Duration::from_millis(0))
@@ -570,8 +1275,8 @@ mod tests {
         );
 
         let extractors = verifier.extractors().unwrap();
-        let extractor_foo_id = extractors.first().unwrap().0;
-        let extractor_bar_id = extractors.get(1).unwrap().0;
+        let extractor_foo_id = extractors.first().unwrap().id;
+        let extractor_bar_id = extractors.get(1).unwrap().id;
 
         let time = SystemTime::UNIX_EPOCH
             .checked_add(Duration::from_millis(0))
@@ -607,8 +1312,8 @@ mod tests {
         );
 
         let extractors = verifier.extractors().unwrap();
-        let extractor_foo_id = extractors.first().unwrap().0;
-        let extractor_bar_id = extractors.get(1).unwrap().0;
+        let extractor_foo_id = extractors.first().unwrap().id;
+        let extractor_bar_id = extractors.get(1).unwrap().id;
 
         let time = SystemTime::UNIX_EPOCH
             .checked_add(Duration::from_millis(0))
@@ -642,7 +1347,7 @@ mod tests {
             "#,
         );
 
-        let extractor_id = verifier.extractors().unwrap().first().unwrap().0;
+        let extractor_id = verifier.extractors().unwrap().first().unwrap().id;
 
         let time_at = |i: u64| {
             SystemTime::UNIX_EPOCH
@@ -675,6 +1380,98 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_step_index() {
+        let mut verifier = verifier(
+            r#"
+            import { actions, always, time } from "@antithesishq/bombadil";
+            export const _actions = actions(() => []);
+
+            export const my_prop = always(
+              () => time.stepIndex < 3 && time.elapsed === time.stepIndex * 10,
+            );
+            "#,
+        );
+
+        for i in 0..=3 {
+            let time = SystemTime::UNIX_EPOCH
+                .checked_add(Duration::from_millis(1000 + i * 10))
+                .unwrap();
+            let result: StepResult<json::Value> =
+                verifier.step(vec![], time).unwrap();
+
+            let (_, value) = result.properties.first().unwrap();
+            if i == 3 {
+                assert!(matches!(value, ltl::Value::False(_)));
+            } else {
+                assert!(matches!(value, ltl::Value::Residual(_)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_bytes_seeded() {
+        let actions = |seed| {
+            let mut verifier = Verifier::new(
+                Specification {
+                    path: PathBuf::from("fake.ts"),
+                    contents: r#"
+                    import { actions } from "@antithesishq/bombadil";
+                    import { integers } from "@antithesishq/bombadil/random";
+                    export const numbers = actions(() => [
+                      integers().min(0).max(1000000).generate(),
+                    ]);
+                    "#
+                    .to_string()
+                    .into_bytes(),
+                    remote_modules: vec![],
+                    local_modules: vec![],
+                    merged: vec![],
+                    driven: false,
+                    prelude: None,
+                    read_files: true,
+                },
+                seed,
+            )
+            .unwrap();
+            (0..5)
+                .map(|_| {
+                    let result: StepResult<json::Value> =
+                        verifier.step(vec![], SystemTime::UNIX_EPOCH).unwrap();
+                    result.actions
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(actions(1), actions(1));
+        assert_ne!(actions(1), actions(2));
+    }
+
+    #[test]
+    fn test_step_index_at_same_time() {
+        let mut verifier = verifier(
+            r#"
+            import { actions, always, time } from "@antithesishq/bombadil";
+            export const _actions = actions(() => []);
+
+            export const my_prop = always(() => time.stepIndex < 3);
+            "#,
+        );
+
+        // States captured within the same millisecond still count as steps.
+        let time = SystemTime::UNIX_EPOCH;
+        for i in 0..=3 {
+            let result: StepResult<json::Value> =
+                verifier.step(vec![], time).unwrap();
+
+            let (_, value) = result.properties.first().unwrap();
+            if i == 3 {
+                assert!(matches!(value, ltl::Value::False(_)));
+            } else {
+                assert!(matches!(value, ltl::Value::Residual(_)));
+            }
+        }
+    }
+
     #[test]
     fn test_property_evaluation_always() {
         let mut verifier = verifier(
@@ -688,7 +1485,7 @@ mod tests {
             "#,
         );
 
-        let extractor_id = verifier.extractors().unwrap().first().unwrap().0;
+        let extractor_id = verifier.extractors().unwrap().first().unwrap().id;
 
         let time_at = |i: u64| {
             SystemTime::UNIX_EPOCH
@@ -728,6 +1525,37 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_replay() {
+        let mut verifier = verifier(
+            r#"
+            import { extract, always, actions } from "@antithesishq/bombadil";
+            export const _actions = actions(() => []);
+
+            const foo = extract((state) => state.foo);
+
+            export const my_prop = always(() => foo.current < 3);
+            "#,
+        );
+
+        let extractor_id = verifier.extractors().unwrap().first().unwrap().id;
+
+        let snapshots = (0..10)
+            .map(|i| {
+                let time = SystemTime::UNIX_EPOCH
+                    .checked_add(Duration::from_millis(i))
+                    .unwrap();
+                (time, vec![(extractor_id, json::json!(i))])
+            })
+            .collect();
+
+        let properties = verifier.replay(snapshots).unwrap();
+
+        let (name, value) = properties.first().unwrap();
+        assert_eq!(*name, "my_prop");
+        assert!(matches!(value, ltl::Value::False(Violation::Always { .. })));
+    }
+
     #[test]
     fn test_property_evaluation_always_bounded() {
         let mut verifier = verifier(
@@ -741,7 +1569,7 @@ mod tests {
             "#,
         );
 
-        let extractor_id = verifier.extractors().unwrap().first().unwrap().0;
+        let extractor_id = verifier.extractors().unwrap().first().unwrap().id;
 
         let time_at = |i: u64| {
             SystemTime::UNIX_EPOCH
@@ -787,7 +1615,7 @@ mod tests {
             "#,
         );
 
-        let extractor_id = verifier.extractors().unwrap().first().unwrap().0;
+        let extractor_id = verifier.extractors().unwrap().first().unwrap().id;
 
         let time_at = |i: u64| {
             SystemTime::UNIX_EPOCH
@@ -833,7 +1661,7 @@ mod tests {
             "#,
         );
 
-        let extractor_id = verifier.extractors().unwrap().first().unwrap().0;
+        let extractor_id = verifier.extractors().unwrap().first().unwrap().id;
 
         let time_at = |i: u64| {
             SystemTime::UNIX_EPOCH
@@ -866,6 +1694,44 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_property_evaluation_eventually_within_steps() {
+        let mut verifier = verifier(
+            r#"
+            import { actions, extract, eventually } from "@antithesishq/bombadil";
+            export const _actions = actions(() => []);
+
+            const foo = extract((state) => state.foo);
+
+            export const my_prop = eventually(() => foo.current === 9).withinSteps(3);
+            "#,
+        );
+
+        let extractor_id = verifier.extractors().unwrap().first().unwrap().id;
+
+        // All states at the same time: the bound only depends on the states.
+        let time = SystemTime::UNIX_EPOCH;
+
+        for i in 0..10 {
+            let result: StepResult<json::Value> = verifier
+                .step(vec![(extractor_id, json::json!(i))], time)
+                .unwrap();
+
+            let (name, value) = result.properties.first().unwrap();
+            assert_eq!(*name, "my_prop");
+
+            if i < 4 {
+                assert!(
+                    matches!(value, ltl::Value::Residual(_)),
+                    "should be residual but was: {:?}",
+                    value
+                );
+            } else {
+                assert!(matches!(value, ltl::Value::False(_)));
+            }
+        }
+    }
+
     #[test]
     fn test_load_ts_file() {
         let mut imported_file =
@@ -890,7 +1756,44 @@ mod tests {
         ));
 
         let extractors = verifier.extractors().unwrap();
-        let (_, name) = extractors.first().unwrap();
-        assert_eq!(name, "(state) => state.example");
+        assert_eq!(
+            extractors.first().unwrap().source,
+            "(state) => state.example"
+        );
+    }
+
+    #[test]
+    fn test_end_policies() {
+        let mut verifier = verifier(
+            r#"
+            import { actions, eventually, extract } from "@antithesishq/bombadil";
+            export const _actions = actions(() => []);
+
+            const loaded = extract((state) => state.loaded);
+
+            export const loads_failing = eventually(() => loaded.current).atEnd("fail");
+            export const loads_warning = eventually(() => loaded.current).atEnd("warn");
+            export const loads_ignored = eventually(() => loaded.current);
+            "#,
+        );
+        let extractor_id = verifier.extractors().unwrap().first().unwrap().id;
+        let time = SystemTime::UNIX_EPOCH;
+        let _: StepResult<json::Value> = verifier
+            .step(vec![(extractor_id, json::json!(false))], time)
+            .unwrap();
+
+        let mut policies: Vec<(String, EndPolicy)> = verifier
+            .finish(time)
+            .into_iter()
+            .map(|(name, policy, _)| (name, policy))
+            .collect();
+        policies.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            policies,
+            vec![
+                ("loads_failing".to_string(), EndPolicy::Fail),
+                ("loads_warning".to_string(), EndPolicy::Warn),
+            ]
+        );
     }
 }
@@ -1,4 +1,6 @@
+use futures::future::BoxFuture;
 use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use serde_json as json;
 use std::sync::Arc;
 use tokio::sync::{mpsc, oneshot};
@@ -7,7 +9,10 @@ use crate::specification::js::RuntimeFunction;
 use crate::specification::ltl::{self};
 use crate::specification::render::PrettyFunction;
 use crate::specification::result::SpecificationError;
-use crate::specification::verifier::{Specification, Verifier};
+use crate::specification::stop::EndPolicy;
+use crate::specification::verifier::{
+    ExtractorFunction, Specification, Verifier,
+};
 use crate::tree::Tree;
 
 enum Command {
@@ -15,7 +20,11 @@ enum Command {
         reply: oneshot::Sender<Vec<String>>,
     },
     GetExtractors {
-        reply: oneshot::Sender<Result<Vec<(u64, String)>, SpecificationError>>,
+        reply:
+            oneshot::Sender<Result<Vec<ExtractorFunction>, SpecificationError>>,
+    },
+    GetSetup {
+        reply: oneshot::Sender<Vec<json::Value>>,
     },
 
     Step {
@@ -23,20 +32,29 @@ enum Command {
         time: ltl::Time,
         reply: oneshot::Sender<Result<RawStepResult, SpecificationError>>,
     },
+
+    Finish {
+        time: ltl::Time,
+        reply: oneshot::Sender<Vec<EndViolation>>,
+    },
 }
 
 struct RawStepResult {
     properties: Vec<(String, PropertyValue)>,
     actions: Tree<json::Value>,
+    metrics: Vec<(String, f64)>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StepResult<A> {
     pub properties: Vec<(String, PropertyValue)>,
     pub actions: Tree<A>,
+    /// The metrics emitted by the specification in this step, in emission order.
+    #[serde(default)]
+    pub metrics: Vec<(String, f64)>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum PropertyValue {
     True,
     False(ltl::Violation<PrettyFunction>),
@@ -55,6 +73,86 @@ impl From<&ltl::Value<RuntimeFunction>> for PropertyValue {
     }
 }
 
+/// A property undecided when the run ended that would be false if it ended there, and
+/// what its end policy makes of it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EndViolation {
+    pub name: String,
+    pub policy: EndPolicy,
+    pub violation: ltl::Violation<PrettyFunction>,
+}
+
+/// Evaluates a specification's properties, extractors and action generators, either
+/// in-process ([`VerifierWorker`]) or in another process or host
+/// ([`RemoteVerifier`](crate::specification::remote::RemoteVerifier)).
+pub trait VerifierBackend: Send + Sync {
+    fn properties(&self) -> BoxFuture<'_, Result<Vec<String>, WorkerError>>;
+
+    fn extractors(
+        &self,
+    ) -> BoxFuture<'_, Result<Vec<ExtractorFunction>, WorkerError>>;
+
+    /// The actions of the specification's setup, left as JSON.
+    fn setup_json(
+        &self,
+    ) -> BoxFuture<'_, Result<Vec<json::Value>, WorkerError>>;
+
+    /// Steps the properties and action generators, with actions left as JSON.
+    fn step_json(
+        &self,
+        snapshots: Vec<(u64, json::Value)>,
+        time: ltl::Time,
+    ) -> BoxFuture<'_, Result<StepResult<json::Value>, WorkerError>>;
+
+    /// The properties reported at the end of the run, according to their end policies.
+    fn finish(
+        &self,
+        time: ltl::Time,
+    ) -> BoxFuture<'_, Result<Vec<EndViolation>, WorkerError>>;
+}
+
+impl dyn VerifierBackend {
+    /// The actions to apply in order at the start of the run, before exploring.
+    pub async fn setup<A: DeserializeOwned>(
+        &self,
+    ) -> Result<Vec<A>, WorkerError> {
+        self.setup_json()
+            .await?
+            .into_iter()
+            .map(|v| {
+                json::from_value(v).map_err(|e| {
+                    WorkerError::SpecificationError(
+                        SpecificationError::OtherError(format!(
+                            "failed to deserialize setup action: {}",
+                            e
+                        )),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    pub async fn step<A: DeserializeOwned>(
+        &self,
+        snapshots: Vec<(u64, json::Value)>,
+        time: ltl::Time,
+    ) -> Result<StepResult<A>, WorkerError> {
+        let result = self.step_json(snapshots, time).await?;
+        let actions = result.actions.try_map(&mut |v| {
+            json::from_value(v).map_err(|e| {
+                WorkerError::SpecificationError(SpecificationError::OtherError(
+                    format!("failed to deserialize action: {}", e),
+                ))
+            })
+        })?;
+        Ok(StepResult {
+            properties: result.properties,
+            actions,
+            metrics: result.metrics,
+        })
+    }
+}
+
 #[derive(Clone)]
 pub struct VerifierWorker {
     tx: mpsc::Sender<Command>,
@@ -66,6 +164,7 @@ impl VerifierWorker {
     /// Call this once at startup and share the handle as needed.
     pub async fn start(
         specification: Specification,
+        seed: u64,
     ) -> Result<Arc<Self>, SpecificationError> {
         let (ready_tx, ready_rx) =
             oneshot::channel::<Result<(), SpecificationError>>();
@@ -74,7 +173,7 @@ impl VerifierWorker {
         let handle = Arc::new(VerifierWorker { tx });
 
         let _worker_thread = std::thread::spawn(move || {
-            let mut verifier = match Verifier::new(specification) {
+            let mut verifier = match Verifier::new(specification, seed) {
                 Ok(verifier) => {
                     let _ = ready_tx.send(Ok(()));
                     verifier
@@ -92,6 +191,9 @@ impl VerifierWorker {
                     Command::GetExtractors { reply } => {
                         let _ = reply.send(verifier.extractors());
                     }
+                    Command::GetSetup { reply } => {
+                        let _ = reply.send(verifier.setup());
+                    }
                     Command::Step {
                         snapshots,
                         time,
@@ -111,10 +213,25 @@ impl VerifierWorker {
                                         })
                                         .collect(),
                                     actions: result.actions,
+                                    metrics: result.metrics,
                                 },
                             ),
                         );
                     }
+                    Command::Finish { time, reply } => {
+                        let _ = reply.send(
+                            verifier
+                                .finish(time)
+                                .into_iter()
+                                .map(|(name, policy, violation)| EndViolation {
+                                    name,
+                                    policy,
+                                    violation: violation
+                                        .with_pretty_functions(),
+                                })
+                                .collect(),
+                        );
+                    }
                 }
             }
         });
@@ -127,54 +244,92 @@ impl VerifierWorker {
         })??;
         Ok(handle)
     }
+}
 
-    pub async fn properties(&self) -> Result<Vec<String>, WorkerError> {
-        let (reply_tx, reply_rx) = oneshot::channel();
-        self.tx
-            .send(Command::GetProperties { reply: reply_tx })
-            .await
-            .map_err(|_| WorkerError::WorkerGone)?;
-        reply_rx.await.map_err(|_| WorkerError::WorkerGone)
+impl VerifierBackend for VerifierWorker {
+    fn properties(&self) -> BoxFuture<'_, Result<Vec<String>, WorkerError>> {
+        Box::pin(async move {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            self.tx
+                .send(Command::GetProperties { reply: reply_tx })
+                .await
+                .map_err(|_| WorkerError::WorkerGone)?;
+            reply_rx.await.map_err(|_| WorkerError::WorkerGone)
+        })
     }
-    pub async fn extractors(&self) -> Result<Vec<(u64, String)>, WorkerError> {
-        let (reply_tx, reply_rx) = oneshot::channel();
-        self.tx
-            .send(Command::GetExtractors { reply: reply_tx })
-            .await
-            .map_err(|_| WorkerError::WorkerGone)?;
-        reply_rx
-            .await
-            .map_err(|_| WorkerError::WorkerGone)
-            .and_then(|result| result.map_err(WorkerError::SpecificationError))
+
+    fn extractors(
+        &self,
+    ) -> BoxFuture<'_, Result<Vec<ExtractorFunction>, WorkerError>> {
+        Box::pin(async move {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            self.tx
+                .send(Command::GetExtractors { reply: reply_tx })
+                .await
+                .map_err(|_| WorkerError::WorkerGone)?;
+            reply_rx
+                .await
+                .map_err(|_| WorkerError::WorkerGone)
+                .and_then(|result| {
+                    result.map_err(WorkerError::SpecificationError)
+                })
+        })
     }
-    pub async fn step<A: DeserializeOwned>(
+
+    fn setup_json(
+        &self,
+    ) -> BoxFuture<'_, Result<Vec<json::Value>, WorkerError>> {
+        Box::pin(async move {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            self.tx
+                .send(Command::GetSetup { reply: reply_tx })
+                .await
+                .map_err(|_| WorkerError::WorkerGone)?;
+            reply_rx.await.map_err(|_| WorkerError::WorkerGone)
+        })
+    }
+
+    fn step_json(
         &self,
         snapshots: Vec<(u64, json::Value)>,
         time: ltl::Time,
-    ) -> Result<StepResult<A>, WorkerError> {
-        let (reply_tx, reply_rx) = oneshot::channel();
-        self.tx
-            .send(Command::Step {
-                reply: reply_tx,
-                snapshots,
-                time,
+    ) -> BoxFuture<'_, Result<StepResult<json::Value>, WorkerError>> {
+        Box::pin(async move {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            self.tx
+                .send(Command::Step {
+                    reply: reply_tx,
+                    snapshots,
+                    time,
+                })
+                .await
+                .map_err(|_| WorkerError::WorkerGone)?;
+            let result = reply_rx
+                .await
+                .map_err(|_| WorkerError::WorkerGone)?
+                .map_err(WorkerError::SpecificationError)?;
+            Ok(StepResult {
+                properties: result.properties,
+                actions: result.actions,
+                metrics: result.metrics,
             })
-            .await
-            .map_err(|_| WorkerError::WorkerGone)?;
-        let result = reply_rx
-            .await
-            .map_err(|_| WorkerError::WorkerGone)?
-            .map_err(WorkerError::SpecificationError)?;
-        let actions = result.actions.try_map(&mut |v| {
-            json::from_value(v).map_err(|e| {
-                WorkerError::SpecificationError(SpecificationError::OtherError(
-                    format!("failed to deserialize action: {}", e),
-                ))
-            })
-        })?;
-        Ok(StepResult {
-            properties: result.properties,
-            actions,
+        })
+    }
+
+    fn finish(
+        &self,
+        time: ltl::Time,
+    ) -> BoxFuture<'_, Result<Vec<EndViolation>, WorkerError>> {
+        Box::pin(async move {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            self.tx
+                .send(Command::Finish {
+                    reply: reply_tx,
+                    time,
+                })
+                .await
+                .map_err(|_| WorkerError::WorkerGone)?;
+            reply_rx.await.map_err(|_| WorkerError::WorkerGone)
         })
     }
 }
@@ -183,6 +338,8 @@ impl VerifierWorker {
 pub enum WorkerError {
     WorkerGone,
     SpecificationError(SpecificationError),
+    /// Connecting or talking to a remote verifier failed.
+    Remote(String),
 }
 
 impl std::fmt::Display for WorkerError {
@@ -192,6 +349,9 @@ impl std::fmt::Display for WorkerError {
             WorkerError::SpecificationError(specification_error) => {
                 specification_error.fmt(f)
             }
+            WorkerError::Remote(message) => {
+                write!(f, "remote verifier: {}", message)
+            }
         }
     }
 }
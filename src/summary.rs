@@ -0,0 +1,177 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+
+use crate::browser::state::EdgeIndex;
+use crate::run_id::RunId;
+use crate::runner::{PropertyVerdict, StepTimings};
+use crate::specification::render::render_violation;
+use crate::suppressions::Suppression;
+use crate::trace::PropertyViolation;
+
+/// A violation found during a run.
+#[derive(Clone, Debug, Serialize)]
+pub struct ViolationSummary {
+    pub property: String,
+    /// The index of the state it was found in, or the number of states when found at the
+    /// end of the run.
+    pub step: u32,
+    /// The URL of the state it was found in, if not at the end of the run.
+    pub url: Option<String>,
+    pub fingerprint: String,
+    /// The violation, rendered as in the logs.
+    pub message: String,
+    pub suppressed: Option<Suppression>,
+}
+
+impl ViolationSummary {
+    pub fn new(
+        violation: &PropertyViolation,
+        step: u32,
+        url: Option<String>,
+    ) -> Self {
+        ViolationSummary {
+            property: violation.name.clone(),
+            step,
+            url,
+            fingerprint: violation.fingerprint.clone(),
+            message: render_violation(&violation.violation),
+            suppressed: violation.suppressed.clone(),
+        }
+    }
+}
+
+/// The average time of each stage of a step, in milliseconds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct StepLatency {
+    pub screenshot: f64,
+    pub pause: f64,
+    pub evaluation: f64,
+    pub extractors: f64,
+    pub verifier: f64,
+}
+
+impl StepLatency {
+    pub fn average(total: &StepTimings, steps: u32) -> Self {
+        let average = |duration: Duration| {
+            duration.as_secs_f64() * 1000.0 / steps.max(1) as f64
+        };
+        StepLatency {
+            screenshot: average(total.capture.screenshot),
+            pause: average(total.capture.pause),
+            evaluation: average(total.capture.evaluation),
+            extractors: average(total.extractors),
+            verifier: average(total.verifier),
+        }
+    }
+}
+
+/// The outcome of a single run.
+#[derive(Clone, Debug, Serialize)]
+pub struct RunSummary {
+    pub seed: u64,
+    pub run_id: RunId,
+    pub started: SystemTime,
+    pub duration: Duration,
+    pub steps: u32,
+    pub edges_covered: usize,
+    pub step_latency: StepLatency,
+    /// What each property evaluated to by the end of the run.
+    pub verdicts: BTreeMap<String, PropertyVerdict>,
+    pub violations: Vec<ViolationSummary>,
+    pub exit_code: Option<i32>,
+}
+
+/// The machine-readable outcome of all runs, for CI pipelines to act on instead of the
+/// logs.
+#[derive(Debug, Default, Serialize)]
+pub struct Summary {
+    /// The exit code of the process.
+    pub exit_code: i32,
+    pub steps: u64,
+    /// Edges covered by any run.
+    pub edges_total: usize,
+    /// Properties violated in any run, leaving out suppressed violations.
+    pub violated_properties: BTreeSet<String>,
+    pub runs: Vec<RunSummary>,
+    #[serde(skip)]
+    covered: BTreeSet<EdgeIndex>,
+}
+
+impl Summary {
+    /// Adds a run, with the edges it covered.
+    pub fn add(&mut self, run: RunSummary, covered: &BTreeSet<EdgeIndex>) {
+        self.steps += run.steps as u64;
+        self.covered.extend(covered);
+        self.edges_total = self.covered.len();
+        self.violated_properties.extend(
+            run.violations
+                .iter()
+                .filter(|violation| violation.suppressed.is_none())
+                .map(|violation| violation.property.clone()),
+        );
+        self.runs.push(run);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_of_runs() {
+        let violation = |property: &str, suppressed| ViolationSummary {
+            property: property.to_string(),
+            step: 3,
+            url: Some("http://localhost/".to_string()),
+            fingerprint: "0123".to_string(),
+            message: "!".to_string(),
+            suppressed,
+        };
+        let run = |seed, violations| RunSummary {
+            seed,
+            run_id: RunId::new(seed),
+            started: SystemTime::UNIX_EPOCH,
+            duration: Duration::from_secs(60),
+            steps: 10,
+            edges_covered: 0,
+            step_latency: StepLatency::default(),
+            verdicts: BTreeMap::from([
+                ("a".to_string(), PropertyVerdict::False),
+                (
+                    "b".to_string(),
+                    PropertyVerdict::Residual {
+                        stop_default: false,
+                        policy: None,
+                    },
+                ),
+            ]),
+            violations,
+            exit_code: None,
+        };
+        let suppression = Suppression {
+            property: "b".to_string(),
+            fingerprint: None,
+            reason: "known".to_string(),
+            expires: "2100-01-01".to_string(),
+        };
+        let mut summary = Summary::default();
+        summary
+            .add(run(1, vec![violation("a", None)]), &BTreeSet::from([1, 2]));
+        summary.add(
+            run(2, vec![violation("b", Some(suppression))]),
+            &BTreeSet::from([2, 3]),
+        );
+
+        assert_eq!(summary.steps, 20);
+        assert_eq!(summary.edges_total, 3);
+        assert_eq!(
+            summary.violated_properties,
+            BTreeSet::from(["a".to_string()])
+        );
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["runs"][0]["verdicts"]["a"]["verdict"], "false");
+        assert_eq!(json["runs"][0]["verdicts"]["b"]["stop_default"], false);
+    }
+}
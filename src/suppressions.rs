@@ -0,0 +1,202 @@
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json as json;
+
+use crate::specification::ltl::Violation;
+use crate::specification::render::PrettyFunction;
+
+/// A known violation that doesn't fail the test until it expires, e.g. while a fix is under
+/// way. Suppressed violations are still recorded in the trace and summarized.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Suppression {
+    pub property: String,
+    /// Fingerprint of the suppressed violation, or none to suppress all violations of the
+    /// property.
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+    pub reason: String,
+    /// Last day (`YYYY-MM-DD`, in UTC) that the suppression applies.
+    pub expires: String,
+}
+
+/// The suppressions of a checked-in suppression file: a JSON array of [`Suppression`]s.
+#[derive(Clone, Debug, Default)]
+pub struct Suppressions {
+    suppressions: Vec<(Suppression, SystemTime)>,
+}
+
+impl Suppressions {
+    pub async fn from_path(path: &Path) -> Result<Self> {
+        let contents = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("read {}", path.display()))?;
+        Self::from_json(&contents)
+            .with_context(|| format!("parse {}", path.display()))
+    }
+
+    fn from_json(contents: &str) -> Result<Self> {
+        let suppressions: Vec<Suppression> = json::from_str(contents)?;
+        let now = SystemTime::now();
+        let mut parsed = Vec::with_capacity(suppressions.len());
+        for suppression in suppressions {
+            let expiry =
+                end_of_day(&suppression.expires).with_context(|| {
+                    format!("suppression of `{}`", suppression.property)
+                })?;
+            if expiry < now {
+                log::warn!(
+                    "suppression of `{}` expired on {}, its violations are reported again",
+                    suppression.property,
+                    suppression.expires
+                );
+            }
+            parsed.push((suppression, expiry));
+        }
+        Ok(Suppressions {
+            suppressions: parsed,
+        })
+    }
+
+    /// The unexpired suppression applying to a violation, if any.
+    pub fn find(
+        &self,
+        property: &str,
+        fingerprint: &str,
+        now: SystemTime,
+    ) -> Option<&Suppression> {
+        self.suppressions
+            .iter()
+            .find(|(suppression, expiry)| {
+                suppression.property == property
+                    && suppression
+                        .fingerprint
+                        .as_ref()
+                        .is_none_or(|expected| expected == fingerprint)
+                    && now <= *expiry
+            })
+            .map(|(suppression, _)| suppression)
+    }
+}
+
+/// Identifies a violation across runs, regardless of when it happened: a hash of its
+/// structure, conditions and formulas, leaving out timestamps.
+pub fn fingerprint(violation: &Violation<PrettyFunction>) -> String {
+    let mut value =
+        json::to_value(violation).expect("violation can't be serialized");
+    strip_timestamps(&mut value);
+    // FNV-1a, which unlike the standard library hasher is stable across releases, as
+    // fingerprints are checked in.
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in value.to_string().bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+fn strip_timestamps(value: &mut json::Value) {
+    match value {
+        json::Value::Object(object) => {
+            if object.contains_key("secs_since_epoch") {
+                *value = json::Value::Null;
+            } else {
+                object.values_mut().for_each(strip_timestamps);
+            }
+        }
+        json::Value::Array(values) => {
+            values.iter_mut().for_each(strip_timestamps)
+        }
+        _ => {}
+    }
+}
+
+/// The end of a `YYYY-MM-DD` day, in UTC.
+fn end_of_day(date: &str) -> Result<SystemTime> {
+    let parts: Vec<&str> = date.split('-').collect();
+    let [year, month, day] = parts.as_slice() else {
+        bail!("expected a YYYY-MM-DD date, got {:?}", date);
+    };
+    let (year, month, day): (i64, u32, u32) = (
+        year.parse().context("invalid year")?,
+        month.parse().context("invalid month")?,
+        day.parse().context("invalid day")?,
+    );
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        bail!("expected a YYYY-MM-DD date, got {:?}", date);
+    }
+    let days = days_from_civil(year, month, day);
+    if days < 0 {
+        bail!("date {:?} is before 1970", date);
+    }
+    Ok(UNIX_EPOCH + Duration::from_secs((days as u64 + 1) * 86400))
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5
+            + day as i64
+            - 1;
+    let day_of_era =
+        year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_end_of_day() {
+        assert_eq!(
+            end_of_day("1970-01-01").unwrap(),
+            UNIX_EPOCH + Duration::from_secs(86400)
+        );
+        assert_eq!(
+            end_of_day("2024-03-01").unwrap(),
+            UNIX_EPOCH + Duration::from_secs(1709337600)
+        );
+        assert!(end_of_day("2024-13-01").is_err());
+        assert!(end_of_day("tomorrow").is_err());
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_time() {
+        let violation = |seconds| Violation::False {
+            time: UNIX_EPOCH + Duration::from_secs(seconds),
+            condition: "x > 0".to_string(),
+        };
+        assert_eq!(fingerprint(&violation(1)), fingerprint(&violation(2)));
+        assert_ne!(
+            fingerprint(&violation(1)),
+            fingerprint(&Violation::False {
+                time: UNIX_EPOCH,
+                condition: "x > 1".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_find_suppression() {
+        let suppressions = Suppressions::from_json(
+            r#"[
+                { "property": "a", "reason": "known", "expires": "2100-01-01" },
+                { "property": "b", "fingerprint": "ff", "reason": "known", "expires": "2100-01-01" },
+                { "property": "c", "reason": "fixed", "expires": "2000-01-01" }
+            ]"#,
+        )
+        .unwrap();
+        let now = SystemTime::now();
+        assert!(suppressions.find("a", "00", now).is_some());
+        assert!(suppressions.find("b", "ff", now).is_some());
+        assert!(suppressions.find("b", "00", now).is_none());
+        assert!(suppressions.find("c", "00", now).is_none());
+    }
+}
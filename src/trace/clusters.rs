@@ -0,0 +1,153 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::Serialize;
+use url::Url;
+
+use crate::trace::PropertyViolation;
+
+/// Differing bits between the state hashes of violations in the same cluster, out of 64.
+/// State hashes are simhashes of the covered edges, so similar states have close hashes.
+const SIMILAR_HASH_DISTANCE: u32 = 8;
+
+/// Violations of the same property, on the same page, in similar states, which are most
+/// likely the same failure.
+#[derive(Clone, Debug, Serialize)]
+pub struct ViolationCluster {
+    pub property: String,
+    /// Origin and path of the page, without query or fragment.
+    pub page: String,
+    /// State hash of the first violation, which later ones are compared with.
+    pub hash: Option<u64>,
+    pub count: usize,
+    pub first: SystemTime,
+    pub last: SystemTime,
+    /// Screenshot of the state of the first violation.
+    pub screenshot: PathBuf,
+    pub fingerprints: BTreeSet<String>,
+}
+
+/// Groups the (unsuppressed) violations of a run into clusters, in order of first
+/// occurrence.
+#[derive(Debug, Default)]
+pub struct ViolationClusters {
+    clusters: Vec<ViolationCluster>,
+}
+
+impl ViolationClusters {
+    pub fn add(
+        &mut self,
+        violation: &PropertyViolation,
+        url: &Url,
+        hash: Option<u64>,
+        timestamp: SystemTime,
+        screenshot: &Path,
+    ) {
+        if violation.suppressed.is_some() {
+            return;
+        }
+        let page = page(url);
+        let existing = self.clusters.iter_mut().find(|cluster| {
+            cluster.property == violation.name
+                && cluster.page == page
+                && similar(cluster.hash, hash)
+        });
+        match existing {
+            Some(cluster) => {
+                cluster.count += 1;
+                cluster.last = timestamp;
+                cluster.fingerprints.insert(violation.fingerprint.clone());
+            }
+            None => self.clusters.push(ViolationCluster {
+                property: violation.name.clone(),
+                page,
+                hash,
+                count: 1,
+                first: timestamp,
+                last: timestamp,
+                screenshot: screenshot.to_path_buf(),
+                fingerprints: BTreeSet::from([violation.fingerprint.clone()]),
+            }),
+        }
+    }
+
+    pub fn clusters(&self) -> &[ViolationCluster] {
+        &self.clusters
+    }
+}
+
+fn page(url: &Url) -> String {
+    let mut page = url.clone();
+    page.set_query(None);
+    page.set_fragment(None);
+    page.to_string()
+}
+
+fn similar(a: Option<u64>, b: Option<u64>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => (a ^ b).count_ones() <= SIMILAR_HASH_DISTANCE,
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::specification::ltl::Violation;
+    use std::time::UNIX_EPOCH;
+
+    fn violation(name: &str) -> PropertyViolation {
+        PropertyViolation {
+            name: name.to_string(),
+            violation: Violation::False {
+                time: UNIX_EPOCH,
+                condition: "x > 0".to_string(),
+            },
+            fingerprint: "00".to_string(),
+            suppressed: None,
+        }
+    }
+
+    #[test]
+    fn test_cluster_violations() {
+        let mut clusters = ViolationClusters::default();
+        let cart = Url::parse("https://example.com/cart?item=1").unwrap();
+        let other_cart = Url::parse("https://example.com/cart?item=2").unwrap();
+        let home = Url::parse("https://example.com/").unwrap();
+        let screenshot = Path::new("screenshot.webp");
+        let mut add = |name, url, hash| {
+            clusters.add(&violation(name), url, hash, UNIX_EPOCH, screenshot)
+        };
+
+        add("a", &cart, Some(0b1111));
+        add("a", &other_cart, Some(0b0111));
+        add("a", &cart, Some(u64::MAX));
+        add("a", &home, Some(0b1111));
+        add("b", &cart, Some(0b1111));
+        add("b", &cart, None);
+
+        let counts: Vec<(&str, &str, usize)> = clusters
+            .clusters()
+            .iter()
+            .map(|cluster| {
+                (
+                    cluster.property.as_str(),
+                    cluster.page.as_str(),
+                    cluster.count,
+                )
+            })
+            .collect();
+        assert_eq!(
+            counts,
+            vec![
+                ("a", "https://example.com/cart", 2),
+                ("a", "https://example.com/cart", 1),
+                ("a", "https://example.com/", 1),
+                ("b", "https://example.com/cart", 1),
+                ("b", "https://example.com/cart", 1),
+            ]
+        );
+    }
+}
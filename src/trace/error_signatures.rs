@@ -0,0 +1,224 @@
+use std::time::SystemTime;
+
+use serde::Serialize;
+use serde_json as json;
+
+use crate::browser::state::{BrowserState, ConsoleEntryLevel, Exception};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    ConsoleError,
+    Exception,
+}
+
+/// Console errors or uncaught exceptions with the same message, up to the numbers and
+/// quoted strings in it, thrown from the same place.
+#[derive(Clone, Debug, Serialize)]
+pub struct ErrorSignature {
+    pub kind: ErrorKind,
+    /// The message with numbers replaced by `<n>` and quoted strings by `<string>`.
+    pub template: String,
+    /// Where an exception was thrown, as `function (url:line:column)`.
+    pub top_frame: Option<String>,
+    pub count: usize,
+    pub first: SystemTime,
+    /// The first message with this signature, as is.
+    pub example: String,
+}
+
+/// Aggregates the console errors and uncaught exceptions of a run into signatures, in order
+/// of first occurrence. Entries dropped beyond the per-step log limits aren't counted.
+#[derive(Debug, Default)]
+pub struct ErrorSignatures {
+    signatures: Vec<ErrorSignature>,
+}
+
+impl ErrorSignatures {
+    pub fn add_state(&mut self, state: &BrowserState) {
+        for entry in &state.console_entries {
+            if !matches!(entry.level, ConsoleEntryLevel::Error) {
+                continue;
+            }
+            let message = entry
+                .args
+                .iter()
+                .map(|arg| match arg {
+                    json::Value::String(string) => string.clone(),
+                    other => other.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            self.add(ErrorKind::ConsoleError, message, None, entry.timestamp);
+        }
+        for exception in &state.exceptions {
+            self.add(
+                ErrorKind::Exception,
+                exception_message(exception),
+                top_frame(exception),
+                exception.timestamp,
+            );
+        }
+    }
+
+    fn add(
+        &mut self,
+        kind: ErrorKind,
+        message: String,
+        top_frame: Option<String>,
+        timestamp: SystemTime,
+    ) {
+        let template = template(&message);
+        let existing = self.signatures.iter_mut().find(|signature| {
+            signature.kind == kind
+                && signature.template == template
+                && signature.top_frame == top_frame
+        });
+        match existing {
+            Some(signature) => signature.count += 1,
+            None => self.signatures.push(ErrorSignature {
+                kind,
+                template,
+                top_frame,
+                count: 1,
+                first: timestamp,
+                example: message,
+            }),
+        }
+    }
+
+    pub fn signatures(&self) -> &[ErrorSignature] {
+        &self.signatures
+    }
+}
+
+/// The first line of the exception's description (e.g. "TypeError: x is undefined"),
+/// falling back to its text (e.g. "Uncaught").
+fn exception_message(exception: &Exception) -> String {
+    exception
+        .remote_object
+        .as_ref()
+        .and_then(|object| object.description.as_deref())
+        .and_then(|description| description.lines().next())
+        .unwrap_or(&exception.text)
+        .to_string()
+}
+
+fn top_frame(exception: &Exception) -> Option<String> {
+    match exception
+        .stacktrace
+        .as_deref()
+        .and_then(|frames| frames.first())
+    {
+        Some(frame) => Some(format!(
+            "{} ({}:{}:{})",
+            if frame.name.is_empty() {
+                "<anonymous>"
+            } else {
+                &frame.name
+            },
+            frame.url,
+            frame.line,
+            frame.column
+        )),
+        None => exception.url.as_ref().map(|url| {
+            format!("{}:{}:{}", url, exception.line, exception.column)
+        }),
+    }
+}
+
+fn template(message: &str) -> String {
+    let mut template = String::with_capacity(message.len());
+    let mut chars = message.chars().peekable();
+    while let Some(c) = chars.next() {
+        if matches!(c, '"' | '\'' | '`') {
+            // Skip to the closing quote, if any, keeping apostrophes in words as is.
+            let rest: String = chars.clone().collect();
+            if let Some(end) = rest.find(c)
+                && !template.ends_with(char::is_alphanumeric)
+            {
+                template.push_str("<string>");
+                for _ in 0..=rest[..end].chars().count() {
+                    chars.next();
+                }
+                continue;
+            }
+            template.push(c);
+        } else if c.is_alphanumeric() || c == '_' {
+            let mut word = String::from(c);
+            while let Some(&next) = chars.peek()
+                && (next.is_alphanumeric() || next == '_')
+            {
+                word.push(next);
+                chars.next();
+            }
+            if word.chars().any(|c| c.is_ascii_digit()) {
+                template.push_str("<n>");
+            } else {
+                template.push_str(&word);
+            }
+        } else {
+            template.push(c);
+        }
+    }
+    template
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template() {
+        assert_eq!(
+            template("Failed to load user 1234 (\"alice\") after 3.5s"),
+            "Failed to load user <n> (<string>) after <n>.<n>"
+        );
+        assert_eq!(
+            template("can't find item a1b2c3 at /api/items/42"),
+            "can't find item <n> at /api/items/<n>"
+        );
+    }
+
+    #[test]
+    fn test_aggregate_signatures() {
+        let mut signatures = ErrorSignatures::default();
+        let frame = Some("render (app.js:1:2)".to_string());
+        let now = SystemTime::UNIX_EPOCH;
+        signatures.add(
+            ErrorKind::Exception,
+            "TypeError: item 1 is undefined".to_string(),
+            frame.clone(),
+            now,
+        );
+        signatures.add(
+            ErrorKind::Exception,
+            "TypeError: item 2 is undefined".to_string(),
+            frame.clone(),
+            now,
+        );
+        signatures.add(
+            ErrorKind::Exception,
+            "TypeError: item 2 is undefined".to_string(),
+            None,
+            now,
+        );
+        signatures.add(
+            ErrorKind::ConsoleError,
+            "TypeError: item 2 is undefined".to_string(),
+            None,
+            now,
+        );
+
+        let counts: Vec<usize> = signatures
+            .signatures()
+            .iter()
+            .map(|signature| signature.count)
+            .collect();
+        assert_eq!(counts, vec![2, 1, 1]);
+        assert_eq!(
+            signatures.signatures()[0].example,
+            "TypeError: item 1 is undefined"
+        );
+    }
+}
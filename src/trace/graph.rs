@@ -0,0 +1,184 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::path::Path;
+
+use serde_json as json;
+use url::Url;
+
+use crate::browser::actions::BrowserAction;
+
+/// A state of the graph, identified by its URL and state hash.
+#[derive(Debug)]
+struct Node {
+    url: String,
+    hash: Option<u64>,
+    visits: usize,
+}
+
+/// The states reached during a run and the actions leading from one to the other, to get
+/// a map of what was explored.
+#[derive(Debug, Default)]
+pub struct StateGraph {
+    nodes: Vec<Node>,
+    indices: HashMap<(String, Option<u64>), usize>,
+    /// How many times each action (by its name) led from a state to another, by index.
+    edges: BTreeMap<(usize, usize, String), usize>,
+    last: Option<usize>,
+}
+
+/// The name of an action, e.g. `Click` or `Back`.
+fn action_name(action: &BrowserAction) -> String {
+    match json::to_value(action) {
+        Ok(json::Value::String(name)) => name,
+        Ok(json::Value::Object(object)) if object.len() == 1 => {
+            object.keys().next().cloned().unwrap_or_default()
+        }
+        _ => format!("{:?}", action),
+    }
+}
+
+impl StateGraph {
+    /// Records a state, reached from the last one by `action`.
+    pub fn add(
+        &mut self,
+        action: Option<&BrowserAction>,
+        url: &Url,
+        hash: Option<u64>,
+    ) {
+        let key = (url.to_string(), hash);
+        let index = match self.indices.get(&key) {
+            Some(index) => *index,
+            None => {
+                let index = self.nodes.len();
+                self.nodes.push(Node {
+                    url: key.0.clone(),
+                    hash,
+                    visits: 0,
+                });
+                self.indices.insert(key, index);
+                index
+            }
+        };
+        self.nodes[index].visits += 1;
+        if let (Some(last), Some(action)) = (self.last, action) {
+            *self
+                .edges
+                .entry((last, index, action_name(action)))
+                .or_default() += 1;
+        }
+        self.last = Some(index);
+    }
+
+    /// The graph in Graphviz's DOT language.
+    pub fn to_dot(&self) -> String {
+        let escape =
+            |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut dot = String::from("digraph states {\n");
+        for (index, node) in self.nodes.iter().enumerate() {
+            let _ = writeln!(
+                dot,
+                "  n{} [label=\"{}\\n{} ({} visits)\"];",
+                index,
+                escape(&node.url),
+                hash_label(node.hash),
+                node.visits
+            );
+        }
+        for ((from, to, action), count) in &self.edges {
+            let _ = writeln!(
+                dot,
+                "  n{} -> n{} [label=\"{} ({})\"];",
+                from,
+                to,
+                escape(action),
+                count
+            );
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// The graph in GraphML, e.g. for Gephi or yEd.
+    pub fn to_graphml(&self) -> String {
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n  <key id=\"url\" for=\"node\" attr.name=\"url\" attr.type=\"string\"/>\n  <key id=\"hash\" for=\"node\" attr.name=\"hash\" attr.type=\"string\"/>\n  <key id=\"visits\" for=\"node\" attr.name=\"visits\" attr.type=\"int\"/>\n  <key id=\"action\" for=\"edge\" attr.name=\"action\" attr.type=\"string\"/>\n  <key id=\"count\" for=\"edge\" attr.name=\"count\" attr.type=\"int\"/>\n  <graph id=\"states\" edgedefault=\"directed\">\n",
+        );
+        for (index, node) in self.nodes.iter().enumerate() {
+            let _ = writeln!(
+                xml,
+                "    <node id=\"n{}\"><data key=\"url\">{}</data><data key=\"hash\">{}</data><data key=\"visits\">{}</data></node>",
+                index,
+                escape_xml(&node.url),
+                hash_label(node.hash),
+                node.visits
+            );
+        }
+        for ((from, to, action), count) in &self.edges {
+            let _ = writeln!(
+                xml,
+                "    <edge source=\"n{}\" target=\"n{}\"><data key=\"action\">{}</data><data key=\"count\">{}</data></edge>",
+                from,
+                to,
+                escape_xml(action),
+                count
+            );
+        }
+        xml.push_str("  </graph>\n</graphml>\n");
+        xml
+    }
+
+    /// The graph as GraphML when `path` ends in `.graphml`, in the DOT language otherwise.
+    pub fn render_for(&self, path: &Path) -> String {
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "graphml")
+        {
+            self.to_graphml()
+        } else {
+            self.to_dot()
+        }
+    }
+}
+
+fn hash_label(hash: Option<u64>) -> String {
+    match hash {
+        Some(hash) => format!("{:016x}", hash),
+        None => "no hash".to_string(),
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_graph() {
+        let home = Url::parse("http://localhost/").unwrap();
+        let cart = Url::parse("http://localhost/cart?a&b").unwrap();
+        let mut graph = StateGraph::default();
+        graph.add(None, &home, Some(1));
+        graph.add(Some(&BrowserAction::Reload), &cart, Some(2));
+        graph.add(Some(&BrowserAction::Back), &home, Some(1));
+        graph.add(Some(&BrowserAction::Reload), &cart, Some(2));
+
+        let dot = graph.to_dot();
+        assert!(dot.contains(
+            "n0 [label=\"http://localhost/\\n0000000000000001 (2 visits)\"];"
+        ));
+        assert!(dot.contains("n0 -> n1 [label=\"Reload (2)\"];"));
+        assert!(dot.contains("n1 -> n0 [label=\"Back (1)\"];"));
+
+        let graphml = graph.render_for(Path::new("graph.graphml"));
+        assert!(graphml.contains("http://localhost/cart?a&amp;b"));
+        assert!(graphml.contains(
+            "<edge source=\"n0\" target=\"n1\"><data key=\"action\">Reload</data><data key=\"count\">2</data></edge>"
+        ));
+    }
+}
@@ -1,13 +1,22 @@
-use std::{path::PathBuf, time::SystemTime};
+use std::{collections::BTreeMap, path::PathBuf, time::SystemTime};
 
 use serde::Serialize;
+use serde_json as json;
 use url::Url;
 
 use crate::{
-    browser::actions::BrowserAction,
+    browser::{actions::BrowserAction, mutations::MutationSummary},
     specification::{ltl, render},
+    suppressions::Suppression,
 };
 
+pub mod clusters;
+pub mod error_signatures;
+pub mod graph;
+pub mod replay;
+#[cfg(feature = "report")]
+pub mod report;
+pub mod reproduction;
 pub mod writer;
 
 #[derive(Debug, Clone, Serialize)]
@@ -17,12 +26,43 @@ pub struct TraceEntry {
     pub hash_previous: Option<u64>,
     pub hash_current: Option<u64>,
     pub action: Option<BrowserAction>,
+    /// DOM mutations following the action.
+    pub mutations: MutationSummary,
     pub screenshot: PathBuf,
+    /// The accessibility tree of the state, captured for violations when enabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accessibility_tree: Option<PathBuf>,
+    /// The serialized DOM of the state, captured when enabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dom_snapshot: Option<PathBuf>,
+    /// The directory of the screenshots leading to the state, written for violations when
+    /// enabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artifacts: Option<PathBuf>,
     pub violations: Vec<PropertyViolation>,
+    pub transaction_failures: Vec<TransactionFailure>,
+    /// The metrics emitted by the specification in this state, the last value of each.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub metrics: BTreeMap<String, f64>,
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct PropertyViolation {
     pub name: String,
     pub violation: ltl::Violation<render::PrettyFunction>,
+    /// Identifies this violation across runs, for suppressing it.
+    pub fingerprint: String,
+    /// The suppression matching this violation, if any, in which case it doesn't fail the
+    /// test.
+    pub suppressed: Option<Suppression>,
+}
+
+/// A transaction whose postcondition didn't hold after applying all of its steps, with the
+/// values extracted before the first step and after the last one.
+#[derive(Debug, Clone, Serialize)]
+pub struct TransactionFailure {
+    pub name: String,
+    pub steps: Vec<BrowserAction>,
+    pub before: json::Value,
+    pub after: json::Value,
 }
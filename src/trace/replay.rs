@@ -0,0 +1,149 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json as json;
+
+use crate::browser::actions::BrowserAction;
+use crate::browser::checkpoints::{CHECKPOINTS_FILE_NAME, SavedCheckpoint};
+
+/// The part of a trace entry needed to replay it.
+#[derive(Debug, Deserialize)]
+struct ReplayEntry {
+    action: Option<BrowserAction>,
+}
+
+/// The path of the trace to replay: either a `trace.jsonl` file or the directory of a run.
+pub fn trace_file(path: &Path) -> PathBuf {
+    if path.is_dir() {
+        path.join("trace.jsonl")
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Reads the actions of a recorded trace, in the order they were applied. States without
+/// an action, i.e. the initial one and those only observed, are skipped.
+pub async fn read_actions(path: &Path) -> Result<Vec<BrowserAction>> {
+    let trace_path = trace_file(path);
+    let trace = tokio::fs::read_to_string(&trace_path)
+        .await
+        .with_context(|| format!("failed reading {}", trace_path.display()))?;
+    let mut actions = vec![];
+    for (index, line) in trace
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+    {
+        let entry: ReplayEntry = json::from_str(line).with_context(|| {
+            format!("invalid entry {} of {}", index, trace_path.display())
+        })?;
+        actions.extend(entry.action);
+    }
+    Ok(actions)
+}
+
+/// Reads the checkpoints written alongside a trace with `--save-checkpoints`, for the
+/// actions restoring them, or none if they weren't written.
+pub async fn read_checkpoints(path: &Path) -> Result<Vec<SavedCheckpoint>> {
+    let checkpoints_path =
+        trace_file(path).with_file_name(CHECKPOINTS_FILE_NAME);
+    let checkpoints = match tokio::fs::read_to_string(&checkpoints_path).await {
+        Ok(checkpoints) => checkpoints,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(vec![]);
+        }
+        Err(error) => {
+            return Err(error).with_context(|| {
+                format!("failed reading {}", checkpoints_path.display())
+            });
+        }
+    };
+    checkpoints
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(index, line)| {
+            json::from_str(line).with_context(|| {
+                format!(
+                    "invalid checkpoint {} of {}",
+                    index,
+                    checkpoints_path.display()
+                )
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_actions() {
+        let directory = tempfile::TempDir::new().unwrap();
+        let entries = [
+            json::json!({ "url": "http://localhost/", "action": null }),
+            json::json!({
+                "url": "http://localhost/",
+                "action": { "Click": { "name": "button", "content": null, "point": { "x": 10.0, "y": 20.0 } } },
+            }),
+            json::json!({ "url": "http://localhost/cart", "action": "Back" }),
+            json::json!({
+                "url": "http://localhost/",
+                "action": { "TypeText": { "text": "hello", "delay_millis": 50 } },
+            }),
+        ];
+        let trace = entries
+            .iter()
+            .map(|entry| entry.to_string() + "\n")
+            .collect::<String>();
+        tokio::fs::write(directory.path().join("trace.jsonl"), trace)
+            .await
+            .unwrap();
+
+        let actions = read_actions(directory.path()).await.unwrap();
+        assert_eq!(actions.len(), 3);
+        assert!(matches!(
+            &actions[0],
+            BrowserAction::Click { name, point, .. } if name == "button" && point.x == 10.0
+        ));
+        assert!(matches!(actions[1], BrowserAction::Back));
+        assert!(matches!(
+            &actions[2],
+            BrowserAction::TypeText { text, delay_millis: 50 } if text == "hello"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_read_checkpoints() {
+        let directory = tempfile::TempDir::new().unwrap();
+        assert!(read_checkpoints(directory.path()).await.unwrap().is_empty());
+
+        let checkpoint = json::json!({
+            "id": 2,
+            "url": "http://localhost/cart",
+            "cookies": [],
+            "localStorage": { "cart": "[1]" },
+            "sessionStorage": {},
+        });
+        tokio::fs::write(
+            directory.path().join(CHECKPOINTS_FILE_NAME),
+            checkpoint.to_string() + "\n",
+        )
+        .await
+        .unwrap();
+
+        let checkpoints =
+            read_checkpoints(&directory.path().join("trace.jsonl"))
+                .await
+                .unwrap();
+        assert_eq!(checkpoints.len(), 1);
+        assert_eq!(checkpoints[0].id, 2);
+        assert_eq!(checkpoints[0].checkpoint.url, "http://localhost/cart");
+        assert_eq!(
+            checkpoints[0].checkpoint.local_storage.get("cart"),
+            Some(&"[1]".to_string())
+        );
+    }
+}
@@ -0,0 +1,451 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use serde::Deserialize;
+use serde_json as json;
+
+use crate::specification::{
+    ltl::Violation,
+    render::{PrettyFunction, render_violation},
+};
+use crate::suppressions::Suppression;
+use crate::trace::reproduction::Reproduction;
+
+const STYLE: &str = r#"
+body { font-family: system-ui, sans-serif; margin: 2rem auto; max-width: 72rem; color: #222; }
+header { border-bottom: 1px solid #ddd; margin-bottom: 1rem; }
+.summary span { margin-right: 1.5rem; }
+.step { border-left: 4px solid #ddd; margin: 1rem 0; padding: 0.5rem 1rem; }
+.step.violated { border-left-color: #c62828; background: #fff5f5; }
+.step.suppressed { border-left-color: #f9a825; }
+.step h3 { font-size: 1rem; margin: 0 0 0.5rem; }
+.step img { max-width: 36rem; border: 1px solid #ccc; display: block; margin: 0.5rem 0; }
+.meta { color: #666; font-size: 0.9rem; word-break: break-all; }
+pre { background: #f6f6f6; padding: 0.5rem; overflow-x: auto; white-space: pre-wrap; }
+.violation h4 { color: #c62828; margin: 0.5rem 0 0; }
+.violation.suppressed h4 { color: #a0760a; }
+table.metrics { border-collapse: collapse; margin: 0.5rem 0; }
+table.metrics th, table.metrics td { border: 1px solid #ddd; padding: 0.2rem 0.6rem; text-align: right; }
+table.metrics th:first-child, table.metrics td:first-child { text-align: left; }
+"#;
+
+/// The parts of a trace entry shown in the report, read back from `trace.jsonl`.
+#[derive(Debug, Deserialize)]
+struct ReportEntry {
+    timestamp: SystemTime,
+    url: String,
+    action: Option<json::Value>,
+    screenshot: PathBuf,
+    violations: Vec<ReportViolation>,
+    #[serde(default)]
+    transaction_failures: Vec<ReportTransactionFailure>,
+    #[serde(default)]
+    metrics: BTreeMap<String, f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReportViolation {
+    name: String,
+    violation: Violation<PrettyFunction>,
+    suppressed: Option<Suppression>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReportTransactionFailure {
+    name: String,
+    steps: Vec<json::Value>,
+    before: json::Value,
+    after: json::Value,
+}
+
+impl ReportEntry {
+    fn violated(&self) -> bool {
+        self.violations
+            .iter()
+            .any(|violation| violation.suppressed.is_none())
+            || !self.transaction_failures.is_empty()
+    }
+}
+
+/// The values a metric took over the states it was emitted in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MetricSummary {
+    count: usize,
+    min: f64,
+    max: f64,
+    sum: f64,
+    last: f64,
+}
+
+impl MetricSummary {
+    fn add(&mut self, value: f64) {
+        self.count += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sum += value;
+        self.last = value;
+    }
+
+    fn mean(&self) -> f64 {
+        self.sum / self.count as f64
+    }
+}
+
+fn summarize_metrics(entries: &[ReportEntry]) -> BTreeMap<&str, MetricSummary> {
+    let mut summaries: BTreeMap<&str, MetricSummary> = BTreeMap::new();
+    for entry in entries {
+        for (name, value) in &entry.metrics {
+            summaries
+                .entry(name)
+                .or_insert(MetricSummary {
+                    count: 0,
+                    min: f64::INFINITY,
+                    max: f64::NEG_INFINITY,
+                    sum: 0.0,
+                    last: *value,
+                })
+                .add(*value);
+        }
+    }
+    summaries
+}
+
+/// Renders the trace of a run (the directory with its `trace.jsonl`) as a self-contained
+/// HTML page: a timeline of its states with their actions, screenshots and violations.
+pub async fn render_report(trace_directory: &Path) -> Result<String> {
+    let trace_path = trace_directory.join("trace.jsonl");
+    let trace = tokio::fs::read_to_string(&trace_path)
+        .await
+        .with_context(|| format!("failed reading {}", trace_path.display()))?;
+    let entries = trace
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(index, line)| {
+            json::from_str::<ReportEntry>(line).with_context(|| {
+                format!("invalid entry {} of {}", index, trace_path.display())
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut screenshots = Vec::with_capacity(entries.len());
+    for entry in &entries {
+        screenshots.push(screenshot_data_url(trace_directory, entry).await);
+    }
+    // Traces of older versions don't have it.
+    let reproduction =
+        tokio::fs::read_to_string(trace_directory.join("reproduction.json"))
+            .await
+            .ok()
+            .and_then(|contents| {
+                json::from_str::<Reproduction>(&contents).ok()
+            });
+    Ok(render(
+        &trace_directory.display().to_string(),
+        &entries,
+        &screenshots,
+        reproduction.as_ref(),
+    ))
+}
+
+/// The screenshot of an entry, inlined. It's looked up in the trace's `screenshots`
+/// directory, so that the report can be rendered after moving the trace.
+async fn screenshot_data_url(
+    trace_directory: &Path,
+    entry: &ReportEntry,
+) -> Option<String> {
+    let file_name = entry.screenshot.file_name()?;
+    let path = trace_directory.join("screenshots").join(file_name);
+    let data = match tokio::fs::read(&path).await {
+        Ok(data) => data,
+        Err(error) => {
+            log::warn!("missing screenshot {}: {}", path.display(), error);
+            return None;
+        }
+    };
+    let mime = match path.extension()?.to_str()? {
+        "png" => "image/png",
+        "jpeg" | "jpg" => "image/jpeg",
+        _ => "image/webp",
+    };
+    Some(format!(
+        "data:{};base64,{}",
+        mime,
+        BASE64_STANDARD.encode(data)
+    ))
+}
+
+fn render(
+    title: &str,
+    entries: &[ReportEntry],
+    screenshots: &[Option<String>],
+    reproduction: Option<&Reproduction>,
+) -> String {
+    let first = entries.first().map(|entry| entry.timestamp);
+    let violating: Vec<usize> = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| entry.violated())
+        .map(|(index, _)| index)
+        .collect();
+    let ended_by_violation = violating
+        .last()
+        .is_some_and(|index| *index + 1 == entries.len());
+
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Bombadil report: {}</title>\n<style>{}</style>\n</head>\n<body>\n<header>\n<h1>Bombadil report</h1>\n<p class=\"meta\">{}</p>\n<p class=\"summary\"><span>{} states</span><span>{} with violations</span>",
+        escape(title),
+        STYLE,
+        escape(title),
+        entries.len(),
+        violating.len()
+    );
+    if let (Some(first), Some(last)) = (first, entries.last()) {
+        let _ = write!(html, "<span>{}</span>", elapsed(first, last.timestamp));
+    }
+    html.push_str("</p>\n");
+    match violating.first() {
+        Some(index) => {
+            let _ = writeln!(
+                html,
+                "<p>First violation at <a href=\"#step-{index}\">step {index}</a>{}.</p>",
+                if ended_by_violation {
+                    format!(
+                        ", the run ended with the one at <a href=\"#step-{}\">step {}</a>",
+                        entries.len() - 1,
+                        entries.len() - 1
+                    )
+                } else {
+                    String::new()
+                }
+            );
+        }
+        None => html.push_str("<p>No violations.</p>\n"),
+    }
+    if let Some(reproduction) = reproduction {
+        let _ = writeln!(
+            html,
+            "<p>Reproduce with Bombadil {}:</p>\n<pre>{}</pre>",
+            escape(&reproduction.version),
+            escape(&reproduction.command)
+        );
+    }
+    let metrics = summarize_metrics(entries);
+    if !metrics.is_empty() {
+        html.push_str("<table class=\"metrics\">\n<tr><th>Metric</th><th>States</th><th>Min</th><th>Mean</th><th>Max</th><th>Last</th></tr>\n");
+        for (name, summary) in &metrics {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{:.2}</td><td>{}</td><td>{}</td></tr>",
+                escape(name),
+                summary.count,
+                summary.min,
+                summary.mean(),
+                summary.max,
+                summary.last
+            );
+        }
+        html.push_str("</table>\n");
+    }
+    html.push_str("</header>\n<main>\n");
+
+    for (index, entry) in entries.iter().enumerate() {
+        let class = if entry.violated() {
+            "step violated"
+        } else if !entry.violations.is_empty() {
+            "step suppressed"
+        } else {
+            "step"
+        };
+        let _ = write!(
+            html,
+            "<section class=\"{}\" id=\"step-{}\">\n<h3>Step {} &middot; {}</h3>\n<p class=\"meta\">{}</p>\n",
+            class,
+            index,
+            index,
+            escape(&describe_action(entry.action.as_ref())),
+            escape(&entry.url)
+        );
+        if let Some(first) = first {
+            let _ = writeln!(
+                html,
+                "<p class=\"meta\">at {}</p>",
+                elapsed(first, entry.timestamp)
+            );
+        }
+        if !entry.metrics.is_empty() {
+            let _ = writeln!(
+                html,
+                "<p class=\"meta\">{}</p>",
+                escape(
+                    &entry
+                        .metrics
+                        .iter()
+                        .map(|(name, value)| format!("{} = {}", name, value))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            );
+        }
+        if let Some(Some(screenshot)) = screenshots.get(index) {
+            let _ = writeln!(
+                html,
+                "<img loading=\"lazy\" alt=\"screenshot of step {}\" src=\"{}\">",
+                index, screenshot
+            );
+        }
+        for violation in &entry.violations {
+            let (class, label) = match &violation.suppressed {
+                Some(suppression) => (
+                    "violation suppressed",
+                    format!(
+                        "{} (suppressed: {})",
+                        violation.name, suppression.reason
+                    ),
+                ),
+                None => ("violation", violation.name.clone()),
+            };
+            let _ = write!(
+                html,
+                "<div class=\"{}\">\n<h4>{}</h4>\n<pre>{}</pre>\n</div>\n",
+                class,
+                escape(&label),
+                escape(&render_violation(&violation.violation))
+            );
+        }
+        for failure in &entry.transaction_failures {
+            let _ = write!(
+                html,
+                "<div class=\"violation\">\n<h4>Transaction {} failed</h4>\n<pre>steps: {}\nbefore: {}\nafter: {}</pre>\n</div>\n",
+                escape(&failure.name),
+                escape(
+                    &failure
+                        .steps
+                        .iter()
+                        .map(|step| describe_action(Some(step)))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                escape(&failure.before.to_string()),
+                escape(&failure.after.to_string())
+            );
+        }
+        html.push_str("</section>\n");
+    }
+    html.push_str("</main>\n</body>\n</html>\n");
+    html
+}
+
+/// A one-line description of an action, e.g. `Click {"name":"button",...}`.
+fn describe_action(action: Option<&json::Value>) -> String {
+    match action {
+        None => "initial state".to_string(),
+        Some(json::Value::String(name)) => name.clone(),
+        Some(json::Value::Object(object)) if object.len() == 1 => {
+            let (name, fields) = object.iter().next().expect("one entry");
+            format!("{} {}", name, fields)
+        }
+        Some(other) => other.to_string(),
+    }
+}
+
+fn elapsed(first: SystemTime, timestamp: SystemTime) -> String {
+    let elapsed = timestamp.duration_since(first).unwrap_or_default();
+    format!("{:.1}s", elapsed.as_secs_f64())
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_render_report() {
+        let directory = tempfile::TempDir::new().unwrap();
+        tokio::fs::create_dir_all(directory.path().join("screenshots"))
+            .await
+            .unwrap();
+        tokio::fs::write(
+            directory.path().join("screenshots").join("1.png"),
+            b"png",
+        )
+        .await
+        .unwrap();
+        let entries = [
+            json::json!({
+                "timestamp": { "secs_since_epoch": 10, "nanos_since_epoch": 0 },
+                "url": "http://localhost/",
+                "action": null,
+                "screenshot": "/elsewhere/screenshots/1.png",
+                "violations": [],
+                "transaction_failures": [],
+                "metrics": { "cart_size": 1.0 },
+            }),
+            json::json!({
+                "timestamp": { "secs_since_epoch": 12, "nanos_since_epoch": 0 },
+                "url": "http://localhost/<cart>",
+                "action": { "Click": { "name": "button" } },
+                "screenshot": "/elsewhere/screenshots/2.png",
+                "violations": [{
+                    "name": "no_errors",
+                    "violation": { "False": { "time": { "secs_since_epoch": 12, "nanos_since_epoch": 0 }, "condition": "errors.length === 0" } },
+                    "fingerprint": "abc",
+                    "suppressed": null,
+                }],
+                "transaction_failures": [],
+                "metrics": { "cart_size": 4.0 },
+            }),
+        ];
+        let trace = entries
+            .iter()
+            .map(|entry| entry.to_string() + "\n")
+            .collect::<String>();
+        tokio::fs::write(directory.path().join("trace.jsonl"), trace)
+            .await
+            .unwrap();
+        tokio::fs::write(
+            directory.path().join("reproduction.json"),
+            json::to_string(&Reproduction::new(
+                &["test".to_string(), "http://localhost/".to_string()],
+                7,
+            ))
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let html = render_report(directory.path()).await.unwrap();
+        assert!(html.contains("data:image/png;base64,cG5n"));
+        assert!(html.contains("http://localhost/&lt;cart&gt;"));
+        assert!(html.contains("Click {&quot;name&quot;:&quot;button&quot;}"));
+        assert!(html.contains("<h4>no_errors</h4>"));
+        assert!(html.contains("errors.length === 0"));
+        assert!(html.contains("the run ended with the one at"));
+        assert!(html.contains("bombadil test http://localhost/ --seed 7"));
+        assert!(html.contains(
+            "<tr><td>cart_size</td><td>2</td><td>1</td><td>2.50</td><td>4</td><td>4</td></tr>"
+        ));
+        assert!(html.contains("cart_size = 4"));
+    }
+}
@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+
+/// Flags whose values are replaced, or that don't apply, when reproducing a single run.
+const DROPPED_FLAGS: &[&str] = &[
+    "--seed",
+    "--runs",
+    "--parallel",
+    "--duration",
+    "--rotate-seeds",
+];
+
+/// How to reproduce a run: the command line that started it, narrowed down to the run's
+/// seed, and the version of Bombadil that ran it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reproduction {
+    pub version: String,
+    pub seed: u64,
+    /// A copy-pasteable shell command.
+    pub command: String,
+}
+
+impl Reproduction {
+    /// The reproduction of a run with `seed` started by the command line `args` (without
+    /// the program name). Soak tests are reproduced as a single test with the seed of the
+    /// segment.
+    pub fn new(args: &[String], seed: u64) -> Self {
+        let mut words = vec!["bombadil".to_string()];
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if DROPPED_FLAGS.contains(&arg.as_str()) {
+                args.next();
+                continue;
+            }
+            if DROPPED_FLAGS
+                .iter()
+                .any(|flag| arg.starts_with(&format!("{flag}=")))
+            {
+                continue;
+            }
+            if words.len() == 1 && arg == "soak" {
+                words.push("test".to_string());
+                continue;
+            }
+            words.push(arg.clone());
+        }
+        words.push("--seed".to_string());
+        words.push(seed.to_string());
+        Reproduction {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            seed,
+            command: words
+                .iter()
+                .map(|word| shell_quote(word))
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+}
+
+fn shell_quote(word: &str) -> String {
+    let safe = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=@,%+".contains(c));
+    if safe {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reproduction_command() {
+        let args = [
+            "soak",
+            "https://example.com/?q=a b",
+            "spec.ts",
+            "--duration",
+            "8h",
+            "--seed=1",
+            "--deny-click",
+            "don't",
+            "--headless",
+        ]
+        .map(String::from);
+        assert_eq!(
+            Reproduction::new(&args, 42).command,
+            "bombadil test 'https://example.com/?q=a b' spec.ts --deny-click 'don'\\''t' --headless --seed 42"
+        );
+    }
+}
@@ -1,20 +1,64 @@
-use std::{path::PathBuf, time::UNIX_EPOCH};
+use std::{
+    collections::{BTreeSet, VecDeque},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
 
-use anyhow::Result;
+use anyhow::{Context, Result, anyhow};
 use serde_json as json;
-use tokio::{fs::File, io::AsyncWriteExt};
+use tokio::{fs::File, io::AsyncWriteExt, sync::mpsc, task::JoinHandle};
 
+#[cfg(feature = "verifier")]
+use crate::runner::diagnostics::NoActionsDiagnostics;
 use crate::{
-    browser::{actions::BrowserAction, state::BrowserState},
-    trace::{PropertyViolation, TraceEntry},
+    browser::{
+        accessibility::AccessibilityNode,
+        actions::BrowserAction,
+        gpu::GpuInfo,
+        instrumentation::{CachingAlteration, ScriptSizesReport},
+        state::{BrowserState, EdgeIndex, Screenshot},
+    },
+    trace::{
+        PropertyViolation, TraceEntry, TransactionFailure,
+        clusters::{ViolationCluster, ViolationClusters},
+        error_signatures::{ErrorSignature, ErrorSignatures},
+        graph::StateGraph,
+        reproduction::Reproduction,
+    },
 };
 
+/// What's captured of a state when enabled, besides its screenshot.
+#[derive(Default)]
+pub struct StateCaptures {
+    /// Captured for states with violations.
+    pub accessibility_tree: Option<AccessibilityNode>,
+    /// Captured for states with violations.
+    pub full_page_screenshot: Option<Screenshot>,
+    pub dom_snapshot: Option<String>,
+}
+
 pub struct TraceWriter {
+    root_path: PathBuf,
     screenshots_path: PathBuf,
+    accessibility_path: PathBuf,
+    dom_path: PathBuf,
     trace_file: File,
     last_transition_hash: Option<u64>,
+    screenshots: mpsc::Sender<(PathBuf, Screenshot)>,
+    /// The background task writing screenshots, until it's awaited for its error.
+    screenshots_done: Option<JoinHandle<Result<()>>>,
+    clusters: ViolationClusters,
+    error_signatures: ErrorSignatures,
+    graph: StateGraph,
+    /// Screenshots of the states written to the artifacts of a violation.
+    violation_screenshots: usize,
+    recent_screenshots: VecDeque<(PathBuf, Screenshot)>,
 }
 
+/// Screenshots waiting to be written in the background, beyond which writing the trace
+/// waits for them.
+const SCREENSHOT_QUEUE_SIZE: usize = 16;
+
 impl TraceWriter {
     pub async fn initialize(root_path: PathBuf) -> Result<Self> {
         log::info!(
@@ -24,33 +68,149 @@ impl TraceWriter {
                 .expect("states directory path is not valid unicode")
         );
         let screenshots_path = root_path.join("screenshots");
+        let accessibility_path = root_path.join("accessibility");
+        let dom_path = root_path.join("dom");
         tokio::fs::create_dir_all(&screenshots_path).await?;
         let trace_file = File::options()
             .append(true)
             .create(true)
             .open(root_path.join("trace.jsonl"))
             .await?;
+        let (screenshots, mut screenshots_receiver) =
+            mpsc::channel::<(PathBuf, Screenshot)>(SCREENSHOT_QUEUE_SIZE);
+        let screenshots_done = tokio::spawn(async move {
+            while let Some((path, screenshot)) =
+                screenshots_receiver.recv().await
+            {
+                async {
+                    File::create_new(&path)
+                        .await?
+                        .write_all(&screenshot.data)
+                        .await
+                }
+                .await
+                .with_context(|| {
+                    format!("write screenshot {}", path.display())
+                })?;
+            }
+            Ok(())
+        });
         Ok(TraceWriter {
+            root_path,
             screenshots_path,
+            accessibility_path,
+            dom_path,
             trace_file,
             last_transition_hash: None,
+            screenshots,
+            screenshots_done: Some(screenshots_done),
+            clusters: ViolationClusters::default(),
+            error_signatures: ErrorSignatures::default(),
+            graph: StateGraph::default(),
+            violation_screenshots: 0,
+            recent_screenshots: VecDeque::new(),
         })
     }
+
+    /// Writes the screenshots of the last `count` states, and a full-page screenshot if
+    /// any, to the `violations` directory for every state with a violation.
+    pub fn with_violation_screenshots(mut self, count: usize) -> Self {
+        self.violation_screenshots = count;
+        self
+    }
+
     pub async fn write(
         &mut self,
         last_action: Option<BrowserAction>,
         state: BrowserState,
         violations: Vec<PropertyViolation>,
-    ) -> Result<()> {
+        transaction_failures: Vec<TransactionFailure>,
+        captures: StateCaptures,
+        metrics: Vec<(String, f64)>,
+    ) -> Result<Option<PathBuf>> {
+        self.error_signatures.add_state(&state);
+        let mutations = state.mutation_summary();
         let screenshot_path = self.screenshots_path.join(format!(
             "{}.{}",
             state.timestamp.duration_since(UNIX_EPOCH)?.as_micros(),
             &state.screenshot.format.extension()
         ));
-        File::create_new(&screenshot_path)
-            .await?
-            .write_all(&state.screenshot.data)
+        if self.violation_screenshots > 0 {
+            if self.recent_screenshots.len() == self.violation_screenshots {
+                self.recent_screenshots.pop_front();
+            }
+            self.recent_screenshots
+                .push_back((screenshot_path.clone(), state.screenshot.clone()));
+        }
+        if self
+            .screenshots
+            .send((screenshot_path.clone(), state.screenshot))
+            .await
+            .is_err()
+        {
+            return Err(self.screenshot_writer_error().await);
+        }
+
+        let accessibility_tree_path = match captures.accessibility_tree {
+            Some(tree) => {
+                let path = self.accessibility_path.join(format!(
+                    "{}.json",
+                    state.timestamp.duration_since(UNIX_EPOCH)?.as_micros()
+                ));
+                tokio::fs::create_dir_all(&self.accessibility_path).await?;
+                tokio::fs::write(&path, json::to_string_pretty(&tree)?).await?;
+                Some(path)
+            }
+            None => None,
+        };
+
+        let dom_snapshot_path = match captures.dom_snapshot {
+            Some(dom) => {
+                let path = self.dom_path.join(format!(
+                    "{}.html",
+                    state.timestamp.duration_since(UNIX_EPOCH)?.as_micros()
+                ));
+                tokio::fs::create_dir_all(&self.dom_path).await?;
+                tokio::fs::write(&path, dom).await?;
+                Some(path)
+            }
+            None => None,
+        };
+
+        let artifacts_path = if self.violation_screenshots > 0
+            && violations
+                .iter()
+                .any(|violation| violation.suppressed.is_none())
+        {
+            let path = self.root_path.join("violations").join(
+                state
+                    .timestamp
+                    .duration_since(UNIX_EPOCH)?
+                    .as_micros()
+                    .to_string(),
+            );
+            self.write_violation_artifacts(
+                &path,
+                captures.full_page_screenshot,
+            )
             .await?;
+            Some(path)
+        } else {
+            None
+        };
+
+        for violation in &violations {
+            self.clusters.add(
+                violation,
+                &state.url,
+                state.transition_hash,
+                state.timestamp,
+                &screenshot_path,
+            );
+        }
+
+        self.graph
+            .add(last_action.as_ref(), &state.url, state.transition_hash);
 
         let entry = TraceEntry {
             timestamp: state.timestamp,
@@ -58,8 +218,14 @@ impl TraceWriter {
             hash_previous: self.last_transition_hash,
             hash_current: state.transition_hash,
             action: last_action,
+            mutations,
             screenshot: screenshot_path,
+            accessibility_tree: accessibility_tree_path,
+            dom_snapshot: dom_snapshot_path,
+            artifacts: artifacts_path.clone(),
             violations,
+            transaction_failures,
+            metrics: metrics.into_iter().collect(),
         };
 
         self.last_transition_hash = state.transition_hash;
@@ -69,6 +235,248 @@ impl TraceWriter {
             .await?;
         self.trace_file.write_u8(b'\n').await?;
 
+        Ok(artifacts_path)
+    }
+
+    /// Writes the screenshots of the last states, numbered from the oldest one, and the
+    /// full-page screenshot of the violating state.
+    async fn write_violation_artifacts(
+        &self,
+        path: &Path,
+        full_page_screenshot: Option<Screenshot>,
+    ) -> Result<()> {
+        tokio::fs::create_dir_all(path).await?;
+        for (index, (screenshot_path, screenshot)) in
+            self.recent_screenshots.iter().enumerate()
+        {
+            let name = screenshot_path
+                .file_name()
+                .map(|name| name.to_string_lossy())
+                .unwrap_or_default();
+            tokio::fs::write(
+                path.join(format!("{:02}-{}", index + 1, name)),
+                &*screenshot.data,
+            )
+            .await?;
+        }
+        if let Some(screenshot) = full_page_screenshot {
+            tokio::fs::write(
+                path.join(format!(
+                    "full-page.{}",
+                    screenshot.format.extension()
+                )),
+                &*screenshot.data,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Writes the report of resources whose caching semantics were altered by
+    /// instrumentation, next to the trace.
+    pub async fn write_caching_report(
+        &self,
+        report: &[CachingAlteration],
+    ) -> Result<()> {
+        tokio::fs::write(
+            self.root_path.join("caching.json"),
+            json::to_string_pretty(report)?,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Writes what's known of the state the specification had no action for, and its
+    /// screenshot, to the `no-actions` directory next to the trace.
+    #[cfg(feature = "verifier")]
+    pub async fn write_no_actions(
+        &self,
+        diagnostics: &NoActionsDiagnostics,
+    ) -> Result<()> {
+        let path = self.root_path.join("no-actions");
+        tokio::fs::create_dir_all(&path).await?;
+        if let Some(screenshot) = &diagnostics.screenshot {
+            tokio::fs::write(
+                path.join(format!(
+                    "screenshot.{}",
+                    screenshot.format.extension()
+                )),
+                &*screenshot.data,
+            )
+            .await?;
+        }
+        tokio::fs::write(
+            path.join("diagnostics.json"),
+            json::to_string_pretty(diagnostics)?,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Writes whether WebGL was available in the browser, and what rendered it, next to the
+    /// trace.
+    pub async fn write_gpu_info(&self, gpu_info: &GpuInfo) -> Result<()> {
+        tokio::fs::write(
+            self.root_path.join("gpu.json"),
+            json::to_string_pretty(gpu_info)?,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Writes how to reproduce the run, next to the trace.
+    pub async fn write_reproduction(
+        &self,
+        reproduction: &Reproduction,
+    ) -> Result<()> {
+        tokio::fs::write(
+            self.root_path.join("reproduction.json"),
+            json::to_string_pretty(reproduction)?,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Writes the sizes of the scripts loaded during the run, next to the trace.
+    pub async fn write_script_sizes(
+        &self,
+        report: &ScriptSizesReport,
+    ) -> Result<()> {
+        tokio::fs::write(
+            self.root_path.join("script_sizes.json"),
+            json::to_string_pretty(report)?,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Writes the clusters of the violations so far, next to the trace, and returns them.
+    pub async fn write_violation_clusters(
+        &self,
+    ) -> Result<&[ViolationCluster]> {
+        let clusters = self.clusters.clusters();
+        tokio::fs::write(
+            self.root_path.join("violation_clusters.json"),
+            json::to_string_pretty(clusters)?,
+        )
+        .await?;
+        Ok(clusters)
+    }
+
+    /// Writes the signatures of the console errors and uncaught exceptions so far, next to
+    /// the trace, and returns them.
+    pub async fn write_error_signatures(&self) -> Result<&[ErrorSignature]> {
+        let signatures = self.error_signatures.signatures();
+        tokio::fs::write(
+            self.root_path.join("error_signatures.json"),
+            json::to_string_pretty(signatures)?,
+        )
+        .await?;
+        Ok(signatures)
+    }
+
+    /// Writes the indices of the edges covered during the run, to compare or merge the
+    /// coverage of runs.
+    pub async fn write_coverage(
+        &self,
+        edges: &BTreeSet<EdgeIndex>,
+    ) -> Result<()> {
+        tokio::fs::write(
+            self.root_path.join("coverage.json"),
+            json::to_string(edges)?,
+        )
+        .await?;
         Ok(())
     }
+
+    /// Writes the graph of the states reached so far and the actions between them to
+    /// `path`, or to `<RUN_ID>.dot` in it when it's a directory, and returns where.
+    pub async fn write_graph(
+        &self,
+        path: &Path,
+        run_id: &str,
+    ) -> Result<PathBuf> {
+        let path = if tokio::fs::metadata(path)
+            .await
+            .is_ok_and(|metadata| metadata.is_dir())
+        {
+            path.join(format!("{}.dot", run_id))
+        } else {
+            path.to_path_buf()
+        };
+        tokio::fs::write(&path, self.graph.render_for(&path)).await?;
+        Ok(path)
+    }
+
+    /// Why the background task writing screenshots stopped.
+    async fn screenshot_writer_error(&mut self) -> anyhow::Error {
+        match self.screenshots_done.take() {
+            Some(done) => match done.await {
+                Ok(Err(error)) => error,
+                Ok(Ok(())) => anyhow!("screenshot writer has stopped"),
+                Err(error) => error.into(),
+            },
+            None => anyhow!("screenshot writer has stopped"),
+        }
+    }
+
+    /// Waits for the screenshots still being written in the background.
+    pub async fn finish(self) -> Result<()> {
+        let TraceWriter {
+            screenshots,
+            screenshots_done,
+            ..
+        } = self;
+        drop(screenshots);
+        match screenshots_done {
+            Some(done) => done.await?,
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use url::Url;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_finish_reports_screenshot_errors() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut writer =
+            TraceWriter::initialize(directory.path().to_path_buf())
+                .await
+                .unwrap();
+        let state = BrowserState::fixture(
+            Url::parse("http://localhost/").unwrap(),
+            String::new(),
+            "text/html".to_string(),
+            Arc::default(),
+        );
+        // The screenshot can't be written where a directory already is.
+        std::fs::create_dir(directory.path().join("screenshots").join(
+            format!(
+                "{}.png",
+                state.timestamp.duration_since(UNIX_EPOCH).unwrap().as_micros()
+            ),
+        ))
+        .unwrap();
+
+        writer
+            .write(
+                None,
+                state,
+                vec![],
+                vec![],
+                StateCaptures::default(),
+                vec![],
+            )
+            .await
+            .unwrap();
+        let error = writer.finish().await.unwrap_err();
+        assert!(format!("{:#}", error).contains("write screenshot"));
+    }
 }
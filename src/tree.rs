@@ -45,6 +45,15 @@ impl<T> Tree<T> {
         }
     }
 
+    pub fn leaves(&self) -> Vec<&T> {
+        match self {
+            Tree::Leaf { value } => vec![value],
+            Tree::Branch { branches } => {
+                branches.iter().flat_map(|(_, t)| t.leaves()).collect()
+            }
+        }
+    }
+
     fn prune_to_size(&mut self) -> usize {
         match self {
             Tree::Leaf { .. } => 1,
@@ -167,6 +176,26 @@ mod tests {
         assert_eq!(filtered, expected);
     }
 
+    #[test]
+    fn test_leaves() {
+        let tree = Branch {
+            branches: vec![
+                (1, Leaf { value: 1 }),
+                (
+                    2,
+                    Branch {
+                        branches: vec![
+                            (1, Leaf { value: 2 }),
+                            (1, Branch { branches: vec![] }),
+                        ],
+                    },
+                ),
+                (1, Leaf { value: 3 }),
+            ],
+        };
+        assert_eq!(tree.leaves(), vec![&1, &2, &3]);
+    }
+
     #[test]
     fn test_try_map_ok() {
         let tree = Branch {
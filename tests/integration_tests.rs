@@ -10,9 +10,13 @@ use url::Url;
 
 use bombadil::{
     browser::{
-        Browser, BrowserOptions, DebuggerOptions, Emulation, LaunchOptions,
-        actions::BrowserAction,
+        Browser, BrowserOptions, DebuggerOptions, Emulation, InitialNavigation,
+        LaunchOptions, actions::BrowserAction, chrome::HeadlessVariant,
+        coverage::CoverageSource, dialogs::BeforeUnloadPolicy,
+        faults::FaultInjection, gpu::GpuPreset,
+        service_workers::ServiceWorkerPolicy, state::LogLimits,
     },
+    run_id::RunId,
     runner::{RunEvent, Runner, RunnerOptions},
     specification::{render::render_violation, verifier::Specification},
 };
@@ -109,20 +113,39 @@ async fn run_browser_test_with_router(
         default_specification,
         RunnerOptions {
             stop_on_violation: true,
+            ..RunnerOptions::default()
         },
         BrowserOptions {
             create_target: true,
+            run_id: RunId::new(0),
+            cache_pass_through: vec![],
+            service_worker_policy: ServiceWorkerPolicy::default(),
+            faults: FaultInjection::default(),
+            before_unload: BeforeUnloadPolicy::default(),
+            seed: 0,
+            permissions: vec![],
+            screenshot_masks: vec![],
+            screencast: None,
             emulation: Emulation {
                 width: 800,
                 height: 600,
                 device_scale_factor: 2.0,
+                mobile: false,
+                touch: false,
+                user_agent: None,
+                reduce_motion: false,
             },
         },
         DebuggerOptions::Managed {
             launch_options: LaunchOptions {
-                headless: true,
+                headless: Some(HeadlessVariant::New),
+                chrome_binary: None,
+                chrome_channel: None,
                 no_sandbox: true,
                 user_data_directory: user_data_directory.path().to_path_buf(),
+                extensions: vec![],
+                gpu: GpuPreset::default(),
+                fake_media: None,
             },
         },
     )
@@ -150,10 +173,11 @@ async fn run_browser_test_with_router(
                         ));
                     }
                 }
-                Ok(None) => break events.shutdown().await,
+                Ok(Some(RunEvent::NoActions(_))) => {}
+                Ok(None) => break events.shutdown().await.map(|_| ()),
                 Err(err) => {
                     log::error!("next event error: {}", err);
-                    break events.shutdown().await;
+                    break events.shutdown().await.map(|_| ());
                 }
             }
         }
@@ -363,24 +387,47 @@ async fn test_browser_lifecycle() {
         origin,
         BrowserOptions {
             create_target: true,
+            run_id: RunId::new(0),
+            cache_pass_through: vec![],
+            service_worker_policy: ServiceWorkerPolicy::default(),
+            faults: FaultInjection::default(),
+            before_unload: BeforeUnloadPolicy::default(),
+            seed: 0,
+            permissions: vec![],
+            screenshot_masks: vec![],
+            screencast: None,
             emulation: Emulation {
                 width: 800,
                 height: 600,
                 device_scale_factor: 2.0,
+                mobile: false,
+                touch: false,
+                user_agent: None,
+                reduce_motion: false,
             },
         },
         DebuggerOptions::Managed {
             launch_options: LaunchOptions {
-                headless: true,
+                headless: Some(HeadlessVariant::New),
+                chrome_binary: None,
+                chrome_channel: None,
                 no_sandbox: true,
                 user_data_directory: user_data_directory.path().to_path_buf(),
+                extensions: vec![],
+                gpu: GpuPreset::default(),
+                fake_media: None,
             },
         },
+        LogLimits::default(),
+        CoverageSource::default(),
     )
     .await
     .unwrap();
 
-    browser.initiate().await.unwrap();
+    browser
+        .initiate(&InitialNavigation::default())
+        .await
+        .unwrap();
 
     match browser.next_event().await.unwrap() {
         bombadil::browser::BrowserEvent::StateChanged(state) => {